@@ -0,0 +1,3 @@
+DROP TABLE pack_file;
+DROP TABLE mod CASCADE;
+DROP TABLE modfile CASCADE;
@@ -0,0 +1,2 @@
+-- 1 for unreleased modfiles of team mods indexed with --drafts, which are not the mod's live file
+ALTER TABLE modfile ADD COLUMN draft BIGINT NOT NULL DEFAULT 0;
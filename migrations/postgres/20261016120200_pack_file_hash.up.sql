@@ -0,0 +1,2 @@
+-- SHA-1 of the entry contents, NULL for pack files analyzed before this column existed
+ALTER TABLE pack_file ADD COLUMN hash TEXT;
@@ -0,0 +1,2 @@
+DROP TABLE collection_mod;
+DROP TABLE collection;
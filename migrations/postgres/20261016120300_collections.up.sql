@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS collection (
+    id_collection        BIGINT GENERATED BY DEFAULT AS IDENTITY,
+    name                 TEXT NOT NULL UNIQUE,
+    date_created         TEXT NOT NULL,
+    PRIMARY KEY (id_collection)
+);
+
+CREATE TABLE IF NOT EXISTS collection_mod (
+    id_collection        BIGINT NOT NULL,
+    id_mod               BIGINT NOT NULL,
+    date_added           TEXT NOT NULL,
+    PRIMARY KEY (id_collection, id_mod),
+    FOREIGN KEY (id_collection) REFERENCES collection (id_collection) DEFERRABLE INITIALLY DEFERRED,
+    FOREIGN KEY (id_mod) REFERENCES mod (id_mod) DEFERRABLE INITIALLY DEFERRED
+);
@@ -0,0 +1,3 @@
+-- modfile a curator pinned the mod to, NULL to follow the current modfile
+ALTER TABLE collection_mod ADD COLUMN id_modfile BIGINT;
+ALTER TABLE collection_mod ADD COLUMN note TEXT;
@@ -0,0 +1,4 @@
+-- Class of the primary export of .uasset and .umap entries, e.g. Texture2D or DataTable. NULL
+-- for other entries, packages that could not be parsed and pack files analyzed before this
+-- column existed
+ALTER TABLE pack_file ADD COLUMN asset_class TEXT;
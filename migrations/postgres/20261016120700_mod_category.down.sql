@@ -0,0 +1 @@
+ALTER TABLE mod DROP COLUMN category;
@@ -0,0 +1,3 @@
+-- Kind of content derived from the pack files of the current modfile, see classify.rs. NULL
+-- until the mod has been analyzed
+ALTER TABLE mod ADD COLUMN category TEXT;
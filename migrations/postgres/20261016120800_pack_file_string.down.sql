@@ -0,0 +1 @@
+DROP TABLE pack_file_string;
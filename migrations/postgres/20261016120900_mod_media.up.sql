@@ -0,0 +1,13 @@
+-- Images of a mod. Logos are cached under media/ with their size in bytes, path and size are
+-- NULL for media that is only linked
+CREATE TABLE IF NOT EXISTS mod_media (
+    id_mod               BIGINT NOT NULL,
+    kind                 TEXT NOT NULL,
+    position             BIGINT NOT NULL,
+    url                  TEXT NOT NULL,
+    path                 TEXT,
+    size                 BIGINT,
+    date_cached          TEXT,
+    PRIMARY KEY (id_mod, kind, position),
+    FOREIGN KEY (id_mod) REFERENCES mod (id_mod) DEFERRABLE INITIALLY DEFERRED
+);
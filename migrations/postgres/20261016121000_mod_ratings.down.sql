@@ -0,0 +1,6 @@
+ALTER TABLE mod_flat DROP COLUMN ratings_display;
+ALTER TABLE mod_flat DROP COLUMN ratings_negative;
+ALTER TABLE mod_flat DROP COLUMN ratings_positive;
+ALTER TABLE mod DROP COLUMN ratings_display;
+ALTER TABLE mod DROP COLUMN ratings_negative;
+ALTER TABLE mod DROP COLUMN ratings_positive;
@@ -0,0 +1,7 @@
+-- Rating summary of a mod as of the last sync. NULL until the mod has been synced again
+ALTER TABLE mod ADD COLUMN ratings_positive BIGINT;
+ALTER TABLE mod ADD COLUMN ratings_negative BIGINT;
+ALTER TABLE mod ADD COLUMN ratings_display TEXT;
+ALTER TABLE mod_flat ADD COLUMN ratings_positive BIGINT;
+ALTER TABLE mod_flat ADD COLUMN ratings_negative BIGINT;
+ALTER TABLE mod_flat ADD COLUMN ratings_display TEXT;
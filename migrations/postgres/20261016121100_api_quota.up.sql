@@ -0,0 +1,9 @@
+-- Rate limit headers of mod.io API responses, one row per response that had them
+CREATE TABLE IF NOT EXISTS api_quota (
+    id_sample            BIGINT GENERATED BY DEFAULT AS IDENTITY,
+    date_sampled         TEXT NOT NULL,
+    rate_limit           BIGINT NOT NULL,
+    remaining            BIGINT NOT NULL,
+    retry_after          BIGINT,
+    PRIMARY KEY (id_sample)
+);
@@ -0,0 +1 @@
+ALTER TABLE collection_mod DROP CONSTRAINT collection_mod_id_modfile_fkey;
@@ -0,0 +1,6 @@
+-- Remove rows violating the constraint added below
+UPDATE collection_mod SET id_modfile = NULL WHERE id_modfile NOT IN (SELECT id_modfile FROM modfile);
+
+-- Pinned modfiles must exist
+ALTER TABLE collection_mod ADD CONSTRAINT collection_mod_id_modfile_fkey
+    FOREIGN KEY (id_modfile) REFERENCES modfile (id_modfile) DEFERRABLE INITIALLY DEFERRED;
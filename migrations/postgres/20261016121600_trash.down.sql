@@ -0,0 +1,2 @@
+ALTER TABLE collection DROP COLUMN date_deleted;
+DROP TABLE trash;
@@ -0,0 +1,15 @@
+-- Data removed by destructive commands, kept for a retention window so it can be restored.
+-- Archives are moved to the trash directory, collections are only flagged as deleted
+CREATE TABLE IF NOT EXISTS trash (
+    id_trash             BIGINT GENERATED BY DEFAULT AS IDENTITY,
+    -- archive or collection
+    kind                 TEXT NOT NULL,
+    -- hash of an archive or name of a collection
+    name                 TEXT NOT NULL,
+    reason               TEXT NOT NULL,
+    date_trashed         TEXT NOT NULL,
+    PRIMARY KEY (id_trash)
+);
+
+-- set while the collection is in the trash
+ALTER TABLE collection ADD COLUMN date_deleted TEXT;
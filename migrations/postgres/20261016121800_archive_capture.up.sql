@@ -0,0 +1,9 @@
+-- When and from where each modfile's archive was downloaded, for the archive manifest
+CREATE TABLE IF NOT EXISTS archive_capture (
+    id_modfile           BIGINT NOT NULL,
+    hash_md5             TEXT NOT NULL,
+    size                 BIGINT NOT NULL,
+    url                  TEXT NOT NULL,
+    date_captured        TEXT NOT NULL,
+    PRIMARY KEY (id_modfile)
+);
@@ -0,0 +1,16 @@
+-- Community lists of mods approved on servers, imported from the files their hosts maintain.
+-- Members are not constrained to indexed mods so a list can name mods the index has not seen
+CREATE TABLE IF NOT EXISTS approved_list (
+    id_approved_list     BIGINT GENERATED BY DEFAULT AS IDENTITY,
+    name                 TEXT NOT NULL UNIQUE,
+    source               TEXT NOT NULL,
+    date_imported        TEXT NOT NULL,
+    PRIMARY KEY (id_approved_list)
+);
+
+CREATE TABLE IF NOT EXISTS approved_list_mod (
+    id_approved_list     BIGINT NOT NULL,
+    id_mod               BIGINT NOT NULL,
+    PRIMARY KEY (id_approved_list, id_mod),
+    FOREIGN KEY (id_approved_list) REFERENCES approved_list (id_approved_list) DEFERRABLE INITIALLY DEFERRED
+);
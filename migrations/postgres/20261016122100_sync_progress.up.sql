@@ -0,0 +1,8 @@
+-- Mods already synced by the current sync run, so an interrupted run resumes after them.
+-- Cleared when a run completes
+CREATE TABLE IF NOT EXISTS sync_progress (
+    id_mod               BIGINT NOT NULL,
+    date_updated         BIGINT NOT NULL,
+    date_synced          TEXT NOT NULL,
+    PRIMARY KEY (id_mod)
+);
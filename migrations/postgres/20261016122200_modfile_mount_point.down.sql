@@ -0,0 +1 @@
+ALTER TABLE modfile DROP COLUMN mount_point;
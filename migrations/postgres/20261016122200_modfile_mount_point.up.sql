@@ -0,0 +1,2 @@
+-- Raw mount point of the modfile's pak, before it is normalized into pack_file paths
+ALTER TABLE modfile ADD COLUMN mount_point TEXT;
@@ -0,0 +1,10 @@
+-- Paks install extracted into a game directory, so uninstall removes exactly those files
+CREATE TABLE IF NOT EXISTS installed_pak (
+    game_dir             TEXT NOT NULL,
+    path                 TEXT NOT NULL,
+    id_mod               BIGINT NOT NULL,
+    id_modfile           BIGINT NOT NULL,
+    size                 BIGINT NOT NULL,
+    date_installed       TEXT NOT NULL,
+    PRIMARY KEY (game_dir, path)
+);
@@ -0,0 +1 @@
+ALTER TABLE game_version DROP COLUMN date_released;
@@ -0,0 +1,2 @@
+DROP TABLE game_audio_object;
+DROP TABLE audio_object;
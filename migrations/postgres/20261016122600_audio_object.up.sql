@@ -0,0 +1,27 @@
+-- Objects of Wwise soundbanks and the ids of .wem media, to tell music, voice lines and sound
+-- effects apart when paths are numeric ids. Game banks are kept per game version to find what a
+-- mod's media replaces
+CREATE TABLE IF NOT EXISTS audio_object (
+    id_modfile           BIGINT NOT NULL,
+    path                 TEXT NOT NULL,
+    kind                 TEXT NOT NULL,
+    id_object            BIGINT NOT NULL,
+    id_source            BIGINT,
+    localized            BIGINT NOT NULL,
+    PRIMARY KEY (id_modfile, path, kind, id_object),
+    FOREIGN KEY (path, id_modfile) REFERENCES pack_file (path, id_modfile) DEFERRABLE INITIALLY DEFERRED
+);
+
+CREATE TABLE IF NOT EXISTS game_audio_object (
+    id_game_version      BIGINT NOT NULL,
+    path                 TEXT NOT NULL,
+    kind                 TEXT NOT NULL,
+    id_object            BIGINT NOT NULL,
+    id_source            BIGINT,
+    localized            BIGINT NOT NULL,
+    PRIMARY KEY (id_game_version, path, kind, id_object),
+    FOREIGN KEY (id_game_version) REFERENCES game_version (id_game_version) DEFERRABLE INITIALLY DEFERRED
+);
+
+CREATE INDEX IF NOT EXISTS game_audio_object_id_object ON game_audio_object (id_game_version, id_object);
+CREATE INDEX IF NOT EXISTS game_audio_object_id_source ON game_audio_object (id_game_version, id_source);
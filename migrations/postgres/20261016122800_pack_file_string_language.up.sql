@@ -0,0 +1,3 @@
+-- Culture of the locres file a string comes from, e.g. de. NULL for StringTable entries, which
+-- hold the source text
+ALTER TABLE pack_file_string ADD COLUMN language TEXT;
@@ -0,0 +1,10 @@
+-- Rows of the DataTable and CurveTable packages of a modfile, as JSON objects of their properties.
+-- Packages with unversioned properties only have rows when indexed with USMAP mappings
+CREATE TABLE IF NOT EXISTS data_table_row (
+    id_modfile           BIGINT NOT NULL,
+    path                 TEXT NOT NULL,
+    row_name             TEXT NOT NULL,
+    data                 TEXT NOT NULL,
+    PRIMARY KEY (id_modfile, path, row_name),
+    FOREIGN KEY (path, id_modfile) REFERENCES pack_file (path, id_modfile) DEFERRABLE INITIALLY DEFERRED
+);
@@ -0,0 +1,2 @@
+ALTER TABLE modfile DROP COLUMN virus_positive;
+ALTER TABLE modfile DROP COLUMN virus_status;
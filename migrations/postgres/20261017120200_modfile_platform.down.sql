@@ -0,0 +1 @@
+DROP TABLE modfile_platform;
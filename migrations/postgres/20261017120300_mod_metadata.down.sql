@@ -0,0 +1,3 @@
+ALTER TABLE mod_flat DROP COLUMN metadata_kvp;
+ALTER TABLE mod_flat DROP COLUMN metadata_blob;
+DROP TABLE mod_metadata;
@@ -0,0 +1,12 @@
+-- Metadata authors attach to a mod, as of the last sync: the free form metadata_blob and the
+-- metadata key-value pairs as a JSON object of arrays of values, e.g. {"version": ["1.39"]}.
+-- Only mods with any metadata have a row
+CREATE TABLE IF NOT EXISTS mod_metadata (
+    id_mod               BIGINT NOT NULL PRIMARY KEY,
+    metadata_blob        TEXT,
+    metadata_kvp         TEXT NOT NULL,
+    FOREIGN KEY (id_mod) REFERENCES mod (id_mod) DEFERRABLE INITIALLY DEFERRED
+);
+
+ALTER TABLE mod_flat ADD COLUMN metadata_blob TEXT;
+ALTER TABLE mod_flat ADD COLUMN metadata_kvp TEXT;
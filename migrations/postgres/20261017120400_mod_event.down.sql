@@ -0,0 +1,3 @@
+DROP TABLE mod_event;
+ALTER TABLE mod DROP COLUMN visible;
+ALTER TABLE mod DROP COLUMN status;
@@ -0,0 +1 @@
+DROP INDEX pack_file_path_trgm;
@@ -0,0 +1,4 @@
+-- Trigram index of pack_file paths for substring searches, which otherwise scan every entry
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS pack_file_path_trgm ON pack_file USING gin (LOWER(path) gin_trgm_ops);
@@ -0,0 +1,5 @@
+-- Lowercase path of the entry. The game mounts paths ignoring case, so entries whose paths only
+-- differ in case overwrite each other just like identical paths do
+ALTER TABLE pack_file ADD COLUMN path_lower TEXT GENERATED ALWAYS AS (LOWER(path)) STORED;
+
+CREATE INDEX IF NOT EXISTS pack_file_path_lower ON pack_file (path_lower);
@@ -0,0 +1,18 @@
+-- Packaging problems lint found in the archive of a modfile, one row per modfile and rule
+CREATE TABLE IF NOT EXISTS lint_finding (
+    id_modfile           BIGINT NOT NULL,
+    rule                 TEXT NOT NULL,
+    -- The first occurrence, e.g. the offending path
+    detail               TEXT NOT NULL,
+    occurrences          BIGINT NOT NULL,
+    PRIMARY KEY (id_modfile, rule),
+    FOREIGN KEY (id_modfile) REFERENCES modfile (id_modfile) DEFERRABLE INITIALLY DEFERRED
+);
+
+-- Modfiles lint has checked, with or without findings, so later runs skip them
+CREATE TABLE IF NOT EXISTS lint_scan (
+    id_modfile           BIGINT NOT NULL,
+    date_linted          TEXT NOT NULL,
+    PRIMARY KEY (id_modfile),
+    FOREIGN KEY (id_modfile) REFERENCES modfile (id_modfile) DEFERRABLE INITIALLY DEFERRED
+);
@@ -0,0 +1,9 @@
+-- Tags of a mod on mod.io as of the last sync, e.g. "Gameplay" or "Approved"
+CREATE TABLE IF NOT EXISTS mod_tag (
+    id_mod               BIGINT NOT NULL,
+    tag                  TEXT NOT NULL,
+    PRIMARY KEY (id_mod, tag),
+    FOREIGN KEY (id_mod) REFERENCES mod (id_mod) DEFERRABLE INITIALLY DEFERRED
+);
+
+CREATE INDEX IF NOT EXISTS mod_tag_tag ON mod_tag (tag);
@@ -0,0 +1,4 @@
+DELETE FROM webhook WHERE tag IS NOT NULL;
+ALTER TABLE webhook DROP CONSTRAINT webhook_check;
+ALTER TABLE webhook DROP COLUMN tag;
+ALTER TABLE webhook ADD CONSTRAINT webhook_check CHECK ((id_mod IS NULL) != (category IS NULL));
@@ -0,0 +1,5 @@
+-- Webhooks can also be registered for every mod with a mod.io tag
+ALTER TABLE webhook ADD COLUMN tag TEXT;
+ALTER TABLE webhook DROP CONSTRAINT webhook_check;
+ALTER TABLE webhook ADD CONSTRAINT webhook_check
+    CHECK ((id_mod IS NOT NULL)::int + (category IS NOT NULL)::int + (tag IS NOT NULL)::int = 1);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS download (
+    id_modfile           INTEGER NOT NULL,
+    state                TEXT NOT NULL,
+    owner                TEXT,
+    date_updated         TEXT NOT NULL,
+    error                TEXT,
+    PRIMARY KEY (id_modfile)
+) STRICT;
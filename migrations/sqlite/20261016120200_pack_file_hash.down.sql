@@ -0,0 +1 @@
+ALTER TABLE pack_file DROP COLUMN hash;
@@ -0,0 +1,2 @@
+ALTER TABLE collection_mod DROP COLUMN note;
+ALTER TABLE collection_mod DROP COLUMN id_modfile;
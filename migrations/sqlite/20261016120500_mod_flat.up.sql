@@ -0,0 +1,17 @@
+-- Denormalized copy of mod x current modfile for spreadsheets and BI tools, rebuilt by Flatten
+-- and after every sync. Never read by the indexer itself.
+CREATE TABLE IF NOT EXISTS mod_flat (
+    id_mod               INTEGER NOT NULL,
+    name                 TEXT NOT NULL,
+    name_id              TEXT NOT NULL,
+    summary              TEXT NOT NULL,
+    id_modfile           INTEGER,
+    version              TEXT,
+    filename             TEXT,
+    date_added           TEXT,
+    hash_md5             TEXT,
+    pack_files           INTEGER NOT NULL,
+    assets               INTEGER NOT NULL,
+    date_flattened       TEXT NOT NULL,
+    PRIMARY KEY (id_mod)
+) STRICT;
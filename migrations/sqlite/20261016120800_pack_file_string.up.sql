@@ -0,0 +1,10 @@
+-- Localized strings of .locres and StringTable entries, for searching the text mods add or change
+CREATE TABLE IF NOT EXISTS pack_file_string (
+    id_modfile           INTEGER NOT NULL,
+    path                 TEXT NOT NULL,
+    namespace            TEXT NOT NULL,
+    key                  TEXT NOT NULL,
+    text                 TEXT NOT NULL,
+    PRIMARY KEY (id_modfile, path, namespace, key),
+    FOREIGN KEY (path, id_modfile) REFERENCES pack_file (path, id_modfile) DEFERRABLE INITIALLY DEFERRED
+) STRICT;
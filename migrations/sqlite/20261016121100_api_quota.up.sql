@@ -0,0 +1,9 @@
+-- Rate limit headers of mod.io API responses, one row per response that had them
+CREATE TABLE IF NOT EXISTS api_quota (
+    id_sample            INTEGER NOT NULL,
+    date_sampled         TEXT NOT NULL,
+    rate_limit           INTEGER NOT NULL,
+    remaining            INTEGER NOT NULL,
+    retry_after          INTEGER,
+    PRIMARY KEY (id_sample)
+) STRICT;
@@ -0,0 +1,16 @@
+-- Comments on mods, indexed by syncs run with --with-comments
+CREATE TABLE IF NOT EXISTS mod_comment (
+    id_comment           INTEGER NOT NULL,
+    id_mod               INTEGER NOT NULL,
+    -- comment this one replies to, 0 for top level comments
+    id_reply             INTEGER NOT NULL,
+    -- position in the thread, e.g. 01 for the first top level comment and 01.02 for its second reply
+    thread_position      TEXT NOT NULL,
+    author               TEXT NOT NULL,
+    id_author            INTEGER NOT NULL,
+    date_added           TEXT NOT NULL,
+    karma                INTEGER NOT NULL,
+    content              TEXT NOT NULL,
+    PRIMARY KEY (id_comment),
+    FOREIGN KEY (id_mod) REFERENCES mod (id_mod) DEFERRABLE INITIALLY DEFERRED
+) STRICT;
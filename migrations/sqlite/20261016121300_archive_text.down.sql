@@ -0,0 +1,2 @@
+DROP TABLE archive_text_scan;
+DROP TABLE archive_text;
@@ -0,0 +1,16 @@
+-- Text-like entries (ini, json, cfg, ...) of archives and the strings of their locres files as
+-- text, cached by Grep so archives are only opened once. Keyed by archive hash like the mods
+-- directory
+CREATE TABLE IF NOT EXISTS archive_text (
+    hash_md5             TEXT NOT NULL,
+    path                 TEXT NOT NULL,
+    content              TEXT NOT NULL,
+    PRIMARY KEY (hash_md5, path)
+) STRICT;
+
+-- Archives whose text has been cached, including those without any text entries
+CREATE TABLE IF NOT EXISTS archive_text_scan (
+    hash_md5             TEXT NOT NULL,
+    date_scanned         TEXT NOT NULL,
+    PRIMARY KEY (hash_md5)
+) STRICT;
@@ -0,0 +1,9 @@
+-- Archives in the content addressed store and how many modfiles refer to each. Modfiles with
+-- identical uploads share one object, across mods as well as versions
+CREATE TABLE IF NOT EXISTS store_object (
+    hash_md5             TEXT NOT NULL,
+    size                 INTEGER NOT NULL,
+    refs                 INTEGER NOT NULL,
+    date_stored          TEXT NOT NULL,
+    PRIMARY KEY (hash_md5)
+) STRICT;
@@ -0,0 +1,14 @@
+CREATE TABLE collection_mod_old (
+    id_collection        INTEGER NOT NULL,
+    id_mod               INTEGER NOT NULL,
+    date_added           TEXT NOT NULL,
+    id_modfile           INTEGER,
+    note                 TEXT,
+    PRIMARY KEY (id_collection, id_mod),
+    FOREIGN KEY (id_collection) REFERENCES collection (id_collection) DEFERRABLE INITIALLY DEFERRED,
+    FOREIGN KEY (id_mod) REFERENCES mod (id_mod) DEFERRABLE INITIALLY DEFERRED
+) STRICT;
+INSERT INTO collection_mod_old(id_collection, id_mod, date_added, id_modfile, note)
+    SELECT id_collection, id_mod, date_added, id_modfile, note FROM collection_mod;
+DROP TABLE collection_mod;
+ALTER TABLE collection_mod_old RENAME TO collection_mod;
@@ -0,0 +1,31 @@
+-- Remove rows violating foreign keys, which SQLite only enforces when the connection enables them
+DELETE FROM collection_mod WHERE id_mod NOT IN (SELECT id_mod FROM mod);
+DELETE FROM collection_mod WHERE id_collection NOT IN (SELECT id_collection FROM collection);
+DELETE FROM mod_media WHERE id_mod NOT IN (SELECT id_mod FROM mod);
+DELETE FROM mod_comment WHERE id_mod NOT IN (SELECT id_mod FROM mod);
+DELETE FROM modfile WHERE id_mod NOT IN (SELECT id_mod FROM mod);
+UPDATE mod SET id_modfile = NULL WHERE id_modfile NOT IN (SELECT id_modfile FROM modfile);
+UPDATE collection_mod SET id_modfile = NULL WHERE id_modfile NOT IN (SELECT id_modfile FROM modfile);
+DELETE FROM pack_file WHERE id_modfile NOT IN (SELECT id_modfile FROM modfile);
+DELETE FROM pack_file_string
+WHERE NOT EXISTS (SELECT 1 FROM pack_file
+                  WHERE pack_file.path = pack_file_string.path
+                    AND pack_file.id_modfile = pack_file_string.id_modfile);
+
+-- Pinned modfiles must exist. SQLite cannot add a constraint to an existing table so it is rebuilt
+CREATE TABLE collection_mod_new (
+    id_collection        INTEGER NOT NULL,
+    id_mod               INTEGER NOT NULL,
+    date_added           TEXT NOT NULL,
+    -- modfile a curator pinned the mod to, NULL to follow the current modfile
+    id_modfile           INTEGER,
+    note                 TEXT,
+    PRIMARY KEY (id_collection, id_mod),
+    FOREIGN KEY (id_collection) REFERENCES collection (id_collection) DEFERRABLE INITIALLY DEFERRED,
+    FOREIGN KEY (id_mod) REFERENCES mod (id_mod) DEFERRABLE INITIALLY DEFERRED,
+    FOREIGN KEY (id_modfile) REFERENCES modfile (id_modfile) DEFERRABLE INITIALLY DEFERRED
+) STRICT;
+INSERT INTO collection_mod_new(id_collection, id_mod, date_added, id_modfile, note)
+    SELECT id_collection, id_mod, date_added, id_modfile, note FROM collection_mod;
+DROP TABLE collection_mod;
+ALTER TABLE collection_mod_new RENAME TO collection_mod;
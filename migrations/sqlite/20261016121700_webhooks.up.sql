@@ -0,0 +1,11 @@
+-- Webhooks registered for the events of one mod or of every mod in a category
+CREATE TABLE IF NOT EXISTS webhook (
+    id_webhook           INTEGER NOT NULL,
+    url                  TEXT NOT NULL,
+    id_mod               INTEGER,
+    category             TEXT,
+    date_created         TEXT NOT NULL,
+    PRIMARY KEY (id_webhook),
+    FOREIGN KEY (id_mod) REFERENCES mod (id_mod) DEFERRABLE INITIALLY DEFERRED,
+    CHECK ((id_mod IS NULL) != (category IS NULL))
+) STRICT;
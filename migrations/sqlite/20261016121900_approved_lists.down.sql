@@ -0,0 +1,2 @@
+DROP TABLE approved_list_mod;
+DROP TABLE approved_list;
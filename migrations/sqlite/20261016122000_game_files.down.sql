@@ -0,0 +1,2 @@
+DROP TABLE game_file;
+DROP TABLE game_version;
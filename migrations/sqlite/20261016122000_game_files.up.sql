@@ -0,0 +1,16 @@
+-- Listings of the base game's paks, one per game version, to find the mods an update breaks
+CREATE TABLE IF NOT EXISTS game_version (
+    id_game_version      INTEGER NOT NULL,
+    version              TEXT NOT NULL UNIQUE,
+    source               TEXT NOT NULL,
+    date_imported        TEXT NOT NULL,
+    PRIMARY KEY (id_game_version)
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS game_file (
+    id_game_version      INTEGER NOT NULL,
+    path                 TEXT NOT NULL,
+    hash                 TEXT NOT NULL,
+    PRIMARY KEY (id_game_version, path),
+    FOREIGN KEY (id_game_version) REFERENCES game_version (id_game_version) DEFERRABLE INITIALLY DEFERRED
+) STRICT;
@@ -0,0 +1,3 @@
+-- 1 if the entry replaces an asset of the latest indexed game version, 0 if it adds new content,
+-- NULL until a game version is indexed with index-game
+ALTER TABLE pack_file ADD COLUMN vanilla_override INTEGER;
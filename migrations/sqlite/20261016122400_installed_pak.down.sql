@@ -0,0 +1 @@
+DROP TABLE installed_pak;
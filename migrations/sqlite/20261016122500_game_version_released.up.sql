@@ -0,0 +1,3 @@
+-- When the game version was released, set for versions recorded with record-game-version. Versions
+-- without one are dated by when their paks were indexed
+ALTER TABLE game_version ADD COLUMN date_released TEXT;
@@ -0,0 +1,16 @@
+-- References from the packages of a modfile to objects of other packages, read from their import
+-- tables. ref_path is the game path of the referenced package without extension, NULL for
+-- native /Script packages
+CREATE TABLE IF NOT EXISTS asset_ref (
+    id_modfile           INTEGER NOT NULL,
+    path                 TEXT NOT NULL,
+    ref_package          TEXT NOT NULL,
+    ref_object           TEXT NOT NULL,
+    ref_class            TEXT NOT NULL,
+    ref_path             TEXT,
+    parent               INTEGER NOT NULL,
+    PRIMARY KEY (id_modfile, path, ref_package, ref_object),
+    FOREIGN KEY (path, id_modfile) REFERENCES pack_file (path, id_modfile) DEFERRABLE INITIALLY DEFERRED
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS asset_ref_ref_path ON asset_ref (ref_path);
@@ -0,0 +1 @@
+ALTER TABLE pack_file_string DROP COLUMN language;
@@ -0,0 +1,3 @@
+ALTER TABLE mod_flat DROP COLUMN maturity_option;
+ALTER TABLE mod DROP COLUMN community_options;
+ALTER TABLE mod DROP COLUMN maturity_option;
@@ -0,0 +1,5 @@
+-- Maturity flags (1 alcohol, 2 drugs, 4 violence, 8 explicit) and community options bitmask of a
+-- mod as of the last sync. NULL until the mod has been synced again
+ALTER TABLE mod ADD COLUMN maturity_option INTEGER;
+ALTER TABLE mod ADD COLUMN community_options INTEGER;
+ALTER TABLE mod_flat ADD COLUMN maturity_option INTEGER;
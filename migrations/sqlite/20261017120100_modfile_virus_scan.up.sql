@@ -0,0 +1,5 @@
+-- Result of mod.io's virus scan of a modfile as of the last sync. virus_status is the state of the
+-- scan (0 not scanned, 1 complete, 2 in progress, 3 too large, 4 file not found, 5 error) and
+-- virus_positive what it found (0 nothing, 1 malicious, 2 potentially harmful files)
+ALTER TABLE modfile ADD COLUMN virus_status INTEGER;
+ALTER TABLE modfile ADD COLUMN virus_positive INTEGER;
@@ -0,0 +1,9 @@
+-- Platforms a modfile was uploaded for and whether mod.io approved it there: pending, approved or
+-- denied. Console approval is moderated, so console approved mods are a curated subset
+CREATE TABLE IF NOT EXISTS modfile_platform (
+    id_modfile           INTEGER NOT NULL,
+    platform             TEXT NOT NULL,
+    status               TEXT NOT NULL,
+    PRIMARY KEY (id_modfile, platform),
+    FOREIGN KEY (id_modfile) REFERENCES modfile (id_modfile) DEFERRABLE INITIALLY DEFERRED
+) STRICT;
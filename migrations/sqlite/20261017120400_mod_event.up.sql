@@ -0,0 +1,18 @@
+-- mod.io status (0 not accepted, 1 accepted, 3 deleted) and visibility (0 hidden, 1 public) of a
+-- mod as of the last sync. NULL until the mod has been synced again
+ALTER TABLE mod ADD COLUMN status INTEGER;
+ALTER TABLE mod ADD COLUMN visible INTEGER;
+
+-- Append-only log of changes a sync observed to a mod: field is first_seen, status, visible,
+-- name_id or modfile. old_value is NULL for first_seen and for a mod without a modfile
+CREATE TABLE IF NOT EXISTS mod_event (
+    id_event             INTEGER NOT NULL,
+    id_mod               INTEGER NOT NULL,
+    date_observed        TEXT NOT NULL,
+    field                TEXT NOT NULL,
+    old_value            TEXT,
+    new_value            TEXT,
+    PRIMARY KEY (id_event)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS mod_event_id_mod ON mod_event (id_mod);
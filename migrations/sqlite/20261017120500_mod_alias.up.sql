@@ -0,0 +1,9 @@
+-- Former name_ids of mods, so links and manifests using a slug from before a rename still resolve
+CREATE TABLE IF NOT EXISTS mod_alias (
+    name_id              TEXT NOT NULL,
+    id_mod               INTEGER NOT NULL,
+    date_renamed         TEXT NOT NULL,
+    PRIMARY KEY (name_id)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS mod_alias_id_mod ON mod_alias (id_mod);
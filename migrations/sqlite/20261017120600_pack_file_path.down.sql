@@ -0,0 +1,4 @@
+DROP TRIGGER pack_file_path_update;
+DROP TRIGGER pack_file_path_delete;
+DROP TRIGGER pack_file_path_insert;
+DROP TABLE pack_file_path;
@@ -0,0 +1,19 @@
+-- Trigram index of pack_file paths for substring searches, which otherwise scan every entry.
+-- Contentless to not store every path twice, its rowids are those of pack_file. VACUUM may
+-- renumber pack_file rowids, so db maintain fills it again afterwards
+CREATE VIRTUAL TABLE IF NOT EXISTS pack_file_path USING fts5(path, content='', tokenize='trigram');
+
+INSERT INTO pack_file_path(rowid, path) SELECT rowid, path FROM pack_file;
+
+CREATE TRIGGER IF NOT EXISTS pack_file_path_insert AFTER INSERT ON pack_file BEGIN
+    INSERT INTO pack_file_path(rowid, path) VALUES (new.rowid, new.path);
+END;
+
+CREATE TRIGGER IF NOT EXISTS pack_file_path_delete AFTER DELETE ON pack_file BEGIN
+    INSERT INTO pack_file_path(pack_file_path, rowid, path) VALUES ('delete', old.rowid, old.path);
+END;
+
+CREATE TRIGGER IF NOT EXISTS pack_file_path_update AFTER UPDATE OF path ON pack_file BEGIN
+    INSERT INTO pack_file_path(pack_file_path, rowid, path) VALUES ('delete', old.rowid, old.path);
+    INSERT INTO pack_file_path(rowid, path) VALUES (new.rowid, new.path);
+END;
@@ -0,0 +1,2 @@
+DROP INDEX pack_file_path_lower;
+ALTER TABLE pack_file DROP COLUMN path_lower;
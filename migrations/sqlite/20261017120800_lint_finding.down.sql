@@ -0,0 +1,2 @@
+DROP TABLE lint_scan;
+DROP TABLE lint_finding;
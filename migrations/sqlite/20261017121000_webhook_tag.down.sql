@@ -0,0 +1,15 @@
+DELETE FROM webhook WHERE tag IS NOT NULL;
+CREATE TABLE webhook_old (
+    id_webhook           INTEGER NOT NULL,
+    url                  TEXT NOT NULL,
+    id_mod               INTEGER,
+    category             TEXT,
+    date_created         TEXT NOT NULL,
+    PRIMARY KEY (id_webhook),
+    FOREIGN KEY (id_mod) REFERENCES mod (id_mod) DEFERRABLE INITIALLY DEFERRED,
+    CHECK ((id_mod IS NULL) != (category IS NULL))
+) STRICT;
+INSERT INTO webhook_old(id_webhook, url, id_mod, category, date_created)
+    SELECT id_webhook, url, id_mod, category, date_created FROM webhook;
+DROP TABLE webhook;
+ALTER TABLE webhook_old RENAME TO webhook;
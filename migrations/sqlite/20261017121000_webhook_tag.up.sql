@@ -0,0 +1,17 @@
+-- Webhooks can also be registered for every mod with a mod.io tag. SQLite cannot change the
+-- constraint of an existing table so it is rebuilt
+CREATE TABLE webhook_new (
+    id_webhook           INTEGER NOT NULL,
+    url                  TEXT NOT NULL,
+    id_mod               INTEGER,
+    category             TEXT,
+    tag                  TEXT,
+    date_created         TEXT NOT NULL,
+    PRIMARY KEY (id_webhook),
+    FOREIGN KEY (id_mod) REFERENCES mod (id_mod) DEFERRABLE INITIALLY DEFERRED,
+    CHECK ((id_mod IS NOT NULL) + (category IS NOT NULL) + (tag IS NOT NULL) = 1)
+) STRICT;
+INSERT INTO webhook_new(id_webhook, url, id_mod, category, date_created)
+    SELECT id_webhook, url, id_mod, category, date_created FROM webhook;
+DROP TABLE webhook;
+ALTER TABLE webhook_new RENAME TO webhook;
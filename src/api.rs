@@ -0,0 +1,360 @@
+use anyhow::{bail, Context, Result};
+use futures::future::BoxFuture;
+use modio::{Credentials, Modio};
+use reqwest_middleware::Next;
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::{info, warn};
+
+use std::env;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+/// mod.io game id of Deep Rock Galactic.
+pub const DRG: u32 = 2475;
+
+/// Build a mod.io client authenticated with [`access_token`], or with only the API key in
+/// `MODIO_KEY` when there is no token. The API key is enough to list mods and download public
+/// files but not for anything done as a user, such as indexing drafts.
+pub fn client() -> Result<Modio> {
+    match (access_token()?, api_key()) {
+        (Some(token), api_key) => {
+            client_with(Credentials::with_token(api_key.unwrap_or_default(), token))
+        }
+        (None, Some(api_key)) => client_with(Credentials::new(api_key)),
+        // a mock API doesn't check the key
+        (None, None) if API_BASE.get().is_some() => client_with(Credentials::new("mock")),
+        (None, None) => bail!(
+            "not logged in, run `login`, set MODIO_ACCESS_TOKEN to a token from \
+             https://mod.io/me/access or set MODIO_KEY for read-only access"
+        ),
+    }
+}
+
+/// Build a mod.io client authenticated with only the API key in `MODIO_KEY`, as needed to log in.
+pub fn key_client() -> Result<Modio> {
+    let api_key =
+        api_key().context("MODIO_KEY must be set, get an API key at https://mod.io/me/access")?;
+    client_with(Credentials::new(api_key))
+}
+
+/// Value of an environment variable, `None` if unset or blank as `.env.example` leaves them.
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// The API key in `MODIO_KEY`.
+pub fn api_key() -> Option<String> {
+    non_empty_var("MODIO_KEY")
+}
+
+static API_BASE: OnceLock<String> = OnceLock::new();
+
+/// Send every request to `url` instead of `https://api.mod.io/v1`, e.g. a `mock-api` server.
+pub fn set_api_base(url: String) {
+    API_BASE.set(url).ok();
+}
+
+fn client_with(credentials: Credentials) -> Result<Modio> {
+    let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+        .with(record_quota)
+        .build();
+    let modio = Modio::new(credentials, client)?;
+    Ok(match API_BASE.get() {
+        Some(url) => modio.host(url),
+        None => modio,
+    })
+}
+
+/// Where `login` stores the access token.
+pub fn token_path() -> Result<PathBuf> {
+    let dir = dirs::config_dir().context("no configuration directory for this platform")?;
+    Ok(dir.join("drg-modio-index").join("token"))
+}
+
+/// The OAuth access token in `MODIO_ACCESS_TOKEN` if set, or else the one stored by `login`.
+/// `None` if there is neither.
+pub fn access_token() -> Result<Option<String>> {
+    if let Some(token) = non_empty_var("MODIO_ACCESS_TOKEN") {
+        return Ok(Some(token));
+    }
+    let path = token_path()?;
+    match std::fs::read_to_string(&path) {
+        Ok(token) => Ok(Some(token.trim().to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Store `token` for later runs, readable only by the current user.
+pub fn save_token(token: &str) -> Result<PathBuf> {
+    let path = token_path()?;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(&path)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    std::io::Write::write_all(&mut file, token.as_bytes())?;
+    Ok(path)
+}
+
+/// API quota as reported by the rate limit headers of a mod.io response.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaSample {
+    pub date_sampled: String,
+    /// Requests allowed per window
+    pub rate_limit: i64,
+    pub remaining: i64,
+    /// Seconds until requests are allowed again, only sent once the quota is used up
+    pub retry_after: Option<i64>,
+}
+
+/// Samples taken since they were last saved by [`save_quota`].
+static QUOTA_SAMPLES: Mutex<Vec<QuotaSample>> = Mutex::new(vec![]);
+
+/// Middleware that samples the rate limit headers of every response.
+fn record_quota<'a>(
+    req: reqwest::Request,
+    extensions: &'a mut task_local_extensions::Extensions,
+    next: Next<'a>,
+) -> BoxFuture<'a, reqwest_middleware::Result<reqwest::Response>> {
+    Box::pin(async move {
+        let res = next
+            .run(req, extensions)
+            .await
+            .inspect_err(|_| crate::metrics::record_api_error())?;
+        if res.status().is_client_error() || res.status().is_server_error() {
+            crate::metrics::record_api_error();
+        }
+        let header = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<i64>().ok())
+        };
+        if let (Some(rate_limit), Some(remaining)) =
+            (header("x-ratelimit-limit"), header("x-ratelimit-remaining"))
+        {
+            let sample = QuotaSample {
+                date_sampled: chrono::Utc::now().to_rfc3339(),
+                rate_limit,
+                remaining,
+                retry_after: header("x-ratelimit-retryafter"),
+            };
+            crate::metrics::record_rate_limit(remaining);
+            if remaining == 0 {
+                warn!(retry_after = sample.retry_after, "mod.io API quota used up");
+            }
+            QUOTA_SAMPLES.lock().unwrap().push(sample);
+        }
+        Ok(res)
+    })
+}
+
+/// Store the quota samples taken by this process.
+pub async fn save_quota(pool: &AnyPool) -> Result<()> {
+    let samples = std::mem::take(&mut *QUOTA_SAMPLES.lock().unwrap());
+    if samples.is_empty() {
+        return Ok(());
+    }
+    let mut tx = pool.begin().await?;
+    for sample in samples {
+        sqlx::query(
+            "INSERT INTO api_quota(date_sampled, rate_limit, remaining, retry_after)
+             VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&sample.date_sampled)
+        .bind(sample.rate_limit)
+        .bind(sample.remaining)
+        .bind(sample.retry_after)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// The most recently stored quota sample.
+pub async fn latest_quota(pool: &AnyPool) -> Result<Option<QuotaSample>> {
+    let row: Option<(String, i64, i64, Option<i64>)> = sqlx::query_as(
+        "SELECT date_sampled, rate_limit, remaining, retry_after FROM api_quota
+         ORDER BY id_sample DESC LIMIT 1",
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(
+        |(date_sampled, rate_limit, remaining, retry_after)| QuotaSample {
+            date_sampled,
+            rate_limit,
+            remaining,
+            retry_after,
+        },
+    ))
+}
+
+/// Format a mod.io unix timestamp the way dates are stored in the index.
+pub fn timestamp(secs: u64) -> Result<String> {
+    i64::try_from(secs)
+        .ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .map(|date| date.to_rfc3339())
+        .with_context(|| format!("timestamp {secs} is out of range"))
+}
+
+/// Public mod.io page of a mod.
+pub fn mod_url(name_id: &str) -> String {
+    format!("https://mod.io/g/drg/m/{name_id}")
+}
+
+/// mod.io search filters narrowing the mods a sync lists, for targeted refreshes that do not
+/// enumerate every mod.
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct ModFilters {
+    /// Only mods with these ids, comma separated
+    #[clap(long, value_delimiter = ',')]
+    pub ids: Vec<u32>,
+    /// Only mods whose name contains this text
+    #[clap(long, value_parser)]
+    pub name_contains: Option<String>,
+    /// Only mods with this tag, repeat to accept any of several
+    #[clap(long, value_parser)]
+    pub tag: Vec<String>,
+    /// Only mods updated at or after this date: YYYY-MM-DD, RFC 3339 or a unix timestamp
+    #[clap(long, value_parser = parse_date)]
+    pub updated_since: Option<u64>,
+    /// Leave out mods flagged with any mature content
+    #[clap(long, conflicts_with = "only_mature")]
+    pub hide_mature: bool,
+    /// Only mods flagged with mature content
+    #[clap(long)]
+    pub only_mature: bool,
+    /// Only mods the logged in user is subscribed to
+    #[clap(long)]
+    pub subscribed: bool,
+}
+
+impl ModFilters {
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+            && self.name_contains.is_none()
+            && self.tag.is_empty()
+            && self.updated_since.is_none()
+            && !self.hide_mature
+            && !self.only_mature
+            && !self.subscribed
+    }
+
+    /// Every mod the logged in user is subscribed to, as synced by `sync-subscribed`.
+    pub fn subscribed() -> Self {
+        ModFilters {
+            subscribed: true,
+            ..Default::default()
+        }
+    }
+
+    fn filter(&self) -> modio::filter::Filter {
+        use modio::filter::prelude::*;
+        use modio::mods::filters::{DateUpdated, Id, MaturityOption, Name, Tags, Visible};
+
+        let mut filter = Visible::_in(vec![0, 1]);
+        if !self.ids.is_empty() {
+            filter = filter.and(Id::_in(self.ids.clone()));
+        }
+        if let Some(text) = &self.name_contains {
+            filter = filter.and(Name::like(format!("*{text}*")));
+        }
+        if !self.tag.is_empty() {
+            filter = filter.and(Tags::_in(self.tag.clone()));
+        }
+        if let Some(date) = self.updated_since {
+            filter = filter.and(DateUpdated::ge(date));
+        }
+        if self.hide_mature {
+            filter = filter.and(MaturityOption::eq(0));
+        } else if self.only_mature {
+            filter = filter.and(MaturityOption::gt(0));
+        }
+        filter
+    }
+}
+
+/// Unix timestamp of a date given as YYYY-MM-DD (midnight UTC), RFC 3339 or a unix timestamp.
+pub fn parse_date(s: &str) -> Result<u64, String> {
+    if let Ok(secs) = s.parse::<u64>() {
+        return match timestamp(secs) {
+            Ok(_) => Ok(secs),
+            Err(_) => Err(format!("{s:?} is too far in the future")),
+        };
+    }
+    let date = chrono::DateTime::parse_from_rfc3339(s)
+        .map(|d| d.timestamp())
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(|d| d.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp())
+        })
+        .map_err(|_| format!("{s:?} is not a YYYY-MM-DD or RFC 3339 date"))?;
+    date.try_into().map_err(|_| format!("{s:?} is before 1970"))
+}
+
+/// Fetch every visible and hidden DRG mod.
+pub async fn mod_list(modio: &Modio) -> Result<Vec<modio::mods::Mod>> {
+    search_mods(modio, &ModFilters::default()).await
+}
+
+/// Fetch the visible and hidden DRG mods matching `filters`.
+pub async fn search_mods(modio: &Modio, filters: &ModFilters) -> Result<Vec<modio::mods::Mod>> {
+    if filters.subscribed {
+        if access_token()?.is_none() {
+            bail!("listing subscriptions needs a logged in user, run `login` or set MODIO_ACCESS_TOKEN");
+        }
+        info!("Grabbing subscriptions...");
+        let mods = modio
+            .user()
+            .subscriptions(filters.filter())
+            .collect()
+            .await?
+            .into_iter()
+            .filter(|m| m.game_id == DRG)
+            .collect::<Vec<_>>();
+        info!("Subscriptions obtained: {} mods", mods.len());
+        return Ok(mods);
+    }
+    info!("Grabbing mod list...");
+    let mods = modio
+        .game(DRG)
+        .mods()
+        .search(filters.filter())
+        .collect()
+        .await?;
+    info!("Mod list obtained: {} mods", mods.len());
+    Ok(mods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(0).unwrap(), "1970-01-01T00:00:00+00:00");
+        assert_eq!(timestamp(1704067200).unwrap(), "2024-01-01T00:00:00+00:00");
+        assert!(timestamp(99999999999999999).is_err());
+        assert!(timestamp(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("1704067200"), Ok(1704067200));
+        assert_eq!(parse_date("2024-01-01"), Ok(1704067200));
+        assert_eq!(parse_date("2024-01-01T01:00:00+01:00"), Ok(1704067200));
+        assert!(parse_date("1969-12-31").is_err());
+        assert!(parse_date("99999999999999999").is_err());
+        assert!(parse_date("yesterday").is_err());
+    }
+}
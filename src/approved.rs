@@ -0,0 +1,509 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::warn;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Number of shared paths listed per conflicting pair before the rest are counted.
+const CONFLICT_SAMPLE: usize = 5;
+
+#[derive(Debug, Serialize)]
+pub struct ApprovedList {
+    pub name: String,
+    pub source: String,
+    pub date_imported: String,
+    pub mods: i64,
+    /// Members that are not in the index
+    pub unindexed: i64,
+}
+
+/// Result of importing an approved list.
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub list: String,
+    pub imported: u64,
+    /// Mod ids in the list that are not in the index yet
+    pub unindexed: Vec<i64>,
+    /// name_ids that could not be resolved to a mod id
+    pub unresolved: Vec<String>,
+}
+
+impl std::fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Imported {} mods into {}", self.imported, self.list)?;
+        if !self.unindexed.is_empty() {
+            let ids = self
+                .unindexed
+                .iter()
+                .map(i64::to_string)
+                .collect::<Vec<_>>();
+            write!(f, "\nnot indexed yet: {}", ids.join(", "))?;
+        }
+        if !self.unresolved.is_empty() {
+            write!(f, "\nunknown name_ids: {}", self.unresolved.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of removing an approved list.
+#[derive(Debug, Serialize)]
+pub struct RemoveReport {
+    pub list: String,
+    /// Whether there was a list of that name
+    pub removed: bool,
+    /// Members the list had
+    pub mods: u64,
+}
+
+impl std::fmt::Display for RemoveReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.removed {
+            write!(f, "Removed {} with {} mods", self.list, self.mods)
+        } else {
+            write!(f, "No approved list named {:?}, nothing removed", self.list)
+        }
+    }
+}
+
+/// Mod references in an approved list file. JSON files hold an array of mod ids or name_ids,
+/// either at the top level or under `mods`, whose elements may also be objects with an `id` or
+/// `name_id`. Any other file is read as CSV with the reference in the first column, skipping
+/// blank lines, `#` comments and a header.
+fn parse(path: &Path, contents: &str) -> Result<Vec<String>> {
+    let is_json = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    if !is_json {
+        return Ok(contents
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let first = line
+                    .split([',', ';', '\t'])
+                    .next()?
+                    .trim()
+                    .trim_matches('"');
+                // a header names the column rather than a mod
+                if i == 0 && matches!(first, "id" | "id_mod" | "mod_id" | "name_id" | "mod") {
+                    return None;
+                }
+                Some(first.to_string()).filter(|r| !r.is_empty())
+            })
+            .collect());
+    }
+
+    let json: serde_json::Value = serde_json::from_str(contents)?;
+    let mods = match &json {
+        serde_json::Value::Object(object) => object.get("mods"),
+        _ => Some(&json),
+    }
+    .and_then(serde_json::Value::as_array)
+    .context("expected an array of mods, or an object with a \"mods\" array")?;
+    mods.iter()
+        .map(|m| {
+            let reference = match m {
+                serde_json::Value::Object(object) => object
+                    .get("id")
+                    .or_else(|| object.get("id_mod"))
+                    .or_else(|| object.get("name_id")),
+                _ => Some(m),
+            };
+            match reference {
+                Some(serde_json::Value::Number(n)) => Ok(n.to_string()),
+                Some(serde_json::Value::String(s)) => Ok(s.clone()),
+                _ => bail!("unrecognized mod entry {m}"),
+            }
+        })
+        .collect()
+}
+
+/// Import the approved list in `file` as `name`, replacing the list if it was imported before.
+/// Mod ids are kept even if the index has not seen them, name_ids have to be indexed.
+pub async fn import(pool: &AnyPool, name: &str, file: &Path) -> Result<ImportReport> {
+    let contents = std::fs::read_to_string(file)
+        .with_context(|| format!("failed to read {}", file.display()))?;
+    let references = parse(file, &contents)
+        .with_context(|| format!("{} is not an approved list", file.display()))?;
+
+    let mut report = ImportReport {
+        list: name.to_string(),
+        imported: 0,
+        unindexed: vec![],
+        unresolved: vec![],
+    };
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO approved_list(name, source, date_imported) VALUES ($1, $2, $3)
+         ON CONFLICT(name) DO UPDATE SET source = excluded.source, date_imported = excluded.date_imported",
+    )
+    .bind(name)
+    .bind(file.display().to_string())
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&mut *tx)
+    .await?;
+    let id_list: i64 =
+        sqlx::query_scalar("SELECT id_approved_list FROM approved_list WHERE name = $1")
+            .bind(name)
+            .fetch_one(&mut *tx)
+            .await?;
+    sqlx::query("DELETE FROM approved_list_mod WHERE id_approved_list = $1")
+        .bind(id_list)
+        .execute(&mut *tx)
+        .await?;
+
+    for reference in references {
+        let indexed: Option<i64> = sqlx::query_scalar(
+            "SELECT id_mod FROM (
+                 SELECT id_mod, 0 AS renamed FROM mod WHERE CAST(id_mod AS TEXT) = $1 OR name_id = $1
+                 UNION ALL
+                 SELECT id_mod, 1 AS renamed FROM mod_alias WHERE name_id = $1
+             ) AS found ORDER BY renamed LIMIT 1",
+        )
+        .bind(&reference)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let id_mod = match (indexed, reference.parse::<i64>()) {
+            (Some(id_mod), _) => id_mod,
+            (None, Ok(id_mod)) => {
+                report.unindexed.push(id_mod);
+                id_mod
+            }
+            (None, Err(_)) => {
+                warn!(name_id = reference, "Skipping mod that is not in the index");
+                report.unresolved.push(reference);
+                continue;
+            }
+        };
+        sqlx::query(
+            "INSERT INTO approved_list_mod(id_approved_list, id_mod) VALUES ($1, $2)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(id_list)
+        .bind(id_mod)
+        .execute(&mut *tx)
+        .await?;
+        report.imported += 1;
+    }
+    tx.commit().await?;
+    Ok(report)
+}
+
+/// Id of the approved list `name`.
+pub async fn resolve(pool: &AnyPool, name: &str) -> Result<i64> {
+    let id: Option<i64> =
+        sqlx::query_scalar("SELECT id_approved_list FROM approved_list WHERE name = $1")
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+    id.with_context(|| format!("no approved list named {name:?}, import it first"))
+}
+
+/// Remove the approved list `name` and its members, if there is one.
+pub async fn remove(pool: &AnyPool, name: &str) -> Result<RemoveReport> {
+    let mut tx = pool.begin().await?;
+    let mods = sqlx::query(
+        "DELETE FROM approved_list_mod WHERE id_approved_list IN
+            (SELECT id_approved_list FROM approved_list WHERE name = $1)",
+    )
+    .bind(name)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let lists = sqlx::query("DELETE FROM approved_list WHERE name = $1")
+        .bind(name)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(RemoveReport {
+        list: name.to_string(),
+        removed: lists > 0,
+        mods,
+    })
+}
+
+pub async fn lists(pool: &AnyPool) -> Result<Vec<ApprovedList>> {
+    let rows: Vec<(String, String, String, i64, i64)> = sqlx::query_as(
+        "SELECT approved_list.name, source, date_imported, COUNT(approved_list_mod.id_mod),
+                CAST(COALESCE(SUM(CASE WHEN approved_list_mod.id_mod IS NOT NULL AND mod.id_mod IS NULL
+                                       THEN 1 ELSE 0 END), 0) AS BIGINT)
+         FROM approved_list
+         LEFT JOIN approved_list_mod
+                ON approved_list_mod.id_approved_list = approved_list.id_approved_list
+         LEFT JOIN mod ON mod.id_mod = approved_list_mod.id_mod
+         GROUP BY approved_list.id_approved_list, approved_list.name, source, date_imported
+         ORDER BY approved_list.name",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(name, source, date_imported, mods, unindexed)| ApprovedList {
+                name,
+                source,
+                date_imported,
+                mods,
+                unindexed,
+            },
+        )
+        .collect())
+}
+
+/// Two members of a list whose current modfiles contain the same paths.
+#[derive(Debug, Serialize)]
+pub struct ListConflict {
+    pub a: String,
+    pub b: String,
+    pub paths: Vec<String>,
+    /// Those of `paths` that are base game assets both mods override, rather than new content
+    /// the two mods add under the same path
+    pub vanilla_overrides: Vec<String>,
+    /// Those of `paths` the two mods spell differently, which still collide since the game
+    /// mounts paths ignoring case
+    pub case_collisions: Vec<String>,
+}
+
+/// How an approved list holds up against the index: members the index has not seen and pairs of
+/// members that overwrite each other's files.
+#[derive(Debug, Serialize)]
+pub struct ListCheck {
+    pub list: String,
+    pub unindexed: Vec<i64>,
+    pub conflicts: Vec<ListConflict>,
+}
+
+impl ListCheck {
+    pub fn has_problems(&self) -> bool {
+        !self.unindexed.is_empty() || !self.conflicts.is_empty()
+    }
+}
+
+impl std::fmt::Display for ListCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for id_mod in &self.unindexed {
+            writeln!(f, "mod {id_mod} is not indexed")?;
+        }
+        for c in &self.conflicts {
+            write!(f, "{} conflicts with {}", c.a, c.b)?;
+            if !c.vanilla_overrides.is_empty() {
+                write!(f, " ({} base game assets)", c.vanilla_overrides.len())?;
+            }
+            writeln!(f, ":")?;
+            for path in c.paths.iter().take(CONFLICT_SAMPLE) {
+                write!(f, "  {path}")?;
+                if c.vanilla_overrides.contains(path) {
+                    write!(f, " (base game)")?;
+                }
+                if c.case_collisions.contains(path) {
+                    write!(f, " (differs in case)")?;
+                }
+                writeln!(f)?;
+            }
+            if c.paths.len() > CONFLICT_SAMPLE {
+                writeln!(f, "  …and {} more", c.paths.len() - CONFLICT_SAMPLE)?;
+            }
+        }
+        write!(
+            f,
+            "{}: {} not indexed, {} conflicting pairs",
+            self.list,
+            self.unindexed.len(),
+            self.conflicts.len()
+        )
+    }
+}
+
+/// Check the members of the list `name` against the current modfiles in the index.
+pub async fn check(pool: &AnyPool, name: &str) -> Result<ListCheck> {
+    let id_list = resolve(pool, name).await?;
+    let unindexed: Vec<i64> = sqlx::query_scalar(
+        "SELECT approved_list_mod.id_mod FROM approved_list_mod
+         LEFT JOIN mod ON mod.id_mod = approved_list_mod.id_mod
+         WHERE id_approved_list = $1 AND mod.id_mod IS NULL
+         ORDER BY approved_list_mod.id_mod",
+    )
+    .bind(id_list)
+    .fetch_all(pool)
+    .await?;
+
+    let shared: Vec<(String, String, String, i64, i64)> = sqlx::query_as(
+        "SELECT mod_a.name_id, mod_b.name_id, file_a.path, COALESCE(file_a.vanilla_override, 0),
+                CASE WHEN file_a.path = file_b.path THEN 0 ELSE 1 END
+         FROM approved_list_mod AS a
+         JOIN approved_list_mod AS b
+              ON b.id_approved_list = a.id_approved_list AND b.id_mod > a.id_mod
+         JOIN mod AS mod_a ON mod_a.id_mod = a.id_mod
+         JOIN mod AS mod_b ON mod_b.id_mod = b.id_mod
+         JOIN pack_file AS file_a ON file_a.id_modfile = mod_a.id_modfile
+         JOIN pack_file AS file_b
+              ON file_b.id_modfile = mod_b.id_modfile AND file_b.path_lower = file_a.path_lower
+         WHERE a.id_approved_list = $1
+         ORDER BY mod_a.name_id, mod_b.name_id, file_a.path",
+    )
+    .bind(id_list)
+    .fetch_all(pool)
+    .await?;
+    let mut conflicts = BTreeMap::<(String, String), ListConflict>::new();
+    for (a, b, path, vanilla_override, case_collision) in shared {
+        let conflict = conflicts
+            .entry((a.clone(), b.clone()))
+            .or_insert_with(|| ListConflict {
+                a,
+                b,
+                paths: vec![],
+                vanilla_overrides: vec![],
+                case_collisions: vec![],
+            });
+        if vanilla_override != 0 {
+            conflict.vanilla_overrides.push(path.clone());
+        }
+        if case_collision != 0 {
+            conflict.case_collisions.push(path.clone());
+        }
+        conflict.paths.push(path);
+    }
+
+    Ok(ListCheck {
+        list: name.to_string(),
+        unindexed,
+        conflicts: conflicts.into_values().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An in-memory index holding mods as (id, name_id, paths of the current modfile) and an
+    /// approved list `list` of `members`. Paths starting with `!` override base game assets.
+    async fn index(mods: &[(i64, &str, &[&str])], members: &[i64]) -> AnyPool {
+        let pool = crate::db::connect("sqlite::memory:", true, true)
+            .await
+            .unwrap();
+        for (id_mod, name_id, paths) in mods {
+            sqlx::query("INSERT INTO mod(id_mod, name, name_id, summary) VALUES ($1, $2, $2, '')")
+                .bind(id_mod)
+                .bind(name_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            let id_modfile = id_mod * 10;
+            sqlx::query(
+                "INSERT INTO modfile(id_modfile, id_mod, date_added, hash_md5, filename)
+                 VALUES ($1, $2, '', '', 'mod.zip')",
+            )
+            .bind(id_modfile)
+            .bind(id_mod)
+            .execute(&pool)
+            .await
+            .unwrap();
+            sqlx::query("UPDATE mod SET id_modfile = $1 WHERE id_mod = $2")
+                .bind(id_modfile)
+                .bind(id_mod)
+                .execute(&pool)
+                .await
+                .unwrap();
+            for path in *paths {
+                let (path, vanilla) = match path.strip_prefix('!') {
+                    Some(path) => (path, 1),
+                    None => (*path, 0),
+                };
+                sqlx::query(
+                    "INSERT INTO pack_file(id_modfile, path, path_no_extension, name, vanilla_override)
+                     VALUES ($1, $2, $2, $2, $3)",
+                )
+                .bind(id_modfile)
+                .bind(path)
+                .bind(vanilla)
+                .execute(&pool)
+                .await
+                .unwrap();
+            }
+        }
+        sqlx::query(
+            "INSERT INTO approved_list(id_approved_list, name, source, date_imported)
+             VALUES (1, 'list', 'list.csv', '')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        for id_mod in members {
+            sqlx::query("INSERT INTO approved_list_mod(id_approved_list, id_mod) VALUES (1, $1)")
+                .bind(id_mod)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        pool
+    }
+
+    #[tokio::test]
+    async fn check_only_pairs_members() {
+        let pool = index(
+            &[
+                (
+                    1,
+                    "drills",
+                    &["!FSD/Content/Drill.uasset", "FSD/Content/New.uasset"],
+                ),
+                (2, "better-drills", &["!FSD/Content/Drill.uasset"]),
+                (3, "not-approved", &["FSD/Content/New.uasset"]),
+                (4, "sounds", &["FSD/Content/new.uasset"]),
+            ],
+            &[1, 2, 4, 5],
+        )
+        .await;
+        let check = check(&pool, "list").await.unwrap();
+        assert!(check.has_problems());
+        assert_eq!(check.unindexed, [5]);
+
+        // not-approved shares a path with drills but is not on the list
+        let pairs = check
+            .conflicts
+            .iter()
+            .map(|c| (c.a.as_str(), c.b.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, [("drills", "better-drills"), ("drills", "sounds")]);
+        assert_eq!(check.conflicts[0].paths, ["FSD/Content/Drill.uasset"]);
+        assert_eq!(
+            check.conflicts[0].vanilla_overrides,
+            ["FSD/Content/Drill.uasset"]
+        );
+        assert!(check.conflicts[0].case_collisions.is_empty());
+        assert_eq!(check.conflicts[1].paths, ["FSD/Content/New.uasset"]);
+        assert!(check.conflicts[1].vanilla_overrides.is_empty());
+        assert_eq!(
+            check.conflicts[1].case_collisions,
+            ["FSD/Content/New.uasset"]
+        );
+    }
+
+    #[tokio::test]
+    async fn check_without_conflicts() {
+        let pool = index(
+            &[
+                (1, "a", &["X.uasset"]),
+                (2, "b", &["X.uasset"]),
+                (3, "c", &["Y.uasset"]),
+            ],
+            &[1, 3],
+        )
+        .await;
+        let check = check(&pool, "list").await.unwrap();
+        assert!(!check.has_problems());
+        assert_eq!(
+            check.to_string(),
+            "list: 0 not indexed, 0 conflicting pairs"
+        );
+        assert!(super::check(&pool, "other").await.is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use std::env;
+
+use crate::store;
+
+/// Whether archive mode is on, from `ARCHIVE_MODE`. In archive mode every downloaded archive is
+/// kept, so `--no-keep`, `--metadata-only` and `MAX_STORE_SIZE` have no effect, to preserve the
+/// history of every mod rather than only its latest upload.
+pub fn enabled() -> bool {
+    env::var("ARCHIVE_MODE")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Record that the archive of `file` was downloaded from its current download URL.
+pub async fn record_capture(pool: &AnyPool, file: &modio::files::File, size: u64) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO archive_capture(id_modfile, hash_md5, size, url, date_captured)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT(id_modfile) DO
+            UPDATE SET
+                hash_md5 = excluded.hash_md5,
+                size = excluded.size,
+                url = excluded.url,
+                date_captured = excluded.date_captured",
+    )
+    .bind(i64::from(file.id))
+    .bind(&file.filehash.md5)
+    .bind(size as i64)
+    .bind(file.download.binary_url.as_str())
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A stored modfile archive in the manifest.
+#[derive(Debug, Serialize)]
+pub struct ManifestEntry {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub id_modfile: i64,
+    pub version: Option<String>,
+    pub date_added: String,
+    pub hash_md5: String,
+    pub size: u64,
+    /// Download URL at the time of capture, unknown for archives downloaded before captures were
+    /// recorded
+    pub url: Option<String>,
+    pub date_captured: Option<String>,
+    /// Whether this is the mod's current modfile rather than a superseded one
+    pub current: bool,
+}
+
+type ManifestRow = (
+    i64,
+    String,
+    i64,
+    Option<String>,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    i64,
+);
+
+/// Every modfile whose archive is stored, oldest upload first, for preserving and sharing the
+/// archive.
+pub async fn manifest(pool: &AnyPool) -> Result<Vec<ManifestEntry>> {
+    let rows: Vec<ManifestRow> = sqlx::query_as(
+        "SELECT modfile.id_mod, name_id, modfile.id_modfile, version, modfile.date_added,
+                modfile.hash_md5, url, date_captured,
+                CASE WHEN mod.id_modfile = modfile.id_modfile THEN 1 ELSE 0 END
+         FROM modfile
+         JOIN mod ON mod.id_mod = modfile.id_mod
+         LEFT JOIN archive_capture ON archive_capture.id_modfile = modfile.id_modfile
+         ORDER BY modfile.date_added, modfile.id_modfile",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut entries = vec![];
+    for (id_mod, name_id, id_modfile, version, date_added, hash_md5, url, date_captured, current) in
+        rows
+    {
+        let Ok(metadata) = std::fs::metadata(store::object_path(&hash_md5)) else {
+            continue;
+        };
+        entries.push(ManifestEntry {
+            id_mod,
+            name_id,
+            id_modfile,
+            version,
+            date_added,
+            hash_md5,
+            size: metadata.len(),
+            url,
+            date_captured,
+            current: current != 0,
+        });
+    }
+    Ok(entries)
+}
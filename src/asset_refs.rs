@@ -0,0 +1,160 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use crate::game;
+
+/// Game path of a content package without extension, e.g. `FSD/Content/Character/BP_Foo` for
+/// `/Game/Character/BP_Foo`. `None` for native `/Script` and engine packages.
+pub fn game_path(package: &str) -> Option<String> {
+    package
+        .strip_prefix("/Game/")
+        .map(|rest| format!("FSD/Content/{rest}"))
+}
+
+/// Package and game path `target` names: a package such as `/Game/Character/BP_Foo`, an object
+/// path such as `/Game/Character/BP_Foo.BP_Foo_C` or a game path with or without extension.
+fn target(target: &str) -> (String, Option<String>) {
+    if target.starts_with('/') {
+        let package = match target.rsplit_once('/') {
+            Some((dir, name)) => match name.split_once('.') {
+                Some((name, _)) => format!("{dir}/{name}"),
+                None => target.to_string(),
+            },
+            None => target.to_string(),
+        };
+        let path = game_path(&package);
+        return (package, path);
+    }
+    let path = [".uasset", ".umap", ".uexp"]
+        .iter()
+        .find_map(|ext| target.strip_suffix(ext))
+        .unwrap_or(target);
+    (String::new(), Some(path.to_string()))
+}
+
+/// A reference from an asset of a mod's current modfile to an object of another package.
+#[derive(Debug, Serialize)]
+pub struct AssetReference {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub path: String,
+    pub ref_package: String,
+    pub ref_object: String,
+    pub ref_class: String,
+    /// Whether the asset derives from the object, as a Blueprint does from its parent class
+    pub parent: bool,
+    /// Whether the referenced package is an asset of the latest indexed game version
+    pub vanilla: bool,
+    /// Whether the mod contains the referenced package itself
+    pub in_mod: bool,
+}
+
+pub fn print_references(references: &[AssetReference]) {
+    for r in references {
+        print!(
+            "{} {} {} -> {}.{} ({})",
+            r.id_mod, r.name_id, r.path, r.ref_package, r.ref_object, r.ref_class
+        );
+        if r.parent {
+            print!(" [parent]");
+        }
+        if r.vanilla {
+            print!(" [base game]");
+        }
+        if r.in_mod {
+            print!(" [in mod]");
+        }
+        println!();
+    }
+}
+
+/// Which references to list.
+#[derive(Debug, Default)]
+pub struct Filter<'a> {
+    /// Mod whose current modfile's references to list, every mod if not set
+    pub id_mod: Option<i64>,
+    /// Only references to this package or asset
+    pub to: Option<&'a str>,
+    /// Only references of this class, e.g. `BlueprintGeneratedClass`
+    pub class: Option<&'a str>,
+    /// Only parent classes
+    pub parents: bool,
+    /// Only references to base game assets
+    pub vanilla: bool,
+}
+
+type ReferenceRow = (i64, String, String, String, String, String, i64, i64, i64);
+
+/// References of the current modfiles of mods matching `filter`, e.g. every Blueprint of a mod
+/// deriving from a base game Blueprint, or every mod referencing an asset.
+pub async fn references(pool: &AnyPool, filter: &Filter<'_>) -> Result<Vec<AssetReference>> {
+    let id_version = game::latest_version(pool).await?;
+    if filter.vanilla && id_version.is_none() {
+        bail!("index the base game with index-game first");
+    }
+    let (package, path) = match filter.to {
+        Some(to) => target(to),
+        None => (String::new(), None),
+    };
+    let rows: Vec<ReferenceRow> = sqlx::query_as(
+        "SELECT mod.id_mod, mod.name_id, asset_ref.path, asset_ref.ref_package, asset_ref.ref_object,
+                asset_ref.ref_class, asset_ref.parent,
+                CASE WHEN EXISTS (
+                    SELECT 1 FROM game_file
+                    WHERE game_file.id_game_version = $1
+                      AND game_file.path IN (asset_ref.ref_path || '.uasset', asset_ref.ref_path || '.umap')
+                ) THEN 1 ELSE 0 END,
+                CASE WHEN EXISTS (
+                    SELECT 1 FROM pack_file
+                    WHERE pack_file.id_modfile = asset_ref.id_modfile
+                      AND pack_file.path IN (asset_ref.ref_path || '.uasset', asset_ref.ref_path || '.umap')
+                ) THEN 1 ELSE 0 END
+         FROM asset_ref JOIN mod ON mod.id_modfile = asset_ref.id_modfile
+         WHERE ($2 = 0 OR mod.id_mod = $3)
+           AND ($4 = 0 OR asset_ref.ref_package = $5 OR asset_ref.ref_path = $6)
+           AND ($7 = '' OR asset_ref.ref_class = $7)
+           AND ($8 = 0 OR asset_ref.parent = 1)
+         ORDER BY mod.name_id, asset_ref.path, asset_ref.ref_package, asset_ref.ref_object",
+    )
+    .bind(id_version.unwrap_or(-1))
+    .bind(i64::from(filter.id_mod.is_some()))
+    .bind(filter.id_mod.unwrap_or_default())
+    .bind(i64::from(filter.to.is_some()))
+    .bind(&package)
+    .bind(path.unwrap_or_default())
+    .bind(filter.class.unwrap_or_default())
+    .bind(i64::from(filter.parents))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(
+                id_mod,
+                name_id,
+                path,
+                ref_package,
+                ref_object,
+                ref_class,
+                parent,
+                vanilla,
+                in_mod,
+            )| {
+                AssetReference {
+                    id_mod,
+                    name_id,
+                    path,
+                    ref_package,
+                    ref_object,
+                    ref_class,
+                    parent: parent != 0,
+                    vanilla: vanilla != 0,
+                    in_mod: in_mod != 0,
+                }
+            },
+        )
+        .filter(|r| !filter.vanilla || r.vanilla)
+        .collect())
+}
@@ -0,0 +1,167 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use std::collections::BTreeMap;
+
+use crate::wwise::{self, Content};
+use crate::{game, lookup};
+
+/// A soundbank or media file of a mod.
+#[derive(Debug, Serialize)]
+pub struct AudioFile {
+    pub path: String,
+    /// `None` for media no indexed base game soundbank plays
+    pub content: Option<Content>,
+    pub objects: usize,
+    /// Base game soundbank playing the file's media, which its content is taken from
+    pub game_bank: Option<String>,
+}
+
+/// The audio files of a mod's current modfile.
+#[derive(Debug, Serialize)]
+pub struct ModAudio {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub id_modfile: i64,
+    pub files: Vec<AudioFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AudioReport {
+    pub mods: Vec<ModAudio>,
+}
+
+impl std::fmt::Display for AudioReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for m in &self.mods {
+            writeln!(f, "{} (modfile {})", m.name_id, m.id_modfile)?;
+            for file in &m.files {
+                let content = file.content.map_or("unknown", Content::as_str);
+                write!(f, "  {content} {} ({} objects)", file.path, file.objects)?;
+                if let Some(bank) = &file.game_bank {
+                    write!(f, " played by {bank}")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        write!(f, "{} mods with audio", self.mods.len())
+    }
+}
+
+/// Content of a mod's media file from the base game soundbank playing the first of its media ids
+/// found, by embedding it or through a sound.
+async fn game_bank(
+    pool: &AnyPool,
+    id_version: i64,
+    media: &[i64],
+) -> Result<Option<(String, Option<Content>)>> {
+    for &id in media {
+        let bank: Option<String> = sqlx::query_scalar(
+            "SELECT path FROM game_audio_object
+             WHERE id_game_version = $1 AND ((kind = 'media' AND id_object = $2) OR id_source = $2)
+             ORDER BY path LIMIT 1",
+        )
+        .bind(id_version)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+        let Some(bank) = bank else {
+            continue;
+        };
+        let objects: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT kind, localized FROM game_audio_object WHERE id_game_version = $1 AND path = $2",
+        )
+        .bind(id_version)
+        .bind(&bank)
+        .fetch_all(pool)
+        .await?;
+        let content = wwise::content(objects.iter().map(|(k, l)| (k.as_str(), *l != 0)));
+        return Ok(Some((bank, content)));
+    }
+    Ok(None)
+}
+
+/// The soundbanks and media of `mods`, or of every mod with any, with what they hold. Media alone
+/// does not tell, so it is looked up in the soundbanks of the latest indexed game version. With
+/// `content` only mods with a file holding it are listed.
+pub async fn report(
+    pool: &AnyPool,
+    mods: &[String],
+    content: Option<Content>,
+) -> Result<AudioReport> {
+    let ids: Vec<i64> = if mods.is_empty() {
+        sqlx::query_scalar(
+            "SELECT id_mod FROM mod
+             WHERE EXISTS (SELECT 1 FROM audio_object WHERE audio_object.id_modfile = mod.id_modfile)
+             ORDER BY name_id",
+        )
+        .fetch_all(pool)
+        .await?
+    } else {
+        let mut ids = vec![];
+        for reference in mods {
+            ids.push(lookup::resolve_mod(pool, reference).await?);
+        }
+        ids
+    };
+    let id_version = game::latest_version(pool).await?;
+
+    let mut report = AudioReport { mods: vec![] };
+    for id_mod in ids {
+        let (name_id, id_modfile): (String, Option<i64>) =
+            sqlx::query_as("SELECT name_id, id_modfile FROM mod WHERE id_mod = $1")
+                .bind(id_mod)
+                .fetch_one(pool)
+                .await?;
+        let Some(id_modfile) = id_modfile else {
+            continue;
+        };
+        let rows: Vec<(String, String, i64, i64)> = sqlx::query_as(
+            "SELECT path, kind, id_object, localized FROM audio_object
+             WHERE id_modfile = $1 ORDER BY path",
+        )
+        .bind(id_modfile)
+        .fetch_all(pool)
+        .await?;
+        let mut paths = BTreeMap::<String, Vec<(String, i64, bool)>>::new();
+        for (path, kind, id, localized) in rows {
+            paths
+                .entry(path)
+                .or_default()
+                .push((kind, id, localized != 0));
+        }
+
+        let mut files = vec![];
+        for (path, objects) in paths {
+            let mut file = AudioFile {
+                content: wwise::content(objects.iter().map(|(k, _, l)| (k.as_str(), *l))),
+                objects: objects.len(),
+                game_bank: None,
+                path,
+            };
+            if let (None, Some(id_version)) = (file.content, id_version) {
+                let media = objects
+                    .iter()
+                    .filter(|(kind, _, _)| kind == "media")
+                    .map(|(_, id, _)| *id)
+                    .collect::<Vec<_>>();
+                if let Some((bank, content)) = game_bank(pool, id_version, &media).await? {
+                    file.content = content;
+                    file.game_bank = Some(bank);
+                }
+            }
+            files.push(file);
+        }
+        if content.is_some_and(|c| !files.iter().any(|f| f.content == Some(c))) {
+            continue;
+        }
+        report.mods.push(ModAudio {
+            id_mod,
+            name_id,
+            id_modfile,
+            files,
+        });
+    }
+    Ok(report)
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::warn;
+
+use crate::{api, local};
+
+/// A difference between a modfile as stored in the index and as currently reported by mod.io.
+#[derive(Debug, Serialize)]
+pub struct AuditFinding {
+    pub id_mod: i64,
+    pub id_modfile: i64,
+    /// Name of the mismatched field, or `missing` if the file no longer exists upstream
+    pub field: &'static str,
+    pub indexed: Option<String>,
+    pub upstream: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditReport {
+    pub audited: u64,
+    pub findings: Vec<AuditFinding>,
+}
+
+impl std::fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for finding in &self.findings {
+            writeln!(
+                f,
+                "mod {} modfile {}: {} indexed {:?} upstream {:?}",
+                finding.id_mod,
+                finding.id_modfile,
+                finding.field,
+                finding.indexed,
+                finding.upstream
+            )?;
+        }
+        write!(
+            f,
+            "{} modfiles audited, {} mismatches",
+            self.audited,
+            self.findings.len()
+        )
+    }
+}
+
+/// Re-fetch modfiles from mod.io and compare them with the index. This catches files that were
+/// silently re-uploaded under the same id, which the id comparison in `update_mod` cannot see.
+pub async fn audit_upstream(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    sample: Option<i64>,
+) -> Result<AuditReport> {
+    let modio = api::client()?;
+
+    let modfiles: Vec<(i64, i64, String, String, String, Option<String>)> =
+        if let Some(sample) = sample {
+            sqlx::query_as(
+                "SELECT id_modfile, id_mod, date_added, hash_md5, filename, version FROM modfile
+                 WHERE id_mod != $2
+                 ORDER BY RANDOM() LIMIT $1",
+            )
+            .bind(sample)
+            .bind(local::LOCAL_MOD)
+            .fetch_all(pool)
+            .await?
+        } else {
+            sqlx::query_as(
+                "SELECT id_modfile, id_mod, date_added, hash_md5, filename, version FROM modfile
+                 WHERE id_mod != $1
+                 ORDER BY id_modfile",
+            )
+            .bind(local::LOCAL_MOD)
+            .fetch_all(pool)
+            .await?
+        };
+
+    let bar = multi_bar.add(ProgressBar::new(modfiles.len().try_into().unwrap()));
+    let mut report = AuditReport {
+        audited: 0,
+        findings: vec![],
+    };
+    for (id_modfile, id_mod, date_added, hash_md5, filename, version) in modfiles {
+        let mut finding = |field, indexed: Option<String>, upstream: Option<String>| {
+            warn!(
+                id_mod,
+                id_modfile,
+                field,
+                ?indexed,
+                ?upstream,
+                "Upstream mismatch"
+            );
+            report.findings.push(AuditFinding {
+                id_mod,
+                id_modfile,
+                field,
+                indexed,
+                upstream,
+            });
+        };
+
+        let upstream = modio
+            .game(api::DRG)
+            .mod_(id_mod as u32)
+            .file(id_modfile as u32)
+            .get()
+            .await;
+        match upstream {
+            Ok(file) => {
+                if file.filehash.md5 != hash_md5 {
+                    finding("hash_md5", Some(hash_md5), Some(file.filehash.md5));
+                }
+                if file.filename != filename {
+                    finding("filename", Some(filename), Some(file.filename));
+                }
+                if file.version != version {
+                    finding("version", version, file.version);
+                }
+                let upstream_date = api::timestamp(file.date_added)?;
+                if upstream_date != date_added {
+                    finding("date_added", Some(date_added), Some(upstream_date));
+                }
+            }
+            Err(e) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                finding("missing", Some(hash_md5), None);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        report.audited += 1;
+        bar.inc(1);
+    }
+    bar.finish();
+
+    Ok(report)
+}
@@ -0,0 +1,309 @@
+use serde::Serialize;
+use sqlx::migrate::MigrateDatabase;
+use sqlx::Any;
+
+use std::env;
+use std::path::Path;
+
+use crate::{api, channel, db, gc, mount, pak_source, priority, steam, store, trash, usmap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigReport {
+    pub checks: Vec<Check>,
+}
+
+impl ConfigReport {
+    pub fn has_errors(&self) -> bool {
+        self.checks.iter().any(|c| c.status == Status::Error)
+    }
+
+    fn push(&mut self, name: &'static str, status: Status, message: impl Into<String>) {
+        self.checks.push(Check {
+            name,
+            status,
+            message: message.into(),
+        });
+    }
+}
+
+impl std::fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for check in &self.checks {
+            let status = match check.status {
+                Status::Ok => "ok",
+                Status::Warning => "warning",
+                Status::Error => "error",
+            };
+            writeln!(f, "{status:<8} {}: {}", check.name, check.message)?;
+        }
+        let errors = self
+            .checks
+            .iter()
+            .filter(|c| c.status == Status::Error)
+            .count();
+        if errors == 0 {
+            write!(f, "configuration looks good")
+        } else {
+            write!(f, "{errors} problems need fixing")
+        }
+    }
+}
+
+/// Check everything a run depends on: the `.env` file, required variables, the mod.io token, the
+/// database and the mods directory. Nothing is created or migrated.
+pub async fn check_config() -> ConfigReport {
+    let mut report = ConfigReport { checks: vec![] };
+
+    match dotenv::dotenv() {
+        Ok(path) => report.push(".env", Status::Ok, format!("loaded {}", path.display())),
+        Err(dotenv::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => report.push(
+            ".env",
+            Status::Warning,
+            "no .env file found, settings must come from the environment. See .env.example",
+        ),
+        Err(e) => report.push(".env", Status::Error, format!("could not be read: {e}")),
+    }
+
+    check_database(&mut report).await;
+    check_token(&mut report).await;
+    check_mods_dir(&mut report);
+
+    match channel::from_env() {
+        Ok(channels) if channels.is_empty() => report.push(
+            "notifications",
+            Status::Ok,
+            "no channel configured, notifications are not sent",
+        ),
+        Ok(channels) => report.push(
+            "notifications",
+            Status::Ok,
+            format!(
+                "sending to {}",
+                channels
+                    .iter()
+                    .map(|c| c.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ),
+        Err(e) => report.push("notifications", Status::Error, format!("{e:#}")),
+    }
+    match priority::rules_from_env() {
+        Ok(rules) if rules.is_empty() => {}
+        Ok(rules) => report.push(
+            "DOWNLOAD_PRIORITY",
+            Status::Ok,
+            format!("{} priority lanes", rules.len()),
+        ),
+        Err(e) => report.push("DOWNLOAD_PRIORITY", Status::Error, format!("{e:#}")),
+    }
+    if let Err(e) = trash::retention() {
+        report.push("TRASH_RETENTION", Status::Error, format!("{e:#}"));
+    }
+    if let Err(e) = gc::deleted_mod_retention() {
+        report.push("DELETED_MOD_RETENTION", Status::Error, format!("{e:#}"));
+    }
+    if let Err(e) = gc::quota_history() {
+        report.push("QUOTA_HISTORY", Status::Error, format!("{e:#}"));
+    }
+    if let Err(e) = pak_source::nested_archive_depth_from_env() {
+        report.push("NESTED_ARCHIVE_DEPTH", Status::Error, format!("{e:#}"));
+    }
+    if let Err(e) = store::max_size() {
+        report.push("MAX_STORE_SIZE", Status::Error, format!("{e:#}"));
+    }
+    match steam::detect_game_dir() {
+        Ok(dir) => report.push("game directory", Status::Ok, format!("{}", dir.display())),
+        Err(e) => report.push(
+            "game directory",
+            Status::Warning,
+            format!("{e:#}, only install and index-game need it"),
+        ),
+    }
+    if let Err(e) = mount::from_env() {
+        report.push("MOUNT_POINT_NORMALIZATION", Status::Error, format!("{e:#}"));
+    }
+    match usmap::from_env() {
+        Ok(Some(mappings)) => report.push(
+            "USMAP",
+            Status::Ok,
+            format!("{} structs and classes", mappings.structs.len()),
+        ),
+        Ok(None) => {}
+        Err(e) => report.push("USMAP", Status::Error, format!("{e:#}")),
+    }
+
+    report
+}
+
+async fn check_database(report: &mut ConfigReport) {
+    let Ok(url) = env::var("DATABASE_URL") else {
+        report.push(
+            "DATABASE_URL",
+            Status::Error,
+            "not set, e.g. DATABASE_URL=sqlite:index.db",
+        );
+        return;
+    };
+    let backend = match db::Backend::from_url(&url) {
+        Ok(backend) => backend,
+        Err(e) => {
+            report.push("DATABASE_URL", Status::Error, format!("{e:#}"));
+            return;
+        }
+    };
+
+    sqlx::any::install_default_drivers();
+    match Any::database_exists(&url).await {
+        Ok(true) => {}
+        Ok(false) => {
+            report.push(
+                "database",
+                Status::Warning,
+                format!("{url} does not exist yet, it is created by the first sync"),
+            );
+            return;
+        }
+        Err(e) => {
+            report.push(
+                "database",
+                Status::Error,
+                format!("could not connect to {url}: {e}"),
+            );
+            return;
+        }
+    }
+    let status = match db::connect(&url, false, false).await {
+        Ok(pool) => db::migration_status(&pool, backend).await,
+        Err(e) => Err(e),
+    };
+    match status {
+        Ok(migrations) => {
+            let pending = migrations.iter().filter(|m| !m.applied).count();
+            if pending == 0 {
+                report.push("database", Status::Ok, format!("connected to {url}"));
+            } else {
+                report.push(
+                    "database",
+                    Status::Warning,
+                    format!(
+                        "connected to {url}, {pending} migrations pending. They are applied \
+                         on the next run or with `migrate run`"
+                    ),
+                );
+            }
+        }
+        Err(e) => report.push(
+            "database",
+            Status::Error,
+            format!("could not connect to {url}: {e:#}"),
+        ),
+    }
+}
+
+async fn check_token(report: &mut ConfigReport) {
+    match api::access_token() {
+        Ok(Some(_)) => {}
+        Ok(None) if api::api_key().is_some() => {
+            report.push(
+                "access token",
+                Status::Warning,
+                "not set, using MODIO_KEY for read-only access. Run `login` or set \
+                 MODIO_ACCESS_TOKEN to index drafts and subscriptions",
+            );
+            return;
+        }
+        Ok(None) => {
+            report.push(
+                "access token",
+                Status::Error,
+                "not logged in, run `login`, set MODIO_ACCESS_TOKEN to a token from \
+                 https://mod.io/me/access or set MODIO_KEY for read-only access",
+            );
+            return;
+        }
+        Err(e) => {
+            report.push("access token", Status::Error, format!("{e:#}"));
+            return;
+        }
+    }
+    let modio = match api::client() {
+        Ok(modio) => modio,
+        Err(e) => {
+            report.push("access token", Status::Error, format!("{e:#}"));
+            return;
+        }
+    };
+    match modio.user().current().await {
+        Ok(Some(user)) => report.push(
+            "access token",
+            Status::Ok,
+            format!("authenticated as {}", user.username),
+        ),
+        Ok(None) => report.push(
+            "access token",
+            Status::Error,
+            "mod.io did not return a user for this token",
+        ),
+        Err(e) if e.status() == Some(reqwest::StatusCode::UNAUTHORIZED) => report.push(
+            "access token",
+            Status::Error,
+            "rejected by mod.io, it may have expired or been revoked",
+        ),
+        Err(e) => report.push(
+            "access token",
+            Status::Error,
+            format!("could not reach mod.io: {e}"),
+        ),
+    }
+}
+
+fn check_mods_dir(report: &mut ConfigReport) {
+    let dir = Path::new(store::STORE_DIR);
+    if !dir.is_dir() {
+        report.push(
+            "mods directory",
+            Status::Warning,
+            format!(
+                "{} does not exist in {}, it is created by the first download. Run from the \
+                 directory that has it if you already have archives",
+                dir.display(),
+                env::current_dir()
+                    .map(|d| d.display().to_string())
+                    .unwrap_or_default()
+            ),
+        );
+        return;
+    }
+    let probe = dir.join(format!(".check-{}", std::process::id()));
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            std::fs::remove_file(&probe).ok();
+            report.push(
+                "mods directory",
+                Status::Ok,
+                format!("{} is writable", dir.display()),
+            );
+        }
+        Err(e) => report.push(
+            "mods directory",
+            Status::Error,
+            format!("{} is not writable: {e}", dir.display()),
+        ),
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use sqlx::AnyPool;
+use tracing::info;
+
+use std::collections::HashMap;
+
+/// Mods synced by an interrupted sync run by id, with the `date_updated` they had when synced.
+pub async fn completed(pool: &AnyPool) -> Result<HashMap<u32, u64>> {
+    let rows: Vec<(i64, i64)> = sqlx::query_as("SELECT id_mod, date_updated FROM sync_progress")
+        .fetch_all(pool)
+        .await?;
+    if !rows.is_empty() {
+        info!(
+            "Resuming interrupted sync, {} mods already synced",
+            rows.len()
+        );
+    }
+    Ok(rows
+        .into_iter()
+        .map(|(id_mod, date_updated)| (id_mod as u32, date_updated as u64))
+        .collect())
+}
+
+/// Whether `m` was synced by the interrupted run and has not been updated on mod.io since, so it
+/// can be skipped when resuming.
+pub fn is_done(completed: &HashMap<u32, u64>, m: &modio::mods::Mod) -> bool {
+    completed.get(&m.id) == Some(&m.date_updated)
+}
+
+/// Record that mod `id_mod`, last updated at `date_updated`, is synced in the current run.
+pub async fn record(pool: &AnyPool, id_mod: u32, date_updated: u64) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO sync_progress(id_mod, date_updated, date_synced) VALUES ($1, $2, $3)
+         ON CONFLICT(id_mod) DO
+            UPDATE SET date_updated = excluded.date_updated, date_synced = excluded.date_synced",
+    )
+    .bind(i64::from(id_mod))
+    .bind(date_updated as i64)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Forget the progress of the current run once every mod is synced, or to start over.
+pub async fn clear(pool: &AnyPool) -> Result<()> {
+    sqlx::query("DELETE FROM sync_progress")
+        .execute(pool)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// What kind of content a mod mostly consists of, derived from the entries of its current
+/// modfile rather than its mod.io tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// Sound banks and sound assets
+    Audio,
+    /// Textures and materials
+    Visual,
+    /// Meshes, skeletons and animations
+    Model,
+    /// Blueprints, data tables and other logic
+    Gameplay,
+    /// Widget blueprints and other HUD and menu assets
+    Ui,
+    /// Blueprint libraries other mods build on
+    Framework,
+    /// Translations: localized strings and string tables
+    Localization,
+    /// No kind of content clearly dominates
+    Mixed,
+}
+
+impl Category {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Audio => "audio",
+            Category::Visual => "visual",
+            Category::Model => "model",
+            Category::Gameplay => "gameplay",
+            Category::Ui => "ui",
+            Category::Framework => "framework",
+            Category::Localization => "localization",
+            Category::Mixed => "mixed",
+        }
+    }
+}
+
+/// Share of classified entries a kind needs before the mod is given that category.
+const DOMINANT_SHARE: f64 = 0.6;
+
+/// Words in paths that mark shared libraries rather than content.
+const FRAMEWORK_WORDS: &[&str] = &[
+    "framework",
+    "library",
+    "lib",
+    "api",
+    "integration",
+    "helper",
+];
+
+/// Directories holding the game's HUD and menus, and where UI mods put their widgets.
+const UI_DIRECTORIES: &[&str] = &["ui", "hud", "widgets", "umg"];
+
+/// Whether `path` is below one of [`UI_DIRECTORIES`].
+fn is_ui_path(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    let mut directories = lower.split('/').rev().skip(1);
+    directories.any(|directory| UI_DIRECTORIES.contains(&directory))
+}
+
+/// Kind of content a single entry is, from its asset class when known and its path otherwise.
+/// Companion files such as `.uexp` and `.ubulk` are not counted. Widgets, and anything but sound
+/// and text in the UI directories, such as icons and HUD materials, count as UI.
+fn entry_kind(path: &str, asset_class: Option<&str>) -> Option<Category> {
+    let kind = content_kind(path, asset_class)?;
+    let widget = matches!(
+        asset_class,
+        Some("WidgetBlueprint" | "WidgetBlueprintGeneratedClass")
+    );
+    let ui_content = !matches!(kind, Category::Audio | Category::Localization);
+    if widget || (ui_content && is_ui_path(path)) {
+        return Some(Category::Ui);
+    }
+    Some(kind)
+}
+
+fn content_kind(path: &str, asset_class: Option<&str>) -> Option<Category> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("bnk" | "wem") => return Some(Category::Audio),
+        Some("locres" | "locmeta") => return Some(Category::Localization),
+        Some("uasset" | "umap") => {}
+        _ => return None,
+    }
+    if let Some(class) = asset_class {
+        let kind = match class {
+            "SoundWave" | "SoundCue" | "AkAudioEvent" | "AkAudioBank" | "SoundClass"
+            | "SoundMix" | "SoundAttenuation" => Category::Audio,
+            "StringTable" => Category::Localization,
+            "Texture2D"
+            | "TextureCube"
+            | "TextureRenderTarget2D"
+            | "Material"
+            | "MaterialInstanceConstant"
+            | "MaterialFunction"
+            | "ParticleSystem"
+            | "NiagaraSystem" => Category::Visual,
+            "StaticMesh" | "SkeletalMesh" | "Skeleton" | "PhysicsAsset" | "AnimSequence"
+            | "AnimMontage" | "BlendSpace" => Category::Model,
+            _ => Category::Gameplay,
+        };
+        return Some(kind);
+    }
+    let lower = path.to_ascii_lowercase();
+    Some(if lower.contains("/audio/") || lower.contains("/sound") {
+        Category::Audio
+    } else if lower.contains("/texture") || lower.contains("/material") {
+        Category::Visual
+    } else if lower.contains("/mesh") || lower.contains("/anim") {
+        Category::Model
+    } else {
+        Category::Gameplay
+    })
+}
+
+/// Classify a mod from the `(path, asset_class)` of its entries. `None` if nothing in it could be
+/// classified.
+pub fn classify<'a>(
+    entries: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+) -> Option<Category> {
+    let mut counts = BTreeMap::<Category, u64>::new();
+    let mut framework_paths = 0;
+    for (path, asset_class) in entries {
+        let Some(kind) = entry_kind(path, asset_class) else {
+            continue;
+        };
+        *counts.entry(kind).or_default() += 1;
+        if kind == Category::Gameplay {
+            let lower = path.to_ascii_lowercase();
+            if lower
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .any(|word| FRAMEWORK_WORDS.contains(&word))
+            {
+                framework_paths += 1;
+            }
+        }
+    }
+    let total = counts.values().sum::<u64>();
+    if total == 0 {
+        return None;
+    }
+    let (&kind, &count) = counts.iter().max_by_key(|(_, &count)| count)?;
+    if (count as f64) < DOMINANT_SHARE * total as f64 {
+        return Some(Category::Mixed);
+    }
+    if kind == Category::Gameplay && framework_paths as f64 >= DOMINANT_SHARE * count as f64 {
+        return Some(Category::Framework);
+    }
+    Some(kind)
+}
+
+type EntryRow = (i64, Option<String>, Option<String>, Option<String>);
+
+/// Re-derive `mod.category` for every mod from the pack files of its current modfile. Returns the
+/// number of mods whose category changed.
+pub async fn refresh(pool: &AnyPool) -> Result<u64> {
+    let rows: Vec<EntryRow> = sqlx::query_as(
+        "SELECT mod.id_mod, mod.category, pack_file.path, pack_file.asset_class
+         FROM mod LEFT JOIN pack_file ON pack_file.id_modfile = mod.id_modfile
+         ORDER BY mod.id_mod",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut mods = BTreeMap::<i64, (Option<String>, Vec<(String, Option<String>)>)>::new();
+    for (id_mod, category, path, asset_class) in rows {
+        let (_, entries) = mods.entry(id_mod).or_insert((category, vec![]));
+        if let Some(path) = path {
+            entries.push((path, asset_class));
+        }
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut changed = 0;
+    for (id_mod, (current, entries)) in mods {
+        let category = classify(
+            entries
+                .iter()
+                .map(|(path, class)| (path.as_str(), class.as_deref())),
+        )
+        .map(Category::as_str);
+        if category == current.as_deref() {
+            continue;
+        }
+        sqlx::query("UPDATE mod SET category = $1 WHERE id_mod = $2")
+            .bind(category)
+            .bind(id_mod)
+            .execute(&mut *tx)
+            .await?;
+        changed += 1;
+    }
+    tx.commit().await?;
+    Ok(changed)
+}
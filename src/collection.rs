@@ -0,0 +1,413 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::AnyPool;
+use tracing::warn;
+
+use crate::lookup;
+use crate::trash::{self, TrashKind};
+
+/// A local curation list of mods. Collections only exist in the index and are never sent to
+/// mod.io.
+#[derive(Debug, Serialize)]
+pub struct Collection {
+    pub name: String,
+    pub date_created: String,
+    pub mods: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CollectionMod {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub name: String,
+    /// Modfile the mod is pinned to, if any
+    pub id_modfile: Option<i64>,
+    pub version: Option<String>,
+    pub note: Option<String>,
+}
+
+/// Version of the collection file format written by [`export`].
+const EXPORT_FORMAT: u32 = 1;
+
+/// A collection as shared between index instances. Mods carry their name_id as well as their id
+/// so the file stays readable and can still be resolved if ids don't match.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollectionFile {
+    pub format: u32,
+    pub name: String,
+    pub mods: Vec<CollectionFileMod>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollectionFileMod {
+    pub id_mod: i64,
+    pub name_id: String,
+    #[serde(default)]
+    pub pinned: Option<PinnedModfile>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PinnedModfile {
+    pub id_modfile: i64,
+    pub version: Option<String>,
+    pub hash_md5: String,
+}
+
+/// Result of importing a collection file.
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub collection: String,
+    pub imported: u64,
+    /// Mods that are not in this index, by name_id
+    pub unresolved: Vec<String>,
+    /// Mods whose pinned modfile is not in this index and were imported unpinned, by name_id
+    pub unpinned: Vec<String>,
+}
+
+impl std::fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for name_id in &self.unresolved {
+            writeln!(f, "skipped {name_id}: not in the index")?;
+        }
+        for name_id in &self.unpinned {
+            writeln!(f, "unpinned {name_id}: pinned modfile not in the index")?;
+        }
+        write!(
+            f,
+            "imported {} mods into {:?}, {} skipped",
+            self.imported,
+            self.collection,
+            self.unresolved.len()
+        )
+    }
+}
+
+/// Resolve a collection name to its id.
+pub async fn resolve(pool: &AnyPool, name: &str) -> Result<i64> {
+    let found: Option<i64> = sqlx::query_scalar(
+        "SELECT id_collection FROM collection WHERE name = $1 AND date_deleted IS NULL",
+    )
+    .bind(name)
+    .fetch_optional(pool)
+    .await?;
+    match found {
+        Some(id) => Ok(id),
+        None => bail!("no collection named {name:?}"),
+    }
+}
+
+pub async fn create(pool: &AnyPool, name: &str) -> Result<()> {
+    let exists: Option<Option<String>> =
+        sqlx::query_scalar("SELECT date_deleted FROM collection WHERE name = $1")
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+    match exists {
+        Some(None) => bail!("collection {name:?} already exists"),
+        Some(Some(_)) => {
+            bail!("collection {name:?} is in the trash, restore it or wait for it to be purged")
+        }
+        None => {}
+    }
+    sqlx::query("INSERT INTO collection(name, date_created) VALUES ($1, $2)")
+        .bind(name)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Move a collection to the trash. It keeps its mods until it is purged, see [`trash`].
+pub async fn delete(pool: &AnyPool, name: &str) -> Result<()> {
+    let id_collection = resolve(pool, name).await?;
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE collection SET date_deleted = $1 WHERE id_collection = $2")
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(id_collection)
+        .execute(&mut *tx)
+        .await?;
+    trash::record(&mut tx, TrashKind::Collection, name, "collection delete").await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Add mods, given as ids or name_ids, to a collection. `pin` is a modfile id or version and can
+/// only be given for a single mod. Adding a mod that is already in the collection updates its pin
+/// and note when given. Returns how many mods were added or updated.
+pub async fn add(
+    pool: &AnyPool,
+    name: &str,
+    mods: &[String],
+    pin: Option<&str>,
+    note: Option<&str>,
+) -> Result<usize> {
+    let id_collection = resolve(pool, name).await?;
+    let mut ids = vec![];
+    for reference in mods {
+        ids.push(lookup::resolve_mod(pool, reference).await?);
+    }
+    let pinned = match (pin, ids.as_slice()) {
+        (None, _) => None,
+        (Some(pin), [id_mod]) => Some(lookup::resolve_modfile(pool, *id_mod, pin).await?),
+        (Some(_), _) => bail!("--pin can only be used when adding a single mod"),
+    };
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut tx = pool.begin().await?;
+    for &id_mod in &ids {
+        insert_mod(&mut tx, id_collection, id_mod, pinned, note, &now).await?;
+    }
+    tx.commit().await?;
+    Ok(ids.len())
+}
+
+async fn insert_mod(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    id_collection: i64,
+    id_mod: i64,
+    id_modfile: Option<i64>,
+    note: Option<&str>,
+    now: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO collection_mod(id_collection, id_mod, date_added, id_modfile, note)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT(id_collection, id_mod) DO
+            UPDATE SET
+                id_modfile = COALESCE(excluded.id_modfile, collection_mod.id_modfile),
+                note = COALESCE(excluded.note, collection_mod.note)",
+    )
+    .bind(id_collection)
+    .bind(id_mod)
+    .bind(now)
+    .bind(id_modfile)
+    .bind(note)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Remove mods, given as ids or name_ids, from a collection. Returns how many were in it.
+pub async fn remove(pool: &AnyPool, name: &str, mods: &[String]) -> Result<u64> {
+    let id_collection = resolve(pool, name).await?;
+    let mut ids = vec![];
+    for reference in mods {
+        ids.push(lookup::resolve_mod(pool, reference).await?);
+    }
+    let mut tx = pool.begin().await?;
+    let mut removed = 0;
+    for id_mod in ids {
+        removed +=
+            sqlx::query("DELETE FROM collection_mod WHERE id_collection = $1 AND id_mod = $2")
+                .bind(id_collection)
+                .bind(id_mod)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+    }
+    tx.commit().await?;
+    Ok(removed)
+}
+
+pub async fn list(pool: &AnyPool) -> Result<Vec<Collection>> {
+    let rows: Vec<(String, String, i64)> = sqlx::query_as(
+        "SELECT name, date_created,
+                (SELECT COUNT(*) FROM collection_mod WHERE collection_mod.id_collection = collection.id_collection)
+         FROM collection WHERE date_deleted IS NULL ORDER BY name",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(name, date_created, mods)| Collection {
+            name,
+            date_created,
+            mods,
+        })
+        .collect())
+}
+
+pub async fn members(pool: &AnyPool, name: &str) -> Result<Vec<CollectionMod>> {
+    let id_collection = resolve(pool, name).await?;
+    let rows: Vec<CollectionModRow> = sqlx::query_as(
+        "SELECT mod.id_mod, name_id, name, collection_mod.id_modfile, version, note
+         FROM collection_mod
+         JOIN mod ON mod.id_mod = collection_mod.id_mod
+         LEFT JOIN modfile ON modfile.id_modfile = collection_mod.id_modfile
+         WHERE id_collection = $1 ORDER BY name_id",
+    )
+    .bind(id_collection)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id_mod, name_id, name, id_modfile, version, note)| CollectionMod {
+                id_mod,
+                name_id,
+                name,
+                id_modfile,
+                version,
+                note,
+            },
+        )
+        .collect())
+}
+
+type CollectionModRow = (
+    i64,
+    String,
+    String,
+    Option<i64>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Serialize a collection for sharing with other index instances.
+pub async fn export(pool: &AnyPool, name: &str) -> Result<CollectionFile> {
+    let id_collection = resolve(pool, name).await?;
+    let rows: Vec<ExportRow> = sqlx::query_as(
+        "SELECT mod.id_mod, name_id, collection_mod.id_modfile, version, hash_md5, note
+         FROM collection_mod
+         JOIN mod ON mod.id_mod = collection_mod.id_mod
+         LEFT JOIN modfile ON modfile.id_modfile = collection_mod.id_modfile
+         WHERE id_collection = $1 ORDER BY name_id",
+    )
+    .bind(id_collection)
+    .fetch_all(pool)
+    .await?;
+    let mods = rows
+        .into_iter()
+        .map(
+            |(id_mod, name_id, id_modfile, version, hash_md5, note)| CollectionFileMod {
+                id_mod,
+                name_id,
+                pinned: id_modfile
+                    .zip(hash_md5)
+                    .map(|(id_modfile, hash_md5)| PinnedModfile {
+                        id_modfile,
+                        version,
+                        hash_md5,
+                    }),
+                note,
+            },
+        )
+        .collect();
+    Ok(CollectionFile {
+        format: EXPORT_FORMAT,
+        name: name.to_string(),
+        mods,
+    })
+}
+
+type ExportRow = (
+    i64,
+    String,
+    Option<i64>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Create a collection from a shared file, named `name` or the name stored in the file. Mods are
+/// matched by id first and name_id second; pinned modfiles by id first and hash second.
+pub async fn import(
+    pool: &AnyPool,
+    file: CollectionFile,
+    name: Option<String>,
+) -> Result<ImportReport> {
+    if file.format != EXPORT_FORMAT {
+        bail!(
+            "unsupported collection file format {}, expected {EXPORT_FORMAT}",
+            file.format
+        );
+    }
+    let name = name.unwrap_or(file.name);
+    create(pool, &name).await?;
+    let id_collection = resolve(pool, &name).await?;
+
+    let mut report = ImportReport {
+        collection: name,
+        imported: 0,
+        unresolved: vec![],
+        unpinned: vec![],
+    };
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut tx = pool.begin().await?;
+    for entry in file.mods {
+        let id_mod: Option<i64> = sqlx::query_scalar(
+            "SELECT id_mod FROM (
+                 SELECT id_mod, CASE WHEN id_mod = $1 THEN 0 ELSE 1 END AS rank FROM mod
+                 WHERE id_mod = $1 OR name_id = $2
+                 UNION ALL
+                 SELECT id_mod, 2 AS rank FROM mod_alias WHERE name_id = $2
+             ) AS found ORDER BY rank LIMIT 1",
+        )
+        .bind(entry.id_mod)
+        .bind(&entry.name_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(id_mod) = id_mod else {
+            warn!(
+                name_id = entry.name_id,
+                "Skipping mod that is not in the index"
+            );
+            report.unresolved.push(entry.name_id);
+            continue;
+        };
+
+        let id_modfile = match &entry.pinned {
+            None => None,
+            Some(pinned) => {
+                let found: Option<i64> = sqlx::query_scalar(
+                    "SELECT id_modfile FROM modfile
+                     WHERE id_mod = $1 AND (id_modfile = $2 OR hash_md5 = $3)
+                     ORDER BY CASE WHEN id_modfile = $2 THEN 0 ELSE 1 END LIMIT 1",
+                )
+                .bind(id_mod)
+                .bind(pinned.id_modfile)
+                .bind(&pinned.hash_md5)
+                .fetch_optional(&mut *tx)
+                .await?;
+                if found.is_none() {
+                    warn!(
+                        name_id = entry.name_id,
+                        id_modfile = pinned.id_modfile,
+                        "Pinned modfile is not in the index, importing unpinned"
+                    );
+                    report.unpinned.push(entry.name_id.clone());
+                }
+                found
+            }
+        };
+
+        insert_mod(
+            &mut tx,
+            id_collection,
+            id_mod,
+            id_modfile,
+            entry.note.as_deref(),
+            &now,
+        )
+        .await?;
+        report.imported += 1;
+    }
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+/// Hashes of the current modfiles of every mod in a collection, for filtering archive listings.
+pub async fn current_hashes(pool: &AnyPool, name: &str) -> Result<Vec<String>> {
+    let id_collection = resolve(pool, name).await?;
+    Ok(sqlx::query_scalar(
+        "SELECT hash_md5 FROM collection_mod
+         JOIN mod ON mod.id_mod = collection_mod.id_mod
+         JOIN modfile ON modfile.id_modfile = mod.id_modfile
+         WHERE id_collection = $1",
+    )
+    .bind(id_collection)
+    .fetch_all(pool)
+    .await?)
+}
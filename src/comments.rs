@@ -0,0 +1,63 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use modio::filter::Filter;
+use modio::Modio;
+use sqlx::AnyPool;
+use tracing::info;
+
+use crate::{api, daemon, SyncSummary};
+
+/// Replace the indexed comments of each mod in `mods` with the comments currently on mod.io.
+/// Costs one request per mod plus one per extra page of comments, so it is opt-in.
+pub async fn index_comments(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    modio: &Modio,
+    mods: &[u32],
+    summary: &mut SyncSummary,
+) -> Result<()> {
+    info!("Grabbing comments of {} mods...", mods.len());
+    let bar = multi_bar.add(ProgressBar::new(mods.len().try_into().unwrap()));
+    for &id_mod in mods {
+        if daemon::shutdown_requested() {
+            info!("Stopping early, shutdown requested");
+            break;
+        }
+        let comments = modio
+            .game(api::DRG)
+            .mod_(id_mod)
+            .comments()
+            .search(Filter::default())
+            .collect()
+            .await?;
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM mod_comment WHERE id_mod = $1")
+            .bind(i64::from(id_mod))
+            .execute(&mut *tx)
+            .await?;
+        for comment in &comments {
+            sqlx::query(
+                "INSERT INTO mod_comment(id_comment, id_mod, id_reply, thread_position, author,
+                                         id_author, date_added, karma, content)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            )
+            .bind(i64::from(comment.id))
+            .bind(i64::from(id_mod))
+            .bind(i64::from(comment.reply_id))
+            .bind(&comment.thread_position)
+            .bind(&comment.submitted_by.username)
+            .bind(i64::from(comment.submitted_by.id))
+            .bind(api::timestamp(comment.date_added)?)
+            .bind(i64::from(comment.karma))
+            .bind(&comment.content)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        summary.comments += comments.len() as u64;
+        bar.inc(1);
+    }
+    bar.finish();
+    Ok(())
+}
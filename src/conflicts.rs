@@ -0,0 +1,419 @@
+//! Every path the current modfiles of two or more mods contain, ignoring case like the game
+//! does, grouped by path and by pair of mods, as text or as a self-contained HTML page to share
+//! with modpack users.
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::api;
+use crate::feed::escape;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct ConflictMod {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub name: String,
+}
+
+/// A path several mods contain.
+#[derive(Debug, Serialize)]
+pub struct PathConflict {
+    pub path: String,
+    /// Whether it is a base game asset rather than content the mods add
+    pub vanilla_override: bool,
+    pub mods: Vec<ConflictMod>,
+}
+
+/// Two mods and the paths both contain.
+#[derive(Debug, Serialize)]
+pub struct PairConflict {
+    pub a: ConflictMod,
+    pub b: ConflictMod,
+    pub paths: Vec<String>,
+    pub vanilla_overrides: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConflictReport {
+    pub paths: Vec<PathConflict>,
+    pub pairs: Vec<PairConflict>,
+}
+
+impl std::fmt::Display for ConflictReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for p in &self.pairs {
+            write!(
+                f,
+                "{} and {} share {} paths",
+                p.a.name_id,
+                p.b.name_id,
+                p.paths.len()
+            )?;
+            if p.vanilla_overrides > 0 {
+                write!(f, " ({} base game assets)", p.vanilla_overrides)?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "{} conflicting paths, {} conflicting pairs",
+            self.paths.len(),
+            self.pairs.len()
+        )
+    }
+}
+
+/// Conflicts between the current modfiles of every indexed mod, most shared paths first.
+pub async fn report(pool: &AnyPool) -> Result<ConflictReport> {
+    let rows: Vec<(String, String, i64, String, String, i64)> = sqlx::query_as(
+        "SELECT path_lower, path, mod.id_mod, mod.name_id, mod.name,
+                COALESCE(vanilla_override, 0)
+         FROM pack_file JOIN mod ON mod.id_modfile = pack_file.id_modfile
+         WHERE path_lower IN (SELECT path_lower
+                              FROM pack_file JOIN mod ON mod.id_modfile = pack_file.id_modfile
+                              GROUP BY path_lower HAVING COUNT(DISTINCT mod.id_mod) > 1)
+         ORDER BY path_lower, mod.name_id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut paths: Vec<(String, PathConflict)> = vec![];
+    for (path_lower, path, id_mod, name_id, name, vanilla_override) in rows {
+        if !matches!(paths.last(), Some((last, _)) if *last == path_lower) {
+            paths.push((
+                path_lower,
+                PathConflict {
+                    path,
+                    vanilla_override: false,
+                    mods: vec![],
+                },
+            ));
+        }
+        let (_, conflict) = paths.last_mut().unwrap();
+        conflict.vanilla_override |= vanilla_override != 0;
+        let m = ConflictMod {
+            id_mod,
+            name_id,
+            name,
+        };
+        // a modfile can hold paths only differing in case
+        if !conflict.mods.contains(&m) {
+            conflict.mods.push(m);
+        }
+    }
+    let paths = paths.into_iter().map(|(_, p)| p).collect::<Vec<_>>();
+
+    let mut pairs = BTreeMap::<(&ConflictMod, &ConflictMod), (Vec<String>, usize)>::new();
+    for p in &paths {
+        for (i, a) in p.mods.iter().enumerate() {
+            for b in &p.mods[i + 1..] {
+                let (shared, vanilla) = pairs.entry((a.min(b), a.max(b))).or_default();
+                shared.push(p.path.clone());
+                *vanilla += usize::from(p.vanilla_override);
+            }
+        }
+    }
+    let mut pairs = pairs
+        .into_iter()
+        .map(|((a, b), (paths, vanilla_overrides))| PairConflict {
+            a: a.clone(),
+            b: b.clone(),
+            paths,
+            vanilla_overrides,
+        })
+        .collect::<Vec<_>>();
+    pairs.sort_by(|x, y| y.paths.len().cmp(&x.paths.len()));
+
+    Ok(ConflictReport { paths, pairs })
+}
+
+fn mod_link(m: &ConflictMod) -> String {
+    format!(
+        "<a href=\"{}\">{}</a>",
+        escape(&api::mod_url(&m.name_id)),
+        escape(&m.name)
+    )
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}
+table{border-collapse:collapse;width:100%;margin-bottom:3em}
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top}
+th{background:#eee;cursor:pointer;user-select:none}
+tr.vanilla td:first-child{font-weight:bold}
+input{width:100%;padding:6px;margin-bottom:1em;box-sizing:border-box}
+td.paths{font-family:monospace;font-size:90%;white-space:pre-line}";
+
+const SCRIPT: &str = "for (const input of document.querySelectorAll('input[data-table]')) {
+  input.addEventListener('input', () => {
+    const needle = input.value.toLowerCase();
+    for (const row of document.getElementById(input.dataset.table).tBodies[0].rows) {
+      row.hidden = !row.textContent.toLowerCase().includes(needle);
+    }
+  });
+}
+for (const th of document.querySelectorAll('th')) {
+  th.addEventListener('click', () => {
+    const table = th.closest('table');
+    const body = table.tBodies[0];
+    const column = th.cellIndex;
+    const ascending = th.dataset.order !== 'asc';
+    th.dataset.order = ascending ? 'asc' : 'desc';
+    const key = row => row.cells[column].textContent;
+    const rows = [...body.rows].sort((a, b) => {
+      const [x, y] = [key(a), key(b)];
+      const order = isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+      return ascending ? order : -order;
+    });
+    body.append(...rows);
+  });
+}";
+
+/// Render `report` as an HTML page without external resources. Both tables can be filtered
+/// and sorted by clicking a column header.
+pub fn render_html(report: &ConflictReport) -> Result<String> {
+    let mut html = String::new();
+    write!(
+        html,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+         <title>DRG mod conflicts</title><style>{STYLE}</style></head><body>\n\
+         <h1>DRG mod conflicts</h1>\n\
+         <p>{} paths are contained in more than one mod, between {} pairs of mods. When two mods \
+         contain the same path only the one loaded last takes effect. Bold paths are base game \
+         assets both mods change.</p>\n",
+        report.paths.len(),
+        report.pairs.len()
+    )?;
+
+    html.push_str(
+        "<h2>By pair of mods</h2>\n\
+         <input data-table=\"pairs\" placeholder=\"Filter by mod or path\">\n\
+         <table id=\"pairs\"><thead><tr><th>Mod</th><th>Mod</th><th>Shared paths</th>\
+         <th>Base game assets</th><th>Paths</th></tr></thead><tbody>\n",
+    );
+    for p in &report.pairs {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"paths\">{}</td></tr>",
+            mod_link(&p.a),
+            mod_link(&p.b),
+            p.paths.len(),
+            p.vanilla_overrides,
+            escape(&p.paths.join("\n"))
+        )?;
+    }
+    html.push_str("</tbody></table>\n");
+
+    html.push_str(
+        "<h2>By path</h2>\n\
+         <input data-table=\"paths\" placeholder=\"Filter by path or mod\">\n\
+         <table id=\"paths\"><thead><tr><th>Path</th><th>Mods</th><th>Number of mods</th>\
+         </tr></thead><tbody>\n",
+    );
+    for p in &report.paths {
+        let mods = p.mods.iter().map(mod_link).collect::<Vec<_>>();
+        writeln!(
+            html,
+            "<tr{}><td class=\"paths\">{}</td><td>{}</td><td>{}</td></tr>",
+            if p.vanilla_override {
+                " class=\"vanilla\""
+            } else {
+                ""
+            },
+            escape(&p.path),
+            mods.join("<br>"),
+            p.mods.len()
+        )?;
+    }
+    write!(
+        html,
+        "</tbody></table>\n<script>{SCRIPT}</script>\n</body></html>\n"
+    )?;
+    Ok(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An in-memory index holding mods as (id, name_id, paths of the current modfile). Paths
+    /// starting with `!` override base game assets.
+    async fn index(mods: &[(i64, &str, &[&str])]) -> AnyPool {
+        let pool = crate::db::connect("sqlite::memory:", true, true)
+            .await
+            .unwrap();
+        for (id_mod, name_id, paths) in mods {
+            sqlx::query("INSERT INTO mod(id_mod, name, name_id, summary) VALUES ($1, $2, $2, '')")
+                .bind(id_mod)
+                .bind(name_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            let id_modfile = id_mod * 10;
+            sqlx::query(
+                "INSERT INTO modfile(id_modfile, id_mod, date_added, hash_md5, filename)
+                 VALUES ($1, $2, '', '', 'mod.zip')",
+            )
+            .bind(id_modfile)
+            .bind(id_mod)
+            .execute(&pool)
+            .await
+            .unwrap();
+            sqlx::query("UPDATE mod SET id_modfile = $1 WHERE id_mod = $2")
+                .bind(id_modfile)
+                .bind(id_mod)
+                .execute(&pool)
+                .await
+                .unwrap();
+            for path in *paths {
+                let (path, vanilla) = match path.strip_prefix('!') {
+                    Some(path) => (path, 1),
+                    None => (*path, 0),
+                };
+                sqlx::query(
+                    "INSERT INTO pack_file(id_modfile, path, path_no_extension, name, vanilla_override)
+                     VALUES ($1, $2, $2, $2, $3)",
+                )
+                .bind(id_modfile)
+                .bind(path)
+                .bind(vanilla)
+                .execute(&pool)
+                .await
+                .unwrap();
+            }
+        }
+        pool
+    }
+
+    fn mods(conflict: &PathConflict) -> Vec<&str> {
+        conflict.mods.iter().map(|m| m.name_id.as_str()).collect()
+    }
+
+    fn pair(conflict: &PairConflict) -> (&str, &str) {
+        (&conflict.a.name_id, &conflict.b.name_id)
+    }
+
+    #[tokio::test]
+    async fn overlapping_paths() {
+        let pool = index(&[
+            (
+                1,
+                "drills",
+                &["!FSD/Content/Drill.uasset", "FSD/Content/Drills/New.uasset"],
+            ),
+            (2, "better-drills", &["!FSD/Content/Drill.uasset"]),
+            (3, "drill-sounds", &["FSD/Content/drills/new.uasset"]),
+            (4, "unrelated", &["FSD/Content/Other.uasset"]),
+        ])
+        .await;
+        let report = report(&pool).await.unwrap();
+
+        assert_eq!(report.paths.len(), 2);
+        assert_eq!(report.paths[0].path, "FSD/Content/Drill.uasset");
+        assert!(report.paths[0].vanilla_override);
+        assert_eq!(mods(&report.paths[0]), ["better-drills", "drills"]);
+        // the game mounts paths ignoring case, so these collide
+        assert!(report.paths[1]
+            .path
+            .eq_ignore_ascii_case("FSD/Content/Drills/New.uasset"));
+        assert!(!report.paths[1].vanilla_override);
+        assert_eq!(mods(&report.paths[1]), ["drill-sounds", "drills"]);
+
+        assert_eq!(report.pairs.len(), 2);
+        assert_eq!(pair(&report.pairs[0]), ("drills", "better-drills"));
+        assert_eq!(report.pairs[0].paths, ["FSD/Content/Drill.uasset"]);
+        assert_eq!(report.pairs[0].vanilla_overrides, 1);
+        assert_eq!(pair(&report.pairs[1]), ("drills", "drill-sounds"));
+        assert_eq!(report.pairs[1].vanilla_overrides, 0);
+    }
+
+    #[tokio::test]
+    async fn most_shared_paths_first() {
+        let pool = index(&[
+            (1, "a", &["X.uasset"]),
+            (2, "b", &["X.uasset", "Y.uasset", "Z.uasset"]),
+            (3, "c", &["Y.uasset", "Z.uasset"]),
+        ])
+        .await;
+        let report = report(&pool).await.unwrap();
+
+        let pairs = report.pairs.iter().map(pair).collect::<Vec<_>>();
+        assert_eq!(pairs, [("b", "c"), ("a", "b")]);
+        assert_eq!(report.pairs[0].paths, ["Y.uasset", "Z.uasset"]);
+        assert_eq!(
+            report.to_string(),
+            "b and c share 2 paths\na and b share 1 paths\n3 conflicting paths, 2 conflicting pairs"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_mod_does_not_conflict_with_itself() {
+        let pool = index(&[
+            (
+                1,
+                "messy",
+                &["FSD/Content/Map.umap", "FSD/Content/map.umap"],
+            ),
+            (2, "clean", &["FSD/Content/Other.umap"]),
+        ])
+        .await;
+        let report = report(&pool).await.unwrap();
+        assert!(report.paths.is_empty());
+        assert!(report.pairs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_mod_is_listed_once_per_path() {
+        // the path differs in case within messy, which is still one conflict with maps
+        let pool = index(&[
+            (
+                1,
+                "messy",
+                &["FSD/Content/Map.umap", "FSD/Content/map.umap"],
+            ),
+            (2, "maps", &["FSD/Content/MAP.umap"]),
+        ])
+        .await;
+        let report = report(&pool).await.unwrap();
+        assert_eq!(report.paths.len(), 1);
+        assert_eq!(mods(&report.paths[0]), ["maps", "messy"]);
+        assert_eq!(report.pairs.len(), 1);
+        assert_eq!(pair(&report.pairs[0]), ("messy", "maps"));
+        assert_eq!(report.pairs[0].paths.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn only_current_modfiles() {
+        let pool = index(&[(1, "a", &["X.uasset"]), (2, "b", &["Y.uasset"])]).await;
+        // an older modfile of b still holding the path a contains
+        sqlx::query(
+            "INSERT INTO modfile(id_modfile, id_mod, date_added, hash_md5, filename)
+             VALUES (19, 2, '', '', 'old.zip')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO pack_file(id_modfile, path, path_no_extension, name)
+             VALUES (19, 'X.uasset', 'X', 'X')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let report = report(&pool).await.unwrap();
+        assert!(report.paths.is_empty());
+        assert!(report.pairs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn html_escapes_names_and_paths() {
+        let pool = index(&[(1, "a", &["<X>.uasset"]), (2, "b", &["<X>.uasset"])]).await;
+        let html = render_html(&report(&pool).await.unwrap()).unwrap();
+        assert!(html.contains("&lt;X&gt;.uasset"));
+        assert!(!html.contains("<X>"));
+        assert!(html.contains(&escape(&api::mod_url("a"))));
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{bail, Context, Result};
+use sqlx::AnyPool;
+use tokio::sync::watch;
+use tracing::{error, info};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Whether the daemon received a shutdown signal. Long running loops check this between units of
+/// work so they stop at a point where the index is consistent instead of being killed mid-write.
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::Relaxed)
+}
+
+/// Parse a duration like `90s`, `30m`, `6h` or `1d`. A bare number is taken as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("invalid duration {s:?}"))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => bail!("invalid duration unit {unit:?}, expected s, m, h or d"),
+    };
+    if number == 0 {
+        bail!("duration must be greater than zero");
+    }
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .with_context(|| format!("duration {s:?} is too long"))
+}
+
+/// Wait for SIGINT or SIGTERM.
+pub async fn wait_for_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// Run a sync every `interval` until SIGINT or SIGTERM.
+///
+/// Pending work is kept in the index rather than in memory: modfiles whose download failed are
+/// retried because their mod is not updated until the download succeeds, and modfiles without
+/// pack files are analyzed by every sync, so nothing is lost if the daemon is stopped or crashes.
+/// A signal lets the current mod finish before stopping.
+pub async fn run(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    interval: Duration,
+    options: crate::SyncOptions,
+) -> Result<()> {
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        if let Err(e) = wait_for_signal().await {
+            error!("Failed to listen for shutdown signals: {e:#}");
+            return;
+        }
+        info!("Shutdown requested, finishing current work");
+        SHUTDOWN.store(true, Ordering::Relaxed);
+        shutdown_tx.send(true).ok();
+    });
+
+    info!(interval = ?interval, "Daemon started");
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown_rx.changed() => break,
+        }
+
+        let (pending_analyses, failed_downloads): (i64, i64) = sqlx::query_as(
+            "SELECT
+                (SELECT COUNT(*) FROM modfile
+                 WHERE NOT EXISTS (SELECT 1 FROM pack_file WHERE pack_file.id_modfile = modfile.id_modfile)),
+                (SELECT COUNT(*) FROM download WHERE state = $1)",
+        )
+        .bind(crate::download::DownloadState::Failed.as_str())
+        .fetch_one(pool)
+        .await?;
+        info!(
+            pending_analyses,
+            failed_downloads, "Starting scheduled sync"
+        );
+
+        let started = std::time::Instant::now();
+        match crate::sync(multi_bar, pool, options, &Default::default()).await {
+            Ok(summary) => {
+                crate::metrics::record_sync(started.elapsed(), Some(&summary));
+                crate::notify(pool, &summary).await;
+                info!("Sync complete: {summary}");
+            }
+            Err(e) => {
+                crate::metrics::record_sync(started.elapsed(), None);
+                error!("Sync failed, retrying next interval: {e:#}");
+            }
+        }
+        if let Err(e) = crate::api::save_quota(pool).await {
+            error!("Failed to save API quota: {e:#}");
+        }
+        if shutdown_requested() {
+            break;
+        }
+    }
+    info!("Daemon stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(15 * 60));
+        assert_eq!(
+            parse_duration(" 6h ").unwrap(),
+            Duration::from_secs(6 * 60 * 60)
+        );
+        assert_eq!(
+            parse_duration("1d").unwrap(),
+            Duration::from_secs(24 * 60 * 60)
+        );
+    }
+
+    #[test]
+    fn parse_duration_rejects_zero() {
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("0d").is_err());
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_duration("-5s").is_err());
+        assert!(parse_duration("1.5h").is_err());
+    }
+
+    #[test]
+    fn parse_duration_overflow() {
+        assert_eq!(
+            parse_duration(&format!("{}s", u64::MAX)).unwrap(),
+            Duration::from_secs(u64::MAX)
+        );
+        assert!(parse_duration(&format!("{}m", u64::MAX)).is_err());
+        assert!(parse_duration(&format!("{}d", u64::MAX / 60)).is_err());
+        // does not fit in a u64 at all
+        assert!(parse_duration("99999999999999999999s").is_err());
+    }
+}
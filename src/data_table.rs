@@ -0,0 +1,178 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::AnyPool;
+
+use std::path::{Component, Path};
+
+/// The rows of a DataTable or CurveTable package of a modfile.
+#[derive(Debug, Serialize)]
+pub struct Table {
+    pub path: String,
+    pub rows: Map<String, Value>,
+}
+
+pub fn print_tables(tables: &[Table]) {
+    for t in tables {
+        println!("{} ({} rows)", t.path, t.rows.len());
+        for (row, data) in &t.rows {
+            println!("    {row}: {data}");
+        }
+    }
+}
+
+/// Write each table to `<output>/<path>.json`, keeping game paths. Returns the number of files
+/// written.
+pub fn write_json(tables: &[Table], output: &Path) -> Result<usize> {
+    for t in tables {
+        let relative = Path::new(&t.path).with_extension("json");
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            bail!(
+                "refusing to write {:?} outside of the output directory",
+                t.path
+            );
+        }
+        let destination = output.join(relative);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&destination, serde_json::to_string_pretty(&t.rows)? + "\n")
+            .with_context(|| format!("failed to write {}", destination.display()))?;
+    }
+    Ok(tables.len())
+}
+
+/// The indexed table rows of `id_modfile`, only of tables whose path contains `table` if given.
+pub async fn tables(pool: &AnyPool, id_modfile: i64, table: Option<&str>) -> Result<Vec<Table>> {
+    let rows: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT path, row_name, data FROM data_table_row
+         WHERE id_modfile = $1 AND ($2 = '' OR path LIKE '%' || $2 || '%')
+         ORDER BY path, row_name",
+    )
+    .bind(id_modfile)
+    .bind(table.unwrap_or_default())
+    .fetch_all(pool)
+    .await?;
+
+    let mut tables: Vec<Table> = vec![];
+    for (path, row, data) in rows {
+        let data = serde_json::from_str(&data)
+            .with_context(|| format!("row {row} of {path} is not valid JSON"))?;
+        if tables.last().is_none_or(|t| t.path != path) {
+            tables.push(Table {
+                path,
+                rows: Map::new(),
+            });
+        }
+        tables.last_mut().unwrap().rows.insert(row, data);
+    }
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uasset::Package;
+
+    const UASSET: &[u8] = include_bytes!("../tests/fixtures/assets/DT_WeaponStats.uasset");
+    const UEXP: &[u8] = include_bytes!("../tests/fixtures/assets/DT_WeaponStats.uexp");
+    const PATH: &str = "FSD/Content/GameElements/Weapons/DT_WeaponStats.uasset";
+
+    /// An index with the rows of the sample DataTable stored for modfile 10.
+    async fn index() -> (AnyPool, Map<String, Value>) {
+        let pool = crate::db::connect("sqlite::memory:", true, true)
+            .await
+            .unwrap();
+        let package = Package::parse(UASSET, Some(UEXP)).unwrap();
+        let rows = package.table_rows(&package.exports[0]).unwrap().unwrap();
+        for query in [
+            "INSERT INTO mod(id_mod, name, name_id, summary) VALUES (1, 'Mod', 'mod', '')",
+            "INSERT INTO modfile(id_modfile, id_mod, date_added, hash_md5, filename)
+             VALUES (10, 1, '', '', 'mod.zip')",
+        ] {
+            sqlx::query(query).execute(&pool).await.unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO pack_file(id_modfile, path, path_no_extension, name) VALUES (10, $1, $2, $3)",
+        )
+        .bind(PATH)
+        .bind(PATH.strip_suffix("uasset").unwrap())
+        .bind("DT_WeaponStats")
+        .execute(&pool)
+        .await
+        .unwrap();
+        for (row, data) in &rows {
+            sqlx::query(
+                "INSERT INTO data_table_row(id_modfile, path, row_name, data) VALUES (10, $1, $2, $3)",
+            )
+            .bind(PATH)
+            .bind(row)
+            .bind(data.to_string())
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        (pool, rows)
+    }
+
+    #[tokio::test]
+    async fn sample_table() {
+        let (pool, rows) = index().await;
+        let found = tables(&pool, 10, None).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, PATH);
+        assert_eq!(found[0].rows, rows);
+        assert_eq!(found[0].rows["Fast"]["Damage"], 12.5);
+
+        assert_eq!(
+            tables(&pool, 10, Some("WeaponStats")).await.unwrap().len(),
+            1
+        );
+        assert!(tables(&pool, 10, Some("Armor")).await.unwrap().is_empty());
+        assert!(tables(&pool, 11, None).await.unwrap().is_empty());
+
+        let output =
+            std::env::temp_dir().join(format!("drg-modio-index-data-table-{}", std::process::id()));
+        assert_eq!(write_json(&found, &output).unwrap(), 1);
+        let written = std::fs::read_to_string(
+            output.join("FSD/Content/GameElements/Weapons/DT_WeaponStats.json"),
+        );
+        std::fs::remove_dir_all(&output).unwrap();
+        let written: Map<String, Value> = serde_json::from_str(&written.unwrap()).unwrap();
+        assert_eq!(written, rows);
+    }
+
+    #[tokio::test]
+    async fn truncated_row() {
+        let (pool, _) = index().await;
+        sqlx::query("UPDATE data_table_row SET data = SUBSTR(data, 1, 10) WHERE row_name = 'Slow'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let err = tables(&pool, 10, None).await.unwrap_err();
+        assert!(err.to_string().contains("row Slow"), "{err:#}");
+    }
+
+    #[test]
+    fn write_json_stays_in_output() {
+        let output = std::env::temp_dir().join(format!(
+            "drg-modio-index-data-table-escape-{}",
+            std::process::id()
+        ));
+        for path in [
+            "../DT_Escape.uasset",
+            "/tmp/DT_Escape.uasset",
+            "FSD/../../DT_Escape.uasset",
+        ] {
+            let table = Table {
+                path: path.into(),
+                rows: Map::new(),
+            };
+            assert!(write_json(&[table], &output).is_err(), "{path}");
+        }
+        assert!(!output.exists());
+    }
+}
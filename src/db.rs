@@ -0,0 +1,233 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use sqlx::any::AnyPoolOptions;
+use sqlx::migrate::{Migrate, MigrateDatabase, Migrator};
+use sqlx::{Any, AnyPool, Executor};
+use tracing::info;
+
+static SQLITE_MIGRATIONS: Migrator = sqlx::migrate!("migrations/sqlite");
+static POSTGRES_MIGRATIONS: Migrator = sqlx::migrate!("migrations/postgres");
+
+/// Database engine backing the index, selected by the scheme of `DATABASE_URL`.
+///
+/// All queries are written against the common subset of SQLite and PostgreSQL using `$N`
+/// placeholders so they can run unchanged through sqlx's `Any` driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+impl Backend {
+    pub fn from_url(url: &str) -> Result<Backend> {
+        if url.starts_with("sqlite:") {
+            Ok(Backend::Sqlite)
+        } else if url.starts_with("postgres:") || url.starts_with("postgresql:") {
+            Ok(Backend::Postgres)
+        } else {
+            bail!("unsupported DATABASE_URL {url:?}: expected a sqlite: or postgres: URL")
+        }
+    }
+
+    pub fn migrator(self) -> &'static Migrator {
+        match self {
+            Backend::Sqlite => &SQLITE_MIGRATIONS,
+            Backend::Postgres => &POSTGRES_MIGRATIONS,
+        }
+    }
+}
+
+/// Connect to the index. A database that does not exist yet is created with `create` set, read-only
+/// commands and dry runs fail instead. When `migrate` is set any pending migrations are applied so
+/// the schema always matches this build.
+pub async fn connect(url: &str, migrate: bool, create: bool) -> Result<AnyPool> {
+    sqlx::any::install_default_drivers();
+
+    let backend = Backend::from_url(url)?;
+    if !Any::database_exists(url).await? {
+        if !create {
+            bail!("no index at {url}, run sync to create one");
+        }
+        info!("Creating database {url}");
+        Any::create_database(url).await?;
+    }
+
+    let options = match backend {
+        // SQLite only allows a single writer so there is nothing to gain from more connections.
+        // The stages of a sync wait for it while another stage stores a mod, which can take a
+        // while for large paks. It also only enforces foreign keys when asked to, per
+        // connection, so that is set explicitly rather than relying on the driver's default
+        Backend::Sqlite => AnyPoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(std::time::Duration::from_secs(10 * 60))
+            .after_connect(|conn, _| {
+                Box::pin(async move {
+                    conn.execute("PRAGMA foreign_keys = ON").await?;
+                    Ok(())
+                })
+            }),
+        Backend::Postgres => AnyPoolOptions::new(),
+    };
+    let pool = options.connect(url).await?;
+
+    if migrate {
+        backend.migrator().run(&pool).await?;
+    }
+    Ok(pool)
+}
+
+#[derive(Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// List every known migration and whether it has been applied to the database.
+pub async fn migration_status(pool: &AnyPool, backend: Backend) -> Result<Vec<MigrationStatus>> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+
+    Ok(backend
+        .migrator()
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.iter().any(|a| a.version == migration.version),
+        })
+        .collect())
+}
+
+/// A maintenance step and how long it took.
+#[derive(Debug, Serialize)]
+pub struct MaintenanceStep {
+    pub step: String,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MaintenanceReport {
+    pub steps: Vec<MaintenanceStep>,
+    /// Problems found by the integrity check, empty if there were none or the backend has no
+    /// integrity check
+    pub integrity_errors: Vec<String>,
+}
+
+impl std::fmt::Display for MaintenanceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{} took {:.1}s", step.step, step.seconds)?;
+        }
+        if self.integrity_errors.is_empty() {
+            write!(f, "No integrity problems found")
+        } else {
+            write!(f, "Integrity problems:")?;
+            for error in &self.integrity_errors {
+                write!(f, "\n  {error}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Contentless trigram index of pack_file paths on SQLite. It can't be rebuilt from its own
+/// content like the other full-text search tables, so it is filled from pack_file again.
+const PATH_INDEX: &str = "pack_file_path";
+
+/// Full-text search tables on SQLite, full-text search indexes on PostgreSQL.
+async fn fts_objects(pool: &AnyPool, backend: Backend) -> Result<Vec<String>> {
+    Ok(match backend {
+        Backend::Sqlite => {
+            sqlx::query_scalar(
+                "SELECT name FROM sqlite_master
+                 WHERE type = 'table' AND sql LIKE '%USING fts%' AND name != $1
+                 ORDER BY name",
+            )
+            .bind(PATH_INDEX)
+            .fetch_all(pool)
+            .await?
+        }
+        Backend::Postgres => {
+            sqlx::query_scalar(
+                "SELECT indexname FROM pg_indexes
+                 WHERE schemaname = current_schema()
+                   AND (indexdef LIKE '%USING gin%' OR indexdef LIKE '%USING gist%')
+                 ORDER BY indexname",
+            )
+            .fetch_all(pool)
+            .await?
+        }
+    })
+}
+
+/// Reclaim free space, refresh the query planner's statistics and check the database for
+/// corruption, optionally rebuilding the full-text search indexes first. The integrity check
+/// only exists on SQLite. The path index is always filled again after VACUUM on SQLite.
+pub async fn maintain(
+    pool: &AnyPool,
+    backend: Backend,
+    reindex_fts: bool,
+) -> Result<MaintenanceReport> {
+    let mut steps = vec![];
+    let mut timed = |step: String, start: std::time::Instant| {
+        let seconds = start.elapsed().as_secs_f64();
+        info!("{step} took {seconds:.1}s");
+        steps.push(MaintenanceStep { step, seconds });
+    };
+
+    if reindex_fts {
+        for object in fts_objects(pool, backend).await? {
+            let start = std::time::Instant::now();
+            let statement = match backend {
+                Backend::Sqlite => format!("INSERT INTO {object}({object}) VALUES ('rebuild')"),
+                Backend::Postgres => format!("REINDEX INDEX {object}"),
+            };
+            pool.execute(statement.as_str()).await?;
+            timed(format!("reindex {object}"), start);
+        }
+    }
+
+    let start = std::time::Instant::now();
+    pool.execute("VACUUM").await?;
+    timed("VACUUM".to_string(), start);
+
+    // VACUUM may renumber the pack_file rowids the path index refers to
+    if backend == Backend::Sqlite {
+        let start = std::time::Instant::now();
+        let mut tx = pool.begin().await?;
+        sqlx::query(&format!(
+            "INSERT INTO {PATH_INDEX}({PATH_INDEX}) VALUES ('delete-all')"
+        ))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!(
+            "INSERT INTO {PATH_INDEX}(rowid, path) SELECT rowid, path FROM pack_file"
+        ))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        timed(format!("reindex {PATH_INDEX}"), start);
+    }
+
+    let start = std::time::Instant::now();
+    pool.execute("ANALYZE").await?;
+    timed("ANALYZE".to_string(), start);
+
+    let mut integrity_errors = vec![];
+    if backend == Backend::Sqlite {
+        let start = std::time::Instant::now();
+        let results: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+            .fetch_all(pool)
+            .await?;
+        integrity_errors = results.into_iter().filter(|r| r != "ok").collect();
+        timed("integrity_check".to_string(), start);
+    }
+
+    Ok(MaintenanceReport {
+        steps,
+        integrity_errors,
+    })
+}
@@ -0,0 +1,342 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::AnyPool;
+use tracing::warn;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::{download, uasset};
+
+/// Assets that differ between two modfiles of a mod.
+#[derive(Debug, Serialize)]
+pub struct ModfileDiff {
+    pub from: i64,
+    pub to: i64,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Assets present in both whose contents differ
+    pub changed: Vec<String>,
+    /// Assets present in both that could not be compared because one side was analyzed before
+    /// entry hashes were recorded. Running UpdateModFilesLocal fills them in.
+    pub unhashed: u64,
+    /// Row level changes of changed DataTables, CurveTables and curves
+    pub tables: Vec<TableDiff>,
+}
+
+/// Rows that differ between two versions of a table or curve asset.
+#[derive(Debug, Serialize)]
+pub struct TableDiff {
+    pub path: String,
+    pub added_rows: Vec<String>,
+    pub removed_rows: Vec<String>,
+    pub changed: Vec<RowChange>,
+}
+
+/// A value that changed within a row. `property` is a path into the row such as
+/// `Damage` or `Keys[2].Value`. `from` or `to` is absent when the property was added or removed.
+#[derive(Debug, Serialize)]
+pub struct RowChange {
+    pub row: String,
+    pub property: String,
+    pub from: Option<Value>,
+    pub to: Option<Value>,
+}
+
+impl TableDiff {
+    /// One line per change, e.g. `~ Row.Damage: 10 -> 12`.
+    pub fn lines(&self) -> Vec<String> {
+        let value = |v: &Option<Value>| match v {
+            Some(v) => v.to_string(),
+            None => "(none)".to_string(),
+        };
+        let mut lines = vec![];
+        for row in &self.added_rows {
+            lines.push(format!("+ {row}"));
+        }
+        for row in &self.removed_rows {
+            lines.push(format!("- {row}"));
+        }
+        for change in &self.changed {
+            lines.push(format!(
+                "~ {}.{}: {} -> {}",
+                change.row,
+                change.property,
+                value(&change.from),
+                value(&change.to)
+            ));
+        }
+        lines
+    }
+}
+
+impl std::fmt::Display for ModfileDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for path in &self.added {
+            writeln!(f, "+ {path}")?;
+        }
+        for path in &self.removed {
+            writeln!(f, "- {path}")?;
+        }
+        for path in &self.changed {
+            writeln!(f, "~ {path}")?;
+            let package = package_path(path);
+            for table in self
+                .tables
+                .iter()
+                .filter(|t| Some(&t.path) == package.as_ref())
+            {
+                for line in table.lines() {
+                    writeln!(f, "    {line}")?;
+                }
+            }
+        }
+        write!(
+            f,
+            "modfile {} -> {}: {} added, {} removed, {} changed",
+            self.from,
+            self.to,
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        )?;
+        if self.unhashed > 0 {
+            write!(
+                f,
+                ", {} not compared (missing entry hashes, run UpdateModFilesLocal)",
+                self.unhashed
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The modfile of `id_mod` added immediately before `id_modfile`.
+pub async fn previous_modfile(pool: &AnyPool, id_mod: i64, id_modfile: i64) -> Result<i64> {
+    let previous: Option<i64> = sqlx::query_scalar(
+        "SELECT modfile.id_modfile FROM modfile, modfile AS target
+         WHERE target.id_modfile = $2 AND modfile.id_mod = $1
+           AND (modfile.date_added < target.date_added
+                OR (modfile.date_added = target.date_added AND modfile.id_modfile < target.id_modfile))
+         ORDER BY modfile.date_added DESC, modfile.id_modfile DESC LIMIT 1",
+    )
+    .bind(id_mod)
+    .bind(id_modfile)
+    .fetch_optional(pool)
+    .await?;
+    previous.with_context(|| format!("modfile {id_modfile} is the oldest indexed version"))
+}
+
+async fn pack_files(pool: &AnyPool, id_modfile: i64) -> Result<BTreeMap<String, Option<String>>> {
+    let rows: Vec<(String, Option<String>)> =
+        sqlx::query_as("SELECT path, hash FROM pack_file WHERE id_modfile = $1")
+            .bind(id_modfile)
+            .fetch_all(pool)
+            .await?;
+    if rows.is_empty() {
+        bail!("modfile {id_modfile} has no pack files, it has not been analyzed");
+    }
+    Ok(rows.into_iter().collect())
+}
+
+/// Compare the pack files of two modfiles.
+pub async fn diff(pool: &AnyPool, from: i64, to: i64) -> Result<ModfileDiff> {
+    let old = pack_files(pool, from).await?;
+    let new = pack_files(pool, to).await?;
+
+    let mut diff = ModfileDiff {
+        from,
+        to,
+        added: vec![],
+        removed: vec![],
+        changed: vec![],
+        unhashed: 0,
+        tables: vec![],
+    };
+    for (path, hash) in &new {
+        match old.get(path) {
+            None => diff.added.push(path.clone()),
+            Some(old_hash) => match (old_hash, hash) {
+                (Some(a), Some(b)) if a != b => diff.changed.push(path.clone()),
+                (Some(_), Some(_)) => {}
+                _ => diff.unhashed += 1,
+            },
+        }
+    }
+    diff.removed = old
+        .keys()
+        .filter(|path| !new.contains_key(*path))
+        .cloned()
+        .collect();
+
+    // cooked packages usually only change in their .uexp, so compare by package
+    let packages = diff
+        .changed
+        .iter()
+        .filter_map(|path| package_path(path))
+        .filter(|path| new.contains_key(path) && old.contains_key(path))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    if !packages.is_empty() {
+        diff.tables = table_diffs(pool, from, to, packages).await?;
+    }
+
+    Ok(diff)
+}
+
+async fn table_diffs(
+    pool: &AnyPool,
+    from: i64,
+    to: i64,
+    packages: Vec<String>,
+) -> Result<Vec<TableDiff>> {
+    let hash = |id_modfile: i64| {
+        sqlx::query_scalar::<_, String>("SELECT hash_md5 FROM modfile WHERE id_modfile = $1")
+            .bind(id_modfile)
+            .fetch_one(pool)
+    };
+    let old_archive = download::archive_path(&hash(from).await?);
+    let new_archive = download::archive_path(&hash(to).await?);
+    if !old_archive.exists() || !new_archive.exists() {
+        warn!(from, to, "Archives not stored, skipping table comparison");
+        return Ok(vec![]);
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let old = read_tables(&old_archive, &packages)?;
+        let new = read_tables(&new_archive, &packages)?;
+        Ok(packages
+            .iter()
+            .filter_map(|path| Some(diff_table(path, old.get(path)?, new.get(path)?)))
+            .filter(|t| {
+                !t.added_rows.is_empty() || !t.removed_rows.is_empty() || !t.changed.is_empty()
+            })
+            .collect())
+    })
+    .await?
+}
+
+/// The `.uasset` a package file belongs to.
+fn package_path(path: &str) -> Option<String> {
+    let stem = path
+        .strip_suffix(".uasset")
+        .or_else(|| path.strip_suffix(".uexp"))?;
+    Some(format!("{stem}.uasset"))
+}
+
+/// Rows of the table-like exports in `packages` of a stored archive, by package path. Packages
+/// without one, or that fail to parse, are left out.
+fn read_tables(archive: &Path, packages: &[String]) -> Result<HashMap<String, Map<String, Value>>> {
+    let mut pak = crate::open_zip_pak(archive)?;
+    let mount_point = pak.pak.mount_point().to_string();
+    let mut records = HashMap::new();
+    for record in pak.pak.files() {
+        records.insert(crate::asset_path(&mount_point, &record)?, record);
+    }
+
+    let mut tables = HashMap::new();
+    for path in packages {
+        let Some(record) = records.get(path) else {
+            continue;
+        };
+        let uasset = pak.get(record)?;
+        let uexp = match path
+            .strip_suffix(".uasset")
+            .and_then(|stem| records.get(&format!("{stem}.uexp")))
+        {
+            Some(record) => Some(pak.get(record)?),
+            None => None,
+        };
+        let package = match uasset::Package::parse(&uasset, uexp.as_deref()) {
+            Ok(package) => package,
+            Err(e) => {
+                warn!(path, "Failed to parse package: {e:#}");
+                continue;
+            }
+        };
+        let rows = package
+            .exports
+            .iter()
+            .find_map(|export| package.table_rows(export));
+        match rows {
+            Some(Ok(rows)) => {
+                tables.insert(path.clone(), rows);
+            }
+            Some(Err(e)) => warn!(path, "Failed to read table rows: {e:#}"),
+            None => {}
+        }
+    }
+    Ok(tables)
+}
+
+fn diff_table(path: &str, old: &Map<String, Value>, new: &Map<String, Value>) -> TableDiff {
+    let mut diff = TableDiff {
+        path: path.to_string(),
+        added_rows: vec![],
+        removed_rows: vec![],
+        changed: vec![],
+    };
+    for (row, new_value) in new {
+        let Some(old_value) = old.get(row) else {
+            diff.added_rows.push(row.clone());
+            continue;
+        };
+        let mut old_leaves = BTreeMap::new();
+        let mut new_leaves = BTreeMap::new();
+        leaves(String::new(), old_value, &mut old_leaves);
+        leaves(String::new(), new_value, &mut new_leaves);
+        for (property, to) in &new_leaves {
+            let from = old_leaves.get(property);
+            if from != Some(to) {
+                diff.changed.push(RowChange {
+                    row: row.clone(),
+                    property: property.clone(),
+                    from: from.cloned(),
+                    to: Some(to.clone()),
+                });
+            }
+        }
+        for (property, from) in old_leaves {
+            if !new_leaves.contains_key(&property) {
+                diff.changed.push(RowChange {
+                    row: row.clone(),
+                    property,
+                    from: Some(from),
+                    to: None,
+                });
+            }
+        }
+    }
+    diff.removed_rows = old
+        .keys()
+        .filter(|row| !new.contains_key(*row))
+        .cloned()
+        .collect();
+    diff
+}
+
+/// Flatten nested objects and arrays into `a.b[0]` paths so changes point at a single value.
+fn leaves(prefix: String, value: &Value, out: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                leaves(path, value, out);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, value) in items.iter().enumerate() {
+                leaves(format!("{prefix}[{i}]"), value, out);
+            }
+        }
+        value => {
+            out.insert(prefix, value.clone());
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use anyhow::{bail, Context, Result};
+use futures::TryStreamExt;
+use md5::{Digest, Md5};
+use modio::download::DownloadAction;
+use modio::Modio;
+use sqlx::AnyPool;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use std::path::{Path, PathBuf};
+
+use crate::pak_source::PartialDownload;
+use crate::{api, archive, events, local, store, virus};
+
+/// Downloads claimed longer ago than this are assumed to belong to a worker that died.
+const STALE_CLAIM_MINUTES: i64 = 60;
+
+/// Location of the stored archive for a modfile, see [`store::object_path`].
+pub fn archive_path(md5: &str) -> PathBuf {
+    store::object_path(md5)
+}
+
+/// Download state of a modfile as recorded in the `download` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadState {
+    InProgress,
+    Complete,
+    Failed,
+    /// Analyzed and then deleted again by a sync run with `--no-keep` or to stay under
+    /// `MAX_STORE_SIZE`, or listed with range requests by one with `--metadata-only` and never
+    /// stored
+    Discarded,
+}
+
+impl DownloadState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DownloadState::InProgress => "in_progress",
+            DownloadState::Complete => "complete",
+            DownloadState::Failed => "failed",
+            DownloadState::Discarded => "discarded",
+        }
+    }
+}
+
+fn owner() -> String {
+    format!("pid {}", std::process::id())
+}
+
+async fn set_state(
+    pool: &AnyPool,
+    id_modfile: i64,
+    state: DownloadState,
+    error: Option<String>,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO download(id_modfile, state, owner, date_updated, error)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT(id_modfile) DO
+            UPDATE SET
+                state = excluded.state,
+                owner = excluded.owner,
+                date_updated = excluded.date_updated,
+                error = excluded.error",
+    )
+    .bind(id_modfile)
+    .bind(state.as_str())
+    .bind(owner())
+    .bind(chrono::Utc::now().to_rfc3339())
+    .bind(error)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Atomically take ownership of downloading `id_modfile`. Fails to claim if another worker is
+/// currently downloading it and its claim has not gone stale.
+async fn claim(pool: &AnyPool, id_modfile: i64) -> Result<bool> {
+    let now = chrono::Utc::now();
+    let stale = now - chrono::Duration::minutes(STALE_CLAIM_MINUTES);
+    let claimed = sqlx::query(
+        "INSERT INTO download(id_modfile, state, owner, date_updated, error)
+         VALUES ($1, $2, $3, $4, NULL)
+         ON CONFLICT(id_modfile) DO
+            UPDATE SET
+                state = excluded.state,
+                owner = excluded.owner,
+                date_updated = excluded.date_updated,
+                error = NULL
+            WHERE download.state != $2 OR download.date_updated < $5",
+    )
+    .bind(id_modfile)
+    .bind(DownloadState::InProgress.as_str())
+    .bind(owner())
+    .bind(now.to_rfc3339())
+    .bind(stale.to_rfc3339())
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(claimed > 0)
+}
+
+/// What [`download_modfile`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Downloaded {
+    /// The archive was stored already
+    Stored,
+    Downloaded,
+    /// Another worker is downloading the archive, it is not stored yet
+    ClaimedElsewhere,
+}
+
+/// Make sure the archive for `file` is present in the mods directory, downloading it if needed.
+///
+/// The download is written to a partial file keyed by modfile id and only renamed into place once
+/// complete, so an interrupted run never leaves a truncated archive behind.
+pub async fn download_modfile(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    modio: &Modio,
+    file: &modio::files::File,
+) -> Result<Downloaded> {
+    download(multi_bar, pool, modio, file, None).await
+}
+
+/// Like [`download_modfile`], but shares the partial file with `partial` as it is written, so the
+/// archive can be read through a [`GrowingFile`](pak_source::GrowingFile) while it downloads.
+/// `partial` is finished however this ends, as complete only if the archive was downloaded.
+pub async fn download_modfile_shared(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    modio: &Modio,
+    file: &modio::files::File,
+    partial: &PartialDownload,
+) -> Result<Downloaded> {
+    let res = download(multi_bar, pool, modio, file, Some(partial)).await;
+    partial.finish(matches!(res, Ok(Downloaded::Downloaded)));
+    res
+}
+
+async fn download(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    modio: &Modio,
+    file: &modio::files::File,
+    partial: Option<&PartialDownload>,
+) -> Result<Downloaded> {
+    let id_modfile = i64::from(file.id);
+    let path = archive_path(&file.filehash.md5);
+
+    if path.exists() {
+        set_state(pool, id_modfile, DownloadState::Complete, None).await?;
+        return Ok(Downloaded::Stored);
+    }
+    if !claim(pool, id_modfile).await? {
+        warn!(
+            id_modfile,
+            "Skipping download: already being downloaded by another worker"
+        );
+        return Ok(Downloaded::ClaimedElsewhere);
+    }
+
+    match fetch(multi_bar, modio, file, &path, partial).await {
+        Ok(()) => {
+            set_state(pool, id_modfile, DownloadState::Complete, None).await?;
+            let size = tokio::fs::metadata(&path).await?.len();
+            archive::record_capture(pool, file, size).await?;
+            Ok(Downloaded::Downloaded)
+        }
+        Err(e) => {
+            set_state(
+                pool,
+                id_modfile,
+                DownloadState::Failed,
+                Some(format!("{e:#}")),
+            )
+            .await?;
+            Err(e).with_context(|| format!("failed to download modfile {id_modfile}"))
+        }
+    }
+}
+
+/// Delete the archive of a modfile that has been analyzed, for indexes that do not keep archives.
+/// The modfile is recorded as discarded so it is not reported as missing.
+pub async fn discard(pool: &AnyPool, id_modfile: i64, md5: &str) -> Result<()> {
+    tokio::fs::remove_file(archive_path(md5)).await?;
+    set_state(pool, id_modfile, DownloadState::Discarded, None).await
+}
+
+/// Record a modfile whose archive is intentionally not stored, e.g. because it was listed without
+/// downloading it or evicted, so it is not reported as missing.
+pub async fn mark_discarded(pool: &AnyPool, id_modfile: i64) -> Result<()> {
+    set_state(pool, id_modfile, DownloadState::Discarded, None).await
+}
+
+/// md5 of the file at `path` as hex, read in chunks so archives of any size are hashed in bounded
+/// memory.
+pub async fn file_md5(path: &Path) -> Result<String> {
+    let path = path.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::io::BufReader::new(
+            std::fs::File::open(&path)
+                .with_context(|| format!("failed to open {}", path.display()))?,
+        );
+        let mut hasher = Md5::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await?
+}
+
+/// Check the stored archive of a modfile that failed to analyze against the md5 mod.io reported
+/// for it and, if it does not match, delete it and download it again. Returns whether the archive
+/// was replaced, `false` means it is intact and the pak itself cannot be read.
+pub async fn redownload_corrupt(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    id_modfile: i64,
+    md5: &str,
+) -> Result<bool> {
+    let path = archive_path(md5);
+    let actual = file_md5(&path).await?;
+    if actual == md5 {
+        return Ok(false);
+    }
+    let id_mod: i64 = sqlx::query_scalar("SELECT id_mod FROM modfile WHERE id_modfile = $1")
+        .bind(id_modfile)
+        .fetch_one(pool)
+        .await?;
+    if id_mod == local::LOCAL_MOD {
+        bail!("local archive {md5} is corrupt, store it again with analyze-path --store");
+    }
+
+    warn!(
+        id_modfile,
+        expected = md5,
+        actual,
+        "Stored archive is corrupt, downloading it again"
+    );
+    tokio::fs::remove_file(&path).await?;
+    let modio = api::client()?;
+    let file = modio
+        .game(api::DRG)
+        .mod_(id_mod as u32)
+        .file(id_modfile as u32)
+        .get()
+        .await?;
+    if file.filehash.md5 != md5 {
+        bail!(
+            "modfile {id_modfile} was re-uploaded with hash {}, run audit-upstream",
+            file.filehash.md5
+        );
+    }
+    if download_modfile(multi_bar, pool, &modio, &file).await? == Downloaded::ClaimedElsewhere {
+        bail!("modfile {id_modfile} is being downloaded by another worker");
+    }
+    Ok(true)
+}
+
+/// Make sure the archive for `file` is present like [`download_modfile`], but without recording
+/// anything in the index. Used to mirror archives without a database.
+pub async fn mirror_modfile(
+    multi_bar: &indicatif::MultiProgress,
+    modio: &Modio,
+    file: &modio::files::File,
+) -> Result<bool> {
+    let path = archive_path(&file.filehash.md5);
+    if path.exists() {
+        return Ok(false);
+    }
+    fetch(multi_bar, modio, file, &path, None)
+        .await
+        .with_context(|| format!("failed to download modfile {}", file.id))?;
+    Ok(true)
+}
+
+/// Download `file` to `path` through a partial file that is removed again on failure, sharing its
+/// progress with `partial` when given.
+async fn fetch(
+    multi_bar: &indicatif::MultiProgress,
+    modio: &Modio,
+    file: &modio::files::File,
+    path: &Path,
+    partial: Option<&PartialDownload>,
+) -> Result<()> {
+    let id_modfile = file.id;
+    if virus::refused(file) {
+        bail!("modfile {id_modfile} was flagged by mod.io's virus scan, pass --allow-flagged to download it anyway");
+    }
+    info!(id_modfile, size = file.filesize, "Downloading");
+    let download_bar = multi_bar.add(indicatif::ProgressBar::new(file.filesize));
+    download_bar.set_style(indicatif::ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")?.progress_chars("#>-"));
+
+    events::emit(events::Event::DownloadStarted {
+        id_modfile,
+        size: file.filesize,
+    });
+
+    let partial_path = path.with_extension(format!("{id_modfile}.part"));
+    let res = async {
+        tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+        let mut stream = Box::pin(
+            modio
+                .download(DownloadAction::FileObj(Box::new(file.clone())))
+                .stream(),
+        );
+        let mut out = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&partial_path)
+            .await?;
+        if let Some(partial) = partial {
+            partial.opened(out.try_clone().await?.into_std().await);
+        }
+        let (mut downloaded, mut reported) = (0, 0);
+        while let Some(bytes) = stream.try_next().await? {
+            out.write_all(&bytes).await?;
+            download_bar.inc(bytes.len() as u64);
+            downloaded += bytes.len() as u64;
+            if let Some(partial) = partial {
+                // written through to the file before a reader is told about it
+                out.flush().await?;
+                partial.advance(downloaded);
+            }
+            crate::metrics::record_download(bytes.len() as u64);
+            if downloaded - reported >= events::PROGRESS_STEP {
+                reported = downloaded;
+                events::emit(events::Event::DownloadProgress {
+                    id_modfile,
+                    downloaded,
+                    size: file.filesize,
+                });
+            }
+        }
+        out.flush().await?;
+        tokio::fs::rename(&partial_path, path).await?;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    multi_bar.remove(&download_bar);
+
+    match &res {
+        Ok(()) => events::emit(events::Event::DownloadFinished {
+            id_modfile,
+            size: file.filesize,
+        }),
+        Err(e) => {
+            tokio::fs::remove_file(&partial_path).await.ok();
+            events::emit(events::Event::Error {
+                id_mod: Some(file.mod_id),
+                id_modfile: Some(i64::from(id_modfile)),
+                message: &format!("failed to download modfile {id_modfile}: {e:#}"),
+            });
+        }
+    }
+    res
+}
+
+/// Progress of a mirror bootstrap, saved after every archive so totals survive restarts until
+/// every archive has been mirrored.
+const MIRROR_PROGRESS: &str = "mirror-progress.json";
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct MirrorProgress {
+    date_started: String,
+    /// Mirror runs that worked on this bootstrap, including the current one
+    runs: u64,
+    /// Size mod.io reported for the archives downloaded so far
+    expected_downloaded_bytes: u64,
+    downloaded_bytes: u64,
+}
+
+impl MirrorProgress {
+    fn load() -> Result<Self> {
+        match std::fs::read_to_string(MIRROR_PROGRESS) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("{MIRROR_PROGRESS} is corrupt, delete it to start over")),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self {
+                date_started: chrono::Utc::now().to_rfc3339(),
+                ..Default::default()
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let partial = format!("{MIRROR_PROGRESS}.part");
+        std::fs::write(&partial, serde_json::to_string_pretty(self)? + "\n")?;
+        std::fs::rename(partial, MIRROR_PROGRESS)?;
+        Ok(())
+    }
+}
+
+/// Counts of what a mirror run did. Byte counts cover the whole bootstrap across restarts.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct MirrorSummary {
+    pub mods: u64,
+    pub downloaded: u64,
+    /// Archives that were already in the mods directory
+    pub present: u64,
+    pub errors: Vec<String>,
+    pub date_started: String,
+    pub runs: u64,
+    /// Size mod.io reports for the current modfile of every mod
+    pub expected_bytes: u64,
+    /// Size of those archives in the mods directory
+    pub stored_bytes: u64,
+    pub expected_downloaded_bytes: u64,
+    pub downloaded_bytes: u64,
+}
+
+impl std::fmt::Display for MirrorSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for e in &self.errors {
+            writeln!(f, "{e}")?;
+        }
+        writeln!(
+            f,
+            "{} mods, {} downloaded, {} already present, {} errors",
+            self.mods,
+            self.downloaded,
+            self.present,
+            self.errors.len()
+        )?;
+        writeln!(
+            f,
+            "bootstrap started {} over {} runs: downloaded {} bytes of {} expected",
+            self.date_started, self.runs, self.downloaded_bytes, self.expected_downloaded_bytes
+        )?;
+        write!(
+            f,
+            "stored {} bytes of {} expected ({:+} bytes)",
+            self.stored_bytes,
+            self.expected_bytes,
+            self.stored_bytes as i64 - self.expected_bytes as i64
+        )
+    }
+}
+
+/// Download the current modfile of every mod into the mods directory without touching the index.
+/// A failed download is reported and the rest continue. The bytes to download are estimated from
+/// the sizes mod.io reports before starting, and overall progress is kept in [`MIRROR_PROGRESS`]
+/// until a run leaves nothing missing, so a bootstrap spread over many restarts reports totals for
+/// all of it.
+pub async fn mirror(multi_bar: &indicatif::MultiProgress) -> Result<MirrorSummary> {
+    let modio = crate::api::client()?;
+    let mods = crate::api::mod_list(&modio).await?;
+
+    let mut progress = MirrorProgress::load()?;
+    progress.runs += 1;
+    progress.save()?;
+
+    let files = mods.iter().filter_map(|m| m.modfile.as_ref());
+    let expected_bytes = files.clone().map(|f| f.filesize).sum::<u64>();
+    let missing_bytes = files
+        .filter(|f| !archive_path(&f.filehash.md5).exists())
+        .map(|f| f.filesize)
+        .sum::<u64>();
+    info!(
+        mods = mods.len(),
+        expected_bytes,
+        missing_bytes,
+        runs = progress.runs,
+        "Mirroring"
+    );
+
+    let mut summary = MirrorSummary::default();
+    let mod_bar = multi_bar.add(indicatif::ProgressBar::new(expected_bytes));
+    mod_bar.set_style(indicatif::ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} overall ({eta})")?.progress_chars("#>-"));
+    for m in &mods {
+        if let Some(file) = &m.modfile {
+            match mirror_modfile(multi_bar, &modio, file).await {
+                Ok(true) => {
+                    summary.downloaded += 1;
+                    progress.expected_downloaded_bytes += file.filesize;
+                    progress.downloaded_bytes +=
+                        std::fs::metadata(archive_path(&file.filehash.md5))?.len();
+                    progress.save()?;
+                }
+                Ok(false) => summary.present += 1,
+                Err(e) => {
+                    warn!(id_mod = m.id, "{e:#}");
+                    summary.errors.push(format!("mod {}: {e:#}", m.id));
+                }
+            }
+            mod_bar.inc(file.filesize);
+        }
+        summary.mods += 1;
+    }
+    mod_bar.finish();
+
+    for file in mods.iter().filter_map(|m| m.modfile.as_ref()) {
+        if let Ok(metadata) = std::fs::metadata(archive_path(&file.filehash.md5)) {
+            summary.stored_bytes += metadata.len();
+        }
+    }
+    summary.expected_bytes = expected_bytes;
+    summary.date_started = progress.date_started;
+    summary.runs = progress.runs;
+    summary.expected_downloaded_bytes = progress.expected_downloaded_bytes;
+    summary.downloaded_bytes = progress.downloaded_bytes;
+    if summary.errors.is_empty() {
+        // the bootstrap is complete, the next run starts a fresh one
+        std::fs::remove_file(MIRROR_PROGRESS)?;
+    }
+    Ok(summary)
+}
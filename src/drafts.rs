@@ -0,0 +1,113 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use modio::filter::Filter;
+use modio::Modio;
+use sqlx::AnyPool;
+use tracing::{info, warn};
+
+use crate::{api, download, virus, SyncSummary};
+
+/// Index unreleased modfiles of the mods the authenticated user is a team member of.
+///
+/// Any file uploaded after a mod's live modfile is treated as a draft: it is downloaded, stored
+/// with `draft = 1` and analyzed, but never becomes the mod's current modfile. Once mod.io makes
+/// it live, the regular sync picks it up and clears the flag. Drafts of mods that are not in the
+/// index yet are skipped.
+pub async fn index_drafts(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    modio: &Modio,
+    summary: &mut SyncSummary,
+) -> Result<()> {
+    if api::access_token()?.is_none() {
+        warn!("Skipping drafts, they can only be listed with an access token");
+        return Ok(());
+    }
+    info!("Grabbing team mods...");
+    let team_mods = modio
+        .user()
+        .mods(Filter::default())
+        .collect()
+        .await?
+        .into_iter()
+        .filter(|m| m.game_id == api::DRG)
+        .collect::<Vec<_>>();
+    info!("Team mods obtained: {} mods", team_mods.len());
+
+    let mut drafts = vec![];
+    for m in team_mods {
+        let indexed: Option<i64> = sqlx::query_scalar("SELECT id_mod FROM mod WHERE id_mod = $1")
+            .bind(i64::from(m.id))
+            .fetch_optional(pool)
+            .await?;
+        if indexed.is_none() {
+            warn!(id_mod = m.id, "Skipping drafts of mod that is not indexed");
+            continue;
+        }
+
+        let live = m.modfile.as_ref().map(|f| (f.id, f.date_added));
+        let files = modio
+            .game(api::DRG)
+            .mod_(m.id)
+            .files()
+            .search(Filter::default())
+            .collect()
+            .await?;
+        for file in files {
+            if live.is_some_and(|(id, date_added)| file.id == id || file.date_added <= date_added) {
+                continue;
+            }
+            let id_modfile = i64::from(file.id);
+            let known: Option<i64> =
+                sqlx::query_scalar("SELECT id_modfile FROM modfile WHERE id_modfile = $1")
+                    .bind(id_modfile)
+                    .fetch_optional(pool)
+                    .await?;
+            if known.is_some() {
+                continue;
+            }
+
+            if virus::refused(&file) {
+                warn!(
+                    id_mod = m.id,
+                    id_modfile, "Skipping draft flagged by the virus scan"
+                );
+                continue;
+            }
+            info!(id_mod = m.id, id_modfile, "Indexing draft");
+            match download::download_modfile(multi_bar, pool, modio, &file).await? {
+                download::Downloaded::Downloaded => summary.downloaded += 1,
+                download::Downloaded::Stored => {}
+                // indexed by the worker downloading it
+                download::Downloaded::ClaimedElsewhere => continue,
+            }
+            sqlx::query(
+                "INSERT INTO modfile(id_modfile, id_mod, date_added, hash_md5, filename, version, changelog, draft,
+                                     virus_status, virus_positive)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, 1, $8, $9)
+                 ON CONFLICT(id_modfile) DO NOTHING",
+            )
+            .bind(id_modfile)
+            .bind(i64::from(m.id))
+            .bind(api::timestamp(file.date_added)?)
+            .bind(&file.filehash.md5)
+            .bind(&file.filename)
+            .bind(&file.version)
+            .bind(&file.changelog)
+            .bind(i64::from(file.virus_status))
+            .bind(i64::from(file.virus_positive))
+            .execute(pool)
+            .await?;
+            summary.drafts += 1;
+            drafts.push((id_modfile, file.filehash.md5));
+        }
+    }
+
+    if !drafts.is_empty() {
+        let bar = multi_bar.add(ProgressBar::new(drafts.len().try_into().unwrap()));
+        crate::analyze_modfiles(multi_bar, pool, &bar, drafts, summary).await?;
+        bar.finish();
+    }
+
+    Ok(())
+}
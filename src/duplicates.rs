@@ -0,0 +1,139 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateKind {
+    /// The modfiles are the same archive
+    Archive,
+    /// The archives differ but their entries have identical contents
+    Content,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateMod {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub name: String,
+    pub id_modfile: i64,
+    pub date_added: String,
+}
+
+/// Mods whose current modfiles are duplicates of each other, oldest upload first.
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub kind: DuplicateKind,
+    pub mods: Vec<DuplicateMod>,
+}
+
+pub fn print_duplicates(groups: &[DuplicateGroup]) {
+    for group in groups {
+        let kind = match group.kind {
+            DuplicateKind::Archive => "identical archive",
+            DuplicateKind::Content => "identical content",
+        };
+        println!("{kind}:");
+        for m in &group.mods {
+            println!(
+                "  {} {} {} (modfile {} added {})",
+                m.id_mod, m.name_id, m.name, m.id_modfile, m.date_added
+            );
+        }
+    }
+}
+
+/// What two modfiles must share to be duplicates: the set of entry hashes if every entry was
+/// hashed, otherwise the archive hash.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Fingerprint {
+    Content(Vec<String>),
+    Archive(String),
+}
+
+type CurrentModfileRow = (i64, String, String, i64, String, String);
+
+/// Find mods whose current modfiles are the same archive or contain entries with identical
+/// hashes, ignoring their paths. Catches re-uploads of a mod under a new page as well as repacks
+/// that only changed the archive. Modfiles analyzed before entry hashes were recorded only match
+/// by archive until UpdateModFilesLocal fills them in.
+pub async fn duplicates(pool: &AnyPool) -> Result<Vec<DuplicateGroup>> {
+    let modfiles: Vec<CurrentModfileRow> = sqlx::query_as(
+        "SELECT mod.id_mod, name_id, mod.name, modfile.id_modfile, hash_md5, modfile.date_added
+         FROM mod JOIN modfile ON modfile.id_modfile = mod.id_modfile",
+    )
+    .fetch_all(pool)
+    .await?;
+    let entries: Vec<(i64, Option<String>)> = sqlx::query_as(
+        "SELECT pack_file.id_modfile, hash
+         FROM mod JOIN pack_file ON pack_file.id_modfile = mod.id_modfile",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut hashes = HashMap::<i64, Option<Vec<String>>>::new();
+    for (id_modfile, hash) in entries {
+        let modfile_hashes = hashes.entry(id_modfile).or_insert_with(|| Some(vec![]));
+        match (modfile_hashes.as_mut(), hash) {
+            (Some(modfile_hashes), Some(hash)) => modfile_hashes.push(hash),
+            _ => *modfile_hashes = None,
+        }
+    }
+
+    // the same archive always has the same content, so an archive's entry hashes are known if
+    // any of its modfiles was hashed
+    let mut archives = BTreeMap::<String, (Option<Vec<String>>, Vec<DuplicateMod>)>::new();
+    for (id_mod, name_id, name, id_modfile, hash_md5, date_added) in modfiles {
+        let (content, mods) = archives.entry(hash_md5).or_default();
+        if let Some(hashes) = hashes.remove(&id_modfile).flatten() {
+            content.get_or_insert(hashes);
+        }
+        mods.push(DuplicateMod {
+            id_mod,
+            name_id,
+            name,
+            id_modfile,
+            date_added,
+        });
+    }
+
+    let mut groups = BTreeMap::<Fingerprint, Vec<(String, DuplicateMod)>>::new();
+    for (hash_md5, (content, mods)) in archives {
+        let fingerprint = match content {
+            Some(mut content) => {
+                content.sort();
+                Fingerprint::Content(content)
+            }
+            None => Fingerprint::Archive(hash_md5.clone()),
+        };
+        groups
+            .entry(fingerprint)
+            .or_default()
+            .extend(mods.into_iter().map(|m| (hash_md5.clone(), m)));
+    }
+
+    let mut duplicates = groups
+        .into_values()
+        .filter(|mods| mods.len() > 1)
+        .map(|mut mods| {
+            mods.sort_by(|(_, a), (_, b)| {
+                (&a.date_added, a.id_mod).cmp(&(&b.date_added, b.id_mod))
+            });
+            let kind = if mods.iter().all(|(md5, _)| *md5 == mods[0].0) {
+                DuplicateKind::Archive
+            } else {
+                DuplicateKind::Content
+            };
+            DuplicateGroup {
+                kind,
+                mods: mods.into_iter().map(|(_, m)| m).collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+    duplicates.sort_by(|a, b| {
+        (&a.mods[0].date_added, a.mods[0].id_mod).cmp(&(&b.mods[0].date_added, b.mods[0].id_mod))
+    });
+    Ok(duplicates)
+}
@@ -0,0 +1,113 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+/// Bytes downloaded between two `download_progress` events.
+pub const PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// Where progress events go, from `--events`: `stderr` or `unix:<path>` for a listening unix
+/// socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sink {
+    Stderr,
+    Socket(PathBuf),
+}
+
+impl std::str::FromStr for Sink {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.split_once(':') {
+            None if s == "stderr" => Sink::Stderr,
+            Some(("unix", path)) if !path.is_empty() => Sink::Socket(path.into()),
+            _ => bail!("unknown event sink {s:?}, expected stderr or unix:<path>"),
+        })
+    }
+}
+
+/// A progress event, written as one JSON object per line with its kind in `event`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    SyncStarted {
+        mods: usize,
+    },
+    SyncFinished {
+        mods: u64,
+        downloaded: u64,
+        analyzed: u64,
+        analysis_errors: usize,
+    },
+    ModStarted {
+        id_mod: u32,
+        name_id: &'a str,
+    },
+    ModFinished {
+        id_mod: u32,
+    },
+    DownloadStarted {
+        id_modfile: u32,
+        size: u64,
+    },
+    DownloadProgress {
+        id_modfile: u32,
+        downloaded: u64,
+        size: u64,
+    },
+    DownloadFinished {
+        id_modfile: u32,
+        size: u64,
+    },
+    AnalysisFinished {
+        id_modfile: i64,
+        files: usize,
+    },
+    Error {
+        id_mod: Option<u32>,
+        id_modfile: Option<i64>,
+        message: &'a str,
+    },
+}
+
+static SINK: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Start writing events to `sink`. Without it, [`emit`] does nothing.
+pub fn init(sink: &Sink) -> Result<()> {
+    let writer: Box<dyn Write + Send> = match sink {
+        Sink::Stderr => Box::new(std::io::stderr()),
+        #[cfg(unix)]
+        Sink::Socket(path) => Box::new(
+            std::os::unix::net::UnixStream::connect(path)
+                .with_context(|| format!("failed to connect to {}", path.display()))?,
+        ),
+        #[cfg(not(unix))]
+        Sink::Socket(_) => bail!("unix sockets are not supported on this platform"),
+    };
+    SINK.set(Mutex::new(writer))
+        .map_err(|_| anyhow::anyhow!("event sink already set"))
+}
+
+/// Write `event` to the sink, with the time it happened. A sink that went away, e.g. a dashboard
+/// that closed its socket, is not an error for the command emitting the event.
+pub fn emit(event: Event) {
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+    #[derive(Serialize)]
+    struct Line<'a> {
+        time: String,
+        #[serde(flatten)]
+        event: Event<'a>,
+    }
+    let line = Line {
+        time: chrono::Utc::now().to_rfc3339(),
+        event,
+    };
+    if let Ok(json) = serde_json::to_string(&line) {
+        let mut sink = sink.lock().unwrap();
+        writeln!(sink, "{json}").and_then(|_| sink.flush()).ok();
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use tracing::warn;
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use crate::flatten::csv_field;
+use crate::{locres, uasset};
+
+#[derive(Debug, Serialize)]
+pub struct ExtractReport {
+    pub output: PathBuf,
+    pub files: u64,
+    pub converted: u64,
+    pub conversion_errors: Vec<String>,
+}
+
+impl std::fmt::Display for ExtractReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for e in &self.conversion_errors {
+            writeln!(f, "{e}")?;
+        }
+        write!(
+            f,
+            "extracted {} files to {}, {} converted, {} conversion errors",
+            self.files,
+            self.output.display(),
+            self.converted,
+            self.conversion_errors.len()
+        )
+    }
+}
+
+/// Extract every file of the pak in `archive` below `output`, keeping game paths. With `convert`,
+/// packages also get a `.json` summary of their imports, exports and DataTable rows, and locres
+/// files a `.csv` of their strings.
+pub fn extract(archive: &Path, output: &Path, convert: bool) -> Result<ExtractReport> {
+    let mut pak = crate::open_zip_pak(archive)?;
+    let mount_point = pak.pak.mount_point().to_string();
+    let records = pak.pak.files().collect::<Vec<_>>();
+    let record_set = records.iter().cloned().collect::<HashSet<_>>();
+
+    let mut report = ExtractReport {
+        output: output.to_path_buf(),
+        files: 0,
+        converted: 0,
+        conversion_errors: vec![],
+    };
+    for record in &records {
+        let path = crate::asset_path(&mount_point, record)?;
+        if !Path::new(&path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            bail!("refusing to extract {path:?} outside of the output directory");
+        }
+        let destination = output.join(&path);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let data = pak.get(record)?;
+        std::fs::write(&destination, &data)
+            .with_context(|| format!("failed to write {}", destination.display()))?;
+        report.files += 1;
+
+        if !convert {
+            continue;
+        }
+        let converted = match Path::new(record).extension().and_then(|e| e.to_str()) {
+            Some("uasset") | Some("umap") => {
+                let uexp = Path::new(record)
+                    .with_extension("uexp")
+                    .to_str()
+                    .map(str::to_string)
+                    .filter(|uexp| record_set.contains(uexp))
+                    .map(|uexp| pak.get(&uexp))
+                    .transpose()?;
+                Some(
+                    uasset::Package::parse(&data, uexp.as_deref())
+                        .map(|package| uasset::summarize(&package))
+                        .and_then(|summary| Ok(serde_json::to_string_pretty(&summary)?))
+                        .map(|json| (destination.with_extension("json"), json)),
+                )
+            }
+            Some("locres") => Some(locres::parse(&data).map(|entries| {
+                let mut csv = String::from("namespace,key,text\n");
+                for entry in entries {
+                    csv.push_str(&format!(
+                        "{},{},{}\n",
+                        csv_field(&entry.namespace),
+                        csv_field(&entry.key),
+                        csv_field(&entry.text)
+                    ));
+                }
+                (destination.with_extension("csv"), csv)
+            })),
+            _ => None,
+        };
+        match converted {
+            Some(Ok((path, contents))) => {
+                std::fs::write(&path, contents)
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+                report.converted += 1;
+            }
+            Some(Err(e)) => {
+                warn!(path, "Failed to convert: {e:#}");
+                report
+                    .conversion_errors
+                    .push(format!("failed to convert {path}: {e:#}"));
+            }
+            None => {}
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use sqlx::AnyPool;
+
+use std::fmt::Write;
+
+use crate::api;
+
+const FEED_ID: &str = "urn:drg-modio-index:feed";
+
+/// Escape `text` for XML and HTML.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // control characters other than whitespace are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+type FeedRow = (
+    i64,
+    String,
+    Option<String>,
+    Option<String>,
+    String,
+    String,
+    String,
+);
+
+/// Render an Atom feed of the `limit` most recently added modfiles, newest first. Drafts are left
+/// out since they are not public.
+pub async fn atom_feed(pool: &AnyPool, limit: i64) -> Result<String> {
+    let rows: Vec<FeedRow> = sqlx::query_as(
+        "SELECT modfile.id_modfile, date_added, version, changelog, name, name_id, summary
+         FROM modfile JOIN mod ON mod.id_mod = modfile.id_mod
+         WHERE draft = 0
+         ORDER BY date_added DESC, modfile.id_modfile DESC LIMIT $1",
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    let updated = rows
+        .first()
+        .map(|(_, date, ..)| date.clone())
+        .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+
+    let mut feed = String::new();
+    writeln!(feed, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    writeln!(feed, r#"<feed xmlns="http://www.w3.org/2005/Atom">"#)?;
+    writeln!(feed, "  <title>Deep Rock Galactic mod updates</title>")?;
+    writeln!(feed, "  <id>{FEED_ID}</id>")?;
+    writeln!(feed, "  <updated>{}</updated>", escape(&updated))?;
+    writeln!(feed, r#"  <link href="https://mod.io/g/drg"/>"#)?;
+    // authors aren't indexed, and Atom requires an author for every entry
+    writeln!(feed, "  <author><name>mod.io</name></author>")?;
+    for (id_modfile, date_added, version, changelog, name, name_id, summary) in rows {
+        let title = match version {
+            Some(version) => format!("{name} {version}"),
+            None => name,
+        };
+        let content = changelog.filter(|c| !c.is_empty()).unwrap_or(summary);
+        writeln!(feed, "  <entry>")?;
+        writeln!(feed, "    <title>{}</title>", escape(&title))?;
+        writeln!(
+            feed,
+            "    <id>urn:drg-modio-index:modfile:{id_modfile}</id>"
+        )?;
+        writeln!(feed, "    <updated>{}</updated>", escape(&date_added))?;
+        writeln!(
+            feed,
+            r#"    <link href="{}"/>"#,
+            escape(&api::mod_url(&name_id))
+        )?;
+        writeln!(
+            feed,
+            "    <author><name>{}</name></author>",
+            escape(&name_id)
+        )?;
+        writeln!(
+            feed,
+            r#"    <content type="text">{}</content>"#,
+            escape(&content)
+        )?;
+        writeln!(feed, "  </entry>")?;
+    }
+    writeln!(feed, "</feed>")?;
+
+    Ok(feed)
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use sqlx::AnyPool;
+
+use std::io::Write;
+use std::path::Path;
+
+/// Columns of `mod_flat` in table order.
+const COLUMNS: &[&str] = &[
+    "id_mod",
+    "name",
+    "name_id",
+    "summary",
+    "id_modfile",
+    "version",
+    "filename",
+    "date_added",
+    "hash_md5",
+    "pack_files",
+    "assets",
+    "date_flattened",
+    "ratings_positive",
+    "ratings_negative",
+    "ratings_display",
+    "maturity_option",
+    "metadata_blob",
+    "metadata_kvp",
+];
+
+/// Rebuild `mod_flat` from the normalized tables. Returns the number of rows written.
+pub async fn refresh(pool: &AnyPool) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM mod_flat")
+        .execute(&mut *tx)
+        .await?;
+    let rows = sqlx::query(
+        "INSERT INTO mod_flat(id_mod, name, name_id, summary, id_modfile, version, filename,
+                              date_added, hash_md5, pack_files, assets, date_flattened,
+                              ratings_positive, ratings_negative, ratings_display,
+                              maturity_option, metadata_blob, metadata_kvp)
+         SELECT mod.id_mod, name, name_id, summary, mod.id_modfile, version, filename,
+                date_added, hash_md5,
+                (SELECT COUNT(*) FROM pack_file WHERE pack_file.id_modfile = mod.id_modfile),
+                (SELECT COUNT(*) FROM pack_file
+                 WHERE pack_file.id_modfile = mod.id_modfile AND extension = 'uasset'),
+                $1, ratings_positive, ratings_negative, ratings_display, maturity_option,
+                metadata_blob, metadata_kvp
+         FROM mod LEFT JOIN modfile ON modfile.id_modfile = mod.id_modfile
+         LEFT JOIN mod_metadata ON mod_metadata.id_mod = mod.id_mod",
+    )
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    tx.commit().await?;
+    Ok(rows)
+}
+
+/// Quote a CSV field if it needs it.
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+type FlatRow = (
+    i64,
+    String,
+    String,
+    String,
+    Option<i64>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    i64,
+    i64,
+    String,
+    Option<i64>,
+    Option<i64>,
+    Option<String>,
+    Option<i64>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Write `mod_flat` to a CSV file with a header row.
+pub async fn write_csv(pool: &AnyPool, path: &Path) -> Result<()> {
+    let rows: Vec<FlatRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM mod_flat ORDER BY id_mod",
+        COLUMNS.join(", ")
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(out, "{}", COLUMNS.join(","))?;
+    for row in rows {
+        let fields = [
+            row.0.to_string(),
+            row.1,
+            row.2,
+            row.3,
+            row.4.map(|id| id.to_string()).unwrap_or_default(),
+            row.5.unwrap_or_default(),
+            row.6.unwrap_or_default(),
+            row.7.unwrap_or_default(),
+            row.8.unwrap_or_default(),
+            row.9.to_string(),
+            row.10.to_string(),
+            row.11,
+            row.12.map(|n| n.to_string()).unwrap_or_default(),
+            row.13.map(|n| n.to_string()).unwrap_or_default(),
+            row.14.unwrap_or_default(),
+            row.15.map(|n| n.to_string()).unwrap_or_default(),
+            row.16.unwrap_or_default(),
+            row.17.unwrap_or_default(),
+        ];
+        let line = fields
+            .iter()
+            .map(|f| csv_field(f))
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(out, "{line}")?;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,679 @@
+use anyhow::{bail, Context, Result};
+use indicatif::ProgressBar;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use sqlx::AnyPool;
+use tracing::info;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::wwise::{self, AudioObject};
+
+/// Result of indexing the base game's paks.
+#[derive(Debug, Serialize)]
+pub struct GameIndex {
+    pub version: String,
+    pub paks: Vec<PathBuf>,
+    pub files: u64,
+    /// Mods likely broken by the update from the version indexed before this one
+    pub update: Option<UpdateReport>,
+}
+
+impl std::fmt::Display for GameIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Indexed {} files of game version {} from {} paks",
+            self.files,
+            self.version,
+            self.paks.len()
+        )?;
+        if let Some(update) = &self.update {
+            write!(f, "\n{update}")?;
+        }
+        Ok(())
+    }
+}
+
+/// `path` itself if it is a pak, otherwise the paks directly in it, e.g. `FSD/Content/Paks`.
+/// Sorted so patch paks such as `FSD-WindowsNoEditor_P.pak` come after the paks they patch.
+fn find_paks(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        if !path.exists() {
+            bail!("{} does not exist", path.display());
+        }
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut paks = vec![];
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("pak"))
+        {
+            paks.push(path);
+        }
+    }
+    if paks.is_empty() {
+        bail!("no paks in {}", path.display());
+    }
+    paks.sort();
+    Ok(paks)
+}
+
+/// Contents of the base game's paks: files by game path and the objects of its soundbanks.
+type GameFiles = (BTreeMap<String, String>, Vec<(String, AudioObject)>);
+
+/// SHA-1 of every file in the paks by game path, hashed the same way as mod pack files so the
+/// two can be compared, and the objects of every soundbank. The game paks are several gigabytes
+/// so entries are read one at a time rather than loading the pak into memory.
+fn hash_paks(paks: &[PathBuf], bar: &ProgressBar) -> Result<GameFiles> {
+    let mut files = BTreeMap::new();
+    let mut audio = vec![];
+    for path in paks {
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        let pak = repak::PakReader::new_any(&mut reader, None)
+            .map_err(|e| crate::PakError::ErrorReadingPak { e })
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mount_point = pak.mount_point().to_string();
+        let records = pak.files();
+        bar.inc_length(records.len() as u64);
+        for record in records {
+            let data = pak
+                .get(&record, &mut reader)
+                .map_err(|e| crate::PakError::ErrorReadingPak { e })?;
+            let path = crate::asset_path(&mount_point, &record)?;
+            if path.ends_with(".bnk") {
+                match wwise::parse_bank(&data) {
+                    Ok(objects) => audio.extend(objects.into_iter().map(|o| (path.clone(), o))),
+                    Err(e) => tracing::debug!(path, "Failed to read soundbank: {e:#}"),
+                }
+            }
+            files.insert(path, format!("{:x}", Sha1::digest(&data)));
+            bar.inc(1);
+        }
+    }
+    Ok((files, audio))
+}
+
+/// Version name for paks indexed without one: the start of the SHA-1 of every path and hash, so
+/// the same paks always get the same name.
+fn fingerprint(files: &BTreeMap<String, String>) -> String {
+    let mut hasher = Sha1::new();
+    for (path, hash) in files {
+        hasher.update(path.as_bytes());
+        hasher.update([0]);
+        hasher.update(hash.as_bytes());
+        hasher.update([0]);
+    }
+    format!("sha1-{:.12x}", hasher.finalize())
+}
+
+/// Index the base game's paks at `path`, a pak or the directory holding them, as game version
+/// `version`, replacing an earlier index of the same version, and report the mods the update from
+/// the previously indexed version likely broke. Without `version` the paks are named after their
+/// [`fingerprint`].
+pub async fn index(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    path: &Path,
+    version: Option<&str>,
+) -> Result<GameIndex> {
+    let mut paks = find_paks(path)?;
+    // mods deployed with install live next to the game's own paks
+    let installed: Vec<String> = sqlx::query_scalar("SELECT path FROM installed_pak")
+        .fetch_all(pool)
+        .await?;
+    let installed = installed
+        .iter()
+        .filter_map(|p| Path::new(p).canonicalize().ok())
+        .collect::<Vec<_>>();
+    paks.retain(|pak| {
+        pak.canonicalize()
+            .map_or(true, |pak| !installed.contains(&pak))
+    });
+    if paks.is_empty() {
+        bail!("no game paks in {}, only installed mods", path.display());
+    }
+    let bar = multi_bar.add(ProgressBar::new(0));
+    let (files, audio) = {
+        let paks = paks.clone();
+        let bar = bar.clone();
+        tokio::task::spawn_blocking(move || hash_paks(&paks, &bar)).await??
+    };
+    bar.finish_and_clear();
+    let version = match version {
+        Some(version) => version.to_string(),
+        None => fingerprint(&files),
+    };
+    let version = version.as_str();
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO game_version(version, source, date_imported) VALUES ($1, $2, $3)
+         ON CONFLICT(version) DO UPDATE SET source = excluded.source, date_imported = excluded.date_imported",
+    )
+    .bind(version)
+    .bind(path.display().to_string())
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&mut *tx)
+    .await?;
+    let id_version: i64 =
+        sqlx::query_scalar("SELECT id_game_version FROM game_version WHERE version = $1")
+            .bind(version)
+            .fetch_one(&mut *tx)
+            .await?;
+    sqlx::query("DELETE FROM game_file WHERE id_game_version = $1")
+        .bind(id_version)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM game_audio_object WHERE id_game_version = $1")
+        .bind(id_version)
+        .execute(&mut *tx)
+        .await?;
+    use sqlx::{Executor, Statement};
+    let insert = (&mut *tx)
+        .prepare("INSERT INTO game_file(id_game_version, path, hash) VALUES ($1, $2, $3)")
+        .await?;
+    for (path, hash) in &files {
+        insert
+            .query()
+            .bind(id_version)
+            .bind(path)
+            .bind(hash)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let insert_audio = (&mut *tx)
+        .prepare(
+            "INSERT INTO game_audio_object(id_game_version, path, kind, id_object, id_source, localized)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT DO NOTHING",
+        )
+        .await?;
+    for (path, object) in &audio {
+        insert_audio
+            .query()
+            .bind(id_version)
+            .bind(path)
+            .bind(object.kind)
+            .bind(i64::from(object.id))
+            .bind(object.source.map(i64::from))
+            .bind(i64::from(object.localized))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    info!(version, files = files.len(), "Indexed game paks");
+    flag_overrides(pool, true).await?;
+
+    Ok(GameIndex {
+        version: version.to_string(),
+        paks,
+        files: files.len() as u64,
+        update: update_report(pool, None, Some(version)).await?,
+    })
+}
+
+/// Id of the latest indexed game version.
+pub async fn latest_version(pool: &AnyPool) -> Result<Option<i64>> {
+    Ok(resolve(pool, None, None).await?.map(|(id, _, _)| id))
+}
+
+/// Set `pack_file.vanilla_override` from the game files of the latest indexed game version, for
+/// every pack file with `all` or else only for those not flagged yet, e.g. after an analysis.
+/// Returns the number of pack files flagged, none if no game version is indexed.
+pub async fn flag_overrides(pool: &AnyPool, all: bool) -> Result<u64> {
+    let Some((id_version, _, _)) = resolve(pool, None, None).await? else {
+        return Ok(0);
+    };
+    let flagged = sqlx::query(
+        "UPDATE pack_file SET vanilla_override = CASE WHEN EXISTS (
+             SELECT 1 FROM game_file
+             WHERE game_file.id_game_version = $1 AND game_file.path = pack_file.path
+         ) THEN 1 ELSE 0 END
+         WHERE $2 = 1 OR vanilla_override IS NULL",
+    )
+    .bind(id_version)
+    .bind(i64::from(all))
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(flagged)
+}
+
+/// A mod overriding base game assets that changed between two game versions.
+#[derive(Debug, Serialize)]
+pub struct BrokenMod {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub name: String,
+    pub id_modfile: i64,
+    /// Whether the current modfile was uploaded after the newer version was released, in which
+    /// case the author has likely already updated it
+    pub updated_since: bool,
+    /// Overridden assets whose contents changed in the update
+    pub changed: Vec<String>,
+    /// Overridden assets the update removed
+    pub removed: Vec<String>,
+}
+
+/// Mods likely broken by the update from one game version to another.
+#[derive(Debug, Serialize)]
+pub struct UpdateReport {
+    pub from: String,
+    pub to: String,
+    /// Base game assets whose contents changed
+    pub changed: u64,
+    pub added: u64,
+    pub removed: u64,
+    /// Mods not updated since first, then by name_id
+    pub mods: Vec<BrokenMod>,
+}
+
+impl std::fmt::Display for UpdateReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Game update {} -> {}: {} assets changed, {} added, {} removed",
+            self.from, self.to, self.changed, self.added, self.removed
+        )?;
+        for m in &self.mods {
+            write!(f, "{} {} ({})", m.id_mod, m.name_id, m.name)?;
+            if m.updated_since {
+                write!(f, " [updated since]")?;
+            }
+            writeln!(f)?;
+            for path in &m.changed {
+                writeln!(f, "  ~ {path}")?;
+            }
+            for path in &m.removed {
+                writeln!(f, "  - {path}")?;
+            }
+        }
+        write!(f, "{} mods likely broken by the update", self.mods.len())
+    }
+}
+
+/// Id and release date of the indexed game version `version`, or of the latest indexed version
+/// released before `before` when `version` is not given. Versions recorded without paks have
+/// nothing to compare and are skipped.
+async fn resolve(
+    pool: &AnyPool,
+    version: Option<&str>,
+    before: Option<&str>,
+) -> Result<Option<(i64, String, String)>> {
+    let query = match (version, before) {
+        (Some(version), _) => {
+            let found: Option<(i64, String, String, i64)> = sqlx::query_as(
+                "SELECT id_game_version, version, COALESCE(date_released, date_imported),
+                        CASE WHEN EXISTS (
+                            SELECT 1 FROM game_file
+                            WHERE game_file.id_game_version = game_version.id_game_version
+                        ) THEN 1 ELSE 0 END
+                 FROM game_version WHERE version = $1",
+            )
+            .bind(version)
+            .fetch_optional(pool)
+            .await?;
+            let (id, version, date, indexed) =
+                found.with_context(|| format!("game version {version:?} is not indexed"))?;
+            if indexed == 0 {
+                bail!("game version {version:?} was recorded without paks, index them with index-game to compare it");
+            }
+            return Ok(Some((id, version, date)));
+        }
+        (None, Some(before)) => sqlx::query_as(
+            "SELECT id_game_version, version, COALESCE(date_released, date_imported) FROM game_version
+             WHERE COALESCE(date_released, date_imported) < $1 AND EXISTS (
+                 SELECT 1 FROM game_file WHERE game_file.id_game_version = game_version.id_game_version
+             )
+             ORDER BY COALESCE(date_released, date_imported) DESC LIMIT 1",
+        )
+        .bind(before),
+        (None, None) => sqlx::query_as(
+            "SELECT id_game_version, version, COALESCE(date_released, date_imported) FROM game_version
+             WHERE EXISTS (
+                 SELECT 1 FROM game_file WHERE game_file.id_game_version = game_version.id_game_version
+             )
+             ORDER BY COALESCE(date_released, date_imported) DESC LIMIT 1",
+        ),
+    };
+    Ok(query.fetch_optional(pool).await?)
+}
+
+type BrokenRow = (i64, String, String, i64, String, String, i64);
+
+/// Compare the game files of `from` and `to` and find the current modfiles overriding assets that
+/// changed or were removed. `to` defaults to the latest indexed version and `from` to the one
+/// indexed before it. Returns `None` if fewer than two versions are indexed.
+pub async fn update_report(
+    pool: &AnyPool,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<Option<UpdateReport>> {
+    let Some((id_to, to, date_to)) = resolve(pool, to, None).await? else {
+        return Ok(None);
+    };
+    let Some((id_from, from, _)) = resolve(pool, from, Some(&date_to)).await? else {
+        return Ok(None);
+    };
+    if id_from == id_to {
+        bail!("cannot compare game version {to} with itself");
+    }
+
+    let (changed, removed): (i64, i64) = sqlx::query_as(
+        "SELECT CAST(COALESCE(SUM(CASE WHEN new.path IS NOT NULL THEN 1 ELSE 0 END), 0) AS BIGINT),
+                CAST(COALESCE(SUM(CASE WHEN new.path IS NULL THEN 1 ELSE 0 END), 0) AS BIGINT)
+         FROM game_file AS old
+         LEFT JOIN game_file AS new ON new.id_game_version = $2 AND new.path = old.path
+         WHERE old.id_game_version = $1 AND (new.path IS NULL OR new.hash <> old.hash)",
+    )
+    .bind(id_from)
+    .bind(id_to)
+    .fetch_one(pool)
+    .await?;
+    let added: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM game_file AS new
+         LEFT JOIN game_file AS old ON old.id_game_version = $1 AND old.path = new.path
+         WHERE new.id_game_version = $2 AND old.path IS NULL",
+    )
+    .bind(id_from)
+    .bind(id_to)
+    .fetch_one(pool)
+    .await?;
+
+    let rows: Vec<BrokenRow> = sqlx::query_as(
+        "SELECT mod.id_mod, mod.name_id, mod.name, mod.id_modfile, modfile.date_added, pack_file.path,
+                CASE WHEN new.path IS NULL THEN 1 ELSE 0 END
+         FROM mod
+         JOIN modfile ON modfile.id_modfile = mod.id_modfile
+         JOIN pack_file ON pack_file.id_modfile = mod.id_modfile
+         JOIN game_file AS old ON old.id_game_version = $1 AND old.path = pack_file.path
+         LEFT JOIN game_file AS new ON new.id_game_version = $2 AND new.path = pack_file.path
+         WHERE new.path IS NULL OR new.hash <> old.hash
+         ORDER BY mod.name_id, pack_file.path",
+    )
+    .bind(id_from)
+    .bind(id_to)
+    .fetch_all(pool)
+    .await?;
+
+    let mut mods = BTreeMap::<i64, BrokenMod>::new();
+    for (id_mod, name_id, name, id_modfile, date_added, path, is_removed) in rows {
+        let m = mods.entry(id_mod).or_insert_with(|| BrokenMod {
+            id_mod,
+            name_id,
+            name,
+            id_modfile,
+            updated_since: date_added > date_to,
+            changed: vec![],
+            removed: vec![],
+        });
+        if is_removed != 0 {
+            m.removed.push(path);
+        } else {
+            m.changed.push(path);
+        }
+    }
+    let mut mods = mods.into_values().collect::<Vec<_>>();
+    mods.sort_by(|a, b| (a.updated_since, &a.name_id).cmp(&(b.updated_since, &b.name_id)));
+
+    Ok(Some(UpdateReport {
+        from,
+        to,
+        changed: changed as u64,
+        added: added as u64,
+        removed: removed as u64,
+        mods,
+    }))
+}
+
+/// A game version as recorded in the index.
+#[derive(Debug, Serialize)]
+pub struct GameVersion {
+    pub version: String,
+    /// Release date, or the date the paks were indexed if it was not recorded
+    pub date: String,
+    /// Whether the version's paks are indexed, so the assets its update touched are known
+    pub indexed: bool,
+}
+
+impl std::fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.version, self.date)?;
+        if !self.indexed {
+            write!(f, " (recorded without paks)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every game version, oldest first.
+async fn versions(pool: &AnyPool) -> Result<Vec<GameVersion>> {
+    let rows: Vec<(String, String, i64)> = sqlx::query_as(
+        "SELECT version, COALESCE(date_released, date_imported),
+                CASE WHEN EXISTS (
+                    SELECT 1 FROM game_file
+                    WHERE game_file.id_game_version = game_version.id_game_version
+                ) THEN 1 ELSE 0 END
+         FROM game_version ORDER BY COALESCE(date_released, date_imported)",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(version, date, indexed)| GameVersion {
+            version,
+            date,
+            indexed: indexed != 0,
+        })
+        .collect())
+}
+
+/// Record that game version `version` was released at `released`, now if not given, without
+/// indexing its paks, e.g. right after a patch drops. An indexed version keeps its listing and
+/// only gets the release date.
+pub async fn record_version(
+    pool: &AnyPool,
+    version: &str,
+    released: Option<&str>,
+) -> Result<GameVersion> {
+    let now = chrono::Utc::now().to_rfc3339();
+    sqlx::query(
+        "INSERT INTO game_version(version, source, date_imported, date_released) VALUES ($1, $2, $3, $4)
+         ON CONFLICT(version) DO UPDATE SET date_released = excluded.date_released",
+    )
+    .bind(version)
+    .bind("manual")
+    .bind(&now)
+    .bind(released.unwrap_or(&now))
+    .execute(pool)
+    .await?;
+    info!(version, "Recorded game version");
+    versions(pool)
+        .await?
+        .into_iter()
+        .find(|v| v.version == version)
+        .context("recorded game version is missing")
+}
+
+/// A game update released after a mod's current modfile was uploaded.
+#[derive(Debug, Serialize)]
+pub struct StaleUpdate {
+    pub version: String,
+    /// Overridden assets whose contents changed in the update
+    pub changed: Vec<String>,
+    /// Overridden assets the update removed
+    pub removed: Vec<String>,
+    /// Base game assets the mod overrides, for an update recorded without paks whose changes are
+    /// unknown
+    pub overridden: Vec<String>,
+}
+
+/// A mod whose current modfile predates game updates that touched or may have touched its assets.
+#[derive(Debug, Serialize)]
+pub struct StaleMod {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub name: String,
+    pub id_modfile: i64,
+    pub date_added: String,
+    pub updates: Vec<StaleUpdate>,
+}
+
+/// Mods possibly left behind by game updates.
+#[derive(Debug, Serialize)]
+pub struct StaleReport {
+    /// Game versions considered, oldest first
+    pub versions: Vec<GameVersion>,
+    /// By name_id
+    pub mods: Vec<StaleMod>,
+}
+
+impl std::fmt::Display for StaleReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for v in &self.versions {
+            writeln!(f, "game version {v}")?;
+        }
+        for m in &self.mods {
+            writeln!(
+                f,
+                "{} {} ({}) modfile {} from {}",
+                m.id_mod, m.name_id, m.name, m.id_modfile, m.date_added
+            )?;
+            for u in &m.updates {
+                if u.overridden.is_empty() {
+                    writeln!(
+                        f,
+                        "  {}: {} overridden assets changed, {} removed",
+                        u.version,
+                        u.changed.len(),
+                        u.removed.len()
+                    )?;
+                } else {
+                    writeln!(
+                        f,
+                        "  {}: not indexed, overrides {} base game assets",
+                        u.version,
+                        u.overridden.len()
+                    )?;
+                }
+                for path in &u.changed {
+                    writeln!(f, "    ~ {path}")?;
+                }
+                for path in &u.removed {
+                    writeln!(f, "    - {path}")?;
+                }
+                for path in &u.overridden {
+                    writeln!(f, "    ? {path}")?;
+                }
+            }
+        }
+        write!(
+            f,
+            "{} mods predate game updates touching their assets",
+            self.mods.len()
+        )
+    }
+}
+
+/// Find the mods whose current modfile was uploaded before a game update that changed or removed
+/// assets it overrides, across every update after `since`, or every update. Updates recorded
+/// without paks flag every mod overriding base game assets, as what they touched is unknown.
+pub async fn possibly_stale(pool: &AnyPool, since: Option<&str>) -> Result<StaleReport> {
+    let mut versions = versions(pool).await?;
+    // the indexed version the first considered update is compared with
+    let mut previous = None;
+    if let Some(since) = since {
+        let position = versions
+            .iter()
+            .position(|v| v.version == since)
+            .with_context(|| format!("game version {since:?} is not recorded"))?;
+        previous = versions[..=position]
+            .iter()
+            .rev()
+            .find(|v| v.indexed)
+            .map(|v| v.version.clone());
+        versions.drain(..=position);
+    }
+
+    let mut mods = BTreeMap::<i64, StaleMod>::new();
+    for (i, version) in versions.iter().enumerate() {
+        if !version.indexed {
+            if i == 0 && since.is_none() {
+                continue;
+            }
+            let rows: Vec<(i64, String, String, i64, String, String)> = sqlx::query_as(
+                "SELECT mod.id_mod, mod.name_id, mod.name, mod.id_modfile, modfile.date_added, pack_file.path
+                 FROM mod
+                 JOIN modfile ON modfile.id_modfile = mod.id_modfile
+                 JOIN pack_file ON pack_file.id_modfile = mod.id_modfile
+                 WHERE pack_file.vanilla_override = 1 AND modfile.date_added < $1
+                 ORDER BY mod.name_id, pack_file.path",
+            )
+            .bind(&version.date)
+            .fetch_all(pool)
+            .await?;
+            for (id_mod, name_id, name, id_modfile, date_added, path) in rows {
+                let m = mods.entry(id_mod).or_insert_with(|| StaleMod {
+                    id_mod,
+                    name_id,
+                    name,
+                    id_modfile,
+                    date_added,
+                    updates: vec![],
+                });
+                if m.updates
+                    .last()
+                    .is_none_or(|u| u.version != version.version)
+                {
+                    m.updates.push(StaleUpdate {
+                        version: version.version.clone(),
+                        changed: vec![],
+                        removed: vec![],
+                        overridden: vec![],
+                    });
+                }
+                m.updates.last_mut().unwrap().overridden.push(path);
+            }
+            continue;
+        }
+
+        if let Some(from) = &previous {
+            let report = update_report(pool, Some(from), Some(&version.version))
+                .await?
+                .context("indexed game version is missing")?;
+            for broken in report.mods.into_iter().filter(|m| !m.updated_since) {
+                let date_added: String =
+                    sqlx::query_scalar("SELECT date_added FROM modfile WHERE id_modfile = $1")
+                        .bind(broken.id_modfile)
+                        .fetch_one(pool)
+                        .await?;
+                mods.entry(broken.id_mod)
+                    .or_insert_with(|| StaleMod {
+                        id_mod: broken.id_mod,
+                        name_id: broken.name_id,
+                        name: broken.name,
+                        id_modfile: broken.id_modfile,
+                        date_added,
+                        updates: vec![],
+                    })
+                    .updates
+                    .push(StaleUpdate {
+                        version: version.version.clone(),
+                        changed: broken.changed,
+                        removed: broken.removed,
+                        overridden: vec![],
+                    });
+            }
+        }
+        previous = Some(version.version.clone());
+    }
+
+    let mut mods = mods.into_values().collect::<Vec<_>>();
+    mods.sort_by(|a, b| a.name_id.cmp(&b.name_id));
+    Ok(StaleReport { versions, mods })
+}
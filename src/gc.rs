@@ -0,0 +1,226 @@
+//! Removal of rows nothing needs anymore: rows of modfiles that no longer exist, the modfiles of
+//! mods deleted from mod.io long enough ago and old API quota samples.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::time::Duration;
+
+use crate::daemon;
+
+/// Tables of per-modfile rows, children before their parents.
+const MODFILE_TABLES: &[&str] = &[
+    "pack_file_string",
+    "audio_object",
+    "asset_ref",
+    "data_table_row",
+    "pack_file",
+    "modfile_platform",
+    "download",
+    "lint_finding",
+    "lint_scan",
+];
+
+/// How long modfiles of deleted mods are kept when `DELETED_MOD_RETENTION` is not set.
+const DEFAULT_DELETED_MOD_RETENTION: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// How long API quota samples are kept when `QUOTA_HISTORY` is not set.
+const DEFAULT_QUOTA_HISTORY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Time the modfiles of a mod deleted from mod.io are kept after a sync saw it deleted, from
+/// `DELETED_MOD_RETENTION`, e.g. `180d`.
+pub fn deleted_mod_retention() -> Result<Duration> {
+    match env::var("DELETED_MOD_RETENTION") {
+        Ok(retention) => {
+            daemon::parse_duration(&retention).context("invalid DELETED_MOD_RETENTION")
+        }
+        Err(_) => Ok(DEFAULT_DELETED_MOD_RETENTION),
+    }
+}
+
+/// Time API quota samples are kept, from `QUOTA_HISTORY`, e.g. `7d`.
+pub fn quota_history() -> Result<Duration> {
+    match env::var("QUOTA_HISTORY") {
+        Ok(history) => daemon::parse_duration(&history).context("invalid QUOTA_HISTORY"),
+        Err(_) => Ok(DEFAULT_QUOTA_HISTORY),
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct GcReport {
+    /// Whether anything was removed or only counted
+    pub dry_run: bool,
+    /// Rows per table whose modfile no longer exists
+    pub dangling: BTreeMap<&'static str, u64>,
+    /// Modfiles of mods deleted from mod.io before the retention window
+    pub deleted_modfiles: Vec<i64>,
+    pub quota_samples: u64,
+}
+
+impl std::fmt::Display for GcReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let verb = if self.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        };
+        let dangling = self
+            .dangling
+            .iter()
+            .filter(|(_, rows)| **rows > 0)
+            .map(|(table, rows)| format!("{rows} {table}"))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "{verb} {} dangling rows{}, {} modfiles of deleted mods and {} API quota samples",
+            self.dangling.values().sum::<u64>(),
+            if dangling.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", dangling.join(", "))
+            },
+            self.deleted_modfiles.len(),
+            self.quota_samples
+        )
+    }
+}
+
+fn cutoff(age: Duration) -> Result<String> {
+    Ok((chrono::Utc::now() - chrono::Duration::from_std(age)?).to_rfc3339())
+}
+
+/// Find the rows to collect and remove them unless `dry_run`. Modfiles pinned in a collection are
+/// kept even if their mod was deleted.
+pub async fn gc(pool: &AnyPool, dry_run: bool) -> Result<GcReport> {
+    let mut report = GcReport {
+        dry_run,
+        ..Default::default()
+    };
+    let mut tx = pool.begin().await?;
+
+    // the latest observation of each deleted mod is the event that marked it deleted
+    report.deleted_modfiles = sqlx::query_scalar(
+        "SELECT modfile.id_modfile FROM modfile JOIN mod ON mod.id_mod = modfile.id_mod
+         WHERE mod.status = 3
+           AND (SELECT MAX(date_observed) FROM mod_event
+                WHERE mod_event.id_mod = mod.id_mod AND field = 'status') < $1
+           AND NOT EXISTS (SELECT 1 FROM collection_mod
+                           WHERE collection_mod.id_modfile = modfile.id_modfile)
+         ORDER BY modfile.id_modfile",
+    )
+    .bind(cutoff(deleted_mod_retention()?)?)
+    .fetch_all(&mut *tx)
+    .await?;
+    if !dry_run {
+        for id_modfile in &report.deleted_modfiles {
+            sqlx::query("UPDATE mod SET id_modfile = NULL WHERE id_modfile = $1")
+                .bind(id_modfile)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM modfile WHERE id_modfile = $1")
+                .bind(id_modfile)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    // after the deleted modfiles so their rows are collected as dangling ones. A dry run keeps
+    // them, their rows are counted as well
+    let kept = report
+        .deleted_modfiles
+        .iter()
+        .map(i64::to_string)
+        .collect::<Vec<_>>();
+    for table in MODFILE_TABLES {
+        let mut condition = format!(
+            "NOT EXISTS (SELECT 1 FROM modfile WHERE modfile.id_modfile = {table}.id_modfile)"
+        );
+        if dry_run && !kept.is_empty() {
+            condition += &format!(" OR {table}.id_modfile IN ({})", kept.join(", "));
+        }
+        let rows = if dry_run {
+            let rows: i64 =
+                sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table} WHERE {condition}"))
+                    .fetch_one(&mut *tx)
+                    .await?;
+            rows as u64
+        } else {
+            sqlx::query(&format!("DELETE FROM {table} WHERE {condition}"))
+                .execute(&mut *tx)
+                .await?
+                .rows_affected()
+        };
+        report.dangling.insert(table, rows);
+    }
+
+    let horizon = cutoff(quota_history()?)?;
+    report.quota_samples = if dry_run {
+        let rows: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM api_quota WHERE date_sampled < $1")
+                .bind(&horizon)
+                .fetch_one(&mut *tx)
+                .await?;
+        rows as u64
+    } else {
+        sqlx::query("DELETE FROM api_quota WHERE date_sampled < $1")
+            .bind(&horizon)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+    };
+
+    tx.commit().await?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An index with a mod deleted from mod.io a year ago, whose modfile has a pack file.
+    async fn index() -> AnyPool {
+        let pool = crate::db::connect("sqlite::memory:", true, true)
+            .await
+            .unwrap();
+        let deleted = cutoff(Duration::from_secs(365 * 24 * 60 * 60)).unwrap();
+        for query in [
+            "INSERT INTO mod(id_mod, name, name_id, summary, status) VALUES (1, 'Mod', 'mod', '', 3)",
+            "INSERT INTO modfile(id_modfile, id_mod, date_added, hash_md5, filename)
+             VALUES (10, 1, '', '', 'mod.zip')",
+            "UPDATE mod SET id_modfile = 10",
+            "INSERT INTO pack_file(id_modfile, path, path_no_extension, name)
+             VALUES (10, 'FSD/Content/A.uasset', 'FSD/Content/A.', 'A')",
+        ] {
+            sqlx::query(query).execute(&pool).await.unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO mod_event(id_mod, date_observed, field, old_value, new_value)
+             VALUES (1, $1, 'status', '1', '3')",
+        )
+        .bind(deleted)
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn dry_run_counts_rows_of_deleted_modfiles() {
+        let pool = index().await;
+        let dry_run = gc(&pool, true).await.unwrap();
+        assert_eq!(dry_run.deleted_modfiles, [10]);
+        assert_eq!(dry_run.dangling["pack_file"], 1);
+
+        let collected = gc(&pool, false).await.unwrap();
+        assert_eq!(collected.deleted_modfiles, dry_run.deleted_modfiles);
+        assert_eq!(collected.dangling, dry_run.dangling);
+        let pack_files: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pack_file")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(pack_files, 0);
+    }
+}
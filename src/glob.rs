@@ -0,0 +1,183 @@
+use anyhow::{bail, Result};
+
+use std::path::{Path, PathBuf};
+
+/// Whether `s` contains glob syntax rather than naming a single path.
+pub fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// Regex matching the same paths as the glob `pattern`: `*` and `?` within a path component,
+/// `**` across any number of components and `[...]` character classes.
+fn to_regex(pattern: &str) -> Result<regex::Regex> {
+    let mut re = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => {
+                re.push('[');
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    re.push('^');
+                }
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some('\\') => re.push_str("\\\\"),
+                        Some(c) => re.push(c),
+                        None => bail!("unterminated [ in {pattern:?}"),
+                    }
+                }
+                re.push(']');
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Ok(regex::Regex::new(&re)?)
+}
+
+/// Files matching the glob `pattern`, sorted. Only the directory below the pattern's literal
+/// prefix is searched, e.g. `archive` for `archive/**/*.zip`.
+pub fn expand(pattern: &str) -> Result<Vec<PathBuf>> {
+    let pattern = pattern.replace('\\', "/");
+    let re = to_regex(&pattern)?;
+    let literal = pattern[..pattern.find(['*', '?', '[']).unwrap_or(pattern.len())]
+        .rsplit_once('/')
+        .map(|(dir, _)| if dir.is_empty() { "/" } else { dir });
+    let root = Path::new(literal.unwrap_or("."));
+    // without ** a match is exactly as many components below the prefix as the pattern has
+    let max_depth = (!pattern.contains("**")).then(|| {
+        let prefix = literal.map_or(0, |l| l.len() + 1).min(pattern.len());
+        pattern[prefix..].matches('/').count()
+    });
+
+    let mut files = vec![];
+    let mut dirs = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                if max_depth.is_none_or(|max| depth < max) {
+                    dirs.push((path, depth + 1));
+                }
+                continue;
+            }
+            // match relative patterns against paths without the leading ./
+            let relative = match literal {
+                None => path.strip_prefix(".").unwrap_or(&path),
+                Some(_) => &path,
+            };
+            if re.is_match(&relative.to_string_lossy().replace('\\', "/")) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        to_regex(pattern).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(is_pattern("archive/*.zip"));
+        assert!(is_pattern("mod?.pak"));
+        assert!(is_pattern("[ab].pak"));
+        assert!(!is_pattern("archive/mod.zip"));
+    }
+
+    #[test]
+    fn star_stays_in_component() {
+        assert!(matches("*.zip", "mod.zip"));
+        assert!(matches("archive/*.zip", "archive/mod.zip"));
+        assert!(!matches("*.zip", "archive/mod.zip"));
+        assert!(!matches("archive/*.zip", "archive/nested/mod.zip"));
+        assert!(!matches("*.zip", "mod.zip.part"));
+    }
+
+    #[test]
+    fn double_star_crosses_components() {
+        assert!(matches("archive/**/*.zip", "archive/mod.zip"));
+        assert!(matches("archive/**/*.zip", "archive/a/mod.zip"));
+        assert!(matches("archive/**/*.zip", "archive/a/b/c/mod.zip"));
+        assert!(!matches("archive/**/*.zip", "other/a/mod.zip"));
+        assert!(!matches("archive/**/*.zip", "archive/a/mod.pak"));
+        assert!(matches("**/*.pak", "mod.pak"));
+        assert!(matches("**/*.pak", "a/b/mod.pak"));
+        assert!(matches("archive/**", "archive/a/b/mod.zip"));
+        assert!(matches("archive/a**.zip", "archive/a/b.zip"));
+    }
+
+    #[test]
+    fn question_mark_and_classes() {
+        assert!(matches("mod?.pak", "mod1.pak"));
+        assert!(!matches("mod?.pak", "mod12.pak"));
+        assert!(!matches("a?b", "a/b"));
+        assert!(matches("mod[12].pak", "mod2.pak"));
+        assert!(!matches("mod[12].pak", "mod3.pak"));
+        assert!(matches("mod[!12].pak", "mod3.pak"));
+        assert!(!matches("mod[!12].pak", "mod1.pak"));
+        assert!(matches("mod[0-9].pak", "mod7.pak"));
+    }
+
+    #[test]
+    fn literals_are_escaped() {
+        assert!(matches("mod (1).zip", "mod (1).zip"));
+        assert!(!matches("mod.zip", "modxzip"));
+        assert!(matches("a+b$.zip", "a+b$.zip"));
+    }
+
+    #[test]
+    fn unterminated_class() {
+        assert!(to_regex("mod[12.pak").is_err());
+    }
+
+    #[test]
+    fn expand_searches_below_prefix() {
+        let root =
+            std::env::temp_dir().join(format!("drg-modio-index-glob-{}", std::process::id()));
+        for file in ["a.zip", "b.pak", "x/c.zip", "x/y/d.zip"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+        let root_str = root.to_str().unwrap();
+
+        let top = expand(&format!("{root_str}/*.zip")).unwrap();
+        let all = expand(&format!("{root_str}/**/*.zip")).unwrap();
+        let nested = expand(&format!("{root_str}/*/*.zip")).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(top, vec![root.join("a.zip")]);
+        assert_eq!(
+            all,
+            vec![
+                root.join("a.zip"),
+                root.join("x/c.zip"),
+                root.join("x/y/d.zip")
+            ]
+        );
+        assert_eq!(nested, vec![root.join("x/c.zip")]);
+    }
+}
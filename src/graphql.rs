@@ -0,0 +1,503 @@
+//! GraphQL schema over the index, served by `serve` so frontends can fetch mods with their
+//! modfiles, pack files, conflicts and tags in a single request.
+
+use async_graphql::{ComplexObject, Context, EmptyMutation, EmptySubscription, Object};
+use async_graphql::{Result, Schema, SimpleObject, ID};
+use sqlx::AnyPool;
+
+use crate::{lookup, tags};
+
+/// Most items a single page returns, whatever `first` asks for.
+const MAX_PAGE: i64 = 1000;
+
+/// The page size asked for, within bounds.
+fn page_size(first: i64) -> i64 {
+    first.clamp(0, MAX_PAGE)
+}
+
+/// An id cursor as an integer, before every id if absent.
+fn id_cursor(after: Option<ID>) -> Result<i64> {
+    match after {
+        Some(after) => Ok(after.parse()?),
+        None => Ok(i64::MIN),
+    }
+}
+
+/// A [`PackFile::cursor`] as a modfile id and path, before every pack file if absent.
+fn pack_file_cursor(after: Option<ID>) -> Result<(i64, String)> {
+    match after {
+        Some(after) => {
+            let (id_modfile, path) = after
+                .split_once('/')
+                .ok_or("a pack file cursor is a modfile id and a path")?;
+            Ok((id_modfile.parse()?, path.to_string()))
+        }
+        None => Ok((i64::MIN, String::new())),
+    }
+}
+
+pub type IndexSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub fn schema(pool: AnyPool) -> IndexSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(pool)
+        .limit_depth(10)
+        .finish()
+}
+
+type ModRow = (
+    i64,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<i64>,
+    Option<i64>,
+    Option<String>,
+    Option<i64>,
+);
+
+const MOD_COLUMNS: &str = "mod.id_mod, mod.name_id, mod.name, mod.summary, mod.category,
+     mod.ratings_positive, mod.ratings_negative, mod.ratings_display, mod.id_modfile";
+
+/// Filter of `mods` on a category bound to `$1` and a tag bound to `$2`, both optional.
+const MOD_FILTER: &str = "($1 = '' OR category = $1)
+     AND ($2 = '' OR id_mod IN (SELECT id_mod FROM mod_tag WHERE tag = $2))";
+
+/// The columns of [`ModRow`] for the other mod, followed by the shared path as spelled in it and
+/// as spelled in this mod.
+type ConflictRow = (
+    i64,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<i64>,
+    Option<i64>,
+    Option<String>,
+    Option<i64>,
+    String,
+    String,
+);
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Mod {
+    pub id: i64,
+    pub name_id: String,
+    pub name: String,
+    pub summary: String,
+    /// Kind of content, see the classify module
+    pub category: Option<String>,
+    pub ratings_positive: Option<i64>,
+    pub ratings_negative: Option<i64>,
+    /// e.g. "Very Positive"
+    pub ratings_display: Option<String>,
+    #[graphql(skip)]
+    pub id_modfile: Option<i64>,
+}
+
+impl From<ModRow> for Mod {
+    fn from(
+        (
+            id,
+            name_id,
+            name,
+            summary,
+            category,
+            ratings_positive,
+            ratings_negative,
+            ratings_display,
+            id_modfile,
+        ): ModRow,
+    ) -> Self {
+        Mod {
+            id,
+            name_id,
+            name,
+            summary,
+            category,
+            ratings_positive,
+            ratings_negative,
+            ratings_display,
+            id_modfile,
+        }
+    }
+}
+
+async fn get_mod(pool: &AnyPool, id_mod: i64) -> Result<Option<Mod>> {
+    let row: Option<ModRow> =
+        sqlx::query_as(&format!("SELECT {MOD_COLUMNS} FROM mod WHERE id_mod = $1"))
+            .bind(id_mod)
+            .fetch_optional(pool)
+            .await?;
+    Ok(row.map(Mod::from))
+}
+
+#[ComplexObject]
+impl Mod {
+    async fn current_modfile(&self, ctx: &Context<'_>) -> Result<Option<Modfile>> {
+        match self.id_modfile {
+            Some(id_modfile) => get_modfile(ctx.data()?, id_modfile).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Tags of the mod on mod.io, sorted.
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<String>> {
+        Ok(tags::of_mod(ctx.data()?, self.id).await?)
+    }
+
+    /// Every indexed modfile, newest first.
+    async fn modfiles(&self, ctx: &Context<'_>) -> Result<Vec<Modfile>> {
+        let rows: Vec<ModfileRow> = sqlx::query_as(&format!(
+            "SELECT {MODFILE_COLUMNS} FROM modfile WHERE id_mod = $1
+             ORDER BY date_added DESC, id_modfile DESC"
+        ))
+        .bind(self.id)
+        .fetch_all(ctx.data::<AnyPool>()?)
+        .await?;
+        Ok(rows.into_iter().map(Modfile::from).collect())
+    }
+
+    /// Metadata the author attached to the mod, null if there is none.
+    async fn metadata(&self, ctx: &Context<'_>) -> Result<Option<Metadata>> {
+        let row: Option<(Option<String>, String)> = sqlx::query_as(
+            "SELECT metadata_blob, metadata_kvp FROM mod_metadata WHERE id_mod = $1",
+        )
+        .bind(self.id)
+        .fetch_optional(ctx.data::<AnyPool>()?)
+        .await?;
+        let Some((blob, kvp)) = row else {
+            return Ok(None);
+        };
+        let kvp: std::collections::BTreeMap<String, Vec<String>> = serde_json::from_str(&kvp)?;
+        Ok(Some(Metadata {
+            blob,
+            pairs: kvp
+                .into_iter()
+                .map(|(key, values)| MetadataPair { key, values })
+                .collect(),
+        }))
+    }
+
+    /// Other mods whose current modfile has pack files at the same paths as this one's, ignoring
+    /// case like the game does.
+    async fn conflicts(&self, ctx: &Context<'_>) -> Result<Vec<Conflict>> {
+        let shared: Vec<ConflictRow> = sqlx::query_as(&format!(
+            "SELECT {}, file_b.path, file_a.path
+             FROM mod JOIN pack_file AS file_a ON file_a.id_modfile = mod.id_modfile
+             JOIN pack_file AS file_b ON file_b.path_lower = file_a.path_lower
+             JOIN mod AS other ON other.id_modfile = file_b.id_modfile AND other.id_mod != mod.id_mod
+             WHERE mod.id_mod = $1
+             ORDER BY other.id_mod, file_b.path",
+            MOD_COLUMNS.replace("mod.", "other.")
+        ))
+        .bind(self.id)
+        .fetch_all(ctx.data::<AnyPool>()?)
+        .await?;
+        let mut conflicts: Vec<Conflict> = vec![];
+        for (a, b, c, d, e, f, g, h, i, path, ours) in shared {
+            if !matches!(conflicts.last(), Some(last) if last.other.id == a) {
+                conflicts.push(Conflict {
+                    other: Mod::from((a, b, c, d, e, f, g, h, i)),
+                    paths: vec![],
+                    case_collisions: vec![],
+                });
+            }
+            let conflict = conflicts.last_mut().unwrap();
+            if path != ours {
+                conflict.case_collisions.push(path.clone());
+            }
+            conflict.paths.push(path);
+        }
+        Ok(conflicts)
+    }
+}
+
+type ModfileRow = (
+    i64,
+    i64,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+);
+
+const MODFILE_COLUMNS: &str =
+    "id_modfile, id_mod, date_added, hash_md5, filename, version, changelog";
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Modfile {
+    pub id: i64,
+    #[graphql(skip)]
+    pub id_mod: i64,
+    pub date_added: String,
+    pub hash_md5: String,
+    pub filename: String,
+    pub version: Option<String>,
+    pub changelog: Option<String>,
+}
+
+impl From<ModfileRow> for Modfile {
+    fn from((id, id_mod, date_added, hash_md5, filename, version, changelog): ModfileRow) -> Self {
+        Modfile {
+            id,
+            id_mod,
+            date_added,
+            hash_md5,
+            filename,
+            version,
+            changelog,
+        }
+    }
+}
+
+async fn get_modfile(pool: &AnyPool, id_modfile: i64) -> Result<Option<Modfile>> {
+    let row: Option<ModfileRow> = sqlx::query_as(&format!(
+        "SELECT {MODFILE_COLUMNS} FROM modfile WHERE id_modfile = $1"
+    ))
+    .bind(id_modfile)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(Modfile::from))
+}
+
+#[ComplexObject]
+impl Modfile {
+    #[graphql(name = "mod")]
+    async fn owner(&self, ctx: &Context<'_>) -> Result<Option<Mod>> {
+        get_mod(ctx.data()?, self.id_mod).await
+    }
+
+    /// Entries of the modfile's pak ordered by path, only those with this extension if given, e.g.
+    /// `uasset`. The next page starts after the path of the last entry given as `after`.
+    async fn pack_files(
+        &self,
+        ctx: &Context<'_>,
+        extension: Option<String>,
+        #[graphql(default = 500)] first: i64,
+        after: Option<ID>,
+    ) -> Result<Vec<PackFile>> {
+        let rows: Vec<PackFileRow> = sqlx::query_as(&format!(
+            "SELECT {PACK_FILE_COLUMNS} FROM pack_file
+             WHERE id_modfile = $1 AND ($2 = '' OR extension = $2) AND path > $3
+             ORDER BY path LIMIT $4"
+        ))
+        .bind(self.id)
+        .bind(extension.unwrap_or_default())
+        .bind(after.map(|after| after.0).unwrap_or_default())
+        .bind(page_size(first))
+        .fetch_all(ctx.data::<AnyPool>()?)
+        .await?;
+        Ok(rows.into_iter().map(PackFile::from).collect())
+    }
+}
+
+type PackFileRow = (
+    i64,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<i64>,
+);
+
+const PACK_FILE_COLUMNS: &str = "id_modfile, path, extension, hash, asset_class, vanilla_override";
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct PackFile {
+    #[graphql(skip)]
+    pub id_modfile: i64,
+    /// Game path, e.g. `FSD/Content/...`
+    pub path: String,
+    pub extension: Option<String>,
+    /// SHA-1 of the contents, null for paks listed remotely
+    pub hash: Option<String>,
+    /// Class of the primary export of packages, e.g. `DataTable`
+    pub asset_class: Option<String>,
+    /// Whether the path is an asset of the base game, null before index-game ran
+    pub vanilla_override: Option<bool>,
+}
+
+impl From<PackFileRow> for PackFile {
+    fn from(
+        (id_modfile, path, extension, hash, asset_class, vanilla_override): PackFileRow,
+    ) -> Self {
+        PackFile {
+            id_modfile,
+            path,
+            extension,
+            hash,
+            asset_class,
+            vanilla_override: vanilla_override.map(|v| v != 0),
+        }
+    }
+}
+
+#[ComplexObject]
+impl PackFile {
+    /// Position of this pack file in `packFiles` of the query, to continue after it
+    async fn cursor(&self) -> ID {
+        ID(format!("{}/{}", self.id_modfile, self.path))
+    }
+
+    async fn modfile(&self, ctx: &Context<'_>) -> Result<Option<Modfile>> {
+        get_modfile(ctx.data()?, self.id_modfile).await
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Metadata {
+    pub blob: Option<String>,
+    /// Key-value pairs, ordered by key
+    pub pairs: Vec<MetadataPair>,
+}
+
+#[derive(SimpleObject)]
+pub struct MetadataPair {
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+/// A mod.io tag.
+#[derive(SimpleObject)]
+pub struct Tag {
+    pub name: String,
+    /// Number of indexed mods carrying it
+    pub mods: i64,
+}
+
+/// Another mod overriding some of the same paths.
+#[derive(SimpleObject)]
+pub struct Conflict {
+    #[graphql(name = "mod")]
+    pub other: Mod,
+    pub paths: Vec<String>,
+    /// Those of `paths` spelled differently in this mod, which still collide in game
+    pub case_collisions: Vec<String>,
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Indexed mods ordered by id, or by closeness to `search` when given, which matches names
+    /// and name_ids loosely, only those with `category` and `tag` if given. The next page starts
+    /// after the id of the last mod given as `after`.
+    async fn mods(
+        &self,
+        ctx: &Context<'_>,
+        search: Option<String>,
+        category: Option<String>,
+        tag: Option<String>,
+        #[graphql(default = 50)] first: i64,
+        after: Option<ID>,
+    ) -> Result<Vec<Mod>> {
+        let pool = ctx.data::<AnyPool>()?;
+        let first = page_size(first);
+        let after = id_cursor(after)?;
+        let category = category.unwrap_or_default();
+        let tag = tag.unwrap_or_default();
+        let Some(search) = search else {
+            let rows: Vec<ModRow> = sqlx::query_as(&format!(
+                "SELECT {MOD_COLUMNS} FROM mod WHERE {MOD_FILTER} AND id_mod > $3
+                 ORDER BY id_mod LIMIT $4"
+            ))
+            .bind(&category)
+            .bind(&tag)
+            .bind(after)
+            .bind(first)
+            .fetch_all(pool)
+            .await?;
+            return Ok(rows.into_iter().map(Mod::from).collect());
+        };
+
+        // ranked in memory, the page continues after the cursor's rank
+        let ranked = lookup::fuzzy_matches(pool, &search)
+            .await?
+            .into_iter()
+            .map(|c| c.id_mod)
+            .collect::<Vec<_>>();
+        let start = match ranked.iter().position(|id| *id == after) {
+            Some(rank) => rank + 1,
+            None if after == i64::MIN => 0,
+            None => ranked.len(),
+        };
+        let mut mods = vec![];
+        for chunk in ranked[start..].chunks(first.max(1) as usize) {
+            if mods.len() as i64 >= first {
+                break;
+            }
+            let ids = chunk.iter().map(i64::to_string).collect::<Vec<_>>();
+            let rows: Vec<ModRow> = sqlx::query_as(&format!(
+                "SELECT {MOD_COLUMNS} FROM mod WHERE id_mod IN ({}) AND {MOD_FILTER}",
+                ids.join(", ")
+            ))
+            .bind(&category)
+            .bind(&tag)
+            .fetch_all(pool)
+            .await?;
+            let mut page = rows.into_iter().map(Mod::from).collect::<Vec<_>>();
+            page.sort_by_key(|m| chunk.iter().position(|id| *id == m.id));
+            mods.extend(page);
+        }
+        mods.truncate(first as usize);
+        Ok(mods)
+    }
+
+    /// Every tag of indexed mods, most used first.
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        Ok(tags::counts(ctx.data()?)
+            .await?
+            .into_iter()
+            .map(|t| Tag {
+                name: t.tag,
+                mods: t.mods,
+            })
+            .collect())
+    }
+
+    /// A mod by id or name_id.
+    #[graphql(name = "mod")]
+    async fn find_mod(&self, ctx: &Context<'_>, reference: String) -> Result<Option<Mod>> {
+        let pool = ctx.data::<AnyPool>()?;
+        match lookup::find_mod(pool, &reference).await? {
+            Some(id_mod) => get_mod(pool, id_mod).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn modfile(&self, ctx: &Context<'_>, id: i64) -> Result<Option<Modfile>> {
+        get_modfile(ctx.data()?, id).await
+    }
+
+    /// Pack files of current modfiles at a game path, ignoring case like the game does, i.e. the
+    /// mods overriding it, ordered by modfile and path. The next page starts after the `cursor` of
+    /// the last one given as `after`.
+    async fn pack_files(
+        &self,
+        ctx: &Context<'_>,
+        path: String,
+        #[graphql(default = 500)] first: i64,
+        after: Option<ID>,
+    ) -> Result<Vec<PackFile>> {
+        let (after_modfile, after_path) = pack_file_cursor(after)?;
+        let rows: Vec<PackFileRow> = sqlx::query_as(&format!(
+            "SELECT {PACK_FILE_COLUMNS} FROM pack_file
+             WHERE path_lower = LOWER($1) AND id_modfile IN (SELECT id_modfile FROM mod)
+               AND (id_modfile > $2 OR (id_modfile = $2 AND path > $3))
+             ORDER BY id_modfile, path LIMIT $4"
+        ))
+        .bind(path)
+        .bind(after_modfile)
+        .bind(after_path)
+        .bind(page_size(first))
+        .fetch_all(ctx.data::<AnyPool>()?)
+        .await?;
+        Ok(rows.into_iter().map(PackFile::from).collect())
+    }
+}
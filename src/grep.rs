@@ -0,0 +1,163 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use regex::Regex;
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::warn;
+
+use std::path::Path;
+
+use crate::{download, locres};
+
+/// Extensions of pak entries searched as text.
+const TEXT_EXTENSIONS: &[&str] = &["ini", "json", "cfg", "txt", "csv", "xml", "yaml", "yml"];
+
+#[derive(Debug, Serialize)]
+pub struct GrepMatch {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub path: String,
+    pub line_number: u64,
+    pub line: String,
+}
+
+pub fn print_matches(matches: &[GrepMatch]) {
+    for m in matches {
+        println!("{}:{}:{}: {}", m.name_id, m.path, m.line_number, m.line);
+    }
+}
+
+/// Decode a text entry, which UE writes as UTF-8 or as UTF-16 with a byte order mark.
+fn decode(data: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units = bytes
+            .chunks_exact(2)
+            .map(|c| from([c[0], c[1]]))
+            .collect::<Vec<_>>();
+        String::from_utf16_lossy(&units)
+    };
+    match data {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
+/// The searchable text of an archive: text-like entries as they are and locres files as one
+/// `namespace/key: text` line per string.
+fn archive_text(archive: &Path) -> Result<Vec<(String, String)>> {
+    let mut pak = crate::open_zip_pak(archive)?;
+    let mount_point = pak.pak.mount_point().to_string();
+    let records = pak.pak.files().collect::<Vec<_>>();
+
+    let mut text = vec![];
+    for record in records {
+        let path = crate::asset_path(&mount_point, &record)?;
+        let extension = Path::new(&path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        let content = match extension.as_deref() {
+            Some("locres") => match locres::parse(&pak.get(&record)?) {
+                Ok(entries) => entries
+                    .iter()
+                    .map(|e| format!("{}/{}: {}", e.namespace, e.key, e.text))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Err(e) => {
+                    warn!(path, "Failed to read locres: {e:#}");
+                    continue;
+                }
+            },
+            Some(ext) if TEXT_EXTENSIONS.contains(&ext) => decode(&pak.get(&record)?),
+            _ => continue,
+        };
+        text.push((path, content));
+    }
+    Ok(text)
+}
+
+/// Make sure the text of the archive with hash `hash_md5` is cached.
+async fn cache_archive(pool: &AnyPool, hash_md5: &str) -> Result<()> {
+    let scanned: Option<String> =
+        sqlx::query_scalar("SELECT hash_md5 FROM archive_text_scan WHERE hash_md5 = $1")
+            .bind(hash_md5)
+            .fetch_optional(pool)
+            .await?;
+    if scanned.is_some() {
+        return Ok(());
+    }
+
+    let text = archive_text(&download::archive_path(hash_md5))?;
+    let mut tx = pool.begin().await?;
+    for (path, content) in text {
+        sqlx::query(
+            "INSERT INTO archive_text(hash_md5, path, content) VALUES ($1, $2, $3)
+             ON CONFLICT(hash_md5, path) DO UPDATE SET content = excluded.content",
+        )
+        .bind(hash_md5)
+        .bind(path)
+        .bind(content)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query("INSERT INTO archive_text_scan(hash_md5, date_scanned) VALUES ($1, $2)")
+        .bind(hash_md5)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Search the text-like entries of the current modfile of every mod for lines matching
+/// `pattern`. Archives are read once and their text cached by hash, so later searches only read
+/// the index. Archives that are not stored are skipped.
+pub async fn grep(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    pattern: &Regex,
+) -> Result<Vec<GrepMatch>> {
+    let archives: Vec<(i64, String, String)> = sqlx::query_as(
+        "SELECT mod.id_mod, name_id, hash_md5
+         FROM mod JOIN modfile ON modfile.id_modfile = mod.id_modfile
+         ORDER BY name_id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let bar = multi_bar.add(ProgressBar::new(archives.len().try_into().unwrap()));
+    let mut matches = vec![];
+    for (id_mod, name_id, hash_md5) in archives {
+        bar.inc(1);
+        if !download::archive_path(&hash_md5).exists() {
+            continue;
+        }
+        if let Err(e) = cache_archive(pool, &hash_md5).await {
+            warn!(id_mod, "Failed to read archive: {e:#}");
+            continue;
+        }
+        let text: Vec<(String, String)> = sqlx::query_as(
+            "SELECT path, content FROM archive_text WHERE hash_md5 = $1 ORDER BY path",
+        )
+        .bind(&hash_md5)
+        .fetch_all(pool)
+        .await?;
+        for (path, content) in text {
+            for (i, line) in content.lines().enumerate() {
+                if pattern.is_match(line) {
+                    matches.push(GrepMatch {
+                        id_mod,
+                        name_id: name_id.clone(),
+                        path: path.clone(),
+                        line_number: i as u64 + 1,
+                        line: line.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    bar.finish();
+    Ok(matches)
+}
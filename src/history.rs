@@ -0,0 +1,88 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+#[derive(Debug, Serialize)]
+pub struct ModfileVersion {
+    pub id_modfile: i64,
+    pub date_added: String,
+    pub filename: String,
+    pub version: Option<String>,
+    pub changelog: Option<String>,
+    /// Mount point of the pak as stored, `None` if not analyzed since it was recorded
+    pub mount_point: Option<String>,
+    pub current: bool,
+    /// Unreleased file indexed with `--drafts`
+    pub draft: bool,
+}
+
+type ModfileVersionRow = (
+    i64,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    i64,
+    i64,
+);
+
+/// Every modfile of a mod the index has seen, oldest first.
+pub async fn history(pool: &AnyPool, id_mod: i64) -> Result<Vec<ModfileVersion>> {
+    let rows: Vec<ModfileVersionRow> = sqlx::query_as(
+        "SELECT modfile.id_modfile, date_added, filename, version, changelog, mount_point,
+                CASE WHEN mod.id_modfile = modfile.id_modfile THEN 1 ELSE 0 END, draft
+         FROM modfile JOIN mod ON mod.id_mod = modfile.id_mod
+         WHERE modfile.id_mod = $1
+         ORDER BY date_added, modfile.id_modfile",
+    )
+    .bind(id_mod)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(
+                id_modfile,
+                date_added,
+                filename,
+                version,
+                changelog,
+                mount_point,
+                current,
+                draft,
+            )| {
+                ModfileVersion {
+                    id_modfile,
+                    date_added,
+                    filename,
+                    version,
+                    changelog,
+                    mount_point,
+                    current: current != 0,
+                    draft: draft != 0,
+                }
+            },
+        )
+        .collect())
+}
+
+pub fn print_history(versions: &[ModfileVersion]) {
+    for v in versions {
+        println!(
+            "{} {} {} {}{}{}",
+            v.id_modfile,
+            v.date_added,
+            v.version.as_deref().unwrap_or("-"),
+            v.filename,
+            if v.current { " (current)" } else { "" },
+            if v.draft { " (draft)" } else { "" }
+        );
+        if let Some(changelog) = v.changelog.as_deref().filter(|c| !c.is_empty()) {
+            for line in changelog.lines() {
+                println!("    {line}");
+            }
+        }
+    }
+}
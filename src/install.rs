@@ -0,0 +1,217 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::{info, warn};
+
+use std::path::{Path, PathBuf};
+
+use crate::{download, lookup, modpack};
+
+/// Directory below the game directory the game loads paks from.
+pub const PAKS_DIR: &str = "FSD/Content/Paks";
+
+/// A pak placed in the game directory.
+#[derive(Debug, Serialize)]
+pub struct InstalledPak {
+    pub path: PathBuf,
+    pub id_mod: i64,
+    pub name_id: String,
+    pub id_modfile: i64,
+    pub size: u64,
+}
+
+/// Result of an install.
+#[derive(Debug, Serialize)]
+pub struct Installation {
+    pub game_dir: PathBuf,
+    pub installed: Vec<InstalledPak>,
+    pub removed: Uninstallation,
+}
+
+impl std::fmt::Display for Installation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for pak in &self.installed {
+            writeln!(f, "{} {}", pak.name_id, pak.path.display())?;
+        }
+        write!(
+            f,
+            "Installed {} paks into {}, removed {} from the previous install",
+            self.installed.len(),
+            self.game_dir.display(),
+            self.removed.removed.len()
+        )
+    }
+}
+
+/// Result of removing installed paks.
+#[derive(Debug, Default, Serialize)]
+pub struct Uninstallation {
+    pub removed: Vec<PathBuf>,
+    /// Installed paks changed since, which are left alone
+    pub kept: Vec<PathBuf>,
+}
+
+impl std::fmt::Display for Uninstallation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for path in &self.kept {
+            writeln!(f, "kept {}: changed since it was installed", path.display())?;
+        }
+        write!(f, "Removed {} installed paks", self.removed.len())
+    }
+}
+
+/// The modfiles to install for `targets`, in priority order: the locked modfiles of a modpack
+/// manifest given by path, or the current modfile of a mod given by id or name_id.
+async fn resolve(pool: &AnyPool, targets: &[String]) -> Result<Vec<(i64, String, i64, String)>> {
+    let mut modfiles = vec![];
+    for target in targets {
+        let path = Path::new(target);
+        if path.is_file() {
+            let lockfile = modpack::read_lockfile(path)?;
+            for m in lockfile.mods {
+                modfiles.push((m.id_mod, m.name_id, m.id_modfile, m.hash_md5));
+            }
+            continue;
+        }
+        let id_mod = lookup::resolve_mod(pool, target).await?;
+        let id_modfile = lookup::current_modfile(pool, id_mod).await?;
+        let (name_id, md5): (String, String) = sqlx::query_as(
+            "SELECT mod.name_id, modfile.hash_md5
+             FROM modfile JOIN mod ON mod.id_mod = modfile.id_mod
+             WHERE modfile.id_modfile = $1",
+        )
+        .bind(id_modfile)
+        .fetch_one(pool)
+        .await?;
+        modfiles.push((id_mod, name_id, id_modfile, md5));
+    }
+    Ok(modfiles)
+}
+
+/// Copy the pak out of the stored zip with md5 `md5` to `target`. Returns its size.
+fn extract_pak(md5: &str, target: &Path) -> Result<u64> {
+    let archive = download::archive_path(md5);
+    if !archive.exists() {
+        bail!(
+            "archive {} is not stored, run fetch-missing first",
+            archive.display()
+        );
+    }
+    let mut pak = crate::open_zip_pak(&archive)?;
+    let mut file = std::fs::File::create(target)
+        .with_context(|| format!("failed to write {}", target.display()))?;
+    Ok(pak.copy_to(&mut file)?)
+}
+
+fn game_dir_key(game_dir: &Path) -> Result<String> {
+    Ok(game_dir.canonicalize()?.display().to_string())
+}
+
+/// Extract the paks of `targets`, mods or modpack manifests, into the paks directory of the game
+/// at `game_dir`, replacing what an earlier install put there. Paks are named
+/// `<priority>_<name_id>_P.pak` so the game loads them in the order given, later mods taking
+/// precedence over earlier ones on the paths they share.
+pub async fn install(pool: &AnyPool, game_dir: &Path, targets: &[String]) -> Result<Installation> {
+    let paks_dir = game_dir.join(PAKS_DIR);
+    if !paks_dir.is_dir() {
+        bail!(
+            "{} is not a DRG install, {} does not exist",
+            game_dir.display(),
+            paks_dir.display()
+        );
+    }
+    let modfiles = resolve(pool, targets).await?;
+    let removed = uninstall(pool, game_dir, &[]).await?;
+    let key = game_dir_key(game_dir)?;
+
+    let mut installed = vec![];
+    let width = modfiles.len().to_string().len().max(3);
+    for (i, (id_mod, name_id, id_modfile, md5)) in modfiles.into_iter().enumerate() {
+        if installed.iter().any(|p: &InstalledPak| p.id_mod == id_mod) {
+            warn!(name_id, "Skipping mod listed twice");
+            continue;
+        }
+        let path = paks_dir.join(format!("{:0width$}_{name_id}_P.pak", i + 1));
+        if path.exists() {
+            bail!(
+                "{} was not put there by install, move it away first",
+                path.display()
+            );
+        }
+        let size = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || extract_pak(&md5, &path)).await??
+        };
+        sqlx::query(
+            "INSERT INTO installed_pak(game_dir, path, id_mod, id_modfile, size, date_installed)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT(game_dir, path) DO UPDATE SET
+                id_mod = excluded.id_mod,
+                id_modfile = excluded.id_modfile,
+                size = excluded.size,
+                date_installed = excluded.date_installed",
+        )
+        .bind(&key)
+        .bind(path.display().to_string())
+        .bind(id_mod)
+        .bind(id_modfile)
+        .bind(size as i64)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(pool)
+        .await?;
+        info!(name_id, path = %path.display(), "Installed pak");
+        installed.push(InstalledPak {
+            path,
+            id_mod,
+            name_id,
+            id_modfile,
+            size,
+        });
+    }
+    Ok(Installation {
+        game_dir: game_dir.to_path_buf(),
+        installed,
+        removed,
+    })
+}
+
+/// Remove the paks installed into the game at `game_dir`, only those of `mods` if any are given.
+/// A pak whose size changed since was replaced by something else and is left in place.
+pub async fn uninstall(pool: &AnyPool, game_dir: &Path, mods: &[String]) -> Result<Uninstallation> {
+    let key = game_dir_key(game_dir)?;
+    let mut ids = vec![];
+    for reference in mods {
+        ids.push(lookup::resolve_mod(pool, reference).await?);
+    }
+    let rows: Vec<(String, i64, i64)> =
+        sqlx::query_as("SELECT path, id_mod, size FROM installed_pak WHERE game_dir = $1")
+            .bind(&key)
+            .fetch_all(pool)
+            .await?;
+
+    let mut uninstallation = Uninstallation::default();
+    for (path, id_mod, size) in rows {
+        if !ids.is_empty() && !ids.contains(&id_mod) {
+            continue;
+        }
+        let pak = PathBuf::from(&path);
+        match std::fs::metadata(&pak) {
+            Ok(metadata) if metadata.len() != size as u64 => {
+                warn!(path, "Leaving installed pak that changed since");
+                uninstallation.kept.push(pak);
+            }
+            Ok(_) => {
+                std::fs::remove_file(&pak).with_context(|| format!("failed to remove {path}"))?;
+                uninstallation.removed.push(pak);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("failed to read {path}")),
+        }
+        sqlx::query("DELETE FROM installed_pak WHERE game_dir = $1 AND path = $2")
+            .bind(&key)
+            .bind(&path)
+            .execute(pool)
+            .await?;
+    }
+    Ok(uninstallation)
+}
@@ -0,0 +1,168 @@
+use anyhow::Result;
+use sqlx::AnyPool;
+
+use serde::Serialize;
+
+use std::collections::{BTreeSet, HashMap};
+
+/// Names used in the game's asset paths below a labeled directory, as path segments or `_`
+/// separated parts of asset names, and what they are called in game. The first name found in a
+/// path wins, so generic internal names come last.
+type Names = &'static [(&'static str, &'static str)];
+
+const ENEMIES: Names = &[
+    ("gruntguard", "Glyphid Grunt Guard"),
+    ("gruntslasher", "Glyphid Slasher"),
+    ("grunt", "Glyphid Grunt"),
+    ("praetorian", "Glyphid Praetorian"),
+    ("oppressor", "Glyphid Oppressor"),
+    ("swarmer", "Glyphid Swarmer"),
+    ("exploder", "Glyphid Exploder"),
+    ("spitter", "Glyphid Acid Spitter"),
+    ("webspitter", "Glyphid Web Spitter"),
+    ("menace", "Glyphid Menace"),
+    ("warden", "Glyphid Warden"),
+    ("septic", "Glyphid Septic Spreader"),
+    ("detonator", "Glyphid Bulk Detonator"),
+    ("dreadnought", "Glyphid Dreadnought"),
+    ("hiveguard", "Hiveguard"),
+    ("macteragrabber", "Mactera Grabber"),
+    ("tripleshooter", "Mactera Tri-Jaw"),
+    ("trijaw", "Mactera Tri-Jaw"),
+    ("goobomber", "Mactera Goo Bomber"),
+    ("brundle", "Mactera Brundle"),
+    ("mactera", "Mactera Spawn"),
+    ("naedocyte", "Naedocyte"),
+    ("caveleech", "Cave Leech"),
+    ("spitball", "Spitball Infector"),
+    ("stingtail", "Stingtail"),
+    ("qronar", "Q'ronar Shellback"),
+    ("shellback", "Q'ronar Shellback"),
+    ("korlok", "Korlok Tyrant-Weed"),
+    ("nayak", "Nayaka Trawler"),
+    ("shredder", "Shredder"),
+    ("patrolbot", "Patrol Bot"),
+    ("caretaker", "Caretaker"),
+    ("lootbug", "Lootbug"),
+    // internal names shared with other assets, only used when nothing above matches
+    ("tank", "Glyphid Oppressor"),
+    ("shooter", "Glyphid Acid Spitter"),
+    ("grabber", "Mactera Grabber"),
+    ("leech", "Cave Leech"),
+];
+
+const BIOMES: Names = &[
+    ("crystalcaves", "Crystalline Caverns"),
+    ("saltpits", "Salt Pits"),
+    ("fungusbogs", "Fungus Bogs"),
+    ("magmacore", "Magma Core"),
+    ("icecaves", "Glacial Strata"),
+    ("glacialstrata", "Glacial Strata"),
+    ("hollowbough", "Hollow Bough"),
+    ("azureweald", "Azure Weald"),
+    ("sandblastedcorridors", "Sandblasted Corridors"),
+    ("radioactivezone", "Radioactive Exclusion Zone"),
+    ("densebiozone", "Dense Biozone"),
+];
+
+const MISSIONS: Names = &[
+    ("mining", "Mining Expedition"),
+    ("egghunt", "Egg Hunt"),
+    ("egg", "Egg Hunt"),
+    ("salvage", "Salvage Operation"),
+    ("refinery", "On-Site Refining"),
+    ("escort", "Escort Duty"),
+    ("elimination", "Elimination"),
+    ("pointextraction", "Point Extraction"),
+    ("facility", "Industrial Sabotage"),
+    ("deepscan", "Deep Scan"),
+    ("deepdive", "Deep Dive"),
+];
+
+/// Name of what an asset belongs to if it is below a `directory` directory. Assets whose path
+/// contains none of `names` are named after the directory holding them, so new creatures, biomes
+/// and mission types still show up.
+fn label(directory: &str, names: Names, path: &str) -> Option<String> {
+    let lower = path.to_ascii_lowercase();
+    let (_, rest) = lower.split_once(&format!("/{directory}/"))?;
+    let tokens = rest
+        .split(['/', '_', '.'])
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>();
+    if let Some((_, name)) = names.iter().find(|(key, _)| tokens.contains(key)) {
+        return Some(name.to_string());
+    }
+
+    // keep the original case of the directory for display
+    let start = path.len() - rest.len();
+    let directory = path[start..].rsplit_once('/')?.0;
+    let name = directory.rsplit('/').next()?;
+    Some(name.to_string())
+}
+
+/// What a modfile's assets touch in game, each sorted by name.
+#[derive(Debug, Default, Serialize)]
+pub struct Affected {
+    /// Creatures whose assets are modified
+    pub enemies: Vec<String>,
+    pub biomes: Vec<String>,
+    pub missions: Vec<String>,
+}
+
+impl Affected {
+    fn from_paths(paths: &[String]) -> Self {
+        let labels = |directory, names| {
+            paths
+                .iter()
+                .filter_map(|path| label(directory, names, path))
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect()
+        };
+        Affected {
+            enemies: labels("enemies", ENEMIES),
+            biomes: labels("biomes", BIOMES),
+            missions: labels("missions", MISSIONS),
+        }
+    }
+
+    /// Biomes and mission types together, e.g. for an "affects ..." column.
+    pub fn places(&self) -> Vec<String> {
+        [&self.biomes[..], &self.missions[..]].concat()
+    }
+}
+
+const LABELED_PATHS: &str = "(LOWER(path) LIKE '%/enemies/%'
+                              OR LOWER(path) LIKE '%/biomes/%'
+                              OR LOWER(path) LIKE '%/missions/%')";
+
+/// Creatures, biomes and mission types affected by a modfile.
+pub async fn affected(pool: &AnyPool, id_modfile: i64) -> Result<Affected> {
+    let paths: Vec<String> = sqlx::query_scalar(&format!(
+        "SELECT path FROM pack_file WHERE id_modfile = $1 AND {LABELED_PATHS}"
+    ))
+    .bind(id_modfile)
+    .fetch_all(pool)
+    .await?;
+    Ok(Affected::from_paths(&paths))
+}
+
+/// What the current modfile of every mod affects, for listings. Mods affecting nothing are left
+/// out.
+pub async fn affected_by_mod(pool: &AnyPool) -> Result<HashMap<i64, Affected>> {
+    let rows: Vec<(i64, String)> = sqlx::query_as(&format!(
+        "SELECT mod.id_mod, path
+         FROM mod JOIN pack_file ON pack_file.id_modfile = mod.id_modfile
+         WHERE {LABELED_PATHS}"
+    ))
+    .fetch_all(pool)
+    .await?;
+    let mut paths = HashMap::<i64, Vec<String>>::new();
+    for (id_mod, path) in rows {
+        paths.entry(id_mod).or_default().push(path);
+    }
+    Ok(paths
+        .into_iter()
+        .map(|(id_mod, paths)| (id_mod, Affected::from_paths(&paths)))
+        .collect())
+}
@@ -0,0 +1,493 @@
+//! Checks of mod archives for packaging mistakes that make a mod load wrong or not at all, run
+//! over the index or over an author's archive before it is uploaded.
+
+use anyhow::Result;
+use indicatif::ProgressBar;
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::warn;
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+use crate::pak_source::ArchiveFormat;
+use crate::{download, OpenPak};
+
+/// Mount point of DRG's own paks and of correctly packaged mods.
+const EXPECTED_MOUNT_POINT: &str = "../../../";
+
+/// Root every game path of a mod is expected under.
+const CONTENT_ROOT: &str = "fsd/content/";
+
+/// Folders a pak or other file may be nested in inside a zip before it is reported.
+const MAX_DEPTH: usize = 2;
+
+/// Size of everything in a zip besides paks above which it is reported.
+const MAX_PAYLOAD: u64 = 50 * 1024 * 1024;
+
+/// Extensions of files that run code outside the game and have no place in a mod.
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "dll", "bat", "cmd", "ps1", "msi", "scr", "com", "vbs", "jar", "sh",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rule {
+    /// The pak is not mounted at `../../../`
+    MountPoint,
+    /// A path outside `FSD/Content`, e.g. a stray `AssetRegistry.bin` or a misplaced folder
+    OutsideContent,
+    /// A path `MOUNT_POINT_NORMALIZATION` fails on or leaves `..` or a root in
+    MangledPath,
+    /// A zip without a pak
+    NoPak,
+    /// A zip whose pak is only inside an archive nested in it
+    NestedArchive,
+    /// A zip with several paks, of which only the first is loaded by most mod managers
+    MultiplePaks,
+    /// A file nested in more than [`MAX_DEPTH`] folders of a zip
+    DeepNesting,
+    /// An executable or library in a zip
+    Executable,
+    /// More than [`MAX_PAYLOAD`] of files besides paks in a zip
+    LargePayload,
+}
+
+impl Rule {
+    fn as_str(self) -> &'static str {
+        match self {
+            Rule::MountPoint => "mount_point",
+            Rule::OutsideContent => "outside_content",
+            Rule::MangledPath => "mangled_path",
+            Rule::NoPak => "no_pak",
+            Rule::NestedArchive => "nested_archive",
+            Rule::MultiplePaks => "multiple_paks",
+            Rule::DeepNesting => "deep_nesting",
+            Rule::Executable => "executable",
+            Rule::LargePayload => "large_payload",
+        }
+    }
+}
+
+/// A rule an archive breaks.
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    pub rule: String,
+    /// The first occurrence, e.g. the offending path
+    pub detail: String,
+    pub occurrences: i64,
+}
+
+/// Occurrences per rule while checking an archive, with the first occurrence's detail.
+#[derive(Default)]
+struct Findings(BTreeMap<Rule, (String, i64)>);
+
+impl Findings {
+    fn add(&mut self, rule: Rule, detail: impl FnOnce() -> String) {
+        self.0
+            .entry(rule)
+            .and_modify(|(_, n)| *n += 1)
+            .or_insert_with(|| (detail(), 1));
+    }
+
+    fn into_vec(self) -> Vec<Finding> {
+        self.0
+            .into_iter()
+            .map(|(rule, (detail, occurrences))| Finding {
+                rule: rule.as_str().to_string(),
+                detail,
+                occurrences,
+            })
+            .collect()
+    }
+}
+
+fn check_pak(pak: &OpenPak, findings: &mut Findings) {
+    let mount_point = pak.pak.mount_point().to_string();
+    if mount_point != EXPECTED_MOUNT_POINT {
+        findings.add(Rule::MountPoint, || mount_point.clone());
+    }
+    for record in pak.pak.files() {
+        match crate::asset_path(&mount_point, &record) {
+            Err(e) => findings.add(Rule::MangledPath, || format!("{mount_point}{record}: {e}")),
+            Ok(path)
+                if Path::new(&path)
+                    .components()
+                    .any(|c| !matches!(c, Component::Normal(_))) =>
+            {
+                findings.add(Rule::MangledPath, || path)
+            }
+            Ok(path) if !path.to_ascii_lowercase().starts_with(CONTENT_ROOT) => {
+                findings.add(Rule::OutsideContent, || path)
+            }
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Check how a zip is laid out and return its first pak, the one the indexer reads, if any. Of
+/// rar and 7z archives only the pak is looked for.
+fn check_zip(path: &Path, findings: &mut Findings) -> Result<Option<OpenPak>> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    if ArchiveFormat::detect(&mut file)? != ArchiveFormat::Zip {
+        return match crate::open_zip_pak(path) {
+            Ok(pak) => Ok(Some(pak)),
+            Err(crate::PakError::MissingPakFile) => {
+                findings.add(Rule::NoPak, || "no .pak in the archive".to_string());
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut paks = vec![];
+    let mut nested = vec![];
+    let mut payload = 0;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        if !file.is_file() {
+            continue;
+        }
+        let name = file.name().to_string();
+        if name.matches('/').count() > MAX_DEPTH {
+            findings.add(Rule::DeepNesting, || name.clone());
+        }
+        let extension = Path::new(&name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        if extension == "pak" {
+            paks.push(name);
+            continue;
+        }
+        if matches!(extension.as_str(), "zip" | "rar" | "7z") {
+            nested.push(name.clone());
+        }
+        if EXECUTABLE_EXTENSIONS.contains(&extension.as_str()) {
+            findings.add(Rule::Executable, || name.clone());
+        }
+        payload += file.size();
+    }
+    if payload > MAX_PAYLOAD {
+        findings.add(Rule::LargePayload, || {
+            format!("{} MiB besides paks", payload / 1024 / 1024)
+        });
+    }
+    match paks.as_slice() {
+        [] => match crate::open_zip_pak(path) {
+            Ok(pak) => {
+                findings.add(Rule::NestedArchive, || nested.join(", "));
+                Ok(Some(pak))
+            }
+            Err(crate::PakError::MissingPakFile) => {
+                findings.add(Rule::NoPak, || "no .pak in the zip".to_string());
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        },
+        [_, rest @ ..] => {
+            if !rest.is_empty() {
+                findings.add(Rule::MultiplePaks, || paks.join(", "));
+            }
+            Ok(Some(crate::open_zip_pak(path)?))
+        }
+    }
+}
+
+/// Check a zip holding a pak, as mod.io serves them, a rar or 7z archive or a bare pak.
+fn lint_archive(path: &Path) -> Result<Vec<Finding>> {
+    let is_pak = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("pak"));
+    let mut findings = Findings::default();
+    let pak = if is_pak {
+        Some(crate::open_pak(path)?)
+    } else {
+        check_zip(path, &mut findings)?
+    };
+    if let Some(pak) = pak {
+        check_pak(&pak, &mut findings);
+    }
+    Ok(findings.into_vec())
+}
+
+/// The findings of an archive.
+#[derive(Debug, Serialize)]
+pub struct LintedArchive {
+    /// name_id of the mod, or the path of an archive checked with `--file`
+    pub name: String,
+    pub id_modfile: Option<i64>,
+    pub findings: Vec<Finding>,
+}
+
+pub fn print_linted(archives: &[LintedArchive]) {
+    for a in archives {
+        let rules = a
+            .findings
+            .iter()
+            .map(|f| f.rule.as_str())
+            .collect::<Vec<_>>();
+        println!("{}: {}", a.name, rules.join(", "));
+        for f in &a.findings {
+            print!("  {}: {}", f.rule, f.detail);
+            if f.occurrences > 1 {
+                print!(" (and {} more)", f.occurrences - 1);
+            }
+            println!();
+        }
+    }
+}
+
+/// Check an archive that is not indexed, e.g. before uploading it.
+pub async fn lint_file(path: &Path) -> Result<LintedArchive> {
+    let owned = path.to_path_buf();
+    let findings = tokio::task::spawn_blocking(move || lint_archive(&owned)).await??;
+    Ok(LintedArchive {
+        name: path.display().to_string(),
+        id_modfile: None,
+        findings,
+    })
+}
+
+async fn store_findings(pool: &AnyPool, id_modfile: i64, findings: &[Finding]) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM lint_finding WHERE id_modfile = $1")
+        .bind(id_modfile)
+        .execute(&mut *tx)
+        .await?;
+    for f in findings {
+        sqlx::query(
+            "INSERT INTO lint_finding(id_modfile, rule, detail, occurrences)
+             VALUES ($1, $2, $3, $4)",
+        )
+        .bind(id_modfile)
+        .bind(&f.rule)
+        .bind(&f.detail)
+        .bind(f.occurrences)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query(
+        "INSERT INTO lint_scan(id_modfile, date_linted) VALUES ($1, $2)
+         ON CONFLICT(id_modfile) DO UPDATE SET date_linted = excluded.date_linted",
+    )
+    .bind(id_modfile)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Check the stored archives of the current modfiles of `mods`, or of every mod if empty, and
+/// return those with findings. Modfiles checked before are only checked again with `relint`,
+/// their stored findings are returned as they are.
+pub async fn lint(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    mods: &[i64],
+    relint: bool,
+) -> Result<Vec<LintedArchive>> {
+    let modfiles: Vec<(i64, String, i64, String, i64)> = sqlx::query_as(
+        "SELECT mod.id_mod, name_id, modfile.id_modfile, hash_md5,
+                CASE WHEN lint_scan.id_modfile IS NULL THEN 0 ELSE 1 END
+         FROM mod JOIN modfile ON modfile.id_modfile = mod.id_modfile
+         LEFT JOIN lint_scan ON lint_scan.id_modfile = modfile.id_modfile
+         ORDER BY name_id",
+    )
+    .fetch_all(pool)
+    .await?;
+    let modfiles = modfiles
+        .into_iter()
+        .filter(|(id_mod, ..)| mods.is_empty() || mods.contains(id_mod))
+        .collect::<Vec<_>>();
+
+    let bar = multi_bar.add(ProgressBar::new(modfiles.len().try_into().unwrap()));
+    let mut linted = vec![];
+    for (id_mod, name_id, id_modfile, hash_md5, scanned) in modfiles {
+        bar.inc(1);
+        if scanned == 0 || relint {
+            let archive = download::archive_path(&hash_md5);
+            if !archive.exists() {
+                continue;
+            }
+            match tokio::task::spawn_blocking(move || lint_archive(&archive)).await? {
+                Ok(findings) => store_findings(pool, id_modfile, &findings).await?,
+                Err(e) => {
+                    warn!(id_mod, id_modfile, "Failed to read archive: {e:#}");
+                    continue;
+                }
+            }
+        }
+        let rows: Vec<(String, String, i64)> = sqlx::query_as(
+            "SELECT rule, detail, occurrences FROM lint_finding WHERE id_modfile = $1
+             ORDER BY rule",
+        )
+        .bind(id_modfile)
+        .fetch_all(pool)
+        .await?;
+        if rows.is_empty() {
+            continue;
+        }
+        linted.push(LintedArchive {
+            name: name_id,
+            id_modfile: Some(id_modfile),
+            findings: rows
+                .into_iter()
+                .map(|(rule, detail, occurrences)| Finding {
+                    rule,
+                    detail,
+                    occurrences,
+                })
+                .collect(),
+        });
+    }
+    bar.finish();
+    Ok(linted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    // paks holding `Sample.uasset` and `Sample.uexp` unless listed otherwise
+
+    /// Mounted at `../../../`
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/lint/Sample.pak");
+    /// Mounted at `../../../FSD/`
+    const MOUNT_POINT: &[u8] = include_bytes!("../tests/fixtures/lint/MountPoint.pak");
+    /// Mounted at `/Game/`, which `strip:3` fails on
+    const UNMOUNTABLE: &[u8] = include_bytes!("../tests/fixtures/lint/Unmountable.pak");
+    /// Mounted at `../../../`, holding `FSD/Content/Sample.uasset`, `FSD/AssetRegistry.bin` and
+    /// `FSD/Content/../../Escape.uasset`
+    const MISPLACED: &[u8] = include_bytes!("../tests/fixtures/lint/Misplaced.pak");
+
+    fn zip_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        for (name, data) in files {
+            zip.start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// Lint `data` written to a file called `name`, which is deleted afterwards.
+    fn lint(name: &str, data: &[u8]) -> Vec<Finding> {
+        let path = std::env::temp_dir().join(format!(
+            "drg-modio-index-lint-{}-{name}",
+            std::process::id()
+        ));
+        std::fs::write(&path, data).unwrap();
+        let findings = lint_archive(&path);
+        std::fs::remove_file(&path).unwrap();
+        findings.unwrap()
+    }
+
+    fn rules(findings: &[Finding]) -> Vec<&str> {
+        findings.iter().map(|f| f.rule.as_str()).collect()
+    }
+
+    #[test]
+    fn clean_archives() {
+        assert!(lint("clean.pak", SAMPLE).is_empty());
+        assert!(lint("clean.zip", &zip_bytes(&[("Sample.pak", SAMPLE)])).is_empty());
+        let findings = lint(
+            "clean-folders.zip",
+            &zip_bytes(&[
+                ("Mod/Paks/Sample.pak", SAMPLE),
+                ("Mod/readme.txt", b"Install with a mod manager"),
+            ]),
+        );
+        assert!(findings.is_empty());
+    }
+
+    #[test]
+    fn mount_point() {
+        let findings = lint("mount-point.pak", MOUNT_POINT);
+        assert_eq!(rules(&findings), ["mount_point"]);
+        assert_eq!(findings[0].detail, "../../../FSD/");
+        assert_eq!(findings[0].occurrences, 1);
+    }
+
+    #[test]
+    fn mangled_and_outside_content() {
+        let findings = lint("misplaced.pak", MISPLACED);
+        assert_eq!(rules(&findings), ["outside_content", "mangled_path"]);
+        assert_eq!(findings[0].detail, "FSD/AssetRegistry.bin");
+        assert_eq!(findings[1].detail, "FSD/Content/../../Escape.uasset");
+
+        let findings = lint("unmountable.pak", UNMOUNTABLE);
+        assert_eq!(rules(&findings), ["mount_point", "mangled_path"]);
+        assert!(findings[1].detail.starts_with("/Game/Sample.u"));
+        assert_eq!(findings[1].occurrences, 2);
+    }
+
+    #[test]
+    fn no_pak() {
+        let findings = lint("no-pak.zip", &zip_bytes(&[("readme.txt", b"Coming soon")]));
+        assert_eq!(rules(&findings), ["no_pak"]);
+    }
+
+    #[test]
+    fn nested_archive() {
+        let inner = zip_bytes(&[("Sample.pak", SAMPLE)]);
+        let findings = lint("nested.zip", &zip_bytes(&[("Sample.zip", &inner)]));
+        assert_eq!(rules(&findings), ["nested_archive"]);
+        assert_eq!(findings[0].detail, "Sample.zip");
+    }
+
+    #[test]
+    fn multiple_paks() {
+        let findings = lint(
+            "multiple.zip",
+            &zip_bytes(&[("Sample.pak", SAMPLE), ("Sample_P.pak", SAMPLE)]),
+        );
+        assert_eq!(rules(&findings), ["multiple_paks"]);
+        assert_eq!(findings[0].detail, "Sample.pak, Sample_P.pak");
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let findings = lint(
+            "deep.zip",
+            &zip_bytes(&[("Mod/Content/Paks/Sample.pak", SAMPLE)]),
+        );
+        assert_eq!(rules(&findings), ["deep_nesting"]);
+        assert_eq!(findings[0].detail, "Mod/Content/Paks/Sample.pak");
+    }
+
+    #[test]
+    fn executable() {
+        let findings = lint(
+            "executable.zip",
+            &zip_bytes(&[
+                ("Sample.pak", SAMPLE),
+                ("Install.EXE", b"MZ"),
+                ("tools/patch.dll", b"MZ"),
+            ]),
+        );
+        assert_eq!(rules(&findings), ["executable"]);
+        assert_eq!(findings[0].detail, "Install.EXE");
+        assert_eq!(findings[0].occurrences, 2);
+    }
+
+    #[test]
+    fn large_payload() {
+        let video = vec![0; MAX_PAYLOAD as usize + 1];
+        let findings = lint(
+            "large.zip",
+            &zip_bytes(&[("Sample.pak", SAMPLE), ("trailer.mp4", &video)]),
+        );
+        assert_eq!(rules(&findings), ["large_payload"]);
+        assert_eq!(findings[0].detail, "50 MiB besides paks");
+
+        let findings = lint(
+            "limit.zip",
+            &zip_bytes(&[("Sample.pak", SAMPLE), ("trailer.mp4", &video[1..])]),
+        );
+        assert!(findings.is_empty());
+    }
+}
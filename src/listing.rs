@@ -0,0 +1,189 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::flatten::csv_field;
+
+/// Output format of `list-files`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Plain,
+    Json,
+    Csv,
+    Tree,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListedEntry {
+    pub path: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArchiveListing {
+    pub archive: PathBuf,
+    /// Size of the pak, uncompressed if it is inside a zip
+    pub pak_size: Option<u64>,
+    /// Size of the pak as compressed in the zip, `None` for a bare pak
+    pub pak_compressed_size: Option<u64>,
+    /// Mount point of the pak as stored, before normalization
+    pub mount_point: Option<String>,
+    pub entries: Vec<ListedEntry>,
+    pub error: Option<String>,
+}
+
+/// Sizes of the pak in a zip, uncompressed and compressed, or of a bare pak, from the zip's
+/// central directory without reading the pak.
+fn pak_sizes(path: &Path) -> Result<(u64, Option<u64>)> {
+    let file = std::fs::File::open(path)?;
+    if path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("pak"))
+    {
+        return Ok((file.metadata()?.len(), None));
+    }
+    let mut archive = zip::ZipArchive::new(std::io::BufReader::new(file))?;
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if entry.is_file() && entry.name().to_lowercase().ends_with(".pak") {
+            return Ok((entry.size(), Some(entry.compressed_size())));
+        }
+    }
+    Err(crate::PakError::MissingPakFile.into())
+}
+
+fn list_archive(path: PathBuf) -> ArchiveListing {
+    let mut listing = ArchiveListing {
+        archive: path,
+        pak_size: None,
+        pak_compressed_size: None,
+        mount_point: None,
+        entries: vec![],
+        error: None,
+    };
+    match crate::list_archive_files(&listing.archive) {
+        Ok(pak) => {
+            listing.mount_point = Some(pak.mount_point);
+            listing.entries = pak
+                .entries
+                .into_iter()
+                .map(|entry| ListedEntry {
+                    path: entry.path,
+                    size: entry.size.unwrap_or_default(),
+                })
+                .collect();
+            if let Ok((size, compressed)) = pak_sizes(&listing.archive) {
+                listing.pak_size = Some(size);
+                listing.pak_compressed_size = compressed;
+            }
+        }
+        Err(e) => listing.error = Some(e.to_string()),
+    }
+    listing
+}
+
+/// List the entries of `paths` in parallel, in the order given.
+pub async fn list_archives(paths: Vec<PathBuf>) -> Result<Vec<ArchiveListing>> {
+    use futures::stream::StreamExt;
+
+    futures::stream::iter(
+        paths
+            .into_iter()
+            .map(|path| tokio::task::spawn_blocking(move || list_archive(path))),
+    )
+    .buffered(std::thread::available_parallelism()?.get())
+    .map(|listing| Ok(listing?))
+    .collect::<Vec<Result<_>>>()
+    .await
+    .into_iter()
+    .collect()
+}
+
+pub fn print(listings: &[ArchiveListing], format: Format) {
+    match format {
+        Format::Plain => print_plain(listings),
+        Format::Json => match serde_json::to_string(listings) {
+            Ok(json) => println!("{json}"),
+            Err(e) => eprintln!("{e}"),
+        },
+        Format::Csv => print_csv(listings),
+        Format::Tree => print_tree(listings),
+    }
+}
+
+fn print_plain(listings: &[ArchiveListing]) {
+    for listing in listings {
+        for entry in &listing.entries {
+            println!("{} {}", listing.archive.display(), entry.path);
+        }
+        if let Some(e) = &listing.error {
+            println!("{} {}", listing.archive.display(), e);
+        }
+    }
+}
+
+fn print_csv(listings: &[ArchiveListing]) {
+    println!("archive,path,size,error");
+    for listing in listings {
+        let archive = csv_field(&listing.archive.to_string_lossy());
+        for entry in &listing.entries {
+            println!("{archive},{},{},", csv_field(&entry.path), entry.size);
+        }
+        if let Some(e) = &listing.error {
+            println!("{archive},,,{}", csv_field(e));
+        }
+    }
+}
+
+/// A directory of a pak in the tree format.
+#[derive(Default)]
+struct Dir<'a> {
+    size: u64,
+    dirs: BTreeMap<&'a str, Dir<'a>>,
+    files: BTreeMap<&'a str, u64>,
+}
+
+impl<'a> Dir<'a> {
+    fn insert(&mut self, path: &'a str, size: u64) {
+        self.size += size;
+        match path.split_once('/') {
+            Some((dir, rest)) => self.dirs.entry(dir).or_default().insert(rest, size),
+            None => {
+                self.files.insert(path, size);
+            }
+        }
+    }
+
+    fn print(&self, depth: usize) {
+        let indent = "  ".repeat(depth);
+        for (name, dir) in &self.dirs {
+            println!("{indent}{name}/ {}", dir.size);
+            dir.print(depth + 1);
+        }
+        for (name, size) in &self.files {
+            println!("{indent}{name} {size}");
+        }
+    }
+}
+
+fn print_tree(listings: &[ArchiveListing]) {
+    for listing in listings {
+        let mut root = Dir::default();
+        for entry in &listing.entries {
+            root.insert(&entry.path, entry.size);
+        }
+        print!("{} {}", listing.archive.display(), root.size);
+        if let (Some(size), Some(compressed)) = (listing.pak_size, listing.pak_compressed_size) {
+            print!(" (pak {size}, {compressed} compressed)");
+        }
+        println!();
+        if let Some(e) = &listing.error {
+            println!("  {e}");
+        }
+        root.print(1);
+    }
+}
@@ -0,0 +1,466 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::classify::Category;
+use crate::{collection, lookup};
+
+/// A mod at its place in a suggested load order.
+#[derive(Debug, Serialize)]
+pub struct OrderedMod {
+    pub position: usize,
+    pub id_mod: i64,
+    pub name_id: String,
+    pub id_modfile: Option<i64>,
+    pub category: Option<String>,
+    pub entries: usize,
+    /// Why the mod is placed where it is
+    pub reasons: Vec<String>,
+}
+
+/// Suggested load order, first to last. Mods later in the order take precedence over earlier ones
+/// on the paths they share.
+#[derive(Debug, Serialize)]
+pub struct LoadOrder {
+    pub mods: Vec<OrderedMod>,
+}
+
+impl std::fmt::Display for LoadOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, m) in self.mods.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{}. {} ({})",
+                m.position,
+                m.name_id,
+                m.category.as_deref().unwrap_or("unclassified")
+            )?;
+            for reason in &m.reasons {
+                write!(f, "\n   {reason}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Group a mod is placed in before conflicts are considered: frameworks first so the mods built
+/// on them can override their assets, audio last as sound packs replace whole banks that other
+/// mods rarely mean to change.
+fn tier(category: Option<Category>) -> u8 {
+    match category {
+        Some(Category::Framework) => 0,
+        Some(Category::Gameplay) => 1,
+        Some(Category::Ui | Category::Localization | Category::Mixed) | None => 2,
+        Some(Category::Model | Category::Visual) => 3,
+        Some(Category::Audio) => 4,
+    }
+}
+
+fn tier_reason(category: Option<Category>) -> &'static str {
+    match category {
+        Some(Category::Framework) => "framework, loaded first so the mods using it can override it",
+        Some(Category::Audio) => "audio pack, loaded last so other mods do not replace its sounds",
+        Some(Category::Gameplay) => "gameplay logic, loaded before cosmetic mods",
+        Some(Category::Model | Category::Visual) => "cosmetic, loaded after gameplay mods",
+        Some(Category::Ui | Category::Localization | Category::Mixed) | None => {
+            "loaded between gameplay and cosmetic mods"
+        }
+    }
+}
+
+struct Candidate {
+    id_mod: i64,
+    name_id: String,
+    id_modfile: Option<i64>,
+    category: Option<Category>,
+    paths: BTreeSet<String>,
+    /// Paths that are base game assets
+    vanilla: BTreeSet<String>,
+}
+
+impl Candidate {
+    /// Share of the mod's entries that other mods in the set also contain. A broad overhaul
+    /// shares a small part of itself, a targeted fix most of itself.
+    fn shared_share(&self, shared: &BTreeSet<&str>) -> f64 {
+        if self.paths.is_empty() {
+            return 0.0;
+        }
+        let n = self
+            .paths
+            .iter()
+            .filter(|p| shared.contains(p.as_str()))
+            .count();
+        n as f64 / self.paths.len() as f64
+    }
+}
+
+/// Mods to order: the members of `collection`, at their pinned modfiles, or the mods referenced
+/// by id or name_id at their current modfiles.
+async fn candidates(
+    pool: &AnyPool,
+    mods: &[String],
+    collection: Option<&str>,
+) -> Result<Vec<Candidate>> {
+    let mut selected = vec![];
+    if let Some(name) = collection {
+        for m in collection::members(pool, name).await? {
+            selected.push((m.id_mod, m.id_modfile));
+        }
+    }
+    for reference in mods {
+        selected.push((lookup::resolve_mod(pool, reference).await?, None));
+    }
+
+    let mut candidates: Vec<Candidate> = vec![];
+    for (id_mod, pinned) in selected {
+        if candidates.iter().any(|c| c.id_mod == id_mod) {
+            continue;
+        }
+        let (name_id, current, category): (String, Option<i64>, Option<String>) =
+            sqlx::query_as("SELECT name_id, id_modfile, category FROM mod WHERE id_mod = $1")
+                .bind(id_mod)
+                .fetch_one(pool)
+                .await?;
+        let id_modfile = pinned.or(current);
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT path_lower, COALESCE(vanilla_override, 0) FROM pack_file WHERE id_modfile = $1",
+        )
+        .bind(id_modfile)
+        .fetch_all(pool)
+        .await?;
+        let mut candidate = Candidate {
+            id_mod,
+            name_id,
+            id_modfile,
+            category: category.and_then(|c| clap::ValueEnum::from_str(&c, true).ok()),
+            paths: BTreeSet::new(),
+            vanilla: BTreeSet::new(),
+        };
+        for (path, vanilla_override) in rows {
+            if vanilla_override != 0 {
+                candidate.vanilla.insert(path.clone());
+            }
+            candidate.paths.insert(path);
+        }
+        candidates.push(candidate);
+    }
+    Ok(candidates)
+}
+
+/// Suggest an order for loading `mods`, or the members of `collection`, that keeps overrides
+/// intended: mods are grouped by what they contain, see [`tier`], and within a group broad mods
+/// come before targeted ones sharing paths with them, so the targeted changes win.
+pub async fn suggest(
+    pool: &AnyPool,
+    mods: &[String],
+    collection: Option<&str>,
+) -> Result<LoadOrder> {
+    let candidates = candidates(pool, mods, collection).await?;
+
+    let mut owners = BTreeMap::<&str, usize>::new();
+    for c in &candidates {
+        for path in &c.paths {
+            *owners.entry(path).or_default() += 1;
+        }
+    }
+    let shared = owners
+        .into_iter()
+        .filter(|(_, n)| *n > 1)
+        .map(|(path, _)| path)
+        .collect::<BTreeSet<_>>();
+    let shares = candidates
+        .iter()
+        .map(|c| c.shared_share(&shared))
+        .collect::<Vec<_>>();
+
+    let mut keyed = candidates
+        .into_iter()
+        .zip(shares)
+        .map(|(c, share)| ((tier(c.category), share), c))
+        .collect::<Vec<_>>();
+    keyed.sort_by(|((tier_a, share_a), a), ((tier_b, share_b), b)| {
+        tier_a
+            .cmp(tier_b)
+            .then(share_a.total_cmp(share_b))
+            .then_with(|| a.name_id.cmp(&b.name_id))
+    });
+    let ordered = keyed.into_iter().map(|(_, c)| c).collect::<Vec<_>>();
+
+    let mods = ordered
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let mut reasons = vec![tier_reason(c.category).to_string()];
+            for (j, other) in ordered.iter().enumerate() {
+                let common = c.paths.intersection(&other.paths).collect::<Vec<_>>();
+                if i == j || common.is_empty() {
+                    continue;
+                }
+                let vanilla = common.iter().filter(|p| c.vanilla.contains(**p)).count();
+                let detail = if vanilla > 0 {
+                    format!(" ({vanilla} base game assets)")
+                } else {
+                    String::new()
+                };
+                reasons.push(if j < i {
+                    format!(
+                        "overrides {} for {} shared paths{detail}",
+                        other.name_id,
+                        common.len()
+                    )
+                } else {
+                    format!(
+                        "overridden by {} for {} shared paths{detail}",
+                        other.name_id,
+                        common.len()
+                    )
+                });
+            }
+            OrderedMod {
+                position: i + 1,
+                id_mod: c.id_mod,
+                name_id: c.name_id.clone(),
+                id_modfile: c.id_modfile,
+                category: c.category.map(|c| c.as_str().to_string()),
+                entries: c.paths.len(),
+                reasons,
+            }
+        })
+        .collect();
+    Ok(LoadOrder { mods })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mod as (id, name_id, category, paths of the current modfile, `None` for a mod without
+    /// one). Paths starting with `!` override base game assets.
+    type IndexedMod<'a> = (i64, &'a str, Option<&'a str>, Option<&'a [&'a str]>);
+
+    /// An in-memory index holding `mods`.
+    async fn index(mods: &[IndexedMod<'_>]) -> AnyPool {
+        let pool = crate::db::connect("sqlite::memory:", true, true)
+            .await
+            .unwrap();
+        for (id_mod, name_id, category, paths) in mods {
+            sqlx::query(
+                "INSERT INTO mod(id_mod, name, name_id, summary, category) VALUES ($1, $2, $2, '', $3)",
+            )
+            .bind(id_mod)
+            .bind(name_id)
+            .bind(category)
+            .execute(&pool)
+            .await
+            .unwrap();
+            let Some(paths) = paths else {
+                continue;
+            };
+            let id_modfile = id_mod * 10;
+            sqlx::query(
+                "INSERT INTO modfile(id_modfile, id_mod, date_added, hash_md5, filename)
+                 VALUES ($1, $2, '', '', 'mod.zip')",
+            )
+            .bind(id_modfile)
+            .bind(id_mod)
+            .execute(&pool)
+            .await
+            .unwrap();
+            sqlx::query("UPDATE mod SET id_modfile = $1 WHERE id_mod = $2")
+                .bind(id_modfile)
+                .bind(id_mod)
+                .execute(&pool)
+                .await
+                .unwrap();
+            for path in *paths {
+                let (path, vanilla) = match path.strip_prefix('!') {
+                    Some(path) => (path, 1),
+                    None => (*path, 0),
+                };
+                sqlx::query(
+                    "INSERT INTO pack_file(id_modfile, path, path_no_extension, name, vanilla_override)
+                     VALUES ($1, $2, $2, $2, $3)",
+                )
+                .bind(id_modfile)
+                .bind(path)
+                .bind(vanilla)
+                .execute(&pool)
+                .await
+                .unwrap();
+            }
+        }
+        pool
+    }
+
+    fn names(order: &LoadOrder) -> Vec<&str> {
+        order.mods.iter().map(|m| m.name_id.as_str()).collect()
+    }
+
+    fn references(mods: &[&str]) -> Vec<String> {
+        mods.iter().map(|m| m.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn tiers_then_names_break_ties() {
+        let pool = index(&[
+            (
+                1,
+                "sound-pack",
+                Some("audio"),
+                Some(&["FSD/Content/Audio/Drill.bnk"]),
+            ),
+            (
+                2,
+                "beta-tweaks",
+                Some("gameplay"),
+                Some(&["FSD/Content/B.uasset"]),
+            ),
+            (
+                3,
+                "alpha-tweaks",
+                Some("gameplay"),
+                Some(&["FSD/Content/A.uasset"]),
+            ),
+            (
+                4,
+                "zeta-lib",
+                Some("framework"),
+                Some(&["FSD/Content/Lib.uasset"]),
+            ),
+            (5, "mystery", None, Some(&["FSD/Content/M.uasset"])),
+        ])
+        .await;
+        let order = suggest(&pool, &references(&["1", "2", "3", "4", "5"]), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            names(&order),
+            [
+                "zeta-lib",
+                "alpha-tweaks",
+                "beta-tweaks",
+                "mystery",
+                "sound-pack"
+            ]
+        );
+        assert_eq!(
+            order.mods.iter().map(|m| m.position).collect::<Vec<_>>(),
+            [1, 2, 3, 4, 5]
+        );
+        assert_eq!(order.mods[3].category, None);
+
+        // the order does not depend on the order mods are given in
+        let reversed = suggest(&pool, &references(&["5", "4", "3", "2", "1"]), None)
+            .await
+            .unwrap();
+        assert_eq!(names(&reversed), names(&order));
+    }
+
+    #[tokio::test]
+    async fn targeted_mods_override_broad_ones() {
+        let pool = index(&[
+            (
+                1,
+                "fix",
+                Some("gameplay"),
+                Some(&["!FSD/Content/WeaponsNTools/Drill.uasset"]),
+            ),
+            (
+                2,
+                "overhaul",
+                Some("gameplay"),
+                Some(&[
+                    // shared paths are compared ignoring case, like the game does
+                    "!fsd/content/weaponsntools/drill.uasset",
+                    "FSD/Content/WeaponsNTools/Shotgun.uasset",
+                    "FSD/Content/WeaponsNTools/Minigun.uasset",
+                ]),
+            ),
+        ])
+        .await;
+        let order = suggest(&pool, &references(&["fix", "overhaul"]), None)
+            .await
+            .unwrap();
+        assert_eq!(names(&order), ["overhaul", "fix"]);
+        assert_eq!(order.mods[0].entries, 3);
+        assert_eq!(
+            order.mods[0].reasons[1..],
+            ["overridden by fix for 1 shared paths (1 base game assets)"]
+        );
+        assert_eq!(
+            order.mods[1].reasons[1..],
+            ["overrides overhaul for 1 shared paths (1 base game assets)"]
+        );
+    }
+
+    #[tokio::test]
+    async fn overrides_in_a_cycle() {
+        // each mod shares a path with the next, the last with the first
+        let pool = index(&[
+            (1, "a", Some("gameplay"), Some(&["X", "Y"])),
+            (2, "b", Some("gameplay"), Some(&["Y", "Z"])),
+            (3, "c", Some("gameplay"), Some(&["Z", "X"])),
+        ])
+        .await;
+        let order = suggest(&pool, &references(&["c", "a", "b"]), None)
+            .await
+            .unwrap();
+        // nothing to tell them apart, the order is by name and still total
+        assert_eq!(names(&order), ["a", "b", "c"]);
+        assert_eq!(
+            order.mods[0].reasons[1..],
+            [
+                "overridden by b for 1 shared paths",
+                "overridden by c for 1 shared paths"
+            ]
+        );
+        assert_eq!(
+            order.mods[1].reasons[1..],
+            [
+                "overrides a for 1 shared paths",
+                "overridden by c for 1 shared paths"
+            ]
+        );
+        assert_eq!(
+            order.mods[2].reasons[1..],
+            [
+                "overrides a for 1 shared paths",
+                "overrides b for 1 shared paths"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_mods() {
+        let pool = index(&[
+            (
+                1,
+                "drill-fix",
+                Some("gameplay"),
+                Some(&["FSD/Content/Drill.uasset"]),
+            ),
+            (2, "unreleased", Some("gameplay"), None),
+        ])
+        .await;
+        let err = suggest(&pool, &references(&["drill-fix", "zzzzzzzzzzzz"]), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("zzzzzzzzzzzz"), "{err:#}");
+
+        // without a modfile there is nothing to override, a mod given twice is ordered once
+        let order = suggest(&pool, &references(&["unreleased", "drill-fix", "1"]), None)
+            .await
+            .unwrap();
+        assert_eq!(names(&order), ["drill-fix", "unreleased"]);
+        assert_eq!(order.mods[1].id_modfile, None);
+        assert_eq!(order.mods[1].entries, 0);
+        assert_eq!(order.mods[1].reasons.len(), 1);
+
+        assert!(suggest(&pool, &[], None).await.unwrap().mods.is_empty());
+    }
+}
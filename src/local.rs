@@ -0,0 +1,242 @@
+use anyhow::{bail, Result};
+use md5::{Digest, Md5};
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::{error, info};
+
+use std::path::{Path, PathBuf};
+
+use crate::{classify, download, game, store, PackFile};
+
+/// Synthetic mod local archives are stored under. mod.io never hands out id 0.
+pub const LOCAL_MOD: i64 = 0;
+
+#[derive(Debug, Serialize)]
+pub struct LocalEntry {
+    pub path: String,
+    pub extension: Option<String>,
+    pub hash: Option<String>,
+    pub asset_class: Option<String>,
+    /// Number of localized strings found in the entry
+    pub strings: usize,
+    /// Number of Wwise objects found in the entry
+    pub audio_objects: usize,
+    /// Number of objects of other packages the entry imports
+    pub references: usize,
+    /// Number of DataTable or CurveTable rows read from the entry
+    pub rows: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LocalAnalysis {
+    pub archive: PathBuf,
+    /// Mount point of the pak as stored, before normalization
+    pub mount_point: Option<String>,
+    pub entries: Vec<LocalEntry>,
+    /// Modfile the archive was stored as with `--store`
+    pub id_modfile: Option<i64>,
+    pub error: Option<String>,
+}
+
+pub fn print_analyses(analyses: &[LocalAnalysis]) {
+    for a in analyses {
+        for e in &a.entries {
+            let hash = e.hash.as_deref().unwrap_or("-");
+            print!("{} {} {hash}", a.archive.display(), e.path);
+            if let Some(class) = &e.asset_class {
+                print!(" {class}");
+            }
+            println!();
+        }
+        if let Some(id_modfile) = a.id_modfile {
+            println!("{} stored as modfile {id_modfile}", a.archive.display());
+        }
+        if let Some(e) = &a.error {
+            println!("{} {e}", a.archive.display());
+        }
+    }
+}
+
+fn is_archive(path: &Path) -> bool {
+    matches!(
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref(),
+        Some("zip" | "rar" | "7z" | "pak")
+    )
+}
+
+/// Whether `path` is an archive holding a pak rather than a bare pak.
+fn is_packed(path: &Path) -> bool {
+    matches!(
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref(),
+        Some("zip" | "rar" | "7z")
+    )
+}
+
+/// `path` itself if it is a file, otherwise every zip, rar, 7z and pak below it, sorted.
+pub fn find_archives(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        if !path.exists() {
+            bail!("{} does not exist", path.display());
+        }
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut archives = vec![];
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if is_archive(&path) {
+                archives.push(path);
+            }
+        }
+    }
+    archives.sort();
+    Ok(archives)
+}
+
+/// Run the pak analysis on a zip holding a pak, as mod.io serves them, or on a bare pak.
+fn analyze_archive(path: &Path) -> Result<(String, Vec<PackFile>)> {
+    let listing = crate::list_archive_files(path)?;
+    Ok((listing.mount_point, crate::pack_files(listing.entries)))
+}
+
+/// Analyze archives that did not come from mod.io: a zip, a pak or a directory searched for
+/// both. With `pool` set the zips are copied into the store and indexed as modfiles of the
+/// [`LOCAL_MOD`] so queries cover them like any mod, e.g. to check a mod for conflicts before
+/// uploading it.
+pub async fn analyze_path(pool: Option<&AnyPool>, path: &Path) -> Result<Vec<LocalAnalysis>> {
+    let mut analyses = vec![];
+    for archive in find_archives(path)? {
+        let result = {
+            let archive = archive.clone();
+            tokio::task::spawn_blocking(move || analyze_archive(&archive)).await?
+        };
+        let mut analysis = LocalAnalysis {
+            archive,
+            mount_point: None,
+            entries: vec![],
+            id_modfile: None,
+            error: None,
+        };
+        match result {
+            Ok((mount_point, files)) => {
+                analysis.entries = files
+                    .iter()
+                    .map(|f| LocalEntry {
+                        path: f.path.clone(),
+                        extension: f.extension.clone(),
+                        hash: f.hash.clone(),
+                        asset_class: f.asset_class.clone(),
+                        strings: f.strings.len(),
+                        audio_objects: f.audio.len(),
+                        references: f.references.len(),
+                        rows: f.rows.len(),
+                    })
+                    .collect();
+                if let Some(pool) = pool {
+                    if is_packed(&analysis.archive) {
+                        analysis.id_modfile = Some(
+                            store_archive(pool, &analysis.archive, &mount_point, files).await?,
+                        );
+                    } else {
+                        analysis.error = Some(
+                            "only zip, rar and 7z archives can be stored, zip the pak to store it"
+                                .into(),
+                        );
+                    }
+                }
+                analysis.mount_point = Some(mount_point);
+            }
+            Err(e) => {
+                error!(archive = %analysis.archive.display(), "Error analyzing: {e:#}");
+                analysis.error = Some(format!("{e:#}"));
+            }
+        }
+        analyses.push(analysis);
+    }
+    if let Some(pool) = pool {
+        classify::refresh(pool).await?;
+        game::flag_overrides(pool, false).await?;
+        store::refresh(pool).await?;
+    }
+    Ok(analyses)
+}
+
+/// Copy the zip at `path` into the store and index it as the current modfile of the
+/// [`LOCAL_MOD`]. Local modfiles get negative ids so they never collide with mod.io's, and storing
+/// the same archive again replaces its pack files instead of adding another modfile.
+async fn store_archive(
+    pool: &AnyPool,
+    path: &Path,
+    mount_point: &str,
+    files: Vec<PackFile>,
+) -> Result<i64> {
+    let data = tokio::fs::read(path).await?;
+    let md5 = format!("{:x}", Md5::digest(&data));
+    let object = download::archive_path(&md5);
+    if !object.exists() {
+        tokio::fs::create_dir_all(object.parent().unwrap()).await?;
+        tokio::fs::write(&object, &data).await?;
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO mod(id_mod, name, name_id, summary, description)
+         VALUES ($1, 'Local archives', 'local', 'Archives analyzed with analyze-path', NULL)
+         ON CONFLICT(id_mod) DO NOTHING",
+    )
+    .bind(LOCAL_MOD)
+    .execute(&mut *tx)
+    .await?;
+
+    let existing: Option<i64> =
+        sqlx::query_scalar("SELECT id_modfile FROM modfile WHERE id_mod = $1 AND hash_md5 = $2")
+            .bind(LOCAL_MOD)
+            .bind(&md5)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let id_modfile = match existing {
+        Some(id_modfile) => id_modfile,
+        None => {
+            let lowest: i64 = sqlx::query_scalar(
+                "SELECT CAST(COALESCE(MIN(id_modfile), 0) AS BIGINT) FROM modfile",
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            let id_modfile = lowest.min(0) - 1;
+            let filename = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            sqlx::query(
+                "INSERT INTO modfile(id_modfile, id_mod, date_added, hash_md5, filename, draft)
+                 VALUES ($1, $2, $3, $4, $5, 0)",
+            )
+            .bind(id_modfile)
+            .bind(LOCAL_MOD)
+            .bind(chrono::Utc::now().to_rfc3339())
+            .bind(&md5)
+            .bind(filename)
+            .execute(&mut *tx)
+            .await?;
+            id_modfile
+        }
+    };
+    sqlx::query("UPDATE mod SET id_modfile = $1 WHERE id_mod = $2")
+        .bind(id_modfile)
+        .bind(LOCAL_MOD)
+        .execute(&mut *tx)
+        .await?;
+    crate::replace_pack_files(&mut tx, id_modfile, mount_point, &files).await?;
+    tx.commit().await?;
+    info!(id_modfile, archive = %path.display(), "Stored local archive");
+    Ok(id_modfile)
+}
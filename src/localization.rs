@@ -0,0 +1,167 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::flatten::csv_field;
+
+/// Localized keys a mod has in one language.
+#[derive(Debug, Serialize)]
+pub struct LanguageKeys {
+    /// Culture of the locres files, `None` for the source text of string tables
+    pub language: Option<String>,
+    pub keys: i64,
+    /// Whether any of the files replaces one of the base game
+    pub vanilla_override: bool,
+}
+
+/// The languages of a mod's current modfile.
+#[derive(Debug, Serialize)]
+pub struct ModLocalization {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub category: Option<String>,
+    pub languages: Vec<LanguageKeys>,
+}
+
+pub fn print_summary(mods: &[ModLocalization]) {
+    for m in mods {
+        let languages = m
+            .languages
+            .iter()
+            .map(|l| {
+                let language = l.language.as_deref().unwrap_or("source");
+                let vanilla = if l.vanilla_override {
+                    " (replaces base game)"
+                } else {
+                    ""
+                };
+                format!("{language}: {} keys{vanilla}", l.keys)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "{} {} [{}] {languages}",
+            m.id_mod,
+            m.name_id,
+            m.category.as_deref().unwrap_or("unclassified")
+        );
+    }
+}
+
+type SummaryRow = (i64, String, Option<String>, Option<String>, i64, i64);
+
+/// Mods whose current modfile has localized strings, with their key count per language, only in
+/// `language` if given.
+pub async fn summary(pool: &AnyPool, language: Option<&str>) -> Result<Vec<ModLocalization>> {
+    let rows: Vec<SummaryRow> = sqlx::query_as(
+        "SELECT mod.id_mod, mod.name_id, mod.category, pack_file_string.language, COUNT(*),
+                CAST(MAX(COALESCE(pack_file.vanilla_override, 0)) AS BIGINT)
+         FROM pack_file_string
+         JOIN mod ON mod.id_modfile = pack_file_string.id_modfile
+         JOIN pack_file ON pack_file.id_modfile = pack_file_string.id_modfile
+                       AND pack_file.path = pack_file_string.path
+         WHERE $1 = '' OR pack_file_string.language = $1
+         GROUP BY mod.id_mod, mod.name_id, mod.category, pack_file_string.language
+         ORDER BY mod.name_id, pack_file_string.language",
+    )
+    .bind(language.unwrap_or_default())
+    .fetch_all(pool)
+    .await?;
+
+    let mut mods: Vec<ModLocalization> = vec![];
+    for (id_mod, name_id, category, language, keys, vanilla_override) in rows {
+        if mods.last().is_none_or(|m| m.id_mod != id_mod) {
+            mods.push(ModLocalization {
+                id_mod,
+                name_id,
+                category,
+                languages: vec![],
+            });
+        }
+        mods.last_mut().unwrap().languages.push(LanguageKeys {
+            language,
+            keys,
+            vanilla_override: vanilla_override != 0,
+        });
+    }
+    Ok(mods)
+}
+
+/// A localized string of a mod.
+#[derive(Debug, Serialize)]
+pub struct LocalizedString {
+    pub language: Option<String>,
+    pub path: String,
+    pub namespace: String,
+    pub key: String,
+    pub text: String,
+}
+
+pub fn print_strings(strings: &[LocalizedString]) {
+    for s in strings {
+        println!(
+            "{} {} {}/{}: {}",
+            s.language.as_deref().unwrap_or("source"),
+            s.path,
+            s.namespace,
+            s.key,
+            s.text
+        );
+    }
+}
+
+/// Write `strings` to a CSV file with a header row.
+pub fn write_csv(strings: &[LocalizedString], path: &Path) -> Result<()> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(out, "language,path,namespace,key,text")?;
+    for s in strings {
+        let fields = [
+            s.language.as_deref().unwrap_or_default(),
+            &s.path,
+            &s.namespace,
+            &s.key,
+            &s.text,
+        ];
+        let line = fields
+            .iter()
+            .map(|f| csv_field(f))
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(out, "{line}")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The localized strings of the current modfile of `id_mod`, only in `language` if given.
+pub async fn strings(
+    pool: &AnyPool,
+    id_mod: i64,
+    language: Option<&str>,
+) -> Result<Vec<LocalizedString>> {
+    let rows: Vec<(Option<String>, String, String, String, String)> = sqlx::query_as(
+        "SELECT pack_file_string.language, pack_file_string.path, pack_file_string.namespace,
+                pack_file_string.key, pack_file_string.text
+         FROM pack_file_string JOIN mod ON mod.id_modfile = pack_file_string.id_modfile
+         WHERE mod.id_mod = $1 AND ($2 = '' OR pack_file_string.language = $2)
+         ORDER BY pack_file_string.language, pack_file_string.path, pack_file_string.namespace,
+                  pack_file_string.key",
+    )
+    .bind(id_mod)
+    .bind(language.unwrap_or_default())
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(language, path, namespace, key, text)| LocalizedString {
+            language,
+            path,
+            namespace,
+            key,
+            text,
+        })
+        .collect())
+}
@@ -0,0 +1,43 @@
+use anyhow::{bail, Result};
+use fs2::FileExt;
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+
+const LOCK_FILE: &str = "drg-modio-index.lock";
+
+/// Exclusive lock held for the duration of commands that write to the index or the mods
+/// directory, so two of them can never run against the same working directory at once. The lock
+/// belongs to the open file handle and is released by the OS even if the process is killed.
+pub struct WriterLock {
+    _file: File,
+}
+
+impl WriterLock {
+    pub fn acquire(command: &str) -> Result<WriterLock> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            // keep the current holder's description readable until we own the lock
+            .truncate(false)
+            .open(LOCK_FILE)?;
+
+        if let Err(e) = file.try_lock_exclusive() {
+            if e.kind() != fs2::lock_contended_error().kind() {
+                return Err(e.into());
+            }
+            let mut holder = String::new();
+            file.read_to_string(&mut holder).ok();
+            bail!(
+                "cannot run {command}: another command is already writing to the index ({}). \
+                 Wait for it to finish or stop it first",
+                holder.trim()
+            );
+        }
+
+        file.set_len(0)?;
+        write!(file, "{command}, pid {}", std::process::id())?;
+        Ok(WriterLock { _file: file })
+    }
+}
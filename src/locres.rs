@@ -0,0 +1,208 @@
+//! Reader for compiled UE4 localization resources (`.locres`).
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::uasset::Reader;
+
+const MAGIC: [u8; 16] = [
+    0x0E, 0x14, 0x74, 0x75, 0x67, 0x4A, 0x03, 0xFC, 0x4A, 0x15, 0x90, 0x9D, 0xC3, 0x37, 0x7F, 0x1B,
+];
+
+const VERSION_COMPACT: u8 = 1;
+const VERSION_OPTIMIZED_CRC32: u8 = 2;
+const VERSION_LATEST: u8 = 3;
+
+#[derive(Debug, Serialize)]
+pub struct LocresEntry {
+    pub namespace: String,
+    pub key: String,
+    pub text: String,
+}
+
+/// Read every localized string in a locres file.
+pub fn parse(data: &[u8]) -> Result<Vec<LocresEntry>> {
+    let mut r = Reader::new(data);
+    let version = if data.starts_with(&MAGIC) {
+        r.bytes(16)?;
+        r.u8()?
+    } else {
+        0
+    };
+    if version > VERSION_LATEST {
+        bail!("unsupported locres version {version}");
+    }
+
+    let mut strings = vec![];
+    if version >= VERSION_COMPACT {
+        let offset = r.i64()?;
+        if offset != -1 {
+            let resume = r.position();
+            r.seek(usize::try_from(offset)?)?;
+            let count = r.i32()?;
+            for _ in 0..count.max(0) {
+                strings.push(r.fstring()?);
+                if version >= VERSION_OPTIMIZED_CRC32 {
+                    r.i32()?; // reference count
+                }
+            }
+            r.seek(resume)?;
+        }
+    }
+    if version >= VERSION_OPTIMIZED_CRC32 {
+        r.u32()?; // total entry count
+    }
+
+    let mut entries = vec![];
+    let namespaces = r.u32()?;
+    for _ in 0..namespaces {
+        if version >= VERSION_OPTIMIZED_CRC32 {
+            r.u32()?; // namespace hash
+        }
+        let namespace = r.fstring()?;
+        let keys = r.u32()?;
+        for _ in 0..keys {
+            if version >= VERSION_OPTIMIZED_CRC32 {
+                r.u32()?; // key hash
+            }
+            let key = r.fstring()?;
+            r.u32()?; // source string hash
+            let text = if version >= VERSION_COMPACT {
+                let index = r.i32()?;
+                match usize::try_from(index).ok().and_then(|i| strings.get(i)) {
+                    Some(text) => text.clone(),
+                    None => bail!("localized string index {index} out of range"),
+                }
+            } else {
+                r.fstring()?
+            };
+            entries.push(LocresEntry {
+                namespace: namespace.clone(),
+                key,
+                text,
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Culture of a locres file from its path, e.g. `de` for
+/// `FSD/Content/Localization/Game/de/Game.locres`.
+pub fn culture(path: &str) -> Option<&str> {
+    if !path.ends_with(".locres") {
+        return None;
+    }
+    let parts = path.split('/').collect::<Vec<_>>();
+    let i = parts
+        .iter()
+        .position(|p| p.eq_ignore_ascii_case("Localization"))?;
+    // target, culture and file follow
+    (parts.len() == i + 4).then(|| parts[i + 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &[u8] = include_bytes!("../tests/fixtures/assets/Game.locres");
+    const LEGACY: &[u8] = include_bytes!("../tests/fixtures/assets/Legacy.locres");
+
+    fn summary(entries: &[LocresEntry]) -> Vec<(&str, &str, &str)> {
+        entries
+            .iter()
+            .map(|e| (e.namespace.as_str(), e.key.as_str(), e.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn optimized() {
+        assert_eq!(
+            summary(&parse(GAME).unwrap()),
+            [
+                ("", "MissionControl_Greeting", "Rock and Stone!"),
+                ("", "Shop_Title", "Gefährliche Waren"),
+                ("UI", "Menu_Play", "Spielen"),
+                ("UI", "Menu_Quit", "Beenden"),
+                ("UI", "Menu_Greek", "Καλημέρα"),
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy() {
+        assert_eq!(
+            summary(&parse(LEGACY).unwrap()),
+            [("UI", "Menu_Play", "Play")]
+        );
+    }
+
+    #[test]
+    fn cultures() {
+        assert_eq!(
+            culture("FSD/Content/Localization/Game/de/Game.locres"),
+            Some("de")
+        );
+        assert_eq!(
+            culture("FSD/Content/localization/Game/pt-BR/Game.locres"),
+            Some("pt-BR")
+        );
+        assert_eq!(culture("FSD/Content/Localization/Game/Game.locres"), None);
+        assert_eq!(culture("FSD/Content/Localization/Game/de/Game.txt"), None);
+        assert_eq!(culture("FSD/Content/Game.locres"), None);
+    }
+
+    #[test]
+    fn truncated() {
+        for len in 0..GAME.len() {
+            assert!(parse(&GAME[..len]).is_err(), "{len}");
+        }
+        for len in 0..LEGACY.len() {
+            assert!(parse(&LEGACY[..len]).is_err(), "{len}");
+        }
+    }
+
+    #[test]
+    fn garbage() {
+        let mut state = 0xd1b54a32d192ed03u64;
+        for _ in 0..200 {
+            let mut data = MAGIC.to_vec();
+            data.push(VERSION_LATEST);
+            data.extend((0..256).map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            }));
+            assert!(parse(&data).is_err());
+            // without the magic, read as the oldest version
+            assert!(parse(&data[17..]).is_err());
+        }
+        let mut data = MAGIC.to_vec();
+        data.push(VERSION_LATEST + 1);
+        assert!(parse(&data).is_err());
+    }
+
+    #[test]
+    fn out_of_range() {
+        let header = 16 + 1;
+        // string array past the end, or before the start of the file
+        for offset in [i64::MAX, GAME.len() as i64 + 1, -2] {
+            let mut data = GAME.to_vec();
+            data[header..header + 8].copy_from_slice(&offset.to_le_bytes());
+            assert!(parse(&data).is_err(), "{offset}");
+        }
+
+        // a string count in the billions
+        let offset = i64::from_le_bytes(GAME[header..header + 8].try_into().unwrap()) as usize;
+        let mut data = GAME.to_vec();
+        data[offset..offset + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(parse(&data).is_err());
+
+        // an entry pointing past the string array
+        let mut data = GAME.to_vec();
+        let index = offset - 4;
+        data[index..offset].copy_from_slice(&99i32.to_le_bytes());
+        let err = parse(&data).unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{err:#}");
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use indicatif::MultiProgress;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Writes log lines to stderr without tearing the progress bars drawn by `multi_bar`.
+#[derive(Clone)]
+struct ProgressWriter(MultiProgress);
+
+impl Write for ProgressWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.suspend(|| io::stderr().write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Install the global tracing subscriber.
+///
+/// `verbosity` shifts the console level from the default of INFO: positive values for each `-v`,
+/// negative for each `-q`. `RUST_LOG` takes precedence when set. When `log_file` is given, events
+/// are additionally written to it as JSON lines at DEBUG or finer regardless of console verbosity.
+pub fn init(multi_bar: &MultiProgress, verbosity: i8, log_file: Option<&Path>) -> Result<()> {
+    let level = match verbosity {
+        i8::MIN..=-2 => LevelFilter::ERROR,
+        -1 => LevelFilter::WARN,
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        2..=i8::MAX => LevelFilter::TRACE,
+    };
+    let console_filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+
+    let multi_bar = multi_bar.clone();
+    let console = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .with_writer(move || ProgressWriter(multi_bar.clone()))
+        .with_filter(console_filter);
+
+    let file = log_file
+        .map(|path| -> Result<_> {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            Ok(tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(std::sync::Mutex::new(file))
+                .with_filter(level.max(LevelFilter::DEBUG)))
+        })
+        .transpose()?;
+
+    tracing_subscriber::registry()
+        .with(console)
+        .with(file)
+        .init();
+    Ok(())
+}
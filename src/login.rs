@@ -0,0 +1,73 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::api;
+
+#[derive(Debug, Serialize)]
+pub struct LoggedIn {
+    /// Where the access token was stored
+    pub token_path: PathBuf,
+    /// Username of the account, if it could be fetched
+    pub user: Option<String>,
+}
+
+impl std::fmt::Display for LoggedIn {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.user {
+            Some(user) => write!(f, "Logged in as {user}")?,
+            None => write!(f, "Logged in")?,
+        }
+        write!(f, ", token stored in {}", self.token_path.display())
+    }
+}
+
+/// Print `prompt` and read a line from stdin.
+fn prompt(prompt: &str) -> Result<String> {
+    print!("{prompt}");
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    if std::io::stdin().read_line(&mut line)? == 0 {
+        bail!("no input");
+    }
+    Ok(line.trim().to_string())
+}
+
+/// Log in with mod.io's email flow: mod.io mails a security code to `email`, which is exchanged
+/// for an access token that is stored at [`api::token_path`] for later runs.
+pub async fn login(email: Option<String>) -> Result<LoggedIn> {
+    let modio = api::key_client()?;
+
+    let email = match email {
+        Some(email) => email,
+        None => prompt("mod.io account email: ")?,
+    };
+    modio
+        .auth()
+        .request_code(&email)
+        .await
+        .context("failed to request a security code")?;
+    let code = prompt(&format!("Security code sent to {email}: "))?;
+    let credentials = modio
+        .auth()
+        .security_code(&code)
+        .await
+        .context("failed to exchange the security code")?;
+    let token = credentials
+        .token
+        .as_ref()
+        .context("mod.io did not return an access token")?;
+    let token_path = api::save_token(&token.value)?;
+
+    let user = modio
+        .with_credentials(credentials)
+        .user()
+        .current()
+        .await
+        .ok()
+        .flatten()
+        .map(|user| user.username);
+    Ok(LoggedIn { token_path, user })
+}
@@ -0,0 +1,308 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::info;
+
+/// Most candidates listed when a reference is ambiguous.
+const MAX_CANDIDATES: usize = 10;
+
+/// Resolve a user supplied mod reference, either a numeric mod id or a `name_id`, to a mod id.
+/// Anything else is matched loosely against the names and name_ids of indexed mods, see
+/// [`fuzzy_matches`], and resolves if it matches a single mod.
+pub async fn resolve_mod(pool: &AnyPool, reference: &str) -> Result<i64> {
+    if let Some(id) = find_mod(pool, reference).await? {
+        return Ok(id);
+    }
+    let candidates = fuzzy_matches(pool, reference).await?;
+    match candidates.as_slice() {
+        [] => bail!("no indexed mod matches {reference:?}"),
+        [only] => {
+            info!(reference, name_id = only.name_id, "Resolved mod by name");
+            Ok(only.id_mod)
+        }
+        // a name differing only in case or punctuation beats mere typos
+        [best, second, ..] if best.distance == 0 && second.distance > 0 => {
+            info!(reference, name_id = best.name_id, "Resolved mod by name");
+            Ok(best.id_mod)
+        }
+        candidates => {
+            let list = candidates
+                .iter()
+                .take(MAX_CANDIDATES)
+                .map(|c| format!("\n  {} {} ({})", c.id_mod, c.name_id, c.name))
+                .collect::<String>();
+            bail!("{reference:?} matches several mods, pass one of their ids or name_ids:{list}")
+        }
+    }
+}
+
+/// The mod with the numeric id or exact `name_id` given by `reference`, if indexed. A name_id a
+/// mod had before it was renamed resolves to that mod unless another mod has taken it since.
+pub async fn find_mod(pool: &AnyPool, reference: &str) -> Result<Option<i64>> {
+    if let Ok(id) = reference.parse::<i64>() {
+        return Ok(
+            sqlx::query_scalar("SELECT id_mod FROM mod WHERE id_mod = $1")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?,
+        );
+    }
+    let found = sqlx::query_scalar("SELECT id_mod FROM mod WHERE name_id = $1")
+        .bind(reference)
+        .fetch_optional(pool)
+        .await?;
+    if found.is_some() {
+        return Ok(found);
+    }
+    let renamed: Option<(i64, String)> = sqlx::query_as(
+        "SELECT mod.id_mod, mod.name_id FROM mod_alias JOIN mod ON mod.id_mod = mod_alias.id_mod
+         WHERE mod_alias.name_id = $1",
+    )
+    .bind(reference)
+    .fetch_optional(pool)
+    .await?;
+    Ok(renamed.map(|(id_mod, name_id)| {
+        info!(reference, name_id, "Resolved renamed mod");
+        id_mod
+    }))
+}
+
+/// Keep `old_name_id` as an alias of `id_mod`, which was just renamed from it.
+pub async fn record_alias(
+    conn: &mut sqlx::AnyConnection,
+    id_mod: i64,
+    old_name_id: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO mod_alias(name_id, id_mod, date_renamed) VALUES ($1, $2, $3)
+         ON CONFLICT(name_id) DO UPDATE SET id_mod = excluded.id_mod, date_renamed = excluded.date_renamed",
+    )
+    .bind(old_name_id)
+    .bind(id_mod)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// An indexed mod whose name or name_id resembles a query.
+#[derive(Debug, Serialize)]
+pub struct Candidate {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub name: String,
+    /// Edit distance between the query and the closer of name and name_id, ignoring case and
+    /// punctuation
+    pub distance: usize,
+}
+
+/// Indexed mods whose name or name_id is within a few typos of `query`, or contains it, ignoring
+/// case and punctuation so `better spawns` matches `Better-Spawns`. Closest first.
+pub async fn fuzzy_matches(pool: &AnyPool, query: &str) -> Result<Vec<Candidate>> {
+    let query = normalize(query);
+    if query.is_empty() {
+        return Ok(vec![]);
+    }
+    let mods: Vec<(i64, String, String)> = sqlx::query_as("SELECT id_mod, name_id, name FROM mod")
+        .fetch_all(pool)
+        .await?;
+    let mut candidates = mods
+        .into_iter()
+        .filter_map(|(id_mod, name_id, name)| {
+            let distance = [&name_id, &name]
+                .into_iter()
+                .filter_map(|candidate| distance(&query, &normalize(candidate)))
+                .min()?;
+            Some(Candidate {
+                id_mod,
+                name_id,
+                name,
+                distance,
+            })
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| a.distance.cmp(&b.distance).then(a.id_mod.cmp(&b.id_mod)));
+    Ok(candidates)
+}
+
+/// Lowercase alphanumerics of `s`.
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Edit distance from `query` to `candidate` if close enough to suggest: about one typo per four
+/// characters, or any candidate containing a query of at least three characters.
+fn distance(query: &str, candidate: &str) -> Option<usize> {
+    let distance = levenshtein(query, candidate);
+    let allowed = (query.chars().count() / 4).max(1);
+    let contained = query.chars().count() >= 3 && candidate.contains(query);
+    (distance <= allowed || contained).then_some(distance)
+}
+
+/// Number of single character insertions, deletions and substitutions turning `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Resolve a modfile of `id_mod` given either its numeric modfile id or its version string. If
+/// several modfiles share the version, the most recently added one is used.
+pub async fn resolve_modfile(pool: &AnyPool, id_mod: i64, reference: &str) -> Result<i64> {
+    let found: Option<i64> = if let Ok(id) = reference.parse::<i64>() {
+        sqlx::query_scalar("SELECT id_modfile FROM modfile WHERE id_mod = $1 AND id_modfile = $2")
+            .bind(id_mod)
+            .bind(id)
+            .fetch_optional(pool)
+            .await?
+    } else {
+        None
+    };
+    let found = match found {
+        Some(id) => Some(id),
+        None => {
+            sqlx::query_scalar(
+                "SELECT id_modfile FROM modfile WHERE id_mod = $1 AND version = $2
+                 ORDER BY date_added DESC, id_modfile DESC LIMIT 1",
+            )
+            .bind(id_mod)
+            .bind(reference)
+            .fetch_optional(pool)
+            .await?
+        }
+    };
+    match found {
+        Some(id) => Ok(id),
+        None => bail!("mod {id_mod} has no indexed modfile with id or version {reference:?}"),
+    }
+}
+
+/// The current modfile of a mod.
+pub async fn current_modfile(pool: &AnyPool, id_mod: i64) -> Result<i64> {
+    let current: Option<i64> = sqlx::query_scalar("SELECT id_modfile FROM mod WHERE id_mod = $1")
+        .bind(id_mod)
+        .fetch_one(pool)
+        .await?;
+    current.with_context(|| format!("mod {id_mod} has no current modfile"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An in-memory index holding `mods` as (id, name_id, name).
+    async fn index(mods: &[(i64, &str, &str)]) -> AnyPool {
+        let pool = crate::db::connect("sqlite::memory:", true, true)
+            .await
+            .unwrap();
+        for (id_mod, name_id, name) in mods {
+            sqlx::query("INSERT INTO mod(id_mod, name, name_id, summary) VALUES ($1, $2, $3, '')")
+                .bind(id_mod)
+                .bind(name)
+                .bind(name_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        pool
+    }
+
+    #[test]
+    fn levenshtein_distances() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("abc", ""), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("spawns", "spawns"), 0);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("spanws", "spawns"), 2);
+        assert_eq!(levenshtein("flaw", "lawn"), 2);
+        // counts characters, not bytes
+        assert_eq!(levenshtein("größe", "grosse"), 3);
+    }
+
+    #[test]
+    fn distance_allows_a_typo_per_four_characters() {
+        assert_eq!(normalize("Better-Spawns 2!"), "betterspawns2");
+        assert_eq!(distance("ab", "ac"), Some(1));
+        assert_eq!(distance("ab", "cd"), None);
+        assert_eq!(distance("spawns", "spanws"), None);
+        assert_eq!(distance("betterspawns", "betterspanws"), Some(2));
+        assert_eq!(distance("betterspawns", "bettrspnws"), Some(3));
+        assert_eq!(distance("betterspawns", "btrspwns"), None);
+        // contained queries of at least three characters match however long the name is
+        assert_eq!(distance("spawn", "betterspawns"), Some(7));
+        assert_eq!(distance("sp", "betterspawns"), None);
+    }
+
+    #[tokio::test]
+    async fn resolve_by_id_and_name_id() {
+        let pool = index(&[(1, "better-spawns", "Better Spawns"), (2, "dwarf", "Dwarf")]).await;
+        assert_eq!(resolve_mod(&pool, "2").await.unwrap(), 2);
+        assert_eq!(resolve_mod(&pool, "better-spawns").await.unwrap(), 1);
+        assert!(resolve_mod(&pool, "3").await.is_err());
+
+        let mut conn = pool.acquire().await.unwrap();
+        record_alias(&mut conn, 1, "old-spawns").await.unwrap();
+        drop(conn);
+        assert_eq!(resolve_mod(&pool, "old-spawns").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn resolve_fuzzy() {
+        let pool = index(&[
+            (1, "better-spawns", "Better Spawns"),
+            (2, "better-spawns-lite", "Better Spawns Lite"),
+            (3, "mining-speed", "Faster Mining"),
+        ])
+        .await;
+        // a single match
+        assert_eq!(resolve_mod(&pool, "faster minign").await.unwrap(), 3);
+        // differing in case and punctuation only beats the mod containing it
+        assert_eq!(resolve_mod(&pool, "Better Spawns").await.unwrap(), 1);
+        // contained in both, neither exact
+        let err = resolve_mod(&pool, "spawns").await.unwrap_err().to_string();
+        assert!(err.contains("matches several mods"), "{err}");
+        assert!(err.contains("better-spawns-lite"), "{err}");
+        assert!(resolve_mod(&pool, "unrelated").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_fuzzy_ties_are_ambiguous() {
+        let pool = index(&[
+            (1, "double-xp", "Double XP"),
+            (2, "double_xp", "Double-XP"),
+            (3, "triple-xp", "Triple XP"),
+        ])
+        .await;
+        // two exact matches after normalizing
+        let err = resolve_mod(&pool, "double xp")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("1 double-xp"), "{err}");
+        assert!(err.contains("2 double_xp"), "{err}");
+        assert!(!err.contains("triple-xp"), "{err}");
+
+        // equally close typos
+        let candidates = fuzzy_matches(&pool, "doublexq").await.unwrap();
+        assert_eq!(
+            candidates
+                .iter()
+                .map(|c| (c.id_mod, c.distance))
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (2, 1)]
+        );
+        assert!(resolve_mod(&pool, "doublexq").await.is_err());
+    }
+}
@@ -1,92 +1,1811 @@
-use modio::download::DownloadAction;
-use modio::filter::In;
-use modio::{Credentials, Modio};
+use modio::Modio;
 
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use sqlx::AnyPool;
 
 use clap::{Parser, Subcommand};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use dotenv::dotenv;
-use futures::TryStreamExt;
 use std::env;
-use tokio::io::AsyncWriteExt;
 
 use std::fs;
-use std::io::Read;
+use std::io::{Read, Seek};
 use std::path::Path;
 
 use indicatif::ProgressBar;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use tracing::{error, info, info_span, warn, Instrument};
+
+mod api;
+mod approved;
+mod archive;
+mod asset_refs;
+mod audio;
+mod audit;
+mod channel;
+mod check;
+mod checkpoint;
+mod classify;
+mod collection;
+mod comments;
+mod conflicts;
+mod daemon;
+mod data_table;
+mod db;
+mod diff;
+mod download;
+mod drafts;
+mod duplicates;
+mod events;
+mod extract;
+mod feed;
+mod flatten;
+mod game;
+mod gc;
+mod glob;
+mod graphql;
+mod grep;
+mod history;
+mod install;
+mod labels;
+mod lint;
+mod listing;
+mod load_order;
+mod local;
+mod localization;
+mod lock;
+mod locres;
+mod logging;
+mod login;
+mod lookup;
+mod media;
+mod metadata;
+mod metrics;
+#[cfg(feature = "mock-api")]
+mod mock;
+mod mod_event;
+mod modpack;
+mod mount;
+mod notify;
+mod output;
+mod pak_source;
+mod plan;
+mod platform;
+mod priority;
+mod profile;
+mod query;
+mod reconcile;
+mod remote;
+mod server;
+mod stats;
+mod steam;
+mod store;
+mod tags;
+mod trash;
+mod uasset;
+mod usmap;
+mod verify;
+mod virus;
+mod webhook;
+mod wwise;
+
+use lock::WriterLock;
+use output::Output;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 #[clap(propagate_version = true)]
+#[clap(arg_required_else_help = true)]
 struct Cli {
+    /// Emit results and errors as JSON on stdout instead of human readable text
+    #[clap(long, global = true)]
+    json: bool,
+
+    /// Log more detail, repeat for even more
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Log less, repeat to only show errors
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    quiet: u8,
+
+    /// Also write logs as JSON lines to this file
+    #[clap(long, global = true, value_parser)]
+    log_file: Option<std::path::PathBuf>,
+
+    /// Emit progress events as JSON lines for dashboards wrapping the command: `stderr`, which
+    /// hides the progress bars, or `unix:<path>` to connect to a listening unix socket
+    #[clap(long, global = true, value_parser)]
+    events: Option<events::Sink>,
+
+    /// Download modfiles even if mod.io's virus scan flagged them. They are indexed without pack
+    /// files otherwise
+    #[clap(long, global = true)]
+    allow_flagged: bool,
+
+    /// Send mod.io API requests to this base URL instead of https://api.mod.io/v1, e.g. a
+    /// `mock-api` server. Credentials are optional then
+    #[clap(long, global = true, value_name = "URL")]
+    api_base: Option<String>,
+
+    /// Print the mods whose current modfile contains this asset path on a single line, for
+    /// shell pipelines. Exits with 1 if there are none
+    #[clap(long, value_name = "PATH", conflicts_with = "query_mod")]
+    query_asset: Option<String>,
+
+    /// Print a single line summary of a mod given its id or name_id, for shell pipelines
+    #[clap(long, value_name = "MOD")]
+    query_mod: Option<String>,
+
     #[clap(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    GetMods,
+    GetMods {
+        /// Report what would be inserted, updated and downloaded without changing anything
+        #[clap(long)]
+        dry_run: bool,
+        #[clap(flatten)]
+        options: SyncOptions,
+        #[clap(flatten)]
+        filters: api::ModFilters,
+    },
     UpdateModFilesLocal,
+    /// Run the full pipeline: sync mod metadata, download new modfiles and analyze any modfiles
+    /// that are missing pack files
+    Sync {
+        /// Report what would be inserted, updated and downloaded without changing anything
+        #[clap(long)]
+        dry_run: bool,
+        #[clap(flatten)]
+        options: SyncOptions,
+    },
+    /// Run the full pipeline for only the mods the logged in user is subscribed to, for a personal
+    /// mirror rather than the whole catalog. Needs `login` or MODIO_ACCESS_TOKEN
+    SyncSubscribed {
+        /// Report what would be inserted, updated and downloaded without changing anything
+        #[clap(long)]
+        dry_run: bool,
+        #[clap(flatten)]
+        options: SyncOptions,
+    },
+    /// List the entries of archives: the given zips, rars, 7zs and paks, those below given
+    /// directories or matching glob patterns such as 'archive/**/*.zip', or every stored archive by
+    /// default
     ListFiles {
         #[clap(value_parser)]
-        zip: Option<std::path::PathBuf>,
+        paths: Vec<String>,
+        /// Only list the current modfiles of mods in this collection
+        #[clap(long, value_parser, conflicts_with = "paths")]
+        collection: Option<String>,
+        /// plain prints an archive and entry path per line, csv and json add entry sizes and
+        /// tree shows the directories of each archive with their total sizes
+        #[clap(long, value_enum, default_value_t = listing::Format::Plain)]
+        format: listing::Format,
+    },
+    /// Manage the database schema. Pending migrations are otherwise applied automatically on startup
+    Migrate {
+        #[clap(subcommand)]
+        action: MigrateAction,
+    },
+    /// Maintain the database
+    Db {
+        #[clap(subcommand)]
+        action: DbAction,
+    },
+    /// Re-fetch modfiles from mod.io and report any whose hash or metadata differ from the index
+    AuditUpstream {
+        /// Only audit this many randomly chosen modfiles instead of all of them
+        #[clap(long, value_parser)]
+        sample: Option<i64>,
+    },
+    /// List every indexed modfile version of a mod with dates and changelogs
+    History {
+        /// Mod id or name_id
+        #[clap(value_parser)]
+        r#mod: String,
+    },
+    /// List the changes syncs observed to a mod's status, visibility, name_id and current
+    /// modfile, oldest first
+    ModEvents {
+        /// Mod id or name_id
+        #[clap(value_parser)]
+        r#mod: String,
+    },
+    /// Compare the assets of two versions of a mod
+    Diff {
+        /// Mod id or name_id
+        #[clap(value_parser)]
+        r#mod: String,
+        /// Modfile id or version to compare from, defaults to the version before --to
+        #[clap(long, value_parser)]
+        from: Option<String>,
+        /// Modfile id or version to compare to, defaults to the current modfile
+        #[clap(long, value_parser)]
+        to: Option<String>,
+    },
+    /// Sync a single mod: its metadata, current modfile, download and pak analysis
+    Fetch {
+        /// Mod id or name_id
+        #[clap(value_parser)]
+        r#mod: String,
+        /// Delete the archive again once it is analyzed
+        #[clap(long)]
+        no_keep: bool,
+    },
+    /// Keep the index up to date by running a sync on an interval until stopped with SIGINT or
+    /// SIGTERM
+    Daemon {
+        /// Time between the start of syncs, e.g. 90s, 30m, 6h or 1d
+        #[clap(long, value_parser = daemon::parse_duration, default_value = "1h")]
+        interval: std::time::Duration,
+        /// Serve Prometheus metrics at /metrics on this address
+        #[clap(long, value_parser)]
+        metrics: Option<std::net::SocketAddr>,
+        #[clap(flatten)]
+        options: SyncOptions,
+    },
+    /// Serve a GraphQL API over the index at /graphql until stopped with SIGINT or SIGTERM.
+    /// Opening /graphql in a browser shows GraphiQL to explore the schema, the Atom feed of new
+    /// modfiles is served at /feed.xml and Prometheus metrics at /metrics
+    Serve {
+        /// Address to listen on
+        #[clap(long, value_parser, default_value = "127.0.0.1:8080")]
+        listen: std::net::SocketAddr,
+    },
+    /// Rebuild the denormalized mod_flat table used by spreadsheets and BI tools. Sync also
+    /// rebuilds it after every run
+    Flatten {
+        /// Also write the table to this CSV file
+        #[clap(long, value_parser)]
+        csv: Option<std::path::PathBuf>,
+    },
+    /// Write an Atom feed of recently added and updated mods with their changelogs
+    Feed {
+        /// Number of modfiles to include
+        #[clap(long, value_parser, default_value_t = 50)]
+        limit: i64,
+        /// File to write, stdout if omitted
+        #[clap(short, long, value_parser)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Extract the files of a mod's pak, optionally converting known formats to JSON and CSV
+    Extract {
+        /// Mod id or name_id
+        #[clap(value_parser)]
+        r#mod: String,
+        /// Modfile id or version to extract, defaults to the current modfile
+        #[clap(long, value_parser)]
+        modfile: Option<String>,
+        /// Directory to extract into, defaults to extracted/<name_id>
+        #[clap(short, long, value_parser)]
+        output: Option<std::path::PathBuf>,
+        /// Write a .json summary next to each package (imports, exports and DataTable rows) and
+        /// a .csv next to each locres file
+        #[clap(long)]
+        convert: bool,
+    },
+    /// Check that every modfile's archive is stored, every stored archive belongs to a modfile and
+    /// every stored archive has been analyzed
+    Verify {
+        /// Delete extra archives, download missing ones again and analyze what is unanalyzed
+        #[clap(long)]
+        fix: bool,
+    },
+    /// Download the archive of every indexed modfile missing from the mods directory, e.g. to
+    /// restore it from the database after losing it
+    FetchMissing,
+    /// Remove rows of modfiles that no longer exist, modfiles of mods deleted from mod.io more
+    /// than DELETED_MOD_RETENTION (default 90d) ago and API quota samples older than
+    /// QUOTA_HISTORY (default 30d)
+    Gc {
+        /// Only report what would be removed
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// List what destructive commands moved to the trash, or restore an entry given its id.
+    /// Entries older than TRASH_RETENTION (default 30d) are purged after every sync
+    Restore {
+        /// Trash entry to restore
+        #[clap(value_parser)]
+        id: Option<i64>,
+    },
+    /// List mods matching a query, or every mod approved for `--platform` when given alone
+    #[clap(
+        group(clap::ArgGroup::new("query")),
+        group(clap::ArgGroup::new("selection").required(true).multiple(true))
+    )]
+    Query {
+        /// Mods whose content was classified as this kind
+        #[clap(long, value_enum, groups = ["query", "selection"])]
+        category: Option<classify::Category>,
+        /// Localized strings of current modfiles containing this text, case insensitive
+        #[clap(long, value_parser, groups = ["query", "selection"])]
+        text: Option<String>,
+        /// Mods whose current modfile has entries with this extension, e.g. wem, bnk or ucas
+        #[clap(long, value_parser, groups = ["query", "selection"])]
+        extension: Option<String>,
+        /// Mods in this imported approved list
+        #[clap(long, value_parser, groups = ["query", "selection"])]
+        in_list: Option<String>,
+        /// Mods whose name or name_id resembles this, tolerating typos, closest first
+        #[clap(long, value_parser, groups = ["query", "selection"])]
+        name: Option<String>,
+        /// Mods with this metadata key, or KEY=VALUE for mods with that value
+        #[clap(long, value_name = "KEY[=VALUE]", groups = ["query", "selection"])]
+        metadata: Option<String>,
+        /// Mods whose current modfile has entries whose path contains this text, case insensitive
+        #[clap(long, value_parser, groups = ["query", "selection"])]
+        path: Option<String>,
+        #[clap(flatten)]
+        filter: query::ModFilter,
+    },
+    /// List the whole catalog and report where the index has drifted from it (missed mods,
+    /// deletions, replaced or re-uploaded modfiles) without downloading or changing anything.
+    /// Meant to run nightly next to frequent syncs
+    Reconcile,
+    /// Search the text-like entries (ini, json, cfg, ...) and localized strings of every mod's
+    /// current modfile for lines matching a regular expression. Exits with 1 if nothing matches
+    Grep {
+        /// Regular expression to search for
+        #[clap(value_parser)]
+        pattern: String,
+        /// Match case insensitively
+        #[clap(short, long)]
+        ignore_case: bool,
+    },
+    /// List mods whose current modfiles are the same archive or have identical contents, e.g.
+    /// re-uploads of another author's mod
+    Duplicates,
+    /// List modfiles flagged by mod.io's virus scan and whether they were downloaded anyway
+    Flagged,
+    /// Show the size of the index and the mod.io API quota remaining as of the last request
+    Stats,
+    /// Check archives for packaging problems: paks not mounted at ../../../, paths outside
+    /// FSD/Content, paths MOUNT_POINT_NORMALIZATION mangles, zips without or with several paks,
+    /// deeply nested folders, executables and large files besides the pak. Checks the stored
+    /// archives of the current modfiles of the given mods, or of every mod, and records the
+    /// findings. Exits with 1 if anything is found
+    Lint {
+        /// Mods to check, by id or name_id
+        #[clap(value_parser)]
+        mods: Vec<String>,
+        /// Check this zip or pak instead, e.g. before uploading it. DATABASE_URL is not needed
+        #[clap(long, value_parser, conflicts_with = "mods")]
+        file: Option<std::path::PathBuf>,
+        /// Check modfiles checked before again, e.g. after updating
+        #[clap(long)]
+        relint: bool,
+    },
+    /// Manage local collections of mods
+    Collection {
+        #[clap(subcommand)]
+        action: CollectionAction,
+    },
+    /// Analyze archives or paks that did not come from mod.io, or every one in a directory,
+    /// and list their entries. DATABASE_URL is only needed with --store
+    AnalyzePath {
+        /// Archive or directory to analyze
+        #[clap(value_parser)]
+        path: std::path::PathBuf,
+        /// Also store the zips and index them as modfiles of a synthetic "local" mod
+        #[clap(long)]
+        store: bool,
+    },
+    /// Import community lists of mods approved on servers and check them against the index
+    ApprovedList {
+        #[clap(subcommand)]
+        action: ApprovedListAction,
+    },
+    /// Extract the paks of mods, given as ids or name_ids, or of the mods locked by modpack
+    /// manifests into the game's paks directory, replacing what an earlier install put there.
+    /// Paks are numbered so later mods take precedence, see load-order
+    Install {
+        #[clap(value_parser, required = true)]
+        targets: Vec<String>,
+        /// DRG install directory, the one containing FSD, found through Steam by default
+        #[clap(long, value_parser)]
+        game_dir: Option<std::path::PathBuf>,
+    },
+    /// Remove the paks install put into the game directory, only those of the given mods if any.
+    /// Paks changed since they were installed are kept
+    Uninstall {
+        #[clap(value_parser)]
+        mods: Vec<String>,
+        /// DRG install directory, the one containing FSD, found through Steam by default
+        #[clap(long, value_parser)]
+        game_dir: Option<std::path::PathBuf>,
+    },
+    /// Maintain a modpack: a manifest of mods to edit by hand and a lockfile next to it pinning
+    /// the exact modfiles and md5s they resolved to
+    Pack {
+        /// Manifest of the modpack, its lockfile is written next to it as <name>.lock.json
+        #[clap(long, value_parser, default_value = "modpack.json")]
+        manifest: std::path::PathBuf,
+        #[clap(subcommand)]
+        action: PackAction,
+    },
+    /// Manage webhooks receiving the sync notifications about specific mods as JSON
+    Webhook {
+        #[clap(subcommand)]
+        action: WebhookAction,
+    },
+    /// Write a JSON manifest of every stored modfile archive, current and superseded, with its
+    /// hash, size and the URL it was downloaded from. Set ARCHIVE_MODE to keep every archive
+    ArchiveManifest {
+        /// File to write, stdout if omitted
+        #[clap(short, long, value_parser)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Write a mod loader profile of mods, given as ids or name_ids, or of a collection, pinned to
+    /// their current modfiles or the modfiles the collection pins
+    ExportProfile {
+        #[clap(long, value_delimiter = ',', required_unless_present = "collection")]
+        mods: Vec<String>,
+        /// Export the members of this collection, before any --mods
+        #[clap(long, value_parser)]
+        collection: Option<String>,
+        #[clap(long, value_enum, default_value_t = profile::Format::Mint)]
+        format: profile::Format,
+        /// File to write, stdout if omitted
+        #[clap(short, long, value_parser)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Index the file listing of the base game's paks for a game version, then report the mods
+    /// likely broken by the update from the previously indexed version
+    IndexGame {
+        /// FSD-WindowsNoEditor.pak or the directory holding the game's paks, defaults to those of
+        /// the Steam install
+        #[clap(value_parser)]
+        path: Option<std::path::PathBuf>,
+        /// Game version the paks belong to, e.g. 1.38.96. Defaults to a fingerprint of the paks'
+        /// contents, so indexing the same paks again replaces their index
+        #[clap(long, value_parser)]
+        game_version: Option<String>,
+    },
+    /// Suggest an order to load mods in, given as ids or name_ids, that keeps the overrides
+    /// between them intended: frameworks first, audio packs last and targeted mods after the
+    /// broader mods they share paths with. Later mods take precedence
+    LoadOrder {
+        #[clap(value_parser, required_unless_present = "collection")]
+        mods: Vec<String>,
+        /// Order the members of this collection, at their pinned modfiles
+        #[clap(long, value_parser)]
+        collection: Option<String>,
+    },
+    /// Reports about the whole index
+    Report {
+        #[clap(subcommand)]
+        action: ReportAction,
+    },
+    /// Report the mods overriding base game assets that changed or were removed between two
+    /// indexed game versions, the latest two by default
+    UpdateReport {
+        #[clap(long, value_parser)]
+        from: Option<String>,
+        #[clap(long, value_parser)]
+        to: Option<String>,
+    },
+    /// List the references from a mod's assets to objects of other packages, e.g. the base game
+    /// Blueprints it derives from, or with --to the mods referencing an asset
+    AssetRefs {
+        /// Mod id or name_id
+        #[clap(value_parser, required_unless_present = "to")]
+        r#mod: Option<String>,
+        /// Only references to this package, object or game path, e.g.
+        /// /Game/Character/BP_PlayerCharacter or FSD/Content/Character/BP_PlayerCharacter.uasset
+        #[clap(long, value_parser)]
+        to: Option<String>,
+        /// Only references of this class, e.g. BlueprintGeneratedClass
+        #[clap(long, value_parser)]
+        class: Option<String>,
+        /// Only parent classes
+        #[clap(long, value_parser)]
+        parents: bool,
+        /// Only references to base game assets, needs an index-game run
+        #[clap(long, value_parser)]
+        vanilla: bool,
+    },
+    /// List the mods with localized strings and their keys per language, or the strings of one
+    /// mod, e.g. to find translation mods
+    Localization {
+        /// Mod id or name_id to list the strings of
+        #[clap(value_parser)]
+        r#mod: Option<String>,
+        /// Only this culture, e.g. de or pt-BR
+        #[clap(long, value_parser)]
+        language: Option<String>,
+        /// Also write the mod's strings to this CSV file
+        #[clap(long, value_parser, requires = "mod")]
+        csv: Option<std::path::PathBuf>,
+    },
+    /// Print the rows of a mod's DataTables and CurveTables as JSON, or write them to a directory.
+    /// Packages with unversioned properties only have rows when analyzed with USMAP set
+    DataTable {
+        /// Mod id or name_id
+        #[clap(value_parser)]
+        r#mod: String,
+        /// Modfile id or version, defaults to the current modfile
+        #[clap(long, value_parser)]
+        modfile: Option<String>,
+        /// Only tables whose path contains this, e.g. DT_WeaponStats
+        #[clap(long, value_parser)]
+        table: Option<String>,
+        /// Write each table to <dir>/<path>.json instead of printing the rows
+        #[clap(short, long, value_parser)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// List the Wwise soundbanks and media of mods, given as ids or name_ids or every mod with
+    /// any, as music, voice or sound effects. Media is looked up in the soundbanks of the latest
+    /// game version indexed with index-game
+    Audio {
+        #[clap(value_parser)]
+        mods: Vec<String>,
+        /// Only list mods replacing this kind of audio
+        #[clap(long, value_enum)]
+        content: Option<wwise::Content>,
+    },
+    /// Record a game version's release without indexing its paks, e.g. right after a patch drops,
+    /// or set the release date of an indexed version
+    RecordGameVersion {
+        /// Game version, e.g. 1.39.103
+        #[clap(value_parser)]
+        game_version: String,
+        /// Release date as YYYY-MM-DD, RFC 3339 or a unix timestamp, now by default
+        #[clap(long, value_parser = api::parse_date)]
+        released: Option<u64>,
+    },
+    /// Report the mods whose current modfile was uploaded before a game update that changed or
+    /// removed assets they override. Updates recorded without paks flag every mod overriding base
+    /// game assets
+    PossiblyStale {
+        /// Only consider updates released after this game version
+        #[clap(long, value_parser)]
+        since: Option<String>,
+    },
+    /// Download the current modfile of every mod into the mods directory without an index.
+    /// DATABASE_URL is not needed. Progress of an interrupted run is kept in mirror-progress.json
+    Download,
+    /// Check the .env file, environment variables, mod.io token, database and mods directory and
+    /// explain how to fix any problems
+    CheckConfig,
+    /// Log in to mod.io with a security code sent by email and store the access token for later
+    /// runs. Needs MODIO_KEY. MODIO_ACCESS_TOKEN takes precedence over the stored token when set
+    Login {
+        /// Email of the mod.io account, prompted for if omitted
+        #[clap(long, value_parser)]
+        email: Option<String>,
+    },
+    /// Serve a stand-in for the mod.io API from canned fixtures until stopped with SIGINT or
+    /// SIGTERM, for running syncs offline with `--api-base`. See src/mock.rs for the layout
+    #[cfg(feature = "mock-api")]
+    MockApi {
+        /// Directory of fixtures
+        #[clap(value_parser)]
+        fixtures: std::path::PathBuf,
+        /// Address to listen on
+        #[clap(long, value_parser, default_value = "127.0.0.1:8081")]
+        listen: std::net::SocketAddr,
     },
     Test,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    dotenv().ok();
-    let options = SqlitePoolOptions::new().max_connections(1);
-    let pool = options.connect(&env::var("DATABASE_URL")?).await?;
-
-    let cli = Cli::parse();
+/// Optional steps of a sync.
+#[derive(clap::Args, Debug, Clone, Copy)]
+struct SyncOptions {
+    /// Also index unreleased modfiles of mods the authenticated user is a team member of
+    #[clap(long)]
+    drafts: bool,
+    /// Also index the comments of every mod, one extra request per mod
+    #[clap(long = "with-comments")]
+    comments: bool,
+    /// Delete archives again once they are analyzed, for indexes that only need to be searchable.
+    /// Archives that were already stored are kept
+    #[clap(long)]
+    no_keep: bool,
+    /// List the paks of new modfiles with HTTP range requests instead of downloading them, reading
+    /// only the zip central directory and the pak index. Entries are indexed without hashes,
+    /// asset classes or strings. Archives with a compressed pak are downloaded as usual
+    #[clap(long)]
+    metadata_only: bool,
+    /// Sync every mod again instead of resuming an interrupted run after the mods it already
+    /// synced
+    #[clap(long)]
+    restart: bool,
+    /// Modfiles downloaded at the same time
+    #[clap(long, default_value_t = 4)]
+    download_jobs: usize,
+    /// Archives analyzed at the same time, the number of CPUs by default
+    #[clap(long)]
+    analysis_jobs: Option<usize>,
+}
+
+impl Commands {
+    /// Name of the command if it modifies the index or the mods directory and so has to hold the
+    /// [`WriterLock`] while running.
+    fn writer_name(&self) -> Option<&'static str> {
+        match self {
+            Commands::GetMods { dry_run: false, .. } => Some("get-mods"),
+            Commands::UpdateModFilesLocal => Some("update-mod-files-local"),
+            Commands::Sync { dry_run: false, .. } => Some("sync"),
+            Commands::SyncSubscribed { dry_run: false, .. } => Some("sync-subscribed"),
+            Commands::Fetch { .. } => Some("fetch"),
+            Commands::Daemon { .. } => Some("daemon"),
+            Commands::Flatten { .. } => Some("flatten"),
+            Commands::Migrate {
+                action: MigrateAction::Run | MigrateAction::Revert { .. },
+            } => Some("migrate"),
+            Commands::Db {
+                action: DbAction::Maintain { .. },
+            } => Some("db"),
+            Commands::Collection {
+                action:
+                    CollectionAction::Create { .. }
+                    | CollectionAction::Delete { .. }
+                    | CollectionAction::Add { .. }
+                    | CollectionAction::Remove { .. }
+                    | CollectionAction::Import { .. },
+            } => Some("collection"),
+            Commands::Verify { fix: true } => Some("verify"),
+            Commands::FetchMissing => Some("fetch-missing"),
+            Commands::Gc { dry_run: false } => Some("gc"),
+            Commands::Lint { file: None, .. } => Some("lint"),
+            Commands::Restore { .. } => Some("restore"),
+            Commands::Webhook {
+                action: WebhookAction::Add { .. } | WebhookAction::Remove { .. },
+            } => Some("webhook"),
+            Commands::ApprovedList {
+                action: ApprovedListAction::Import { .. } | ApprovedListAction::Remove { .. },
+            } => Some("approved-list"),
+            Commands::Download => Some("download"),
+            Commands::AnalyzePath { store: true, .. } => Some("analyze-path"),
+            Commands::IndexGame { .. } | Commands::RecordGameVersion { .. } => Some("index-game"),
+            Commands::Install { .. } | Commands::Uninstall { .. } => Some("install"),
+            Commands::GetMods { dry_run: true, .. }
+            | Commands::Sync { dry_run: true, .. }
+            | Commands::SyncSubscribed { dry_run: true, .. }
+            | Commands::ListFiles { .. }
+            | Commands::Migrate {
+                action: MigrateAction::Status,
+            }
+            | Commands::AuditUpstream { .. }
+            | Commands::History { .. }
+            | Commands::ModEvents { .. }
+            | Commands::Diff { .. }
+            | Commands::Feed { .. }
+            | Commands::ArchiveManifest { .. }
+            | Commands::UpdateReport { .. }
+            | Commands::Report { .. }
+            | Commands::PossiblyStale { .. }
+            | Commands::Audio { .. }
+            | Commands::Serve { .. }
+            | Commands::AssetRefs { .. }
+            | Commands::Localization { .. }
+            | Commands::DataTable { .. }
+            | Commands::LoadOrder { .. }
+            | Commands::ExportProfile { .. }
+            | Commands::Extract { .. }
+            | Commands::Query { .. }
+            | Commands::Stats
+            | Commands::Grep { .. }
+            | Commands::Reconcile
+            | Commands::Duplicates
+            | Commands::Flagged
+            | Commands::Verify { fix: false }
+            | Commands::Gc { dry_run: true }
+            | Commands::Lint { file: Some(_), .. }
+            | Commands::Collection {
+                action: CollectionAction::List { .. } | CollectionAction::Export { .. },
+            }
+            | Commands::Webhook {
+                action: WebhookAction::List,
+            }
+            | Commands::ApprovedList {
+                action: ApprovedListAction::List | ApprovedListAction::Check { .. },
+            }
+            | Commands::Pack { .. }
+            | Commands::AnalyzePath { store: false, .. }
+            | Commands::CheckConfig
+            | Commands::Login { .. }
+            | Commands::Test => None,
+            #[cfg(feature = "mock-api")]
+            Commands::MockApi { .. } => None,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply all pending migrations
+    Run,
+    /// List migrations and whether they have been applied
+    Status,
+    /// Revert applied migrations newer than the target version
+    Revert {
+        #[clap(value_parser)]
+        target: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReportAction {
+    /// Every path the current modfiles of several mods contain, ignoring case, grouped by pair of
+    /// mods and by path
+    Conflicts {
+        /// Write a self-contained HTML page that can be filtered and sorted instead, e.g. to share
+        /// with modpack users
+        #[clap(long, value_parser)]
+        html: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbAction {
+    /// Run VACUUM and ANALYZE and check the database for corruption, printing how long each
+    /// step took. Worth running now and then on large indexes
+    Maintain {
+        /// Rebuild the full-text search indexes first
+        #[clap(long)]
+        reindex_fts: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ApprovedListAction {
+    /// Import a list from a JSON array or CSV file of mod ids or name_ids, replacing an earlier
+    /// import of the same name
+    Import {
+        #[clap(value_parser)]
+        file: std::path::PathBuf,
+        /// Name of the list, defaults to the file name without extension
+        #[clap(long, value_parser)]
+        name: Option<String>,
+    },
+    Remove {
+        #[clap(value_parser)]
+        name: String,
+    },
+    List,
+    /// Report members that are not indexed and members whose current modfiles conflict. Exits
+    /// with 1 if there are any
+    Check {
+        #[clap(value_parser)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum PackAction {
+    /// Create an empty manifest. DATABASE_URL is not needed
+    Init {
+        /// Name of the modpack, defaults to the name of the manifest's directory
+        #[clap(long, value_parser)]
+        name: Option<String>,
+    },
+    /// Add mods, given as ids or name_ids, to the manifest
+    Add {
+        #[clap(value_parser, required = true)]
+        mods: Vec<String>,
+        /// Lock the mods to this modfile id or version instead of their current modfile
+        #[clap(long, value_parser)]
+        pin: Option<String>,
+        #[clap(long, value_parser)]
+        note: Option<String>,
+    },
+    /// Resolve the manifest against the index and mod.io dependencies and write the lockfile
+    Lock,
+    /// Report manifest changes not locked yet, available updates, re-uploaded archives, missing
+    /// dependencies and conflicts. Exits with 1 if the lock is out of date or a dependency is
+    /// missing
+    Status,
+}
+
+#[derive(Subcommand)]
+enum WebhookAction {
+    /// Register a URL for the new mod, update and conflict notifications of a mod, category or
+    /// mod.io tag
+    #[clap(group(clap::ArgGroup::new("scope").required(true)))]
+    Add {
+        #[clap(value_parser)]
+        url: String,
+        /// Mod id or name_id
+        #[clap(long = "mod", value_parser, group = "scope")]
+        r#mod: Option<String>,
+        /// Every mod whose content was classified as this kind
+        #[clap(long, value_enum, group = "scope")]
+        category: Option<classify::Category>,
+        /// Every mod tagged with this on mod.io, e.g. "Gameplay"
+        #[clap(long, value_parser, group = "scope")]
+        tag: Option<String>,
+    },
+    Remove {
+        #[clap(value_parser)]
+        id: i64,
+    },
+    List,
+}
+
+#[derive(Subcommand)]
+enum CollectionAction {
+    Create {
+        #[clap(value_parser)]
+        name: String,
+    },
+    Delete {
+        #[clap(value_parser)]
+        name: String,
+    },
+    /// Add mods, given as ids or name_ids, to a collection
+    Add {
+        #[clap(value_parser)]
+        collection: String,
+        #[clap(value_parser, required = true)]
+        mods: Vec<String>,
+        /// Pin the mod to this modfile id or version instead of following its current modfile
+        #[clap(long, value_parser)]
+        pin: Option<String>,
+        /// Curator's note on why the mods are in the collection
+        #[clap(long, value_parser)]
+        note: Option<String>,
+    },
+    Remove {
+        #[clap(value_parser)]
+        collection: String,
+        #[clap(value_parser, required = true)]
+        mods: Vec<String>,
+    },
+    /// List all collections, or the mods in one
+    List {
+        #[clap(value_parser)]
+        collection: Option<String>,
+    },
+    /// Write a collection to a JSON file that can be imported into another index
+    Export {
+        #[clap(value_parser)]
+        collection: String,
+        /// File to write, stdout if omitted
+        #[clap(short, long, value_parser)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Create a collection from an exported JSON file
+    Import {
+        #[clap(value_parser)]
+        file: std::path::PathBuf,
+        /// Name for the new collection, defaults to the name in the file
+        #[clap(long, value_parser)]
+        name: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    let cli = Cli::parse();
+    let output = Output { json: cli.json };
+    let multi_bar = if cli.events == Some(events::Sink::Stderr) {
+        indicatif::MultiProgress::with_draw_target(indicatif::ProgressDrawTarget::hidden())
+    } else {
+        output.multi_progress()
+    };
+    let verbosity = cli.verbose.min(i8::MAX as u8) as i8 - cli.quiet.min(i8::MAX as u8) as i8;
+    logging::init(&multi_bar, verbosity, cli.log_file.as_deref())?;
+    if let Some(sink) = &cli.events {
+        events::init(sink)?;
+    }
+    if cli.allow_flagged {
+        virus::allow_flagged();
+    }
+    if let Some(url) = &cli.api_base {
+        api::set_api_base(url.trim_end_matches('/').to_string());
+    }
+
+    let res = run(cli, output, &multi_bar).await;
+    if let Err(e) = &res {
+        events::emit(events::Event::Error {
+            id_mod: None,
+            id_modfile: None,
+            message: &format!("{e:#}"),
+        });
+    }
+    match res {
+        Err(e) if output.json => {
+            output.error(&e);
+            std::process::exit(1);
+        }
+        res => res,
+    }
+}
+
+async fn run(cli: Cli, output: Output, multi_bar: &indicatif::MultiProgress) -> Result<()> {
+    if cli.command.is_some() && (cli.query_asset.is_some() || cli.query_mod.is_some()) {
+        anyhow::bail!("--query-asset and --query-mod cannot be combined with a subcommand");
+    }
+
+    let lock = cli
+        .command
+        .as_ref()
+        .and_then(Commands::writer_name)
+        .map(WriterLock::acquire)
+        .transpose()?;
+    // read-only commands run alongside a writer that may be storing archives
+    if lock.is_some() {
+        store::migrate_layout()?;
+    }
+
+    if let Some(Commands::CheckConfig) = cli.command {
+        let report = check::check_config().await;
+        output.emit(&report, |r| println!("{r}"))?;
+        if report.has_errors() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Commands::Login { email }) = cli.command {
+        let logged_in = login::login(email).await?;
+        output.emit(&logged_in, |l| println!("{l}"))?;
+        return Ok(());
+    }
+    #[cfg(feature = "mock-api")]
+    if let Some(Commands::MockApi { fixtures, listen }) = &cli.command {
+        return mock::serve(fixtures, *listen).await;
+    }
+    if let Some(Commands::Download) = cli.command {
+        let summary = download::mirror(multi_bar).await?;
+        output.emit(&summary, |s| println!("{s}"))?;
+        return Ok(());
+    }
+    if let Some(Commands::Pack {
+        manifest,
+        action: PackAction::Init { name },
+    }) = &cli.command
+    {
+        let name = match name {
+            Some(name) => name.clone(),
+            None => std::env::current_dir()?
+                .join(manifest)
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "modpack".to_string()),
+        };
+        modpack::init(manifest, &name)?;
+        output.emit(&name, |n| {
+            println!("Created modpack {n} in {}", manifest.display())
+        })?;
+        return Ok(());
+    }
+    if let Some(Commands::Lint {
+        file: Some(file), ..
+    }) = &cli.command
+    {
+        let linted = lint::lint_file(file).await?;
+        let clean = linted.findings.is_empty();
+        output.emit(&linted, |l| lint::print_linted(std::slice::from_ref(l)))?;
+        if !clean {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Commands::AnalyzePath { path, store: false }) = &cli.command {
+        let analyses = local::analyze_path(None, path).await?;
+        output.emit(&analyses, |a| local::print_analyses(a))?;
+        return Ok(());
+    }
+
+    let database_url = env::var("DATABASE_URL")
+        .context("DATABASE_URL must be set, e.g. DATABASE_URL=sqlite:index.db")?;
+    let backend = db::Backend::from_url(&database_url)?;
+    let auto_migrate = !matches!(
+        cli.command,
+        Some(
+            Commands::Migrate { .. }
+                | Commands::GetMods { dry_run: true, .. }
+                | Commands::Sync { dry_run: true, .. }
+                | Commands::SyncSubscribed { dry_run: true, .. }
+        )
+    );
+    // only writers create the index, a dry run must not leave an empty database behind
+    let pool = db::connect(&database_url, auto_migrate, lock.is_some()).await?;
+
+    let Some(command) = cli.command else {
+        if let Some(path) = cli.query_asset {
+            let mods = query::mods_with_asset(&pool, backend, &path).await?;
+            output.emit(&mods, |mods| {
+                let names = mods.iter().map(|m| m.name_id.as_str()).collect::<Vec<_>>();
+                println!("{}", names.join(" "));
+            })?;
+            if mods.is_empty() {
+                std::process::exit(1);
+            }
+        } else if let Some(reference) = cli.query_mod {
+            let id_mod = lookup::resolve_mod(&pool, &reference).await?;
+            let summary = query::mod_summary(&pool, id_mod).await?;
+            output.emit(&summary, |s| println!("{s}"))?;
+        } else {
+            anyhow::bail!("a subcommand, --query-asset or --query-mod is required, see --help");
+        }
+        return Ok(());
+    };
+
+    match command {
+        Commands::GetMods {
+            dry_run: true,
+            filters,
+            ..
+        } => {
+            let plan = plan::plan_sync(&pool, &filters).await?;
+            output.emit(&plan, |p| println!("{p}"))?;
+        }
+        Commands::Sync { dry_run: true, .. } => {
+            let plan = plan::plan_sync(&pool, &api::ModFilters::default()).await?;
+            output.emit(&plan, |p| println!("{p}"))?;
+        }
+        Commands::SyncSubscribed { dry_run: true, .. } => {
+            let plan = plan::plan_sync(&pool, &api::ModFilters::subscribed()).await?;
+            output.emit(&plan, |p| println!("{p}"))?;
+        }
+        Commands::GetMods {
+            dry_run: false,
+            options,
+            filters,
+        } => {
+            let mut summary = SyncSummary::default();
+            get_mods(multi_bar, &pool, options, &filters, &mut summary).await?;
+            classify::refresh(&pool).await?;
+            game::flag_overrides(&pool, false).await?;
+            notify(&pool, &summary).await;
+            output.emit(&summary, |s| println!("{s}"))?;
+        }
+        Commands::UpdateModFilesLocal => {
+            let summary = update_pack_files_local(multi_bar, &pool).await?;
+            output.emit(&summary, |s| println!("{s}"))?;
+        }
+        Commands::Sync {
+            dry_run: false,
+            options,
+        } => {
+            let summary = sync(multi_bar, &pool, options, &api::ModFilters::default()).await?;
+            notify(&pool, &summary).await;
+            output.emit(&summary, |s| println!("Sync complete: {s}"))?;
+        }
+        Commands::SyncSubscribed {
+            dry_run: false,
+            options,
+        } => {
+            let summary = sync(multi_bar, &pool, options, &api::ModFilters::subscribed()).await?;
+            notify(&pool, &summary).await;
+            output.emit(&summary, |s| println!("Sync complete: {s}"))?;
+        }
+        Commands::Fetch { r#mod, no_keep } => {
+            let summary = fetch_mod(multi_bar, &pool, &r#mod, !no_keep).await?;
+            notify(&pool, &summary).await;
+            output.emit(&summary, |s| println!("{s}"))?;
+        }
+        Commands::ListFiles {
+            paths,
+            collection,
+            format,
+        } => {
+            let paths = if !paths.is_empty() {
+                let mut archives = vec![];
+                for path in paths {
+                    if glob::is_pattern(&path) {
+                        archives.extend(glob::expand(&path)?);
+                    } else {
+                        archives.extend(local::find_archives(Path::new(&path))?);
+                    }
+                }
+                archives
+            } else if let Some(collection) = collection {
+                collection::current_hashes(&pool, &collection)
+                    .await?
+                    .iter()
+                    .map(|md5| download::archive_path(md5))
+                    .filter(|path| path.exists())
+                    .collect()
+            } else {
+                store::objects()?
+                    .into_iter()
+                    .map(|(_, path)| path)
+                    .collect()
+            };
+            let listings = listing::list_archives(paths).await?;
+            output.emit(&listings, |l| listing::print(l, format))?;
+        }
+        Commands::Migrate { action } => {
+            match action {
+                MigrateAction::Run => backend.migrator().run(&pool).await?,
+                MigrateAction::Status => {}
+                MigrateAction::Revert { target } => backend.migrator().undo(&pool, target).await?,
+            }
+            let status = db::migration_status(&pool, backend).await?;
+            output.emit(&status, |status| {
+                for migration in status {
+                    let state = if migration.applied {
+                        "applied"
+                    } else {
+                        "pending"
+                    };
+                    println!("{} {} ({state})", migration.version, migration.description);
+                }
+            })?;
+        }
+        Commands::Db { action } => match action {
+            DbAction::Maintain { reindex_fts } => {
+                let report = db::maintain(&pool, backend, reindex_fts).await?;
+                output.emit(&report, |r| println!("{r}"))?;
+            }
+        },
+        Commands::AuditUpstream { sample } => {
+            let report = audit::audit_upstream(multi_bar, &pool, sample).await?;
+            output.emit(&report, |r| println!("{r}"))?;
+        }
+        Commands::History { r#mod } => {
+            let id_mod = lookup::resolve_mod(&pool, &r#mod).await?;
+            let versions = history::history(&pool, id_mod).await?;
+            output.emit(&versions, |v| history::print_history(v))?;
+        }
+        Commands::ModEvents { r#mod } => {
+            let id_mod = lookup::resolve_mod(&pool, &r#mod).await?;
+            let events = mod_event::events(&pool, id_mod).await?;
+            output.emit(&events, |e| mod_event::print_events(e))?;
+        }
+        Commands::Diff { r#mod, from, to } => {
+            let id_mod = lookup::resolve_mod(&pool, &r#mod).await?;
+            let to = match to {
+                Some(to) => lookup::resolve_modfile(&pool, id_mod, &to).await?,
+                None => lookup::current_modfile(&pool, id_mod).await?,
+            };
+            let from = match from {
+                Some(from) => lookup::resolve_modfile(&pool, id_mod, &from).await?,
+                None => diff::previous_modfile(&pool, id_mod, to).await?,
+            };
+            let diff = diff::diff(&pool, from, to).await?;
+            output.emit(&diff, |d| println!("{d}"))?;
+        }
+        Commands::Daemon {
+            interval,
+            metrics,
+            options,
+        } => {
+            if let Some(address) = metrics {
+                server::spawn_metrics(pool.clone(), address).await?;
+            }
+            daemon::run(multi_bar, &pool, interval, options).await?;
+        }
+        Commands::Serve { listen } => {
+            server::serve(pool.clone(), listen).await?;
+        }
+        Commands::Flatten { csv } => {
+            let rows = flatten::refresh(&pool).await?;
+            if let Some(path) = csv {
+                flatten::write_csv(&pool, &path).await?;
+            }
+            output.emit(&rows, |rows| {
+                println!("Flattened {rows} mods into mod_flat")
+            })?;
+        }
+        Commands::Feed {
+            limit,
+            output: path,
+        } => {
+            let feed = feed::atom_feed(&pool, limit).await?;
+            match path {
+                Some(path) => {
+                    fs::write(&path, feed)?;
+                    output.emit(&path, |p| println!("Wrote {}", p.display()))?;
+                }
+                None => output.emit(&feed, |f| print!("{f}"))?,
+            }
+        }
+        Commands::ArchiveManifest { output: path } => {
+            let manifest = archive::manifest(&pool).await?;
+            let json = serde_json::to_string_pretty(&manifest)?;
+            match path {
+                Some(path) => {
+                    fs::write(&path, json + "\n")?;
+                    output.emit(&path, |p| println!("Wrote {}", p.display()))?;
+                }
+                None => output.emit(&manifest, |_| println!("{json}"))?,
+            }
+        }
+        Commands::ExportProfile {
+            mods,
+            collection,
+            format,
+            output: path,
+        } => {
+            let mods = profile::resolve(&pool, &mods, collection.as_deref()).await?;
+            let rendered = profile::render(&mods, format)?;
+            match path {
+                Some(path) => {
+                    fs::write(&path, rendered)?;
+                    output.emit(&path, |p| println!("Wrote {}", p.display()))?;
+                }
+                None => output.emit(&rendered, |r| print!("{r}"))?,
+            }
+        }
+        Commands::Extract {
+            r#mod,
+            modfile,
+            output: path,
+            convert,
+        } => {
+            let id_mod = lookup::resolve_mod(&pool, &r#mod).await?;
+            let id_modfile = match modfile {
+                Some(modfile) => lookup::resolve_modfile(&pool, id_mod, &modfile).await?,
+                None => lookup::current_modfile(&pool, id_mod).await?,
+            };
+            let (name_id, hash_md5): (String, String) = sqlx::query_as(
+                "SELECT name_id, hash_md5 FROM modfile JOIN mod ON mod.id_mod = modfile.id_mod
+                 WHERE modfile.id_modfile = $1",
+            )
+            .bind(id_modfile)
+            .fetch_one(&pool)
+            .await?;
+            let archive = download::archive_path(&hash_md5);
+            if !archive.exists() {
+                anyhow::bail!("archive of modfile {id_modfile} is not downloaded");
+            }
+            let path = path.unwrap_or_else(|| Path::new("extracted").join(name_id));
+            let report =
+                tokio::task::spawn_blocking(move || extract::extract(&archive, &path, convert))
+                    .await??;
+            output.emit(&report, |r| println!("{r}"))?;
+        }
+        Commands::Query {
+            category,
+            text,
+            extension,
+            in_list,
+            name,
+            metadata,
+            path,
+            filter,
+        } => {
+            if let Some(category) = category {
+                let mut mods = query::mods_by_category(&pool, category).await?;
+                filter.retain(&pool, &mut mods, |m| m.id_mod).await?;
+                output.emit(&mods, |m| query::print_mods(m))?;
+            } else if let Some(text) = text {
+                let mut strings = query::strings_containing(&pool, &text).await?;
+                filter.retain(&pool, &mut strings, |s| s.id_mod).await?;
+                output.emit(&strings, |s| query::print_strings(s))?;
+            } else if let Some(extension) = extension {
+                let mut mods = query::mods_with_extension(&pool, &extension).await?;
+                filter.retain(&pool, &mut mods, |m| m.id_mod).await?;
+                output.emit(&mods, |m| query::print_extension_matches(m))?;
+            } else if let Some(in_list) = in_list {
+                let mut mods = query::mods_in_list(&pool, &in_list).await?;
+                filter.retain(&pool, &mut mods, |m| m.id_mod).await?;
+                output.emit(&mods, |m| query::print_mods(m))?;
+            } else if let Some(name) = name {
+                let mut mods = query::mods_named(&pool, &name).await?;
+                filter.retain(&pool, &mut mods, |m| m.id_mod).await?;
+                output.emit(&mods, |m| query::print_mods(m))?;
+            } else if let Some(metadata) = metadata {
+                let mut mods = metadata::mods_with(&pool, &metadata).await?;
+                filter.retain(&pool, &mut mods, |m| m.id_mod).await?;
+                output.emit(&mods, |m| metadata::print_matches(m))?;
+            } else if let Some(path) = path {
+                let mut mods = query::mods_with_path(&pool, backend, &path).await?;
+                filter.retain(&pool, &mut mods, |m| m.id_mod).await?;
+                output.emit(&mods, |m| query::print_mods(m))?;
+            } else if filter.platform.is_some() {
+                let mut mods = query::all_mods(&pool).await?;
+                filter.retain(&pool, &mut mods, |m| m.id_mod).await?;
+                output.emit(&mods, |m| query::print_mods(m))?;
+            }
+        }
+        Commands::Verify { fix } => {
+            let report = verify::verify(multi_bar, &pool, fix).await?;
+            output.emit(&report, |r| println!("{r}"))?;
+        }
+        Commands::Restore { id } => match id {
+            Some(id) => {
+                let entry = trash::restore(&pool, id).await?;
+                output.emit(&entry, |e| println!("Restored {} {}", e.kind, e.name))?;
+            }
+            None => {
+                trash::purge_expired(&pool).await?;
+                let entries = trash::list(&pool).await?;
+                output.emit(&entries, |e| trash::print_entries(e))?;
+            }
+        },
+        Commands::Duplicates => {
+            let groups = duplicates::duplicates(&pool).await?;
+            output.emit(&groups, |g| duplicates::print_duplicates(g))?;
+        }
+        Commands::Flagged => {
+            let modfiles = virus::flagged(&pool).await?;
+            output.emit(&modfiles, |m| virus::print_flagged(m))?;
+        }
+        Commands::FetchMissing => {
+            let report = verify::fetch_missing(multi_bar, &pool).await?;
+            output.emit(&report, |r| println!("{r}"))?;
+        }
+        Commands::Gc { dry_run } => {
+            let report = gc::gc(&pool, dry_run).await?;
+            output.emit(&report, |r| println!("{r}"))?;
+        }
+        Commands::Collection { action } => match action {
+            CollectionAction::Create { name } => {
+                collection::create(&pool, &name).await?;
+                output.emit(&name, |name| println!("Created collection {name}"))?;
+            }
+            CollectionAction::Delete { name } => {
+                collection::delete(&pool, &name).await?;
+                output.emit(&name, |name| {
+                    println!("Moved collection {name} to the trash")
+                })?;
+            }
+            CollectionAction::Add {
+                collection,
+                mods,
+                pin,
+                note,
+            } => {
+                let added =
+                    collection::add(&pool, &collection, &mods, pin.as_deref(), note.as_deref())
+                        .await?;
+                output.emit(&added, |n| println!("Added {n} mods to {collection}"))?;
+            }
+            CollectionAction::Remove { collection, mods } => {
+                let removed = collection::remove(&pool, &collection, &mods).await?;
+                output.emit(&removed, |n| println!("Removed {n} mods from {collection}"))?;
+            }
+            CollectionAction::List { collection: None } => {
+                let collections = collection::list(&pool).await?;
+                output.emit(&collections, |collections| {
+                    for c in collections {
+                        println!("{} ({} mods, created {})", c.name, c.mods, c.date_created);
+                    }
+                })?;
+            }
+            CollectionAction::List {
+                collection: Some(name),
+            } => {
+                let mods = collection::members(&pool, &name).await?;
+                output.emit(&mods, |mods| {
+                    for m in mods {
+                        print!("{} {} {}", m.id_mod, m.name_id, m.name);
+                        if let Some(id_modfile) = m.id_modfile {
+                            let version = m.version.as_deref().unwrap_or("-");
+                            print!(" (pinned to {version}, modfile {id_modfile})");
+                        }
+                        println!();
+                        if let Some(note) = &m.note {
+                            println!("    {note}");
+                        }
+                    }
+                })?;
+            }
+            CollectionAction::Export {
+                collection,
+                output: path,
+            } => {
+                let file = collection::export(&pool, &collection).await?;
+                let json = serde_json::to_string_pretty(&file)?;
+                match path {
+                    Some(path) => {
+                        fs::write(&path, json + "\n")?;
+                        output.emit(&path, |p| println!("Wrote {}", p.display()))?;
+                    }
+                    None => output.emit(&file, |_| println!("{json}"))?,
+                }
+            }
+            CollectionAction::Import { file, name } => {
+                let contents = fs::read_to_string(&file)
+                    .with_context(|| format!("failed to read {}", file.display()))?;
+                let file = serde_json::from_str(&contents)
+                    .with_context(|| format!("{} is not a collection file", file.display()))?;
+                let report = collection::import(&pool, file, name).await?;
+                output.emit(&report, |r| println!("{r}"))?;
+            }
+        },
+        Commands::Install { targets, game_dir } => {
+            let game_dir = steam::game_dir(game_dir)?;
+            let installation = install::install(&pool, &game_dir, &targets).await?;
+            output.emit(&installation, |i| println!("{i}"))?;
+        }
+        Commands::Uninstall { mods, game_dir } => {
+            let game_dir = steam::game_dir(game_dir)?;
+            let uninstallation = install::uninstall(&pool, &game_dir, &mods).await?;
+            output.emit(&uninstallation, |u| println!("{u}"))?;
+        }
+        Commands::Pack { manifest, action } => match action {
+            PackAction::Add { mods, pin, note } => {
+                let added =
+                    modpack::add(&pool, &manifest, &mods, pin.as_deref(), note.as_deref()).await?;
+                output.emit(&added, |added| {
+                    println!("Added {} mods to {}", added.len(), manifest.display())
+                })?;
+            }
+            PackAction::Lock | PackAction::Status => {
+                let status = match action {
+                    PackAction::Lock => modpack::lock(&pool, &manifest).await?,
+                    _ => modpack::status(&pool, &manifest).await?,
+                };
+                output.emit(&status, |s| println!("{s}"))?;
+                if status.has_problems() {
+                    std::process::exit(1);
+                }
+            }
+            PackAction::Init { .. } => {}
+        },
+        Commands::ApprovedList { action } => match action {
+            ApprovedListAction::Import { file, name } => {
+                let name = match name {
+                    Some(name) => name,
+                    None => file
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .context("cannot derive a list name from the file name, pass --name")?
+                        .to_string(),
+                };
+                let report = approved::import(&pool, &name, &file).await?;
+                output.emit(&report, |r| println!("{r}"))?;
+            }
+            ApprovedListAction::Remove { name } => {
+                let report = approved::remove(&pool, &name).await?;
+                output.emit(&report, |r| println!("{r}"))?;
+            }
+            ApprovedListAction::List => {
+                let lists = approved::lists(&pool).await?;
+                output.emit(&lists, |lists| {
+                    for l in lists {
+                        println!(
+                            "{} ({} mods, {} not indexed, imported {} from {})",
+                            l.name, l.mods, l.unindexed, l.date_imported, l.source
+                        );
+                    }
+                })?;
+            }
+            ApprovedListAction::Check { name } => {
+                let check = approved::check(&pool, &name).await?;
+                output.emit(&check, |c| println!("{c}"))?;
+                if check.has_problems() {
+                    std::process::exit(1);
+                }
+            }
+        },
+        Commands::Webhook { action } => match action {
+            WebhookAction::Add {
+                url,
+                r#mod,
+                category,
+                tag,
+            } => {
+                let scope = match (&r#mod, category, &tag) {
+                    (Some(reference), _, _) => webhook::Scope::Mod(reference),
+                    (_, Some(category), _) => webhook::Scope::Category(category),
+                    (_, _, Some(tag)) => webhook::Scope::Tag(tag),
+                    // clap requires one of them
+                    _ => unreachable!(),
+                };
+                let id = webhook::add(&pool, &url, scope).await?;
+                output.emit(&id, |id| println!("Added webhook {id}"))?;
+            }
+            WebhookAction::Remove { id } => {
+                webhook::remove(&pool, id).await?;
+                output.emit(&id, |id| println!("Removed webhook {id}"))?;
+            }
+            WebhookAction::List => {
+                let webhooks = webhook::list(&pool).await?;
+                output.emit(&webhooks, |webhooks| {
+                    for w in webhooks {
+                        println!("{w}");
+                    }
+                })?;
+            }
+        },
+        Commands::Reconcile => {
+            let report = reconcile::reconcile(&pool).await?;
+            output.emit(&report, |r| println!("{r}"))?;
+        }
+        Commands::Grep {
+            pattern,
+            ignore_case,
+        } => {
+            let pattern = regex::RegexBuilder::new(&pattern)
+                .case_insensitive(ignore_case)
+                .build()?;
+            let matches = grep::grep(multi_bar, &pool, &pattern).await?;
+            output.emit(&matches, |m| grep::print_matches(m))?;
+            if matches.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Commands::AnalyzePath { path, store: true } => {
+            let analyses = local::analyze_path(Some(&pool), &path).await?;
+            output.emit(&analyses, |a| local::print_analyses(a))?;
+        }
+        Commands::IndexGame { path, game_version } => {
+            let path = match path {
+                Some(path) => path,
+                None => steam::detect_game_dir()?.join(install::PAKS_DIR),
+            };
+            let index = game::index(multi_bar, &pool, &path, game_version.as_deref()).await?;
+            output.emit(&index, |i| println!("{i}"))?;
+        }
+        Commands::Report { action } => match action {
+            ReportAction::Conflicts { html } => {
+                let report = conflicts::report(&pool).await?;
+                match html {
+                    Some(path) => {
+                        fs::write(&path, conflicts::render_html(&report)?)?;
+                        output.emit(&path, |p| {
+                            println!(
+                                "Wrote {} conflicting paths and {} pairs to {}",
+                                report.paths.len(),
+                                report.pairs.len(),
+                                p.display()
+                            )
+                        })?;
+                    }
+                    None => output.emit(&report, |r| println!("{r}"))?,
+                }
+            }
+        },
+        Commands::UpdateReport { from, to } => {
+            let report = game::update_report(&pool, from.as_deref(), to.as_deref())
+                .await?
+                .context("index at least two game versions with index-game first")?;
+            output.emit(&report, |r| println!("{r}"))?;
+        }
+        Commands::RecordGameVersion {
+            game_version,
+            released,
+        } => {
+            let released = released.map(api::timestamp).transpose()?;
+            let version = game::record_version(&pool, &game_version, released.as_deref()).await?;
+            output.emit(&version, |v| println!("Recorded game version {v}"))?;
+        }
+        Commands::AssetRefs {
+            r#mod,
+            to,
+            class,
+            parents,
+            vanilla,
+        } => {
+            let id_mod = match r#mod {
+                Some(reference) => Some(lookup::resolve_mod(&pool, &reference).await?),
+                None => None,
+            };
+            let filter = asset_refs::Filter {
+                id_mod,
+                to: to.as_deref(),
+                class: class.as_deref(),
+                parents,
+                vanilla,
+            };
+            let references = asset_refs::references(&pool, &filter).await?;
+            output.emit(&references, |r| asset_refs::print_references(r))?;
+        }
+        Commands::Localization {
+            r#mod,
+            language,
+            csv,
+        } => match r#mod {
+            Some(reference) => {
+                let id_mod = lookup::resolve_mod(&pool, &reference).await?;
+                let strings = localization::strings(&pool, id_mod, language.as_deref()).await?;
+                if let Some(path) = csv {
+                    localization::write_csv(&strings, &path)?;
+                }
+                output.emit(&strings, |s| localization::print_strings(s))?;
+            }
+            None => {
+                let mods = localization::summary(&pool, language.as_deref()).await?;
+                output.emit(&mods, |m| localization::print_summary(m))?;
+            }
+        },
+        Commands::DataTable {
+            r#mod,
+            modfile,
+            table,
+            output: path,
+        } => {
+            let id_mod = lookup::resolve_mod(&pool, &r#mod).await?;
+            let id_modfile = match modfile {
+                Some(modfile) => lookup::resolve_modfile(&pool, id_mod, &modfile).await?,
+                None => lookup::current_modfile(&pool, id_mod).await?,
+            };
+            let tables = data_table::tables(&pool, id_modfile, table.as_deref()).await?;
+            match path {
+                Some(path) => {
+                    let written = data_table::write_json(&tables, &path)?;
+                    output.emit(&written, |w| {
+                        println!("Wrote {w} tables to {}", path.display())
+                    })?;
+                }
+                None => output.emit(&tables, |t| data_table::print_tables(t))?,
+            }
+        }
+        Commands::Audio { mods, content } => {
+            let report = audio::report(&pool, &mods, content).await?;
+            output.emit(&report, |r| println!("{r}"))?;
+        }
+        Commands::PossiblyStale { since } => {
+            let report = game::possibly_stale(&pool, since.as_deref()).await?;
+            output.emit(&report, |r| println!("{r}"))?;
+        }
+        Commands::LoadOrder { mods, collection } => {
+            let order = load_order::suggest(&pool, &mods, collection.as_deref()).await?;
+            output.emit(&order, |o| println!("{o}"))?;
+        }
+        Commands::Stats => {
+            let stats = stats::stats(&pool).await?;
+            output.emit(&stats, |s| println!("{s}"))?;
+        }
+        Commands::Lint {
+            mods,
+            file: None,
+            relint,
+        } => {
+            let mut ids = vec![];
+            for reference in &mods {
+                ids.push(lookup::resolve_mod(&pool, reference).await?);
+            }
+            let linted = lint::lint(multi_bar, &pool, &ids, relint).await?;
+            output.emit(&linted, |l| lint::print_linted(l))?;
+            if !linted.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Commands::CheckConfig
+        | Commands::Login { .. }
+        | Commands::Download
+        | Commands::AnalyzePath { store: false, .. }
+        | Commands::Lint { file: Some(_), .. }
+        | Commands::Test => {}
+        #[cfg(feature = "mock-api")]
+        Commands::MockApi { .. } => {}
+    }
+
+    api::save_quota(&pool).await?;
+    Ok(())
+}
+
+/// Entries of a zip holding a pak, as mod.io serves them, of a rar or 7z archive holding one, or
+/// of a bare pak.
+fn list_archive_files(path: &Path) -> Result<PakListing, PakError> {
+    let is_pak = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("pak"));
+    if is_pak {
+        list_files(&mut open_pak(path)?)
+    } else {
+        list_zip_files(path)
+    }
+}
+
+fn list_zip_files(path: &Path) -> Result<PakListing, PakError> {
+    list_files(&mut open_zip_pak(path)?)
+}
+
+/// The pak file of a mod archive. Only its index is read up front, files are read from the
+/// archive as they are requested.
+struct OpenPak {
+    pak: repak::PakReader,
+    reader: Box<dyn pak_source::PakSource>,
+}
+
+impl OpenPak {
+    fn get(&mut self, record: &str) -> Result<Vec<u8>, PakError> {
+        self.pak
+            .get(record, &mut self.reader)
+            .map_err(|e| PakError::ErrorReadingPak { e })
+    }
+
+    /// Copy the pak as a whole to `writer`. Returns its size.
+    fn copy_to(&mut self, writer: &mut impl std::io::Write) -> Result<u64, PakError> {
+        self.reader.rewind()?;
+        Ok(std::io::copy(&mut self.reader, writer)?)
+    }
+}
+
+fn open_zip_pak(path: &Path) -> Result<OpenPak, PakError> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    if pak_source::ArchiveFormat::detect(&mut file)? == pak_source::ArchiveFormat::Rar {
+        return read_pak(std::io::BufReader::new(pak_source::extract_rar_pak(path)?));
+    }
+    read_zip_pak(file)
+}
+
+/// Open the first pak of a zip, rar or 7z archive, looking into archives nested in a zip up to
+/// [`pak_source::nested_archive_depth`] levels deep if it holds none itself.
+fn read_zip_pak<R: Read + Seek + Send + 'static>(reader: R) -> Result<OpenPak, PakError> {
+    read_nested_zip_pak(reader, pak_source::nested_archive_depth())
+}
 
-    match cli.command {
-        Commands::GetMods => {
-            get_mods(&pool).await?;
+/// A stored pak is read in place, a compressed one or one in a nested zip is extracted to a
+/// temporary file first, so neither is held in memory as a whole.
+fn read_nested_zip_pak<R: Read + Seek + Send + 'static>(
+    mut reader: R,
+    depth: usize,
+) -> Result<OpenPak, PakError> {
+    match pak_source::ArchiveFormat::detect(&mut reader)? {
+        pak_source::ArchiveFormat::Zip => {}
+        pak_source::ArchiveFormat::SevenZip => {
+            return read_pak(std::io::BufReader::new(pak_source::extract_7z_pak(reader)?));
         }
-        Commands::UpdateModFilesLocal => {
-            update_pack_files_local(&pool).await?;
+        pak_source::ArchiveFormat::Rar => {
+            // unrar only reads archives from paths
+            let mut archive = pak_source::TempFile::create()?;
+            std::io::copy(&mut reader, &mut archive)?;
+            let pak = pak_source::extract_rar_pak(archive.path())?;
+            return read_pak(std::io::BufReader::new(pak));
         }
-        Commands::ListFiles { zip } => {
-            if let Some(path) = zip {
-                list_zip_files(&path)?;
-            } else {
-                for dir_entry in fs::read_dir("mods")? {
-                    let path = &dir_entry?.path();
-                    match list_zip_files(path) {
-                        Ok(files) => {
-                            for file in files {
-                                println!("{} {}", path.display(), file);
-                            }
-                        }
-                        Err(e) => println!("{} {}", path.display(), e),
-                    }
+    }
+    let mut archive = zip::ZipArchive::new(&mut reader)?;
+    let mut entry = None;
+    let mut nested = vec![];
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        if !file.is_file() {
+            continue;
+        }
+        let name = file.name().to_lowercase();
+        if name.ends_with(".pak") {
+            entry = Some((
+                i,
+                file.compression() == zip::CompressionMethod::Stored,
+                file.data_start(),
+                file.size(),
+            ));
+            break;
+        }
+        if [".zip", ".rar", ".7z"].iter().any(|e| name.ends_with(e)) {
+            nested.push(i);
+        }
+    }
+    let Some((i, stored, start, size)) = entry else {
+        if depth > 0 {
+            for i in nested {
+                let mut temp = pak_source::TempFile::create()?;
+                std::io::copy(&mut archive.by_index(i)?, &mut temp)?;
+                temp.rewind()?;
+                match read_nested_zip_pak(std::io::BufReader::new(temp), depth - 1) {
+                    // a nested archive without a pak or one that is no archive after all
+                    Err(
+                        PakError::MissingPakFile
+                        | PakError::ZipError(_)
+                        | PakError::RarError(_)
+                        | PakError::SevenZipError(_),
+                    ) => continue,
+                    result => return result,
                 }
             }
         }
-        Commands::Test => {}
+        return Err(PakError::MissingPakFile);
+    };
+    if stored {
+        drop(archive);
+        return read_pak(pak_source::Window::new(reader, start, size)?);
     }
-
-    Ok(())
+    let mut temp = pak_source::TempFile::create()?;
+    std::io::copy(&mut archive.by_index(i)?, &mut temp)?;
+    temp.rewind()?;
+    read_pak(std::io::BufReader::new(temp))
 }
 
-fn list_zip_files(path: &Path) -> Result<Vec<String>, PakError> {
-    let file = std::fs::File::open(path)?;
-    let reader = std::io::BufReader::new(file);
+/// Open a bare `.pak` that is not packed in a zip.
+fn open_pak(path: &Path) -> Result<OpenPak, PakError> {
+    read_pak(std::io::BufReader::new(fs::File::open(path)?))
+}
 
-    let mut archive = zip::ZipArchive::new(reader)?;
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        if file.is_file() && file.name().to_lowercase().ends_with(".pak") {
-            return list_files(&mut file);
-        }
-    }
-    Err(PakError::MissingPakFile)
+fn read_pak<R: pak_source::PakSource + 'static>(mut reader: R) -> Result<OpenPak, PakError> {
+    let pak = repak::PakReader::new_any(&mut reader, None)
+        .map_err(|e| PakError::ErrorReadingPak { e })?;
+    Ok(OpenPak {
+        pak,
+        reader: Box::new(reader),
+    })
 }
 
 #[derive(Debug)]
@@ -103,6 +1822,8 @@ enum PakError {
         e: std::path::StripPrefixError,
     },
     ZipError(zip::result::ZipError),
+    RarError(unrar::error::UnrarError),
+    SevenZipError(sevenz_rust::Error),
     IoError(std::io::Error),
 }
 
@@ -111,6 +1832,16 @@ impl From<zip::result::ZipError> for PakError {
         PakError::ZipError(e)
     }
 }
+impl From<unrar::error::UnrarError> for PakError {
+    fn from(e: unrar::error::UnrarError) -> PakError {
+        PakError::RarError(e)
+    }
+}
+impl From<sevenz_rust::Error> for PakError {
+    fn from(e: sevenz_rust::Error) -> PakError {
+        PakError::SevenZipError(e)
+    }
+}
 impl From<std::io::Error> for PakError {
     fn from(e: std::io::Error) -> PakError {
         PakError::IoError(e)
@@ -118,6 +1849,21 @@ impl From<std::io::Error> for PakError {
 }
 impl std::error::Error for PakError {}
 
+impl PakError {
+    /// Whether reading the archive or pak failed, which a corrupt archive would cause, rather
+    /// than the archive being readable but not laid out like a mod.
+    fn is_read_error(&self) -> bool {
+        matches!(
+            self,
+            PakError::ErrorReadingPak { .. }
+                | PakError::ZipError(_)
+                | PakError::RarError(_)
+                | PakError::SevenZipError(_)
+                | PakError::IoError(_)
+        )
+    }
+}
+
 impl std::fmt::Display for PakError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -132,113 +1878,773 @@ impl std::fmt::Display for PakError {
             ),
             PakError::StripPrefixError { e } => write!(f, "{self:?}: {e}"),
             PakError::ZipError(e) => write!(f, "{self:?}: {e}"),
+            PakError::RarError(e) => write!(f, "{self:?}: {e}"),
+            PakError::SevenZipError(e) => write!(f, "{self:?}: {e}"),
             PakError::IoError(e) => write!(f, "{self:?}: {e}"),
         }
     }
 }
 
-fn list_files(file: &mut zip::read::ZipFile) -> Result<Vec<String>, PakError> {
-    let mut buffer: Vec<u8> = vec![];
-    file.read_to_end(&mut buffer)?;
-    let mut cursor = std::io::Cursor::new(buffer);
-    let pak = repak::PakReader::new_any(&mut cursor, None)
-        .map_err(|e| PakError::ErrorReadingPak { e })?;
-    let mount_point = pak.mount_point();
+/// A file inside a pak with the SHA-1 of its contents.
+struct PakEntry {
+    path: String,
+    /// `None` for entries listed remotely without reading their contents
+    hash: Option<String>,
+    /// Uncompressed size, `None` for entries listed remotely
+    size: Option<u64>,
+    /// Class of the primary export for packages, see [`uasset::Package::primary_class`]
+    asset_class: Option<String>,
+    /// Localized strings of `.locres` and StringTable entries
+    strings: Vec<locres::LocresEntry>,
+    /// Objects of Wwise soundbanks and the ids of `.wem` media
+    audio: Vec<wwise::AudioObject>,
+    /// Objects of other packages a package imports
+    references: Vec<uasset::AssetRef>,
+    /// Rows of DataTable and CurveTable packages
+    rows: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Game path of a pak record, e.g. `FSD/Content/...`, with the pak's mount point applied and
+/// normalized as configured by `MOUNT_POINT_NORMALIZATION`.
+fn asset_path(mount_point: &str, record: &str) -> Result<String, PakError> {
+    let mut path = std::path::PathBuf::new();
+    path.push(mount_point);
+    path.push(record);
+    let path = mount::normalization()
+        .apply(&path)
+        .map_err(|e| PakError::StripPrefixError { e })?;
+    let path_str = path.to_str().ok_or_else(|| PakError::AssetPathError {
+        mount_point: mount_point.to_string(),
+        asset_path: record.to_string(),
+    })?;
+    Ok(path_str.to_owned())
+}
+
+/// The entries of a pak along with its raw mount point, which is recorded per modfile.
+struct PakListing {
+    mount_point: String,
+    entries: Vec<PakEntry>,
+}
+
+fn list_files(pak: &mut OpenPak) -> Result<PakListing, PakError> {
+    let mount_point = pak.pak.mount_point().to_string();
 
-    pak.files()
+    let entries = pak
+        .pak
+        .files()
         .map(|record| {
-            let mut path = std::path::PathBuf::new();
-            path.push(mount_point);
-            path.push(&record);
-            let path_str = path
-                .as_path()
-                .strip_prefix("../../..")
-                .map_err(|e| PakError::StripPrefixError { e })?
-                .to_str()
-                .ok_or_else(|| PakError::AssetPathError {
-                    mount_point: mount_point.to_string(),
-                    asset_path: record.to_string(),
-                })?;
-            Ok(path_str.to_owned())
+            let path = asset_path(&mount_point, &record)?;
+            let data = pak.get(&record)?;
+            let (asset_class, references) = read_package(&path, &data);
+            let strings = match (path.ends_with(".locres"), asset_class.as_deref()) {
+                (true, _) => locres::parse(&data),
+                (_, Some("StringTable")) => string_table(pak, &record, &data),
+                _ => Ok(vec![]),
+            }
+            .unwrap_or_else(|e| {
+                tracing::debug!(path, "Failed to read strings: {e:#}");
+                vec![]
+            });
+            let audio = wwise::parse(&path, &data).unwrap_or_else(|e| {
+                tracing::debug!(path, "Failed to read audio objects: {e:#}");
+                vec![]
+            });
+            let rows = match asset_class.as_deref() {
+                Some("DataTable" | "CurveTable") => table_rows(pak, &record, &data),
+                _ => Ok(Default::default()),
+            }
+            .unwrap_or_else(|e| {
+                tracing::debug!(path, "Failed to read table rows: {e:#}");
+                Default::default()
+            });
+            Ok(PakEntry {
+                path,
+                hash: Some(format!("{:x}", Sha1::digest(&data))),
+                size: Some(data.len() as u64),
+                asset_class,
+                strings,
+                audio,
+                references,
+                rows,
+            })
         })
-        .collect()
+        .collect::<Result<_, PakError>>()?;
+    Ok(PakListing {
+        mount_point,
+        entries,
+    })
+}
+
+/// Entries of a StringTable package, which live in its `.uexp`.
+fn string_table(
+    pak: &mut OpenPak,
+    record: &str,
+    uasset: &[u8],
+) -> Result<Vec<locres::LocresEntry>> {
+    let uexp = match record.strip_suffix(".uasset") {
+        Some(stem) => Some(pak.get(&format!("{stem}.uexp"))?),
+        None => None,
+    };
+    let package = uasset::Package::parse(uasset, uexp.as_deref())?;
+    let export = package
+        .exports
+        .iter()
+        .find(|e| package.export_class(e) == Some("StringTable"))
+        .context("no StringTable export")?;
+    let (namespace, entries) = package.string_table(export)?;
+    Ok(entries
+        .into_iter()
+        .map(|(key, text)| locres::LocresEntry {
+            namespace: namespace.clone(),
+            key,
+            text,
+        })
+        .collect())
+}
+
+/// Rows of a DataTable or CurveTable package, which live in its `.uexp`.
+fn table_rows(
+    pak: &mut OpenPak,
+    record: &str,
+    uasset: &[u8],
+) -> Result<serde_json::Map<String, serde_json::Value>> {
+    let uexp = match record.strip_suffix(".uasset") {
+        Some(stem) => Some(pak.get(&format!("{stem}.uexp"))?),
+        None => None,
+    };
+    let package = uasset::Package::parse(uasset, uexp.as_deref())?;
+    package
+        .exports
+        .iter()
+        .find_map(|export| package.table_rows(export))
+        .context("no table export")?
+}
+
+/// Class of the primary export of a `.uasset` or `.umap` and the references in its import table.
+/// Only the header is needed so the `.uexp` is not read.
+fn read_package(path: &str, data: &[u8]) -> (Option<String>, Vec<uasset::AssetRef>) {
+    let p = Path::new(path);
+    if !matches!(
+        p.extension().and_then(std::ffi::OsStr::to_str),
+        Some("uasset" | "umap")
+    ) {
+        return (None, vec![]);
+    }
+    let Some(name) = p.file_stem().and_then(std::ffi::OsStr::to_str) else {
+        return (None, vec![]);
+    };
+    match uasset::Package::parse(data, None) {
+        Ok(package) => (
+            package.primary_class(name).map(str::to_string),
+            package.references(),
+        ),
+        Err(e) => {
+            tracing::debug!(path, "Failed to read package: {e:#}");
+            (None, vec![])
+        }
+    }
+}
+
+/// Counts of what a sync run did, reported once at the end of the command.
+#[derive(Debug, Default, Serialize)]
+struct SyncSummary {
+    mods: u64,
+    modfiles_updated: u64,
+    downloaded: u64,
+    analyzed: u64,
+    /// Unreleased modfiles newly indexed with `--drafts`
+    drafts: u64,
+    /// Comments indexed with `--with-comments`
+    comments: u64,
+    /// Logos and thumbnails downloaded into the media cache
+    media_cached: u64,
+    /// Archives of superseded modfiles deleted to stay under `MAX_STORE_SIZE`
+    evicted: u64,
+    /// Mods skipped because an interrupted run already synced them
+    resumed: u64,
+    /// Stored archives that failed to analyze, did not match their md5 and were downloaded again
+    redownloaded: u64,
+    analysis_errors: Vec<String>,
+    /// Ids of mods seen for the first time
+    new_mods: Vec<u32>,
+    /// Ids of mods whose current modfile changed
+    changed_modfiles: Vec<u32>,
+}
+
+impl std::fmt::Display for SyncSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} mods synced, {} resumed from an interrupted run, {} modfiles updated, {} drafts indexed, {} comments indexed, {} downloaded, {} images cached, {} analyzed, {} corrupt archives downloaded again, {} analysis errors, {} archives evicted",
+            self.mods,
+            self.resumed,
+            self.modfiles_updated,
+            self.drafts,
+            self.comments,
+            self.downloaded,
+            self.media_cached,
+            self.analyzed,
+            self.redownloaded,
+            self.analysis_errors.len(),
+            self.evicted
+        )
+    }
+}
+
+/// Send notifications for a finished sync. The index is already updated at this point so a
+/// failure is only logged rather than failing the command.
+async fn notify(pool: &AnyPool, summary: &SyncSummary) {
+    if let Err(e) = notify::notify_sync(pool, summary).await {
+        error!("Failed to send notifications: {e:#}");
+    }
 }
 
-async fn get_mods(pool: &SqlitePool) -> Result<()> {
-    let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+async fn sync(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    options: SyncOptions,
+    filters: &api::ModFilters,
+) -> Result<SyncSummary> {
+    let mut summary = SyncSummary::default();
+
+    get_mods(multi_bar, pool, options, filters, &mut summary).await?;
+
+    // pick up modfiles whose analysis failed or was interrupted in a previous run
+    let pending: Vec<(i64, String)> = sqlx::query_as(
+        "SELECT id_modfile, hash_md5 FROM modfile
+         WHERE NOT EXISTS (SELECT 1 FROM pack_file WHERE pack_file.id_modfile = modfile.id_modfile)",
+    )
+    .fetch_all(pool)
+    .await?;
+    let pending = pending
+        .into_iter()
+        .filter(|(_, md5)| download::archive_path(md5).exists())
+        .collect::<Vec<_>>();
+    if !pending.is_empty() && !daemon::shutdown_requested() {
+        let bar = multi_bar.add(ProgressBar::new(pending.len().try_into().unwrap()));
+        analyze_modfiles(multi_bar, pool, &bar, pending, &mut summary).await?;
+        bar.finish();
+    }
+
+    classify::refresh(pool).await?;
+    game::flag_overrides(pool, false).await?;
+    flatten::refresh(pool).await?;
+    store::refresh(pool).await?;
+    summary.evicted = store::evict(pool).await?.objects;
+    trash::purge_expired(pool).await?;
+
+    events::emit(events::Event::SyncFinished {
+        mods: summary.mods,
+        downloaded: summary.downloaded,
+        analyzed: summary.analyzed,
+        analysis_errors: summary.analysis_errors.len(),
+    });
+    Ok(summary)
+}
+
+/// Items of a channel as a stream.
+fn receive<T>(rx: tokio::sync::mpsc::Receiver<T>) -> impl futures::Stream<Item = T> {
+    futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    })
+}
+
+/// Sync the mods matching `filters`, every mod if empty. Only unfiltered runs are checkpointed
+/// and resumed, as a filtered run only covers part of the mods.
+async fn get_mods(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    options: SyncOptions,
+    filters: &api::ModFilters,
+    summary: &mut SyncSummary,
+) -> Result<()> {
+    use futures::stream::StreamExt;
 
-    let modio = Modio::new(
-        Credentials::with_token("".to_string(), &env::var("MODIO_ACCESS_TOKEN")?),
-        client,
-    )?;
+    let modio = api::client()?;
 
-    let drg = 2475;
+    //let mods = modio.game(api::DRG).mods().search(Filter::default().limit(1)).collect().await?;
 
-    //let mods = modio.game(drg).mods().search(Filter::default().limit(1)).collect().await?;
+    let mut mods = api::search_mods(&modio, filters).await?;
+    priority::prioritize(pool, &modio, &priority::rules_from_env()?, &mut mods).await?;
 
-    println!("Grabbing mod list...");
-    let filter = modio::mods::filters::Visible::_in(vec![0, 1]);
-    let mods = modio.game(drg).mods().search(filter).collect().await?;
-    println!("Mod list obtained");
+    let checkpointed = filters.is_empty();
+    if options.restart && checkpointed {
+        checkpoint::clear(pool).await?;
+    }
+    let completed = if checkpointed {
+        checkpoint::completed(pool).await?
+    } else {
+        Default::default()
+    };
 
-    let multi_bar = indicatif::MultiProgress::new();
+    events::emit(events::Event::SyncStarted { mods: mods.len() });
     let mod_bar = multi_bar.add(ProgressBar::new(mods.len().try_into().unwrap()));
+    let ids = mods.iter().map(|m| m.id).collect::<Vec<_>>();
+    let mut pending = vec![];
     for m in mods {
-        //println!("{}. {} {}", m.id, m.name, m.name_id);
-        update_mod(&multi_bar, pool, &modio, m).await?;
+        if checkpoint::is_done(&completed, &m) {
+            summary.resumed += 1;
+            mod_bar.inc(1);
+        } else {
+            pending.push(m);
+        }
+    }
+
+    // Metadata, downloads and analysis run as separate stages connected by channels, so the
+    // network and the CPU are busy at the same time. Mods leave every stage in order and are
+    // stored one at a time, as the index has a single writer
+    let download_jobs = options.download_jobs.max(1);
+    let analysis_jobs = match options.analysis_jobs {
+        Some(jobs) => jobs.max(1),
+        None => std::thread::available_parallelism()?.get(),
+    };
+    let (plan_tx, plan_rx) = tokio::sync::mpsc::channel(download_jobs);
+    let (fetch_tx, fetch_rx) = tokio::sync::mpsc::channel(analysis_jobs);
+    let (analysis_tx, mut analysis_rx) = tokio::sync::mpsc::channel(analysis_jobs);
+
+    let planner = tokio::spawn({
+        let pool = pool.clone();
+        async move {
+            for m in pending {
+                if daemon::shutdown_requested() {
+                    info!("Stopping early, shutdown requested");
+                    break;
+                }
+                events::emit(events::Event::ModStarted {
+                    id_mod: m.id,
+                    name_id: &m.name_id,
+                });
+                let span = mod_span(&m);
+                let plan = plan_mod(&pool, m).instrument(span).await;
+                if plan_tx.send(plan).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    let downloader = tokio::spawn({
+        let (multi_bar, pool, modio) = (multi_bar.clone(), pool.clone(), modio.clone());
+        async move {
+            let (multi_bar, pool, modio) = (&multi_bar, &pool, &modio);
+            let mut fetched = receive(plan_rx)
+                .map(|plan: Result<ModPlan>| async move {
+                    let plan = plan?;
+                    let span = mod_span(&plan.m);
+                    fetch_modfile(
+                        multi_bar,
+                        pool,
+                        modio,
+                        plan,
+                        !options.no_keep,
+                        options.metadata_only,
+                    )
+                    .instrument(span)
+                    .await
+                })
+                .buffered(download_jobs);
+            while let Some(f) = fetched.next().await {
+                if fetch_tx.send(f).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    let analyzer = tokio::spawn(async move {
+        let mut analyzed = receive(fetch_rx)
+            .map(|fetched: Result<FetchedMod>| async move {
+                let fetched = fetched?;
+                let span = mod_span(&fetched.plan.m);
+                analyze_fetched(fetched).instrument(span).await
+            })
+            .buffered(analysis_jobs);
+        while let Some(a) = analyzed.next().await {
+            if analysis_tx.send(a).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(analyzed) = analysis_rx.recv().await {
+        let analyzed = analyzed?;
+        let (id_mod, date_updated) = (analyzed.plan.m.id, analyzed.plan.m.date_updated);
+        let span = mod_span(&analyzed.plan.m);
+        store_mod(multi_bar, pool, analyzed, summary)
+            .instrument(span)
+            .await?;
+        if checkpointed {
+            checkpoint::record(pool, id_mod, date_updated).await?;
+        }
+        events::emit(events::Event::ModFinished { id_mod });
+        summary.mods += 1;
         mod_bar.inc(1);
     }
+    planner.await?;
+    downloader.await?;
+    analyzer.await?;
+    if daemon::shutdown_requested() {
+        return Ok(());
+    }
     mod_bar.finish();
+    if checkpointed {
+        checkpoint::clear(pool).await?;
+    }
+
+    if options.drafts {
+        drafts::index_drafts(multi_bar, pool, &modio, summary).await?;
+    }
+    if options.comments {
+        comments::index_comments(multi_bar, pool, &modio, &ids, summary).await?;
+    }
 
     Ok(())
 }
 
+/// Sync a single mod, given as an id or name_id, the same way a full sync would without listing
+/// the rest of the catalog. A name_id not yet in the index is looked up on mod.io, and anything
+/// else matched loosely against the names of indexed mods.
+async fn fetch_mod(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    reference: &str,
+    keep: bool,
+) -> Result<SyncSummary> {
+    let modio = api::client()?;
+
+    let id_mod = match reference.parse::<u32>() {
+        Ok(id) => id,
+        Err(_) => match lookup::find_mod(pool, reference).await? {
+            Some(id) => id as u32,
+            None => {
+                use modio::filter::prelude::*;
+                use modio::mods::filters::{NameId, Visible};
+                let found = modio
+                    .game(api::DRG)
+                    .mods()
+                    .search(NameId::eq(reference).and(Visible::_in(vec![0, 1])))
+                    .first()
+                    .await?;
+                match found {
+                    Some(m) => m.id,
+                    // not an exact name_id anywhere, maybe a misspelled indexed mod
+                    None => lookup::resolve_mod(pool, reference)
+                        .await
+                        .with_context(|| format!("no mod on mod.io has name_id {reference:?}"))?
+                        as u32,
+                }
+            }
+        },
+    };
+    let m = modio
+        .game(api::DRG)
+        .mod_(id_mod)
+        .get()
+        .await
+        .with_context(|| format!("failed to fetch mod {id_mod}"))?;
+
+    let mut summary = SyncSummary::default();
+    let span = info_span!("mod", id = m.id, name_id = %m.name_id);
+    update_mod(multi_bar, pool, &modio, m, keep, false, &mut summary)
+        .instrument(span)
+        .await?;
+    summary.mods += 1;
+
+    // the modfile may be unchanged but never successfully analyzed
+    let pending: Vec<(i64, String)> = sqlx::query_as(
+        "SELECT modfile.id_modfile, hash_md5
+         FROM mod JOIN modfile ON modfile.id_modfile = mod.id_modfile
+         WHERE mod.id_mod = $1
+           AND NOT EXISTS (SELECT 1 FROM pack_file WHERE pack_file.id_modfile = modfile.id_modfile)",
+    )
+    .bind(i64::from(id_mod))
+    .fetch_all(pool)
+    .await?;
+    let pending = pending
+        .into_iter()
+        .filter(|(_, md5)| download::archive_path(md5).exists())
+        .collect::<Vec<_>>();
+    if !pending.is_empty() {
+        let bar = multi_bar.add(ProgressBar::new(pending.len().try_into().unwrap()));
+        analyze_modfiles(multi_bar, pool, &bar, pending, &mut summary).await?;
+        bar.finish();
+    }
+
+    classify::refresh(pool).await?;
+    game::flag_overrides(pool, false).await?;
+    flatten::refresh(pool).await?;
+    store::refresh(pool).await?;
+
+    Ok(summary)
+}
+
+/// What syncing a mod involves, decided from the index before anything is fetched.
+struct ModPlan {
+    m: modio::mods::Mod,
+    /// Whether the mod is not indexed yet
+    new: bool,
+    /// Whether its current modfile differs from the indexed one
+    modfile_changed: bool,
+    /// Whether the new modfile is not downloaded for being flagged by the virus scan
+    flagged: bool,
+}
+
+/// A mod that went through the download stage.
+struct FetchedMod {
+    plan: ModPlan,
+    /// The pak of the new modfile if it was listed with range requests instead of downloading it,
+    /// or from the partial file while it downloaded
+    listing: Option<Result<PakListing, PakError>>,
+    /// Whether the new modfile's archive was downloaded rather than already stored
+    downloaded: bool,
+    /// Whether another worker is downloading the new modfile, which leaves the mod to it
+    claimed_elsewhere: bool,
+    /// Modfile and md5 of an archive downloaded only to be analyzed, deleted again afterwards
+    discard: Option<(i64, String)>,
+}
+
+/// A mod that went through the analysis stage.
+struct AnalyzedMod {
+    plan: ModPlan,
+    /// Listing of the new modfile's pak, `None` if there is nothing to analyze
+    listing: Option<Result<PakListing, PakError>>,
+    /// Whether the archive was downloaded
+    downloaded: bool,
+    claimed_elsewhere: bool,
+    discard: Option<(i64, String)>,
+}
+
+fn mod_span(m: &modio::mods::Mod) -> tracing::Span {
+    info_span!("mod", id = m.id, name_id = %m.name_id)
+}
+
+async fn plan_mod(pool: &AnyPool, m: modio::mods::Mod) -> Result<ModPlan> {
+    let modfile: Option<Option<i64>> =
+        sqlx::query_scalar("SELECT id_modfile FROM mod WHERE id_mod = $1")
+            .bind(i64::from(m.id))
+            .fetch_optional(pool)
+            .await?;
+    let new = modfile.is_none();
+    let modfile = modfile.flatten().map(|id| id as u32);
+    let modfile_changed = m.modfile.as_ref().map(|f| f.id) != modfile;
+    // indexed without pack files, as a remote listing reads from the archive as well
+    let flagged = modfile_changed && m.modfile.as_ref().is_some_and(virus::refused);
+    if flagged {
+        warn!(
+            id_modfile = m.modfile.as_ref().map(|f| f.id),
+            "Not downloading modfile flagged by the virus scan, see --allow-flagged"
+        );
+    }
+    Ok(ModPlan {
+        m,
+        new,
+        modfile_changed,
+        flagged,
+    })
+}
+
+/// Download the new modfile of a planned mod, listing its pak from the partial file as it is
+/// written, or list it remotely with `remote` set. With `keep`
+/// unset an archive downloaded here is deleted again once the mod is stored.
+async fn fetch_modfile(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    modio: &Modio,
+    plan: ModPlan,
+    keep: bool,
+    remote: bool,
+) -> Result<FetchedMod> {
+    // archive mode keeps every archive, so it has to be downloaded and stays
+    let keep = keep || archive::enabled();
+    let remote = remote && !archive::enabled();
+
+    let mut fetched = FetchedMod {
+        plan,
+        listing: None,
+        downloaded: false,
+        claimed_elsewhere: false,
+        discard: None,
+    };
+    let file = match &fetched.plan.m.modfile {
+        Some(file) if fetched.plan.modfile_changed && !fetched.plan.flagged => file,
+        _ => return Ok(fetched),
+    };
+    if remote && !download::archive_path(&file.filehash.md5).exists() {
+        match remote::list_modfile(file).await {
+            Ok(listing) => {
+                download::mark_discarded(pool, i64::from(file.id)).await?;
+                fetched.listing = Some(Ok(listing));
+                return Ok(fetched);
+            }
+            Err(e) => warn!(id_modfile = file.id, "Downloading instead: {e:#}"),
+        }
+    }
+    // The pak is listed from the partial file as it is written instead of reading the archive
+    // back once stored. The zip's central directory and the pak's index are both at the end, so
+    // the listing gets going as the last bytes arrive, but in this task and from what was just
+    // written rather than after the archive went through the analysis stage
+    let partial = std::sync::Arc::new(pak_source::PartialDownload::default());
+    let listing = tokio::task::spawn_blocking({
+        let (partial, size) = (partial.clone(), file.filesize);
+        move || {
+            let archive = pak_source::GrowingFile::open(partial, size)?;
+            list_files(&mut read_zip_pak(std::io::BufReader::new(archive))?)
+        }
+    });
+    match download::download_modfile_shared(multi_bar, pool, modio, file, &partial).await? {
+        // the listing gives up as nothing is downloaded, the stored archive is listed instead
+        download::Downloaded::Stored => {}
+        download::Downloaded::Downloaded => {
+            fetched.downloaded = true;
+            match listing.await? {
+                Ok(listing) => fetched.listing = Some(Ok(listing)),
+                // e.g. the archive is not the size mod.io reported, it is listed once stored
+                Err(e) => tracing::debug!(
+                    id_modfile = file.id,
+                    "Listing while downloading failed: {e}"
+                ),
+            }
+        }
+        download::Downloaded::ClaimedElsewhere => fetched.claimed_elsewhere = true,
+    }
+    if fetched.downloaded && !keep {
+        fetched.discard = Some((i64::from(file.id), file.filehash.md5.clone()));
+    }
+    Ok(fetched)
+}
+
+/// List the pak of a fetched mod's new modfile from its stored archive, unless it was listed while
+/// fetching it.
+async fn analyze_fetched(fetched: FetchedMod) -> Result<AnalyzedMod> {
+    let FetchedMod {
+        plan,
+        listing,
+        downloaded,
+        claimed_elsewhere,
+        discard,
+    } = fetched;
+    let listing = match (&plan.m.modfile, listing) {
+        (Some(_), Some(listing)) => Some(listing),
+        (Some(file), None) if plan.modfile_changed && !plan.flagged && !claimed_elsewhere => {
+            let path = download::archive_path(&file.filehash.md5);
+            Some(tokio::task::spawn_blocking(move || list_zip_files(&path)).await?)
+        }
+        _ => None,
+    };
+    Ok(AnalyzedMod {
+        plan,
+        listing,
+        downloaded,
+        claimed_elsewhere,
+        discard,
+    })
+}
+
+/// Index `m` and its current modfile. With `keep` unset an archive downloaded here is deleted
+/// again once analyzed. With `remote` set the pak is listed with range requests if possible
+/// instead of downloading the archive.
 async fn update_mod(
     multi_bar: &indicatif::MultiProgress,
-    pool: &SqlitePool,
+    pool: &AnyPool,
     modio: &Modio,
     m: modio::mods::Mod,
+    keep: bool,
+    remote: bool,
+    summary: &mut SyncSummary,
+) -> Result<()> {
+    let plan = plan_mod(pool, m).await?;
+    let fetched = fetch_modfile(multi_bar, pool, modio, plan, keep, remote).await?;
+    let analyzed = analyze_fetched(fetched).await?;
+    store_mod(multi_bar, pool, analyzed, summary).await
+}
+
+/// Write an analyzed mod to the index, the last stage of syncing it.
+async fn store_mod(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    analyzed: AnalyzedMod,
+    summary: &mut SyncSummary,
 ) -> Result<()> {
+    let AnalyzedMod {
+        plan:
+            ModPlan {
+                m,
+                new,
+                modfile_changed,
+                ..
+            },
+        listing,
+        downloaded,
+        claimed_elsewhere,
+        discard,
+    } = analyzed;
+    if claimed_elsewhere {
+        // stored without its pack files it would not be analyzed again, the worker downloading
+        // the modfile or the next sync indexes it
+        info!("Skipping mod whose modfile another worker is downloading");
+        return Ok(());
+    }
+    if new {
+        summary.new_mods.push(m.id);
+    }
+    if modfile_changed {
+        summary.changed_modfiles.push(m.id);
+    }
+    if downloaded {
+        summary.downloaded += 1;
+    }
+    let mut retry = None;
+
     let mut tx = pool.begin().await?;
 
     //let id_modfile: Option<u32> = m.modfile.as_ref().map(|f| f.id);
-    sqlx::query!(
-        "INSERT INTO mod(id_mod, name, name_id, summary, description)
-                 VALUES (?, ?, ?, ?, ?)
+    mod_event::record_changes(&mut tx, &m).await?;
+    sqlx::query(
+        "INSERT INTO mod(id_mod, name, name_id, summary, description,
+                         ratings_positive, ratings_negative, ratings_display,
+                         maturity_option, community_options, status, visible)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
                  ON CONFLICT(id_mod) DO
                     UPDATE SET
                         name = excluded.name,
                         name_id = excluded.name_id,
                         summary = excluded.summary,
-                        description = excluded.summary;",
-        m.id,
-        m.name,
-        m.name_id,
-        m.summary,
-        m.description
+                        description = excluded.description,
+                        ratings_positive = excluded.ratings_positive,
+                        ratings_negative = excluded.ratings_negative,
+                        ratings_display = excluded.ratings_display,
+                        maturity_option = excluded.maturity_option,
+                        community_options = excluded.community_options,
+                        status = excluded.status,
+                        visible = excluded.visible;",
     )
+    .bind(i64::from(m.id))
+    .bind(&m.name)
+    .bind(&m.name_id)
+    .bind(&m.summary)
+    .bind(&m.description)
+    .bind(i64::from(m.stats.ratings.positive))
+    .bind(i64::from(m.stats.ratings.negative))
+    .bind(&m.stats.ratings.display_text)
+    .bind(i64::from(m.maturity_option.bits()))
+    .bind(i64::from(m.community_options.bits()))
+    .bind(i64::from(m.status.get()))
+    .bind(i64::from(m.visible.get()))
     .execute(&mut *tx)
     .await?;
 
-    let modfile = sqlx::query!("SELECT id_modfile FROM mod WHERE id_mod = ?", m.id)
-        .fetch_one(&mut *tx)
-        .await?
-        .id_modfile
-        .map(|id| id as u32);
+    media::index_gallery(&mut tx, m.id, &m.media).await?;
+    metadata::record(&mut tx, &m).await?;
+    tags::record(&mut tx, &m).await?;
 
-    if m.modfile.as_ref().map(|f| f.id) != modfile {
+    if modfile_changed {
         if let Some(file) = m.modfile {
-            let path = Path::new("mods").join(format!("{}.zip", file.filehash.md5));
+            let path = download::archive_path(&file.filehash.md5);
 
-            let id_modfile = file.id;
-            let date = chrono::DateTime::<chrono::Utc>::from_utc(
-                chrono::NaiveDateTime::from_timestamp_opt(file.date_added.try_into().unwrap(), 0)
-                    .unwrap(),
-                chrono::Utc,
-            )
-            .to_rfc3339();
-            sqlx::query!("INSERT INTO modfile(id_modfile, id_mod, date_added, hash_md5, filename, version, changelog)
-                         VALUES (?, ?, ?, ?, ?, ?, ?)
+            let id_modfile = i64::from(file.id);
+            summary.modfiles_updated += 1;
+            let date = api::timestamp(file.date_added)?;
+            sqlx::query("INSERT INTO modfile(id_modfile, id_mod, date_added, hash_md5, filename, version, changelog, draft,
+                                             virus_status, virus_positive)
+                         VALUES ($1, $2, $3, $4, $5, $6, $7, 0, $8, $9)
                          ON CONFLICT(id_modfile) DO
                             UPDATE SET
                                 id_modfile = excluded.id_modfile,
@@ -247,165 +2653,432 @@ async fn update_mod(
                                 hash_md5 = excluded.hash_md5,
                                 filename = excluded.filename,
                                 version = excluded.version,
-                                changelog = excluded.changelog;", id_modfile, m.id, date, file.filehash.md5, file.filename, file.version, file.changelog).execute(&mut *tx).await?;
-
-            sqlx::query!(
-                "UPDATE mod SET id_modfile = ? WHERE id_mod = ?",
-                id_modfile,
-                m.id
-            )
-            .execute(&mut *tx)
-            .await?;
-
-            if !std::path::Path::new(&path).exists() {
-                multi_bar.println(format!("Downloading mod {}", m.id))?;
-                let download_bar = multi_bar.add(indicatif::ProgressBar::new(file.filesize));
-                download_bar.set_style(indicatif::ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")?.progress_chars("#>-"));
-
-                let mut stream = Box::pin(
-                    modio
-                        .download(DownloadAction::FileObj(Box::new(file)))
-                        .stream(),
-                );
-                let mut file = tokio::fs::OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&path)
-                    .await?;
-                while let Some(bytes) = stream.try_next().await? {
-                    file.write_all(&bytes).await?;
-                    download_bar.inc(bytes.len() as u64);
-                }
-
-                multi_bar.remove(&download_bar);
-            }
+                                changelog = excluded.changelog,
+                                draft = excluded.draft,
+                                virus_status = excluded.virus_status,
+                                virus_positive = excluded.virus_positive;")
+                .bind(id_modfile)
+                .bind(i64::from(m.id))
+                .bind(date)
+                .bind(&file.filehash.md5)
+                .bind(&file.filename)
+                .bind(&file.version)
+                .bind(&file.changelog)
+                .bind(i64::from(file.virus_status))
+                .bind(i64::from(file.virus_positive))
+                .execute(&mut *tx)
+                .await?;
 
-            sqlx::query!("DELETE FROM pack_file WHERE id_modfile = ?", id_modfile)
+            sqlx::query("UPDATE mod SET id_modfile = $1 WHERE id_mod = $2")
+                .bind(id_modfile)
+                .bind(i64::from(m.id))
                 .execute(&mut *tx)
                 .await?;
+            platform::record(&mut tx, &file).await?;
 
-            let res = list_zip_files(&path);
-            match res {
-                Ok(files) => {
-                    for file in files {
-                        let path = std::path::Path::new(&file);
-                        let extension = path.extension().and_then(std::ffi::OsStr::to_str);
-                        let name = path.file_stem().and_then(std::ffi::OsStr::to_str);
-                        let path_no_extension = if let Some(ext) = extension {
-                            file.strip_suffix(&ext).unwrap()
-                        } else {
-                            &file
-                        };
-                        sqlx::query!("INSERT INTO pack_file(id_modfile, path, path_no_extension, extension, name)
-                                     VALUES (?, ?, ?, ?, ?)", id_modfile, file, path_no_extension, extension, name).execute(&mut *tx).await?;
-                    }
+            if !matches!(listing, Some(Ok(_))) {
+                clear_pack_files(&mut tx, id_modfile).await?;
+            }
+            match listing {
+                None => {}
+                Some(Ok(listing)) => {
+                    events::emit(events::Event::AnalysisFinished {
+                        id_modfile,
+                        files: listing.entries.len(),
+                    });
+                    let files = pack_files(listing.entries);
+                    replace_pack_files(&mut tx, id_modfile, &listing.mount_point, &files).await?;
+                    summary.analyzed += 1;
                 }
-                Err(e) => {
-                    multi_bar.println(format!("Error analyzing {}: {}", m.id, e))?;
+                // retried once the transaction is committed, after checking the archive
+                Some(Err(e)) if e.is_read_error() && path.exists() => {
+                    warn!(id_modfile, "Error analyzing, checking the archive: {e}");
+                    retry = Some((id_modfile, file.filehash.md5.clone()));
+                }
+                Some(Err(e)) => {
+                    error!("Error analyzing: {e}");
+                    let message = format!("Error analyzing {}: {}", m.id, e);
+                    events::emit(events::Event::Error {
+                        id_mod: Some(m.id),
+                        id_modfile: Some(id_modfile),
+                        message: &message,
+                    });
+                    summary.analysis_errors.push(message);
                 }
             }
         } else {
-            sqlx::query!("UPDATE mod SET id_modfile = NULL WHERE id_mod = ?", m.id)
+            sqlx::query("UPDATE mod SET id_modfile = NULL WHERE id_mod = $1")
+                .bind(i64::from(m.id))
                 .execute(&mut *tx)
                 .await?;
         }
     }
 
     tx.commit().await?;
+
+    if let Some(modfile) = retry {
+        analyze_modfiles(
+            multi_bar,
+            pool,
+            &ProgressBar::hidden(),
+            vec![modfile],
+            summary,
+        )
+        .await?;
+    }
+    if let Some((id_modfile, md5)) = discard {
+        download::discard(pool, id_modfile, &md5).await?;
+    }
+
+    // after the commit so the media rows have a mod to refer to
+    summary.media_cached += media::cache_logo(pool, m.id, &m.logo).await;
     Ok(())
 }
 
-async fn update_pack_files_local(pool: &SqlitePool) -> Result<()> {
-    let modfiles = sqlx::query!("SELECT id_modfile, hash_md5 FROM modfile")
+async fn update_pack_files_local(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+) -> Result<SyncSummary> {
+    let modfiles: Vec<(i64, String)> = sqlx::query_as("SELECT id_modfile, hash_md5 FROM modfile")
         .fetch_all(pool)
         .await?;
 
+    let mut summary = SyncSummary::default();
+    let bar = multi_bar.add(ProgressBar::new(modfiles.len().try_into().unwrap()));
+    analyze_modfiles(multi_bar, pool, &bar, modfiles, &mut summary).await?;
+    bar.finish();
+    classify::refresh(pool).await?;
+    game::flag_overrides(pool, false).await?;
+
+    Ok(summary)
+}
+
+/// Analyze the stored archives of `modfiles` in parallel and replace their pack files. An archive
+/// that cannot be read is checked against its md5 and downloaded again and retried if corrupt.
+async fn analyze_modfiles(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    bar: &ProgressBar,
+    modfiles: Vec<(i64, String)>,
+    summary: &mut SyncSummary,
+) -> Result<()> {
     use futures::stream::StreamExt;
 
-    let bar = indicatif::ProgressBar::new(modfiles.len().try_into().unwrap());
-    let mut stream = futures::stream::iter(modfiles.into_iter().map(|modfile| {
+    let mut stream = futures::stream::iter(modfiles.into_iter().map(|(id_modfile, hash_md5)| {
         tokio::task::spawn_blocking(move || {
-            (
-                modfile.id_modfile,
-                get_pack_files(modfile.id_modfile, modfile.hash_md5),
-            )
+            let pack_files = get_pack_files(&hash_md5);
+            (id_modfile, hash_md5, pack_files)
         })
     }))
     .buffer_unordered(std::thread::available_parallelism()?.get());
 
-    use sqlx::{Executor, Statement};
-    let delete = pool
-        .prepare("DELETE FROM pack_file WHERE id_modfile = ?")
-        .await?;
-    let insert = pool.prepare("INSERT INTO pack_file(id_modfile, path, path_no_extension, extension, name) VALUES (?, ?, ?, ?, ?)").await?;
-
     while let Some(item) = stream.next().await {
-        let (id, pack_files) = item?;
+        if daemon::shutdown_requested() {
+            info!("Stopping analysis early, shutdown requested");
+            break;
+        }
+        let (id, hash_md5, mut pack_files) = item?;
+        let corrupt = pack_files.as_ref().is_err_and(|e| {
+            e.downcast_ref::<PakError>()
+                .is_some_and(PakError::is_read_error)
+        });
+        if corrupt {
+            match download::redownload_corrupt(multi_bar, pool, id, &hash_md5).await {
+                Ok(true) => {
+                    summary.redownloaded += 1;
+                    pack_files =
+                        tokio::task::spawn_blocking(move || get_pack_files(&hash_md5)).await?;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    error!(id_modfile = id, "Failed to replace corrupt archive: {e:#}");
+                    pack_files = pack_files.context(format!("{e:#}"));
+                }
+            }
+        }
         match pack_files {
-            Ok(pack_files) => {
+            Ok((mount_point, pack_files)) => {
+                events::emit(events::Event::AnalysisFinished {
+                    id_modfile: id,
+                    files: pack_files.len(),
+                });
                 let mut tx = pool.begin().await?;
-                delete.query().bind(id).execute(&mut *tx).await?;
-                for file in pack_files {
-                    insert
-                        .query()
-                        .bind(file.id_modfile)
-                        .bind(file.path)
-                        .bind(file.path_no_extension)
-                        .bind(file.extension)
-                        .bind(file.name)
-                        .execute(&mut *tx)
-                        .await?;
-                }
+                replace_pack_files(&mut tx, id, &mount_point, &pack_files).await?;
                 tx.commit().await?;
+                summary.analyzed += 1;
             }
             Err(err) => {
-                bar.println(format!("Error analyzing modfile_id {id}: {err}"));
+                error!(id_modfile = id, "Error analyzing: {err:#}");
+                let message = format!("Error analyzing modfile_id {id}: {err:#}");
+                events::emit(events::Event::Error {
+                    id_mod: None,
+                    id_modfile: Some(id),
+                    message: &message,
+                });
+                summary.analysis_errors.push(message);
             }
         }
         bar.inc(1);
     }
-    bar.finish();
 
     Ok(())
 }
 
 struct PackFile {
-    id_modfile: i64,
     path: String,
     path_no_extension: String,
     name: Option<String>,
     extension: Option<String>,
+    hash: Option<String>,
+    asset_class: Option<String>,
+    strings: Vec<locres::LocresEntry>,
+    audio: Vec<wwise::AudioObject>,
+    references: Vec<uasset::AssetRef>,
+    rows: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Delete the pack files of a modfile along with their strings, audio objects, references and
+/// rows.
+async fn clear_pack_files(conn: &mut sqlx::AnyConnection, id_modfile: i64) -> Result<()> {
+    for table in [
+        "pack_file_string",
+        "audio_object",
+        "asset_ref",
+        "data_table_row",
+        "pack_file",
+    ] {
+        sqlx::query(&format!("DELETE FROM {table} WHERE id_modfile = $1"))
+            .bind(id_modfile)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Replace the pack files of a modfile with the analyzed `files` and record the raw mount point
+/// of its pak.
+async fn replace_pack_files(
+    conn: &mut sqlx::AnyConnection,
+    id_modfile: i64,
+    mount_point: &str,
+    files: &[PackFile],
+) -> Result<()> {
+    clear_pack_files(conn, id_modfile).await?;
+    sqlx::query("UPDATE modfile SET mount_point = $1 WHERE id_modfile = $2")
+        .bind(mount_point)
+        .bind(id_modfile)
+        .execute(&mut *conn)
+        .await?;
+    for file in files {
+        sqlx::query(
+            "INSERT INTO pack_file(id_modfile, path, path_no_extension, extension, name, hash, asset_class)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(id_modfile)
+        .bind(&file.path)
+        .bind(&file.path_no_extension)
+        .bind(&file.extension)
+        .bind(&file.name)
+        .bind(&file.hash)
+        .bind(&file.asset_class)
+        .execute(&mut *conn)
+        .await?;
+        insert_strings(conn, id_modfile, &file.path, &file.strings).await?;
+        insert_audio(conn, id_modfile, &file.path, &file.audio).await?;
+        insert_references(conn, id_modfile, &file.path, &file.references).await?;
+        insert_rows(conn, id_modfile, &file.path, &file.rows).await?;
+    }
+    Ok(())
+}
+
+async fn insert_strings(
+    conn: &mut sqlx::AnyConnection,
+    id_modfile: i64,
+    path: &str,
+    strings: &[locres::LocresEntry],
+) -> Result<()> {
+    for entry in strings {
+        sqlx::query(
+            "INSERT INTO pack_file_string(id_modfile, path, namespace, key, text, language)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(id_modfile)
+        .bind(path)
+        .bind(&entry.namespace)
+        .bind(&entry.key)
+        .bind(&entry.text)
+        .bind(locres::culture(path))
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn insert_audio(
+    conn: &mut sqlx::AnyConnection,
+    id_modfile: i64,
+    path: &str,
+    objects: &[wwise::AudioObject],
+) -> Result<()> {
+    for object in objects {
+        sqlx::query(
+            "INSERT INTO audio_object(id_modfile, path, kind, id_object, id_source, localized)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(id_modfile)
+        .bind(path)
+        .bind(object.kind)
+        .bind(i64::from(object.id))
+        .bind(object.source.map(i64::from))
+        .bind(i64::from(object.localized))
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
 }
 
-fn get_pack_files(id_modfile: i64, md5: String) -> Result<Vec<PackFile>> {
-    let path = Path::new("mods").join(format!("{md5}.zip"));
+async fn insert_references(
+    conn: &mut sqlx::AnyConnection,
+    id_modfile: i64,
+    path: &str,
+    references: &[uasset::AssetRef],
+) -> Result<()> {
+    for r in references {
+        sqlx::query(
+            "INSERT INTO asset_ref(id_modfile, path, ref_package, ref_object, ref_class, ref_path, parent)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(id_modfile)
+        .bind(path)
+        .bind(&r.package)
+        .bind(&r.object)
+        .bind(&r.class)
+        .bind(asset_refs::game_path(&r.package))
+        .bind(i64::from(r.parent))
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn insert_rows(
+    conn: &mut sqlx::AnyConnection,
+    id_modfile: i64,
+    path: &str,
+    rows: &serde_json::Map<String, serde_json::Value>,
+) -> Result<()> {
+    for (row, data) in rows {
+        sqlx::query(
+            "INSERT INTO data_table_row(id_modfile, path, row_name, data)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(id_modfile)
+        .bind(path)
+        .bind(row)
+        .bind(data.to_string())
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Raw mount point and pack files of the stored archive of a modfile.
+fn get_pack_files(md5: &str) -> Result<(String, Vec<PackFile>)> {
+    let path = download::archive_path(md5);
+    let listing = list_zip_files(&path)?;
+    Ok((listing.mount_point, pack_files(listing.entries)))
+}
 
-    let files = list_zip_files(&path)?;
-    Ok(files
+/// Split the paths of analyzed `entries` into the columns of `pack_file`.
+fn pack_files(entries: Vec<PakEntry>) -> Vec<PackFile> {
+    entries
         .into_iter()
-        .map(|path| {
-            let p = std::path::Path::new(&path);
-            let extension = p
-                .extension()
-                .and_then(std::ffi::OsStr::to_str)
-                .map(|s| s.to_string());
-            let name = p
-                .file_stem()
-                .and_then(std::ffi::OsStr::to_str)
-                .map(|s| s.to_string());
-            let path_no_extension = if let Some(ext) = &extension {
-                path.strip_suffix(ext).unwrap().to_string()
-            } else {
-                path.to_owned()
-            };
-            PackFile {
-                id_modfile,
-                path,
-                path_no_extension,
-                name,
-                extension,
-            }
-        })
-        .collect())
+        .map(
+            |PakEntry {
+                 path,
+                 hash,
+                 asset_class,
+                 strings,
+                 audio,
+                 references,
+                 rows,
+                 ..
+             }| {
+                let p = std::path::Path::new(&path);
+                let extension = p
+                    .extension()
+                    .and_then(std::ffi::OsStr::to_str)
+                    .map(|s| s.to_string());
+                let name = p
+                    .file_stem()
+                    .and_then(std::ffi::OsStr::to_str)
+                    .map(|s| s.to_string());
+                let path_no_extension = if let Some(ext) = &extension {
+                    path.strip_suffix(ext).unwrap().to_string()
+                } else {
+                    path.to_owned()
+                };
+                PackFile {
+                    path,
+                    path_no_extension,
+                    name,
+                    extension,
+                    hash,
+                    asset_class,
+                    strings,
+                    audio,
+                    references,
+                    rows,
+                }
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+
+    fn parse(args: &[&str]) -> Result<Cli, ErrorKind> {
+        Cli::try_parse_from(["drg-modio-index"].iter().chain(args)).map_err(|e| e.kind())
+    }
+
+    #[test]
+    fn cli() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn query_selection() {
+        let Ok(Cli {
+            command: Some(Commands::Query { filter, path, .. }),
+            ..
+        }) = parse(&["query", "--platform", "windows"])
+        else {
+            panic!("--platform alone is a query");
+        };
+        assert_eq!(filter.platform.as_deref(), Some("windows"));
+        assert!(path.is_none());
+
+        assert!(parse(&["query", "--path", "Weapons", "--platform", "windows"]).is_ok());
+        assert!(parse(&["query", "--text", "drill"]).is_ok());
+        assert_eq!(
+            parse(&["query"]).err(),
+            Some(ErrorKind::MissingRequiredArgument)
+        );
+        assert_eq!(
+            parse(&["query", "--hide-mature"]).err(),
+            Some(ErrorKind::MissingRequiredArgument)
+        );
+        assert_eq!(
+            parse(&["query", "--path", "Weapons", "--text", "drill"]).err(),
+            Some(ErrorKind::ArgumentConflict)
+        );
+    }
 }
@@ -0,0 +1,128 @@
+use anyhow::Result;
+use sqlx::AnyPool;
+use tracing::{debug, warn};
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Directory holding cached mod images.
+pub const MEDIA_DIR: &str = "media";
+
+/// Kinds of `mod_media` rows that come from the gallery of a mod rather than its logo.
+const GALLERY_KINDS: [&str; 3] = ["image", "youtube", "sketchfab"];
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new)
+}
+
+/// Location of a cached image, keyed by mod id and kind. The extension of the source URL is kept
+/// so the file can be served with the right content type.
+fn media_path(id_mod: u32, kind: &str, url: &reqwest::Url) -> PathBuf {
+    let extension = Path::new(url.path())
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("img");
+    Path::new(MEDIA_DIR)
+        .join(id_mod.to_string())
+        .join(format!("{kind}.{extension}"))
+}
+
+/// Cache the logo of a mod and its 320x180 thumbnail under the media directory so frontends don't
+/// have to hit mod.io's CDN. Images whose URL is unchanged since they were cached are skipped.
+/// Returns the number of images downloaded. Failures are logged rather than failing the sync.
+pub async fn cache_logo(pool: &AnyPool, id_mod: u32, logo: &modio::mods::Logo) -> u64 {
+    let mut cached = 0;
+    for (kind, url) in [("logo", &logo.original), ("thumbnail", &logo.thumb_320x180)] {
+        match cache_image(pool, id_mod, kind, url).await {
+            Ok(true) => cached += 1,
+            Ok(false) => {}
+            Err(e) => warn!(id_mod, kind, "Failed to cache image: {e:#}"),
+        }
+    }
+    cached
+}
+
+async fn cache_image(pool: &AnyPool, id_mod: u32, kind: &str, url: &reqwest::Url) -> Result<bool> {
+    let path = media_path(id_mod, kind, url);
+    let current: Option<String> = sqlx::query_scalar(
+        "SELECT url FROM mod_media WHERE id_mod = $1 AND kind = $2 AND position = 0",
+    )
+    .bind(i64::from(id_mod))
+    .bind(kind)
+    .fetch_optional(pool)
+    .await?;
+    if current.as_deref() == Some(url.as_str()) && path.exists() {
+        return Ok(false);
+    }
+
+    debug!(id_mod, kind, %url, "Caching image");
+    let bytes = client()
+        .get(url.clone())
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    // replace rather than truncate so a reader never sees a partial image
+    let partial = path.with_extension("part");
+    tokio::fs::write(&partial, &bytes).await?;
+    tokio::fs::rename(&partial, &path).await?;
+
+    sqlx::query(
+        "INSERT INTO mod_media(id_mod, kind, position, url, path, size, date_cached)
+         VALUES ($1, $2, 0, $3, $4, $5, $6)
+         ON CONFLICT(id_mod, kind, position) DO
+            UPDATE SET
+                url = excluded.url,
+                path = excluded.path,
+                size = excluded.size,
+                date_cached = excluded.date_cached",
+    )
+    .bind(i64::from(id_mod))
+    .bind(kind)
+    .bind(url.as_str())
+    .bind(path.to_string_lossy().into_owned())
+    .bind(bytes.len() as i64)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(pool)
+    .await?;
+    Ok(true)
+}
+
+/// Replace the gallery of a mod: its images, YouTube and Sketchfab links, in the order they are
+/// shown on mod.io. Gallery images are only linked, not cached.
+pub async fn index_gallery(
+    conn: &mut sqlx::AnyConnection,
+    id_mod: u32,
+    media: &modio::mods::Media,
+) -> Result<()> {
+    for kind in GALLERY_KINDS {
+        sqlx::query("DELETE FROM mod_media WHERE id_mod = $1 AND kind = $2")
+            .bind(i64::from(id_mod))
+            .bind(kind)
+            .execute(&mut *conn)
+            .await?;
+    }
+    let images = media.images.iter().map(|i| i.original.to_string());
+    let gallery = images
+        .map(|url| ("image", url))
+        .chain(media.youtube.iter().map(|url| ("youtube", url.clone())))
+        .chain(media.sketchfab.iter().map(|url| ("sketchfab", url.clone())));
+    let mut positions = std::collections::HashMap::<&str, i64>::new();
+    for (kind, url) in gallery {
+        let position = positions.entry(kind).or_default();
+        sqlx::query("INSERT INTO mod_media(id_mod, kind, position, url) VALUES ($1, $2, $3, $4)")
+            .bind(i64::from(id_mod))
+            .bind(kind)
+            .bind(*position)
+            .bind(url)
+            .execute(&mut *conn)
+            .await?;
+        *position += 1;
+    }
+    Ok(())
+}
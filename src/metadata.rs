@@ -0,0 +1,100 @@
+//! Metadata authors attach to their mods. Several frameworks keep version compatibility in the
+//! key-value pairs, which mod.io otherwise only exposes per mod.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::AnyPool;
+
+/// Replace the recorded metadata of `m`.
+pub async fn record(conn: &mut sqlx::AnyConnection, m: &modio::mods::Mod) -> Result<()> {
+    let id_mod = i64::from(m.id);
+    sqlx::query("DELETE FROM mod_metadata WHERE id_mod = $1")
+        .bind(id_mod)
+        .execute(&mut *conn)
+        .await?;
+    let kvp = m
+        .metadata_kvp
+        .iter()
+        .map(|(key, values)| (key.clone(), Value::from(values.clone())))
+        .collect::<Map<_, _>>();
+    let blob = m.metadata_blob.as_deref().filter(|b| !b.is_empty());
+    if kvp.is_empty() && blob.is_none() {
+        return Ok(());
+    }
+    sqlx::query(
+        "INSERT INTO mod_metadata(id_mod, metadata_blob, metadata_kvp) VALUES ($1, $2, $3)",
+    )
+    .bind(id_mod)
+    .bind(blob)
+    .bind(Value::Object(kvp).to_string())
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// A mod with a metadata key.
+#[derive(Debug, Serialize)]
+pub struct MetadataMatch {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub name: String,
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+pub fn print_matches(matches: &[MetadataMatch]) {
+    for m in matches {
+        println!(
+            "{} {} {} {}={}",
+            m.id_mod,
+            m.name_id,
+            m.name,
+            m.key,
+            m.values.join(",")
+        );
+    }
+}
+
+/// Mods with metadata `key`, given as `key` or `key=value` to only match mods with that value.
+/// Keys are matched ignoring case.
+pub async fn mods_with(pool: &AnyPool, pair: &str) -> Result<Vec<MetadataMatch>> {
+    let (key, value) = match pair.split_once('=') {
+        Some((key, value)) => (key, Some(value)),
+        None => (pair, None),
+    };
+    let rows: Vec<(i64, String, String, String)> = sqlx::query_as(
+        "SELECT mod.id_mod, name_id, name, metadata_kvp
+         FROM mod JOIN mod_metadata ON mod_metadata.id_mod = mod.id_mod
+         ORDER BY mod.id_mod",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut matches = vec![];
+    for (id_mod, name_id, name, kvp) in rows {
+        let kvp: Map<String, Value> = serde_json::from_str(&kvp)
+            .with_context(|| format!("metadata of mod {id_mod} is not a JSON object"))?;
+        for (k, values) in kvp {
+            if !k.eq_ignore_ascii_case(key) {
+                continue;
+            }
+            let values = values
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect::<Vec<_>>();
+            if value.is_none_or(|value| values.iter().any(|v| v == value)) {
+                matches.push(MetadataMatch {
+                    id_mod,
+                    name_id: name_id.clone(),
+                    name: name.clone(),
+                    key: k,
+                    values,
+                });
+            }
+        }
+    }
+    Ok(matches)
+}
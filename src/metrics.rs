@@ -0,0 +1,171 @@
+//! Prometheus metrics for long running processes, served at `/metrics` by `serve` and by
+//! `daemon --metrics`. Counters cover what this process did since it started, gauges are read from
+//! the index when scraped so `serve` reports the state a separate daemon keeps up to date.
+
+use anyhow::Result;
+use sqlx::AnyPool;
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+const PREFIX: &str = "drg_modio_index";
+
+static MODS_SYNCED: AtomicU64 = AtomicU64::new(0);
+static MODFILES_DOWNLOADED: AtomicU64 = AtomicU64::new(0);
+static MODFILES_ANALYZED: AtomicU64 = AtomicU64::new(0);
+static ANALYSIS_ERRORS: AtomicU64 = AtomicU64::new(0);
+static BYTES_DOWNLOADED: AtomicU64 = AtomicU64::new(0);
+static API_ERRORS: AtomicU64 = AtomicU64::new(0);
+static SYNCS: AtomicU64 = AtomicU64::new(0);
+static SYNC_FAILURES: AtomicU64 = AtomicU64::new(0);
+/// Milliseconds the last sync took
+static LAST_SYNC_DURATION: AtomicU64 = AtomicU64::new(0);
+/// Remaining requests of the mod.io quota as of the last response, -1 before the first
+static RATE_LIMIT_REMAINING: AtomicI64 = AtomicI64::new(-1);
+
+pub fn record_download(bytes: u64) {
+    BYTES_DOWNLOADED.fetch_add(bytes, Ordering::Relaxed);
+}
+
+pub fn record_api_error() {
+    API_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_rate_limit(remaining: i64) {
+    RATE_LIMIT_REMAINING.store(remaining, Ordering::Relaxed);
+}
+
+/// Count a finished sync, `None` if it failed.
+pub fn record_sync(duration: Duration, summary: Option<&crate::SyncSummary>) {
+    SYNCS.fetch_add(1, Ordering::Relaxed);
+    LAST_SYNC_DURATION.store(duration.as_millis() as u64, Ordering::Relaxed);
+    match summary {
+        Some(summary) => {
+            MODS_SYNCED.fetch_add(summary.mods, Ordering::Relaxed);
+            MODFILES_DOWNLOADED.fetch_add(summary.downloaded, Ordering::Relaxed);
+            MODFILES_ANALYZED.fetch_add(summary.analyzed, Ordering::Relaxed);
+            ANALYSIS_ERRORS.fetch_add(summary.analysis_errors.len() as u64, Ordering::Relaxed);
+        }
+        None => {
+            SYNC_FAILURES.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    writeln!(out, "# HELP {PREFIX}_{name} {help}").unwrap();
+    writeln!(out, "# TYPE {PREFIX}_{name} {kind}").unwrap();
+    writeln!(out, "{PREFIX}_{name} {value}").unwrap();
+}
+
+/// The metrics in the Prometheus text format.
+pub async fn render(pool: &AnyPool) -> Result<String> {
+    let (mods, modfiles, pending_analyses, failed_downloads): (i64, i64, i64, i64) =
+        sqlx::query_as(
+            "SELECT
+                (SELECT COUNT(*) FROM mod),
+                (SELECT COUNT(*) FROM modfile),
+                (SELECT COUNT(*) FROM modfile
+                 WHERE NOT EXISTS (SELECT 1 FROM pack_file WHERE pack_file.id_modfile = modfile.id_modfile)),
+                (SELECT COUNT(*) FROM download WHERE state = $1)",
+        )
+        .bind(crate::download::DownloadState::Failed.as_str())
+        .fetch_one(pool)
+        .await?;
+    let remaining = match RATE_LIMIT_REMAINING.load(Ordering::Relaxed) {
+        -1 => crate::api::latest_quota(pool).await?.map(|q| q.remaining),
+        remaining => Some(remaining),
+    };
+
+    let counter = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    let mut out = String::new();
+    metric(&mut out, "mods_indexed", "gauge", "Mods in the index", mods);
+    metric(
+        &mut out,
+        "modfiles_indexed",
+        "gauge",
+        "Modfiles in the index",
+        modfiles,
+    );
+    metric(
+        &mut out,
+        "queue_depth",
+        "gauge",
+        "Modfiles waiting for analysis or a download retry",
+        pending_analyses + failed_downloads,
+    );
+    if let Some(remaining) = remaining {
+        metric(
+            &mut out,
+            "rate_limit_remaining",
+            "gauge",
+            "Requests left in the mod.io API quota",
+            remaining,
+        );
+    }
+    metric(
+        &mut out,
+        "mods_synced_total",
+        "counter",
+        "Mods synced by this process",
+        counter(&MODS_SYNCED),
+    );
+    metric(
+        &mut out,
+        "modfiles_downloaded_total",
+        "counter",
+        "Modfile archives downloaded by this process",
+        counter(&MODFILES_DOWNLOADED),
+    );
+    metric(
+        &mut out,
+        "bytes_downloaded_total",
+        "counter",
+        "Bytes of modfile archives downloaded by this process",
+        counter(&BYTES_DOWNLOADED),
+    );
+    metric(
+        &mut out,
+        "modfiles_analyzed_total",
+        "counter",
+        "Modfiles analyzed by this process",
+        counter(&MODFILES_ANALYZED),
+    );
+    metric(
+        &mut out,
+        "analysis_errors_total",
+        "counter",
+        "Modfiles that failed to analyze",
+        counter(&ANALYSIS_ERRORS),
+    );
+    metric(
+        &mut out,
+        "api_errors_total",
+        "counter",
+        "mod.io API requests that failed or returned an error status",
+        counter(&API_ERRORS),
+    );
+    metric(
+        &mut out,
+        "syncs_total",
+        "counter",
+        "Syncs run by this process",
+        counter(&SYNCS),
+    );
+    metric(
+        &mut out,
+        "sync_failures_total",
+        "counter",
+        "Syncs that failed",
+        counter(&SYNC_FAILURES),
+    );
+    metric(
+        &mut out,
+        "last_sync_duration_seconds",
+        "gauge",
+        "Duration of the last sync",
+        counter(&LAST_SYNC_DURATION) as f64 / 1000.0,
+    );
+    Ok(out)
+}
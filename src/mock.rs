@@ -0,0 +1,244 @@
+//! Stand-in for the mod.io API serving canned fixtures, so the sync pipeline can run end to end
+//! offline and in CI. Point other commands at it with `--api-base`. Built with the `mock-api`
+//! feature.
+//!
+//! Fixtures are JSON objects as mod.io returns them, one per file:
+//!
+//! ```text
+//! <fixtures>/mods/<id_mod>.json           GET /games/{game}/mods/{id_mod}
+//! <fixtures>/files/<id_modfile>.json      GET /games/{game}/mods/{mod_id}/files/{id_modfile}
+//! <fixtures>/archives/<id_modfile>.zip    download of the modfile
+//! ```
+//!
+//! Download links are rewritten to point at the mock. Lists can be filtered by top level fields,
+//! with `-in`, `-min` and `-max` suffixes, and paginated with `_limit` and `_offset`; other
+//! filters are ignored. Endpoints without fixtures, such as comments, return empty lists.
+//!
+//! tests/mock_sync.rs runs `sync` against the fixtures in tests/fixtures/mock with
+//! `cargo test --features mock-api`.
+
+use anyhow::{Context, Result};
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use tracing::{error, info};
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+
+/// Page size when a request has no `_limit`, as on mod.io.
+const DEFAULT_LIMIT: usize = 100;
+
+struct Fixtures {
+    mods: Vec<Value>,
+    files: Vec<Value>,
+    archives: PathBuf,
+}
+
+/// Serve `fixtures` until SIGINT or SIGTERM.
+pub async fn serve(fixtures: &FsPath, address: SocketAddr) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    let local = listener.local_addr()?;
+    let fixtures = load(fixtures, &format!("http://{local}"))?;
+    info!(
+        mods = fixtures.mods.len(),
+        modfiles = fixtures.files.len(),
+        api_base = %format!("http://{local}/v1"),
+        "Serving mock mod.io API"
+    );
+
+    let app = Router::new()
+        .route("/v1/games/:game/mods", get(list_mods))
+        .route("/v1/games/:game/mods/:id_mod", get(get_mod))
+        .route("/v1/games/:game/mods/:id_mod/files", get(list_files))
+        .route(
+            "/v1/games/:game/mods/:id_mod/files/:id_modfile",
+            get(get_file),
+        )
+        .route("/download/:id_modfile", get(download))
+        .fallback(fallback)
+        .with_state(Arc::new(fixtures));
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            if let Err(e) = crate::daemon::wait_for_signal().await {
+                error!("Failed to listen for shutdown signals: {e:#}");
+                std::future::pending::<()>().await;
+            }
+        })
+        .await?;
+    Ok(())
+}
+
+fn load(dir: &FsPath, base: &str) -> Result<Fixtures> {
+    let read_dir = |name: &str| -> Result<Vec<Value>> {
+        let path = dir.join(name);
+        let mut values = vec![];
+        let entries = match std::fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(values),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                let data = std::fs::read(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                values.push(
+                    serde_json::from_slice(&data)
+                        .with_context(|| format!("invalid fixture {}", path.display()))?,
+                );
+            }
+        }
+        values.sort_by_key(|v| v["id"].as_u64());
+        Ok(values)
+    };
+
+    let mut mods = read_dir("mods")?;
+    let mut files = read_dir("files")?;
+    for m in &mut mods {
+        if let Some(modfile) = m.get_mut("modfile").filter(|f| f.is_object()) {
+            rewrite_download(modfile, base);
+        }
+    }
+    for file in &mut files {
+        rewrite_download(file, base);
+    }
+    Ok(Fixtures {
+        mods,
+        files,
+        archives: dir.join("archives"),
+    })
+}
+
+fn rewrite_download(file: &mut Value, base: &str) {
+    let url = format!("{base}/download/{}", file["id"]);
+    file["download"]["binary_url"] = Value::String(url);
+}
+
+/// Display form of a field as it appears in filters, strings without quotes.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn matches(item: &Value, key: &str, filter: &str) -> bool {
+    let number = |field: &str| item.get(field).and_then(Value::as_f64);
+    let bound = filter.parse::<f64>().ok();
+    if let Some(field) = key.strip_suffix("-in") {
+        item.get(field)
+            .is_none_or(|v| filter.split(',').any(|f| f == text(v)))
+    } else if let Some(field) = key.strip_suffix("-min") {
+        number(field).zip(bound).is_none_or(|(n, b)| n >= b)
+    } else if let Some(field) = key.strip_suffix("-max") {
+        number(field).zip(bound).is_none_or(|(n, b)| n <= b)
+    } else {
+        item.get(key).is_none_or(|v| text(v) == filter)
+    }
+}
+
+/// A page of `items` in mod.io's list envelope.
+fn list<'a>(
+    items: impl Iterator<Item = &'a Value>,
+    params: &HashMap<String, String>,
+) -> Json<Value> {
+    let items = items
+        .filter(|item| {
+            params
+                .iter()
+                .filter(|(key, _)| !key.starts_with('_'))
+                .all(|(key, filter)| matches(item, key, filter))
+        })
+        .collect::<Vec<_>>();
+    let param = |name: &str, default| {
+        params
+            .get(name)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    };
+    let (limit, offset) = (param("_limit", DEFAULT_LIMIT), param("_offset", 0));
+    let page = items
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .copied()
+        .collect::<Vec<_>>();
+    Json(json!({
+        "data": page,
+        "result_count": page.len(),
+        "result_offset": offset,
+        "result_limit": limit,
+        "result_total": items.len(),
+    }))
+}
+
+fn not_found(what: &str) -> Response {
+    let body = json!({ "error": { "code": 404, "message": format!("{what} not found") } });
+    (StatusCode::NOT_FOUND, Json(body)).into_response()
+}
+
+async fn list_mods(
+    State(fixtures): State<Arc<Fixtures>>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    list(fixtures.mods.iter(), &params)
+}
+
+async fn get_mod(
+    State(fixtures): State<Arc<Fixtures>>,
+    Path((_game, id_mod)): Path<(u32, u64)>,
+) -> Response {
+    match fixtures.mods.iter().find(|m| m["id"] == id_mod) {
+        Some(m) => Json(m.clone()).into_response(),
+        None => not_found("mod"),
+    }
+}
+
+async fn list_files(
+    State(fixtures): State<Arc<Fixtures>>,
+    Path((_game, id_mod)): Path<(u32, u64)>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    list(
+        fixtures.files.iter().filter(|f| f["mod_id"] == id_mod),
+        &params,
+    )
+}
+
+async fn get_file(
+    State(fixtures): State<Arc<Fixtures>>,
+    Path((_game, id_mod, id_modfile)): Path<(u32, u64, u64)>,
+) -> Response {
+    match fixtures
+        .files
+        .iter()
+        .find(|f| f["id"] == id_modfile && f["mod_id"] == id_mod)
+    {
+        Some(file) => Json(file.clone()).into_response(),
+        None => not_found("modfile"),
+    }
+}
+
+async fn download(State(fixtures): State<Arc<Fixtures>>, Path(id_modfile): Path<u64>) -> Response {
+    let path = fixtures.archives.join(format!("{id_modfile}.zip"));
+    match tokio::fs::read(&path).await {
+        Ok(data) => Body::from(data).into_response(),
+        Err(_) => not_found("archive"),
+    }
+}
+
+/// Empty lists for the endpoints without fixtures, so optional parts of a sync find nothing.
+async fn fallback(method: Method) -> Response {
+    if method == Method::GET {
+        list(std::iter::empty(), &HashMap::new()).into_response()
+    } else {
+        not_found("endpoint")
+    }
+}
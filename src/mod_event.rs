@@ -0,0 +1,124 @@
+//! Append-only log of the changes syncs observe to a mod's status, visibility, name_id and
+//! current modfile, to tell when a mod was hidden, deleted or renamed long after the fact.
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+fn status_name(status: i64) -> String {
+    match status {
+        0 => "not accepted".to_string(),
+        1 => "accepted".to_string(),
+        3 => "deleted".to_string(),
+        status => status.to_string(),
+    }
+}
+
+fn visibility_name(visible: i64) -> String {
+    match visible {
+        0 => "hidden".to_string(),
+        1 => "public".to_string(),
+        visible => visible.to_string(),
+    }
+}
+
+type ModStateRow = (String, Option<i64>, Option<i64>, Option<i64>);
+
+/// Log how `m` differs from its indexed state. Must run before the mod is updated. Status and
+/// visibility are only compared once recorded, so mods indexed before they were don't log a
+/// change on their next sync. The old name_id of a renamed mod is kept as an alias, see
+/// [`crate::lookup::find_mod`].
+pub async fn record_changes(conn: &mut sqlx::AnyConnection, m: &modio::mods::Mod) -> Result<()> {
+    let id_mod = i64::from(m.id);
+    let previous: Option<ModStateRow> =
+        sqlx::query_as("SELECT name_id, status, visible, id_modfile FROM mod WHERE id_mod = $1")
+            .bind(id_mod)
+            .fetch_optional(&mut *conn)
+            .await?;
+    let status = i64::from(m.status.get());
+    let visible = i64::from(m.visible.get());
+    let modfile = m.modfile.as_ref().map(|f| i64::from(f.id));
+
+    let mut changes: Vec<(&str, Option<String>, Option<String>)> = vec![];
+    match previous {
+        None => changes.push(("first_seen", None, Some(m.name_id.clone()))),
+        Some((name_id, old_status, old_visible, old_modfile)) => {
+            if name_id != m.name_id {
+                crate::lookup::record_alias(&mut *conn, id_mod, &name_id).await?;
+                changes.push(("name_id", Some(name_id), Some(m.name_id.clone())));
+            }
+            if let Some(old) = old_status.filter(|s| *s != status) {
+                changes.push(("status", Some(status_name(old)), Some(status_name(status))));
+            }
+            if let Some(old) = old_visible.filter(|v| *v != visible) {
+                changes.push((
+                    "visible",
+                    Some(visibility_name(old)),
+                    Some(visibility_name(visible)),
+                ));
+            }
+            if old_modfile != modfile {
+                let id = |id: Option<i64>| id.map(|id| id.to_string());
+                changes.push(("modfile", id(old_modfile), id(modfile)));
+            }
+        }
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    for (field, old_value, new_value) in changes {
+        sqlx::query(
+            "INSERT INTO mod_event(id_mod, date_observed, field, old_value, new_value)
+             VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(id_mod)
+        .bind(&now)
+        .bind(field)
+        .bind(old_value)
+        .bind(new_value)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModEvent {
+    pub date_observed: String,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+pub fn print_events(events: &[ModEvent]) {
+    for e in events {
+        match e.field.as_str() {
+            "first_seen" => println!("{} first seen", e.date_observed),
+            field => println!(
+                "{} {field}: {} -> {}",
+                e.date_observed,
+                e.old_value.as_deref().unwrap_or("none"),
+                e.new_value.as_deref().unwrap_or("none")
+            ),
+        }
+    }
+}
+
+/// The logged changes of a mod, oldest first.
+pub async fn events(pool: &AnyPool, id_mod: i64) -> Result<Vec<ModEvent>> {
+    let rows: Vec<(String, String, Option<String>, Option<String>)> = sqlx::query_as(
+        "SELECT date_observed, field, old_value, new_value FROM mod_event
+         WHERE id_mod = $1 ORDER BY id_event",
+    )
+    .bind(id_mod)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(date_observed, field, old_value, new_value)| ModEvent {
+            date_observed,
+            field,
+            old_value,
+            new_value,
+        })
+        .collect())
+}
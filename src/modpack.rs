@@ -0,0 +1,462 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::AnyPool;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::approved::ListConflict;
+use crate::{api, local, lookup};
+
+/// Version of the lockfile format written by [`lock`].
+const LOCK_FORMAT: u32 = 1;
+
+/// The mods of a modpack as its maintainer edits them, e.g.
+/// `{"name": "Pack", "mods": [{"mod": "sandbox-utilities"}, {"mod": "1234", "pin": "1.2"}]}`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    #[serde(default)]
+    pub mods: Vec<ManifestMod>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestMod {
+    /// Mod id or name_id
+    #[serde(rename = "mod")]
+    pub reference: String,
+    /// Modfile id or version to lock instead of the current modfile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// The exact modfiles a manifest resolved to when it was last locked.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub format: u32,
+    pub name: String,
+    pub date_locked: String,
+    pub mods: Vec<LockedMod>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LockedMod {
+    /// The manifest entry this was resolved from
+    pub reference: String,
+    pub id_mod: i64,
+    pub name_id: String,
+    pub id_modfile: i64,
+    pub version: Option<String>,
+    pub hash_md5: String,
+    /// Mods the mod requires according to mod.io
+    #[serde(default)]
+    pub dependencies: Vec<i64>,
+}
+
+/// Lockfile next to a manifest: `modpack.lock.json` for `modpack.json`.
+pub fn lockfile_path(manifest: &Path) -> PathBuf {
+    let stem = manifest
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "modpack".to_string());
+    manifest.with_file_name(format!("{stem}.lock.json"))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("{} is malformed", path.display()))
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(value)? + "\n")
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// The lockfile of the manifest at `path`, or the lockfile at `path` itself.
+pub fn read_lockfile(path: &Path) -> Result<Lockfile> {
+    let is_lockfile = path.to_string_lossy().ends_with(".lock.json");
+    let lock_path = if is_lockfile {
+        path.to_path_buf()
+    } else {
+        lockfile_path(path)
+    };
+    if !lock_path.exists() {
+        bail!(
+            "{} does not exist, run pack lock first",
+            lock_path.display()
+        );
+    }
+    let lockfile: Lockfile = read_json(&lock_path)?;
+    if lockfile.format > LOCK_FORMAT {
+        bail!(
+            "{} has format {}, this version reads up to {LOCK_FORMAT}",
+            lock_path.display(),
+            lockfile.format
+        );
+    }
+    Ok(lockfile)
+}
+
+/// Create an empty manifest at `path` for the modpack `name`.
+pub fn init(path: &Path, name: &str) -> Result<()> {
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+    write_json(
+        path,
+        &Manifest {
+            name: name.to_string(),
+            mods: vec![],
+        },
+    )
+}
+
+/// Add `mods`, given as ids or name_ids, to the manifest at `path` by name_id. Mods already in it
+/// are updated with the new pin and note instead. Returns the name_ids added.
+pub async fn add(
+    pool: &AnyPool,
+    path: &Path,
+    mods: &[String],
+    pin: Option<&str>,
+    note: Option<&str>,
+) -> Result<Vec<String>> {
+    let mut manifest: Manifest = read_json(path)?;
+    let mut added = vec![];
+    for reference in mods {
+        let id_mod = lookup::resolve_mod(pool, reference).await?;
+        if let Some(pin) = pin {
+            lookup::resolve_modfile(pool, id_mod, pin).await?;
+        }
+        let name_id: String = sqlx::query_scalar("SELECT name_id FROM mod WHERE id_mod = $1")
+            .bind(id_mod)
+            .fetch_one(pool)
+            .await?;
+        let mut existing = None;
+        for m in &mut manifest.mods {
+            if lookup::resolve_mod(pool, &m.reference).await.ok() == Some(id_mod) {
+                existing = Some(m);
+                break;
+            }
+        }
+        let entry = match existing {
+            Some(entry) => entry,
+            None => {
+                added.push(name_id.clone());
+                manifest.mods.push(ManifestMod {
+                    reference: name_id,
+                    pin: None,
+                    note: None,
+                });
+                manifest.mods.last_mut().unwrap()
+            }
+        };
+        entry.pin = pin.map(str::to_string);
+        entry.note = note.map(str::to_string).or(entry.note.take());
+    }
+    write_json(path, &manifest)?;
+    Ok(added)
+}
+
+/// Resolve every mod of the manifest at `path` to its pinned or current modfile, fetch what it
+/// depends on from mod.io and write the lockfile next to the manifest. Returns the status of the
+/// new lock.
+pub async fn lock(pool: &AnyPool, path: &Path) -> Result<PackStatus> {
+    let manifest: Manifest = read_json(path)?;
+    let modio = api::client()?;
+    let mut mods = vec![];
+    for m in &manifest.mods {
+        let id_mod = lookup::resolve_mod(pool, &m.reference).await?;
+        let id_modfile = match &m.pin {
+            Some(pin) => lookup::resolve_modfile(pool, id_mod, pin).await?,
+            None => lookup::current_modfile(pool, id_mod).await?,
+        };
+        let (name_id, version, hash_md5): (String, Option<String>, String) = sqlx::query_as(
+            "SELECT mod.name_id, modfile.version, modfile.hash_md5
+             FROM modfile JOIN mod ON mod.id_mod = modfile.id_mod
+             WHERE modfile.id_modfile = $1",
+        )
+        .bind(id_modfile)
+        .fetch_one(pool)
+        .await?;
+        let dependencies = if id_mod == local::LOCAL_MOD {
+            vec![]
+        } else {
+            modio
+                .game(api::DRG)
+                .mod_(id_mod as u32)
+                .dependencies()
+                .list()
+                .await
+                .with_context(|| format!("failed to fetch the dependencies of {name_id}"))?
+                .into_iter()
+                .map(|d| i64::from(d.mod_id))
+                .collect()
+        };
+        mods.push(LockedMod {
+            reference: m.reference.clone(),
+            id_mod,
+            name_id,
+            id_modfile,
+            version,
+            hash_md5,
+            dependencies,
+        });
+    }
+    write_json(
+        &lockfile_path(path),
+        &Lockfile {
+            format: LOCK_FORMAT,
+            name: manifest.name,
+            date_locked: chrono::Utc::now().to_rfc3339(),
+            mods,
+        },
+    )?;
+    status(pool, path).await
+}
+
+/// A locked mod whose modfile is no longer what the index has.
+#[derive(Debug, Serialize)]
+pub struct Drift {
+    pub name_id: String,
+    pub locked: i64,
+    pub locked_version: Option<String>,
+    /// Current modfile, or the locked one if its archive was re-uploaded with another md5
+    pub current: Option<i64>,
+    pub current_version: Option<String>,
+}
+
+/// A dependency of a locked mod that is not in the modpack.
+#[derive(Debug, Serialize)]
+pub struct MissingDependency {
+    pub name_id: String,
+    pub id_mod: i64,
+    /// name_id of the dependency if it is indexed
+    pub dependency: Option<String>,
+}
+
+/// How a modpack's lockfile holds up against its manifest and the index.
+#[derive(Debug, Serialize)]
+pub struct PackStatus {
+    pub name: String,
+    /// Manifest entries missing from the lockfile
+    pub unlocked: Vec<String>,
+    /// Locked mods no longer in the manifest
+    pub removed: Vec<String>,
+    /// Locked mods whose current modfile changed, ignoring pinned mods
+    pub updated: Vec<Drift>,
+    /// Locked modfiles whose md5 no longer matches the index, or that left it
+    pub changed: Vec<Drift>,
+    pub missing_dependencies: Vec<MissingDependency>,
+    pub conflicts: Vec<ListConflict>,
+}
+
+impl PackStatus {
+    pub fn has_problems(&self) -> bool {
+        !self.unlocked.is_empty()
+            || !self.removed.is_empty()
+            || !self.changed.is_empty()
+            || !self.missing_dependencies.is_empty()
+    }
+}
+
+impl std::fmt::Display for PackStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let version = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+        for reference in &self.unlocked {
+            writeln!(f, "not locked: {reference}")?;
+        }
+        for name_id in &self.removed {
+            writeln!(f, "locked but removed from the manifest: {name_id}")?;
+        }
+        for d in &self.updated {
+            writeln!(
+                f,
+                "update available: {} {} -> {}",
+                d.name_id,
+                version(&d.locked_version),
+                version(&d.current_version)
+            )?;
+        }
+        for d in &self.changed {
+            match d.current {
+                Some(_) => writeln!(
+                    f,
+                    "archive changed: {} {} was re-uploaded",
+                    d.name_id,
+                    version(&d.locked_version)
+                )?,
+                None => writeln!(f, "not indexed: {} modfile {}", d.name_id, d.locked)?,
+            }
+        }
+        for m in &self.missing_dependencies {
+            let dependency = m
+                .dependency
+                .clone()
+                .unwrap_or_else(|| format!("mod {}", m.id_mod));
+            writeln!(f, "missing dependency: {} requires {dependency}", m.name_id)?;
+        }
+        for c in &self.conflicts {
+            write!(
+                f,
+                "conflict: {} and {} share {} paths",
+                c.a,
+                c.b,
+                c.paths.len()
+            )?;
+            if !c.vanilla_overrides.is_empty() {
+                write!(f, " ({} base game assets)", c.vanilla_overrides.len())?;
+            }
+            if !c.case_collisions.is_empty() {
+                write!(f, " ({} differing in case)", c.case_collisions.len())?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "{}: {} unlocked, {} removed, {} updates, {} changed, {} missing dependencies, {} conflicting pairs",
+            self.name,
+            self.unlocked.len(),
+            self.removed.len(),
+            self.updated.len(),
+            self.changed.len(),
+            self.missing_dependencies.len(),
+            self.conflicts.len()
+        )
+    }
+}
+
+/// Compare the lockfile of the manifest at `path` with the manifest and the index, and check the
+/// locked modfiles for conflicts and missing dependencies.
+pub async fn status(pool: &AnyPool, path: &Path) -> Result<PackStatus> {
+    let manifest: Manifest = read_json(path)?;
+    let lockfile = read_lockfile(path)?;
+
+    let mut status = PackStatus {
+        name: manifest.name,
+        unlocked: vec![],
+        removed: vec![],
+        updated: vec![],
+        changed: vec![],
+        missing_dependencies: vec![],
+        conflicts: vec![],
+    };
+    for m in &manifest.mods {
+        if !lockfile.mods.iter().any(|l| l.reference == m.reference) {
+            status.unlocked.push(m.reference.clone());
+        }
+    }
+    for l in &lockfile.mods {
+        let entry = manifest.mods.iter().find(|m| m.reference == l.reference);
+        let Some(entry) = entry else {
+            status.removed.push(l.name_id.clone());
+            continue;
+        };
+
+        let indexed: Option<(String, Option<String>)> =
+            sqlx::query_as("SELECT hash_md5, version FROM modfile WHERE id_modfile = $1")
+                .bind(l.id_modfile)
+                .fetch_optional(pool)
+                .await?;
+        match indexed {
+            Some((md5, version)) if md5 != l.hash_md5 => status.changed.push(Drift {
+                name_id: l.name_id.clone(),
+                locked: l.id_modfile,
+                locked_version: l.version.clone(),
+                current: Some(l.id_modfile),
+                current_version: version,
+            }),
+            Some(_) => {}
+            None => status.changed.push(Drift {
+                name_id: l.name_id.clone(),
+                locked: l.id_modfile,
+                locked_version: l.version.clone(),
+                current: None,
+                current_version: None,
+            }),
+        }
+
+        if entry.pin.is_none() {
+            let current: Option<(i64, Option<String>)> = sqlx::query_as(
+                "SELECT modfile.id_modfile, modfile.version
+                 FROM mod JOIN modfile ON modfile.id_modfile = mod.id_modfile
+                 WHERE mod.id_mod = $1",
+            )
+            .bind(l.id_mod)
+            .fetch_optional(pool)
+            .await?;
+            if let Some((id_modfile, version)) = current.filter(|(id, _)| *id != l.id_modfile) {
+                status.updated.push(Drift {
+                    name_id: l.name_id.clone(),
+                    locked: l.id_modfile,
+                    locked_version: l.version.clone(),
+                    current: Some(id_modfile),
+                    current_version: version,
+                });
+            }
+        }
+
+        for &dependency in &l.dependencies {
+            if lockfile.mods.iter().any(|other| other.id_mod == dependency) {
+                continue;
+            }
+            let name_id: Option<String> =
+                sqlx::query_scalar("SELECT name_id FROM mod WHERE id_mod = $1")
+                    .bind(dependency)
+                    .fetch_optional(pool)
+                    .await?;
+            status.missing_dependencies.push(MissingDependency {
+                name_id: l.name_id.clone(),
+                id_mod: dependency,
+                dependency: name_id,
+            });
+        }
+    }
+    status.conflicts = conflicts(pool, &lockfile.mods).await?;
+    Ok(status)
+}
+
+/// Pairs of locked modfiles containing the same paths, ignoring case like the game does.
+async fn conflicts(pool: &AnyPool, mods: &[LockedMod]) -> Result<Vec<ListConflict>> {
+    let mut owners = BTreeMap::<String, (bool, Vec<(&str, String)>)>::new();
+    for m in mods {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT path, COALESCE(vanilla_override, 0) FROM pack_file WHERE id_modfile = $1",
+        )
+        .bind(m.id_modfile)
+        .fetch_all(pool)
+        .await?;
+        for (path, vanilla_override) in rows {
+            let (vanilla, owners) = owners.entry(path.to_lowercase()).or_default();
+            *vanilla |= vanilla_override != 0;
+            owners.push((&m.name_id, path));
+        }
+    }
+
+    let mut pairs = BTreeMap::<(&str, &str), ListConflict>::new();
+    for (vanilla, owners) in owners.into_values() {
+        for (i, a) in owners.iter().enumerate() {
+            // the same modfile can hold paths only differing in case
+            for b in owners[i + 1..].iter().filter(|b| b.0 != a.0) {
+                let (a, b) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+                let conflict = pairs.entry((a.0, b.0)).or_insert_with(|| ListConflict {
+                    a: a.0.to_string(),
+                    b: b.0.to_string(),
+                    paths: vec![],
+                    vanilla_overrides: vec![],
+                    case_collisions: vec![],
+                });
+                if vanilla {
+                    conflict.vanilla_overrides.push(a.1.clone());
+                }
+                if a.1 != b.1 {
+                    conflict.case_collisions.push(a.1.clone());
+                }
+                conflict.paths.push(a.1.clone());
+            }
+        }
+    }
+    Ok(pairs.into_values().collect())
+}
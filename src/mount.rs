@@ -0,0 +1,191 @@
+use anyhow::{bail, Context, Result};
+use tracing::warn;
+
+use std::env;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Mount point of a pak with the usual DRG layout, whose records live under `FSD/Content/...`.
+pub const DEFAULT: Normalization = Normalization::Strip(3);
+
+/// How a pak's mount point and record are turned into the game path stored in `pack_file`, from
+/// `MOUNT_POINT_NORMALIZATION`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// `strip:N`: drop N leading `..` components, failing if the path has fewer. DRG's paks are
+    /// mounted at `../../../` so `strip:3` yields `FSD/Content/...`
+    Strip(usize),
+    /// `content-root`: drop any leading `..` and `/` and map the rest onto the game's content
+    /// root, so `../../../FSD/Content/X`, `/FSD/Content/X` and `/Game/X` all become
+    /// `FSD/Content/X`
+    ContentRoot,
+    /// `raw`: the mount point joined with the record as is
+    Raw,
+}
+
+impl std::str::FromStr for Normalization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.trim() {
+            "content-root" => Normalization::ContentRoot,
+            "raw" => Normalization::Raw,
+            s => match s.split_once(':') {
+                Some(("strip", n)) => Normalization::Strip(
+                    n.parse()
+                        .with_context(|| format!("invalid number of levels {n:?}"))?,
+                ),
+                _ => bail!("unknown normalization {s:?}, expected strip:N, content-root or raw"),
+            },
+        })
+    }
+}
+
+impl Normalization {
+    /// Game path of `path`, a mount point joined with a record.
+    pub fn apply(&self, path: &Path) -> Result<PathBuf, std::path::StripPrefixError> {
+        Ok(match self {
+            Normalization::Strip(levels) => path
+                .strip_prefix(vec![".."; *levels].join("/"))?
+                .to_path_buf(),
+            Normalization::ContentRoot => {
+                let components = path
+                    .components()
+                    .skip_while(|c| {
+                        matches!(
+                            c,
+                            Component::ParentDir | Component::CurDir | Component::RootDir
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                match components.first() {
+                    Some(Component::Normal(root)) if *root == "Game" => {
+                        Path::new("FSD/Content").join(components[1..].iter().collect::<PathBuf>())
+                    }
+                    _ => {
+                        let start = components
+                            .iter()
+                            .position(|c| c.as_os_str() == "FSD")
+                            .unwrap_or(0);
+                        components[start..].iter().collect()
+                    }
+                }
+            }
+            Normalization::Raw => path.to_path_buf(),
+        })
+    }
+}
+
+/// The configured normalization, failing if `MOUNT_POINT_NORMALIZATION` is invalid.
+pub fn from_env() -> Result<Normalization> {
+    match env::var("MOUNT_POINT_NORMALIZATION") {
+        Ok(s) if !s.trim().is_empty() => s.parse().context("invalid MOUNT_POINT_NORMALIZATION"),
+        _ => Ok(DEFAULT),
+    }
+}
+
+/// The configured normalization, read once. An invalid setting falls back to [`DEFAULT`] rather
+/// than failing every pak, `check-config` reports it.
+pub fn normalization() -> Normalization {
+    static NORMALIZATION: OnceLock<Normalization> = OnceLock::new();
+    *NORMALIZATION.get_or_init(|| {
+        from_env().unwrap_or_else(|e| {
+            warn!("{e:#}, using strip:3");
+            DEFAULT
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Game path of `record` in a pak mounted at `mount_point`.
+    fn game_path(normalization: Normalization, mount_point: &str, record: &str) -> Option<String> {
+        normalization
+            .apply(&Path::new(mount_point).join(record))
+            .ok()
+            .map(|p| p.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("strip:3".parse::<Normalization>().unwrap(), DEFAULT);
+        assert_eq!(
+            " content-root ".parse::<Normalization>().unwrap(),
+            Normalization::ContentRoot
+        );
+        assert_eq!("raw".parse::<Normalization>().unwrap(), Normalization::Raw);
+        assert!("strip:three".parse::<Normalization>().is_err());
+        assert!("strip".parse::<Normalization>().is_err());
+        assert!("flatten".parse::<Normalization>().is_err());
+    }
+
+    #[test]
+    fn relative_mount_points() {
+        let record = "FSD/Content/WeaponsNTools/Drill.uasset";
+        let mount_point = "../../../";
+        for normalization in [DEFAULT, Normalization::ContentRoot] {
+            assert_eq!(
+                game_path(normalization, mount_point, record).as_deref(),
+                Some(record)
+            );
+        }
+        assert_eq!(
+            game_path(Normalization::Raw, mount_point, record).as_deref(),
+            Some("../../../FSD/Content/WeaponsNTools/Drill.uasset")
+        );
+        assert_eq!(
+            game_path(Normalization::Strip(2), mount_point, record).as_deref(),
+            Some("../FSD/Content/WeaponsNTools/Drill.uasset")
+        );
+        // fewer levels than stripped
+        assert_eq!(game_path(DEFAULT, "../../", record), None);
+    }
+
+    #[test]
+    fn absolute_mount_points() {
+        let record = "FSD/Content/WeaponsNTools/Drill.uasset";
+        assert_eq!(game_path(DEFAULT, "/", record), None);
+        assert_eq!(
+            game_path(Normalization::ContentRoot, "/", record).as_deref(),
+            Some(record)
+        );
+        assert_eq!(
+            game_path(
+                Normalization::ContentRoot,
+                "/Game/",
+                "WeaponsNTools/Drill.uasset"
+            )
+            .as_deref(),
+            Some(record)
+        );
+        assert_eq!(
+            game_path(Normalization::Raw, "/", record).as_deref(),
+            Some("/FSD/Content/WeaponsNTools/Drill.uasset")
+        );
+    }
+
+    #[test]
+    fn empty_mount_points() {
+        let record = "FSD/Content/WeaponsNTools/Drill.uasset";
+        assert_eq!(game_path(DEFAULT, "", record), None);
+        assert_eq!(
+            game_path(Normalization::Strip(0), "", record).as_deref(),
+            Some(record)
+        );
+        assert_eq!(
+            game_path(Normalization::ContentRoot, "", record).as_deref(),
+            Some(record)
+        );
+        // no FSD directory to root it at, kept as is
+        assert_eq!(
+            game_path(Normalization::ContentRoot, "", "Engine/Config/Base.ini").as_deref(),
+            Some("Engine/Config/Base.ini")
+        );
+        assert_eq!(
+            game_path(Normalization::Raw, "", record).as_deref(),
+            Some(record)
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::Result;
+use sqlx::AnyPool;
+use tracing::{error, info, warn};
+
+use std::collections::BTreeSet;
+
+use crate::channel::{self, Kind, Notification};
+use crate::{api, diff, webhook, SyncSummary};
+
+/// Number of conflicting paths listed in a conflict notification before the rest are summarized.
+const CONFLICT_SAMPLE: usize = 5;
+
+/// Send a notification to every configured channel, see [`channel::from_env`], for every new
+/// mod, updated modfile and conflict involving a changed modfile in `summary`, and the ones about
+/// their mods to registered webhooks. Does nothing when no channel or webhook is configured, or
+/// when every synced mod is new since announcing a freshly built index would flood the channels. A
+/// failing channel does not keep the others from being sent to.
+pub async fn notify_sync(pool: &AnyPool, summary: &SyncSummary) -> Result<()> {
+    let channels = channel::from_env()?;
+    let webhooks = webhook::list(pool).await?;
+    if channels.is_empty() && webhooks.is_empty() {
+        return Ok(());
+    }
+    if !summary.new_mods.is_empty() && summary.new_mods.len() as u64 == summary.mods {
+        info!("Skipping notifications for initial index population");
+        return Ok(());
+    }
+
+    let notifications = sync_notifications(pool, summary).await?;
+    if notifications.is_empty() {
+        return Ok(());
+    }
+
+    let client = reqwest::Client::new();
+    for channel in &channels {
+        info!(
+            channel = channel.name(),
+            "Sending {} notifications",
+            notifications.len()
+        );
+        if let Err(e) = channel.send(&client, &notifications).await {
+            error!(
+                channel = channel.name(),
+                "Failed to send notifications: {e:#}"
+            );
+        }
+    }
+    webhook::dispatch(pool, &client, &webhooks, &notifications).await?;
+    Ok(())
+}
+
+type UpdatedRow = (String, String, i64, Option<String>, Option<String>);
+
+/// Row level changes of tables and curves since the previous modfile, for balance tweaks that a
+/// changelog rarely spells out. Empty if there is nothing to compare against.
+async fn table_changes(pool: &AnyPool, id_mod: i64, id_modfile: i64) -> Vec<String> {
+    let Ok(previous) = diff::previous_modfile(pool, id_mod, id_modfile).await else {
+        return vec![];
+    };
+    match diff::diff(pool, previous, id_modfile).await {
+        Ok(diff) => diff
+            .tables
+            .iter()
+            .flat_map(|table| {
+                std::iter::once(table.path.clone())
+                    .chain(table.lines().into_iter().map(|l| format!("  {l}")))
+            })
+            .collect(),
+        Err(e) => {
+            warn!(id_modfile, "Failed to compare tables: {e:#}");
+            vec![]
+        }
+    }
+}
+
+async fn sync_notifications(pool: &AnyPool, summary: &SyncSummary) -> Result<Vec<Notification>> {
+    let mut notifications = vec![];
+
+    for &id_mod in &summary.new_mods {
+        let (name, name_id, mod_summary): (String, String, String) =
+            sqlx::query_as("SELECT name, name_id, summary FROM mod WHERE id_mod = $1")
+                .bind(i64::from(id_mod))
+                .fetch_one(pool)
+                .await?;
+        notifications.push(Notification {
+            title: format!("New mod: {name}"),
+            url: Some(api::mod_url(&name_id)),
+            description: mod_summary,
+            kind: Kind::New,
+            mods: vec![i64::from(id_mod)],
+        });
+    }
+
+    let new = summary.new_mods.iter().collect::<BTreeSet<_>>();
+    for &id_mod in summary
+        .changed_modfiles
+        .iter()
+        .filter(|id| !new.contains(id))
+    {
+        let row: Option<UpdatedRow> = sqlx::query_as(
+            "SELECT name, name_id, mod.id_modfile, version, changelog FROM mod
+             JOIN modfile ON modfile.id_modfile = mod.id_modfile
+             WHERE mod.id_mod = $1",
+        )
+        .bind(i64::from(id_mod))
+        .fetch_optional(pool)
+        .await?;
+        // the mod's modfile was removed rather than replaced
+        let Some((name, name_id, id_modfile, version, changelog)) = row else {
+            continue;
+        };
+        let mut description = changelog.unwrap_or_default();
+        let tables = table_changes(pool, i64::from(id_mod), id_modfile).await;
+        if !tables.is_empty() {
+            description.push_str(&format!("\n```diff\n{}\n```", tables.join("\n")));
+        }
+        let title = match version {
+            Some(version) => format!("Updated: {name} {version}"),
+            None => format!("Updated: {name}"),
+        };
+        notifications.push(Notification {
+            title,
+            url: Some(api::mod_url(&name_id)),
+            description,
+            kind: Kind::Updated,
+            mods: vec![i64::from(id_mod)],
+        });
+    }
+
+    let mut reported = BTreeSet::new();
+    for &id_mod in &summary.changed_modfiles {
+        let rows: Vec<(i64, String, String, String)> = sqlx::query_as(
+            "SELECT other.id_mod, other.name, theirs.path, ours.path
+             FROM mod AS this
+             JOIN pack_file AS ours ON ours.id_modfile = this.id_modfile
+             JOIN pack_file AS theirs
+                  ON theirs.path_lower = ours.path_lower AND theirs.id_modfile != ours.id_modfile
+             JOIN mod AS other ON other.id_modfile = theirs.id_modfile
+             WHERE this.id_mod = $1
+             ORDER BY other.id_mod, theirs.path",
+        )
+        .bind(i64::from(id_mod))
+        .fetch_all(pool)
+        .await?;
+
+        let name: Option<String> = sqlx::query_scalar("SELECT name FROM mod WHERE id_mod = $1")
+            .bind(i64::from(id_mod))
+            .fetch_optional(pool)
+            .await?;
+        let name = name.unwrap_or_default();
+        for other in rows.iter().map(|(id, ..)| *id).collect::<BTreeSet<_>>() {
+            let pair = (i64::from(id_mod).min(other), i64::from(id_mod).max(other));
+            if !reported.insert(pair) {
+                continue;
+            }
+            let shared = rows
+                .iter()
+                .filter(|(id, ..)| *id == other)
+                .collect::<Vec<_>>();
+            let other_name = &shared[0].1;
+            let mut description = shared
+                .iter()
+                .take(CONFLICT_SAMPLE)
+                .map(|(.., path, ours)| {
+                    if path == ours {
+                        format!("`{path}`")
+                    } else {
+                        format!("`{path}` (`{ours}` in {name})")
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            if shared.len() > CONFLICT_SAMPLE {
+                description.push_str(&format!("\n…and {} more", shared.len() - CONFLICT_SAMPLE));
+            }
+            notifications.push(Notification {
+                title: format!("Conflict: {name} and {other_name}"),
+                url: None,
+                description,
+                kind: Kind::Conflict,
+                mods: vec![i64::from(id_mod), other],
+            });
+        }
+    }
+
+    Ok(notifications)
+}
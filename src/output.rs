@@ -0,0 +1,45 @@
+use anyhow::Result;
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use serde::Serialize;
+
+/// Where command results go: human readable text with progress bars, or a single JSON document
+/// per command on stdout for scripts.
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    pub json: bool,
+}
+
+impl Output {
+    /// Progress display for a command. Hidden in JSON mode so stdout only carries the result.
+    pub fn multi_progress(self) -> MultiProgress {
+        if self.json {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        } else {
+            MultiProgress::new()
+        }
+    }
+
+    /// Emit a command result, serialized as JSON or printed by `human`.
+    pub fn emit<T: Serialize>(self, value: &T, human: impl FnOnce(&T)) -> Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string(value)?);
+        } else {
+            human(value);
+        }
+        Ok(())
+    }
+
+    pub fn error(self, error: &anyhow::Error) {
+        #[derive(Serialize)]
+        struct Error {
+            error: String,
+        }
+        let error = Error {
+            error: format!("{error:#}"),
+        };
+        match serde_json::to_string(&error) {
+            Ok(json) => println!("{json}"),
+            Err(_) => println!("{{\"error\":{:?}}}", error.error),
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Readers paks are opened from, so only the parts repak asks for are read instead of the whole
+//! pak, which for large audio mods does not fit in memory many times over, and how deep paks are
+//! looked for in zips nested in a mod's zip. Older mods are uploaded as rar or 7z archives, their
+//! paks are extracted to temporary files.
+
+use anyhow::{Context, Result};
+use tracing::warn;
+
+use std::env;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+
+use crate::PakError;
+
+/// How many zips deep a pak is looked for when `NESTED_ARCHIVE_DEPTH` is not set.
+const DEFAULT_NESTED_ARCHIVE_DEPTH: usize = 2;
+
+/// How many levels of archives inside a zip are searched for a pak, from `NESTED_ARCHIVE_DEPTH`. `0`
+/// only looks at the zip mod.io serves.
+pub fn nested_archive_depth_from_env() -> Result<usize> {
+    match env::var("NESTED_ARCHIVE_DEPTH") {
+        Ok(depth) if !depth.trim().is_empty() => {
+            depth.trim().parse().context("invalid NESTED_ARCHIVE_DEPTH")
+        }
+        _ => Ok(DEFAULT_NESTED_ARCHIVE_DEPTH),
+    }
+}
+
+/// The configured depth, read once. An invalid setting falls back to the default, `check-config`
+/// reports it.
+pub fn nested_archive_depth() -> usize {
+    static DEPTH: OnceLock<usize> = OnceLock::new();
+    *DEPTH.get_or_init(|| {
+        nested_archive_depth_from_env().unwrap_or_else(|e| {
+            warn!("{e:#}, using {DEFAULT_NESTED_ARCHIVE_DEPTH}");
+            DEFAULT_NESTED_ARCHIVE_DEPTH
+        })
+    })
+}
+
+/// Anything a pak can be read from.
+pub trait PakSource: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> PakSource for T {}
+
+/// The bytes `start..start + len` of `inner` as a reader of their own, e.g. a pak stored
+/// uncompressed in a zip read in place.
+pub struct Window<R> {
+    inner: R,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<R: Seek> Window<R> {
+    pub fn new(mut inner: R, start: u64, len: u64) -> io::Result<Self> {
+        inner.seek(SeekFrom::Start(start))?;
+        Ok(Window {
+            inner,
+            start,
+            len,
+            pos: 0,
+        })
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R: Read> Read for Window<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        let max = remaining.min(buf.len() as u64) as usize;
+        if max == 0 {
+            return Ok(0);
+        }
+        let n = self.inner.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Window<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len.checked_add_signed(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        }
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of window")
+        })?;
+        self.inner.seek(SeekFrom::Start(self.start + pos))?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+/// How far a download has been written to its partial file, shared between the task downloading
+/// it and a [`GrowingFile`] reading the partial file at the same time.
+#[derive(Default)]
+pub struct PartialDownload {
+    state: Mutex<Partial>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct Partial {
+    file: Option<std::fs::File>,
+    written: u64,
+    /// Whether the download completed, `None` while it is running
+    finished: Option<bool>,
+}
+
+impl PartialDownload {
+    fn update(&self, update: impl FnOnce(&mut Partial)) {
+        update(&mut self.state.lock().unwrap());
+        self.changed.notify_all();
+    }
+
+    /// The partial file was created, `file` is a handle to it that stays valid once it is
+    /// renamed into place.
+    pub fn opened(&self, file: std::fs::File) {
+        self.update(|p| p.file = Some(file));
+    }
+
+    /// The first `written` bytes are in the partial file.
+    pub fn advance(&self, written: u64) {
+        self.update(|p| p.written = written);
+    }
+
+    /// The download ended, `complete` if every byte was written. Readers waiting for more of an
+    /// incomplete download fail.
+    pub fn finish(&self, complete: bool) {
+        self.update(|p| {
+            p.finished.get_or_insert(complete);
+        });
+    }
+
+    /// Wait until `check` has an answer for the download so far.
+    fn wait<T>(&self, check: impl Fn(&Partial) -> Option<io::Result<T>>) -> io::Result<T> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(result) = check(&state) {
+                return result;
+            }
+            if state.finished == Some(false) {
+                return Err(io::Error::other("the download failed"));
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+}
+
+/// The partial file of a running download, read as if it were complete: reads past what has been
+/// written wait for the download to get there, and its end is at the size the download will have.
+pub struct GrowingFile {
+    file: std::fs::File,
+    download: Arc<PartialDownload>,
+    len: u64,
+    pos: u64,
+}
+
+impl GrowingFile {
+    /// Wait for the partial file of `download`, which will be `len` bytes long.
+    pub fn open(download: Arc<PartialDownload>, len: u64) -> io::Result<Self> {
+        let file = download.wait(|p| p.file.as_ref().map(std::fs::File::try_clone))?;
+        Ok(GrowingFile {
+            file,
+            download,
+            len,
+            pos: 0,
+        })
+    }
+}
+
+impl Read for GrowingFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let pos = self.pos;
+        let written = self
+            .download
+            .wait(|p| (p.written > pos || p.finished == Some(true)).then_some(Ok(p.written)))?;
+        let max = written.saturating_sub(pos).min(buf.len() as u64) as usize;
+        if max == 0 {
+            return Ok(0);
+        }
+        self.file.seek(SeekFrom::Start(pos))?;
+        let n = self.file.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for GrowingFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len.checked_add_signed(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before start of file"))?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+/// A file in the temporary directory, deleted again when dropped. Holds compressed paks and
+/// nested zips extracted from zips.
+pub struct TempFile {
+    path: PathBuf,
+    file: std::fs::File,
+}
+
+impl TempFile {
+    fn unique_path() -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        std::env::temp_dir().join(format!(
+            "drg-modio-index-{}-{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    pub fn create() -> io::Result<Self> {
+        let path = Self::unique_path();
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(TempFile { path, file })
+    }
+
+    /// A temporary file written through its path by `write`, for libraries that only extract to
+    /// paths.
+    pub fn write_with<E: From<io::Error>>(
+        write: impl FnOnce(&Path) -> Result<(), E>,
+    ) -> Result<Self, E> {
+        let path = Self::unique_path();
+        if let Err(e) = write(&path) {
+            let _ = std::fs::remove_file(&path);
+            return Err(e);
+        }
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path);
+        match file {
+            Ok(file) => Ok(TempFile { path, file }),
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                Err(e.into())
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Read for TempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for TempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Container format of a mod archive. Stored archives are named `.zip` whatever they are, so it is
+/// told by their first bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Rar,
+    SevenZip,
+}
+
+impl ArchiveFormat {
+    /// The format of the archive `reader` is at the start of, which it is left at. Anything
+    /// unknown is taken for a zip, so it fails with the zip error it always did.
+    pub fn detect(reader: &mut (impl Read + Seek)) -> io::Result<Self> {
+        let mut magic = vec![];
+        reader.by_ref().take(6).read_to_end(&mut magic)?;
+        reader.rewind()?;
+        Ok(match magic.as_slice() {
+            // RAR 1.5 to 4 continue with 0x00, RAR 5 with 0x01 0x00
+            b"Rar!\x1a\x07" => ArchiveFormat::Rar,
+            b"7z\xbc\xaf\x27\x1c" => ArchiveFormat::SevenZip,
+            _ => ArchiveFormat::Zip,
+        })
+    }
+}
+
+fn is_pak(name: &str) -> bool {
+    name.to_lowercase().ends_with(".pak")
+}
+
+/// Extract the first pak of the rar archive at `path` to a temporary file.
+pub fn extract_rar_pak(path: &Path) -> Result<TempFile, PakError> {
+    let mut archive = unrar::Archive::new(path).open_for_processing()?;
+    while let Some(header) = archive.read_header()? {
+        let entry = header.entry();
+        if entry.is_file() && is_pak(&entry.filename.to_string_lossy()) {
+            return TempFile::write_with(|temp| {
+                header.extract_to(temp)?;
+                Ok(())
+            });
+        }
+        archive = header.skip()?;
+    }
+    Err(PakError::MissingPakFile)
+}
+
+/// Extract the first pak of a 7z archive to a temporary file.
+pub fn extract_7z_pak(mut reader: impl Read + Seek) -> Result<TempFile, PakError> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.rewind()?;
+    let mut archive = sevenz_rust::SevenZReader::new(reader, len, sevenz_rust::Password::empty())?;
+    let mut pak = None;
+    archive.for_each_entries(|entry, data| {
+        if entry.is_directory() || !is_pak(entry.name()) {
+            // entries of a solid block are decompressed in order, skipped ones have to be read
+            // past all the same
+            io::copy(data, &mut io::sink())?;
+            return Ok(true);
+        }
+        let mut temp = TempFile::create()?;
+        io::copy(data, &mut temp)?;
+        temp.rewind()?;
+        pak = Some(temp);
+        Ok(false)
+    })?;
+    pak.ok_or(PakError::MissingPakFile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A download of `data` written in chunks to a temporary file on another thread.
+    fn download(data: &'static [u8], complete: bool) -> Arc<PartialDownload> {
+        let partial = Arc::new(PartialDownload::default());
+        std::thread::spawn({
+            let partial = partial.clone();
+            move || {
+                let mut file = TempFile::create().unwrap();
+                partial.opened(file.file.try_clone().unwrap());
+                for (i, chunk) in data.chunks(3).enumerate() {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                    file.write_all(chunk).unwrap();
+                    partial.advance((i * 3 + chunk.len()) as u64);
+                }
+                partial.finish(complete);
+                // the reader keeps its own handle to the file
+                drop(file);
+            }
+        });
+        partial
+    }
+
+    #[test]
+    fn growing_file() {
+        const DATA: &[u8] = b"PK local header, entries, central directory";
+        let mut file = GrowingFile::open(download(DATA, true), DATA.len() as u64).unwrap();
+        // the end is known before it is written, reading it waits for the download
+        file.seek(SeekFrom::End(-9)).unwrap();
+        let mut end = vec![];
+        file.read_to_end(&mut end).unwrap();
+        assert_eq!(end, b"directory");
+        file.rewind().unwrap();
+        let mut all = vec![];
+        file.read_to_end(&mut all).unwrap();
+        assert_eq!(all, DATA);
+        assert!(file.seek(SeekFrom::Current(-100)).is_err());
+    }
+
+    #[test]
+    fn failed_download() {
+        let mut file = GrowingFile::open(download(b"truncated", false), 100).unwrap();
+        let mut data = vec![];
+        assert!(file.read_to_end(&mut data).is_err());
+
+        let partial = Arc::new(PartialDownload::default());
+        partial.finish(false);
+        assert!(GrowingFile::open(partial, 100).is_err());
+    }
+}
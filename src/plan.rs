@@ -0,0 +1,125 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use std::collections::HashMap;
+
+use crate::{api, download};
+
+#[derive(Debug, Serialize)]
+pub struct PlannedMod {
+    pub id_mod: u32,
+    pub name_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedDownload {
+    pub id_mod: u32,
+    pub id_modfile: u32,
+    pub hash_md5: String,
+    pub filesize: u64,
+}
+
+/// What a sync would change, computed from the current mod list without writing anything.
+#[derive(Debug, Default, Serialize)]
+pub struct SyncPlan {
+    pub inserted: Vec<PlannedMod>,
+    pub updated: Vec<PlannedMod>,
+    /// Mods whose current modfile changed and will be reanalyzed
+    pub modfile_changes: Vec<PlannedMod>,
+    /// Changed modfiles whose archive is not yet in the mods directory
+    pub downloads: Vec<PlannedDownload>,
+    pub download_bytes: u64,
+}
+
+impl std::fmt::Display for SyncPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for m in &self.inserted {
+            writeln!(f, "insert mod {} {}", m.id_mod, m.name_id)?;
+        }
+        for m in &self.updated {
+            writeln!(f, "update mod {} {}", m.id_mod, m.name_id)?;
+        }
+        for d in &self.downloads {
+            writeln!(
+                f,
+                "download mod {} modfile {} ({} bytes)",
+                d.id_mod, d.id_modfile, d.filesize
+            )?;
+        }
+        write!(
+            f,
+            "{} mods to insert, {} to update, {} modfile changes, {} downloads totalling {:.1} MiB",
+            self.inserted.len(),
+            self.updated.len(),
+            self.modfile_changes.len(),
+            self.downloads.len(),
+            self.download_bytes as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IndexedMod {
+    id_mod: i64,
+    id_modfile: Option<i64>,
+    name: String,
+    name_id: String,
+    summary: String,
+    description: Option<String>,
+}
+
+pub async fn plan_sync(pool: &AnyPool, filters: &api::ModFilters) -> Result<SyncPlan> {
+    let modio = api::client()?;
+    let mods = api::search_mods(&modio, filters).await?;
+
+    let indexed: Vec<IndexedMod> =
+        sqlx::query_as("SELECT id_mod, id_modfile, name, name_id, summary, description FROM mod")
+            .fetch_all(pool)
+            .await?;
+    let indexed = indexed
+        .into_iter()
+        .map(|row| (row.id_mod, row))
+        .collect::<HashMap<_, _>>();
+
+    let mut plan = SyncPlan::default();
+    for m in mods {
+        let planned = || PlannedMod {
+            id_mod: m.id,
+            name_id: m.name_id.clone(),
+        };
+        let current_modfile = match indexed.get(&i64::from(m.id)) {
+            None => {
+                plan.inserted.push(planned());
+                None
+            }
+            Some(row) => {
+                if row.name != m.name
+                    || row.name_id != m.name_id
+                    || row.summary != m.summary
+                    || row.description != m.description
+                {
+                    plan.updated.push(planned());
+                }
+                row.id_modfile.map(|id| id as u32)
+            }
+        };
+
+        if m.modfile.as_ref().map(|f| f.id) != current_modfile {
+            plan.modfile_changes.push(planned());
+            if let Some(file) = &m.modfile {
+                if !download::archive_path(&file.filehash.md5).exists() {
+                    plan.download_bytes += file.filesize;
+                    plan.downloads.push(PlannedDownload {
+                        id_mod: m.id,
+                        id_modfile: file.id,
+                        hash_md5: file.filehash.md5.clone(),
+                        filesize: file.filesize,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(plan)
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use modio::files::{File, FilePlatformStatus};
+use sqlx::AnyPool;
+
+use std::collections::HashSet;
+
+fn status_name(status: FilePlatformStatus) -> &'static str {
+    match status {
+        FilePlatformStatus::APPROVED => "approved",
+        FilePlatformStatus::DENIED => "denied",
+        _ => "pending",
+    }
+}
+
+/// Replace the recorded platforms of `file`.
+pub async fn record(conn: &mut sqlx::AnyConnection, file: &File) -> Result<()> {
+    let id_modfile = i64::from(file.id);
+    sqlx::query("DELETE FROM modfile_platform WHERE id_modfile = $1")
+        .bind(id_modfile)
+        .execute(&mut *conn)
+        .await?;
+    for platform in &file.platforms {
+        sqlx::query(
+            "INSERT INTO modfile_platform(id_modfile, platform, status) VALUES ($1, $2, $3)
+             ON CONFLICT(id_modfile, platform) DO UPDATE SET status = excluded.status",
+        )
+        .bind(id_modfile)
+        .bind(platform.target.to_string().to_lowercase())
+        .bind(status_name(platform.status))
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Mods whose current modfile is approved for `platform`, e.g. `windows` or `ps5`.
+pub async fn approved_mods(pool: &AnyPool, platform: &str) -> Result<HashSet<i64>> {
+    let ids: Vec<i64> = sqlx::query_scalar(
+        "SELECT mod.id_mod FROM mod
+         JOIN modfile_platform ON modfile_platform.id_modfile = mod.id_modfile
+         WHERE modfile_platform.platform = $1 AND modfile_platform.status = 'approved'",
+    )
+    .bind(platform.to_lowercase())
+    .fetch_all(pool)
+    .await?;
+    Ok(ids.into_iter().collect())
+}
+
+/// Keep the items whose mod's current modfile is approved for `platform`.
+pub async fn retain<T>(
+    pool: &AnyPool,
+    platform: &str,
+    items: &mut Vec<T>,
+    id_mod: impl Fn(&T) -> i64,
+) -> Result<()> {
+    let approved = approved_mods(pool, platform).await?;
+    items.retain(|item| approved.contains(&id_mod(item)));
+    Ok(())
+}
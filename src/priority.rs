@@ -0,0 +1,115 @@
+use anyhow::{bail, Result};
+use modio::filter::Filter;
+use modio::Modio;
+use sqlx::AnyPool;
+use tracing::{info, warn};
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+
+use crate::classify::Category;
+use crate::{api, collection};
+
+/// A rule selecting mods for a priority lane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// Mods the authenticated user is subscribed to
+    Subscribed,
+    /// Mods in a local collection, e.g. a watch list
+    Collection(String),
+    /// Mods classified as a category
+    Category(Category),
+}
+
+impl std::str::FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.split_once(':') {
+            None if s == "subscribed" => Rule::Subscribed,
+            Some(("collection", name)) if !name.is_empty() => Rule::Collection(name.to_string()),
+            Some(("category", category)) => match clap::ValueEnum::from_str(category, true) {
+                Ok(category) => Rule::Category(category),
+                Err(_) => bail!("unknown category in priority rule: {s}"),
+            },
+            _ => bail!(
+                "unknown priority rule {s:?}, expected subscribed, collection:<name> or \
+                 category:<category>"
+            ),
+        })
+    }
+}
+
+/// Priority rules in `DOWNLOAD_PRIORITY`, a comma separated list from highest to lowest priority,
+/// e.g. `subscribed,collection:watched,category:framework`.
+pub fn rules_from_env() -> Result<Vec<Rule>> {
+    let Ok(rules) = env::var("DOWNLOAD_PRIORITY") else {
+        return Ok(vec![]);
+    };
+    rules
+        .split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Mods selected by `rule`.
+async fn select(pool: &AnyPool, modio: &Modio, rule: &Rule) -> Result<HashSet<u32>> {
+    Ok(match rule {
+        Rule::Subscribed => modio
+            .user()
+            .subscriptions(Filter::default())
+            .collect()
+            .await?
+            .into_iter()
+            .filter(|m| m.game_id == api::DRG)
+            .map(|m| m.id)
+            .collect(),
+        Rule::Collection(name) => collection::members(pool, name)
+            .await?
+            .into_iter()
+            .map(|m| m.id_mod as u32)
+            .collect(),
+        Rule::Category(category) => {
+            let ids: Vec<i64> = sqlx::query_scalar("SELECT id_mod FROM mod WHERE category = $1")
+                .bind(category.as_str())
+                .fetch_all(pool)
+                .await?;
+            ids.into_iter().map(|id| id as u32).collect()
+        }
+    })
+}
+
+/// Reorder `mods` so those matching earlier rules are synced, and so downloaded, first. A mod is
+/// in the lane of the first rule it matches and mods matching no rule go last. The order within
+/// a lane is kept.
+pub async fn prioritize(
+    pool: &AnyPool,
+    modio: &Modio,
+    rules: &[Rule],
+    mods: &mut [modio::mods::Mod],
+) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let mut lanes = HashMap::new();
+    for (lane, rule) in rules.iter().enumerate() {
+        // a misconfigured lane should not hold up the sync itself
+        let ids = match select(pool, modio, rule).await {
+            Ok(ids) => ids,
+            Err(e) => {
+                warn!(?rule, "Skipping priority rule: {e:#}");
+                continue;
+            }
+        };
+        for id in ids {
+            lanes.entry(id).or_insert(lane);
+        }
+    }
+    mods.sort_by_key(|m| lanes.get(&m.id).copied().unwrap_or(rules.len()));
+
+    let prioritized = mods.iter().filter(|m| lanes.contains_key(&m.id)).count();
+    info!("Syncing {prioritized} prioritized mods first");
+    Ok(())
+}
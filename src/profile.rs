@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::warn;
+
+use crate::{api, collection, local, lookup};
+
+/// Format written by `export-profile`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// A mint mod loader profile
+    Mint,
+    /// One mod URL per line, as pasted into mint's add mods dialog
+    Urls,
+}
+
+/// A mod of an exported profile.
+#[derive(Debug, Serialize)]
+pub struct ProfileMod {
+    pub id_mod: i64,
+    pub name_id: String,
+    /// Modfile the mod is pinned to, the current one unless a collection pins another
+    pub id_modfile: Option<i64>,
+    pub version: Option<String>,
+    /// URL mint resolves the mod from, pinned to `id_modfile`
+    pub url: String,
+}
+
+/// URL of a mod as mint takes it, with the mod id and, to pin a version, the modfile id after the
+/// `#`.
+fn mint_url(name_id: &str, id_mod: i64, id_modfile: Option<i64>) -> String {
+    let url = api::mod_url(name_id);
+    match id_modfile {
+        Some(id_modfile) => format!("{url}#{id_mod}/{id_modfile}"),
+        None => format!("{url}#{id_mod}"),
+    }
+}
+
+/// The mods referenced by id or name_id at their current modfiles, after the members of
+/// `collection` at their pinned modfiles. Local archives are skipped as mint cannot fetch them.
+pub async fn resolve(
+    pool: &AnyPool,
+    mods: &[String],
+    collection: Option<&str>,
+) -> Result<Vec<ProfileMod>> {
+    let mut selected = vec![];
+    if let Some(name) = collection {
+        for m in collection::members(pool, name).await? {
+            selected.push((m.id_mod, m.id_modfile));
+        }
+    }
+    for reference in mods {
+        selected.push((lookup::resolve_mod(pool, reference).await?, None));
+    }
+
+    let mut profile: Vec<ProfileMod> = vec![];
+    for (id_mod, pinned) in selected {
+        if profile.iter().any(|m| m.id_mod == id_mod) {
+            continue;
+        }
+        if id_mod == local::LOCAL_MOD {
+            warn!("Skipping local archives, they are not on mod.io");
+            continue;
+        }
+        let (name_id, current): (String, Option<i64>) =
+            sqlx::query_as("SELECT name_id, id_modfile FROM mod WHERE id_mod = $1")
+                .bind(id_mod)
+                .fetch_one(pool)
+                .await?;
+        let id_modfile = pinned.or(current);
+        if id_modfile.is_none() {
+            warn!(name_id, "Mod has no modfile, exporting it unpinned");
+        }
+        let version: Option<String> =
+            sqlx::query_scalar("SELECT version FROM modfile WHERE id_modfile = $1")
+                .bind(id_modfile)
+                .fetch_optional(pool)
+                .await?
+                .flatten();
+        profile.push(ProfileMod {
+            id_mod,
+            url: mint_url(&name_id, id_mod, id_modfile),
+            name_id,
+            id_modfile,
+            version,
+        });
+    }
+    Ok(profile)
+}
+
+#[derive(Serialize)]
+struct MintSpec<'a> {
+    url: &'a str,
+}
+
+#[derive(Serialize)]
+struct MintMod<'a> {
+    spec: MintSpec<'a>,
+    required: bool,
+    enabled: bool,
+}
+
+#[derive(Serialize)]
+struct MintProfile<'a> {
+    mods: Vec<MintMod<'a>>,
+}
+
+/// `mods` as a file in `format`.
+pub fn render(mods: &[ProfileMod], format: Format) -> Result<String> {
+    Ok(match format {
+        Format::Mint => {
+            let profile = MintProfile {
+                mods: mods
+                    .iter()
+                    .map(|m| MintMod {
+                        spec: MintSpec { url: &m.url },
+                        required: true,
+                        enabled: true,
+                    })
+                    .collect(),
+            };
+            serde_json::to_string_pretty(&profile).context("failed to serialize the profile")?
+                + "\n"
+        }
+        Format::Urls => mods.iter().map(|m| format!("{}\n", m.url)).collect(),
+    })
+}
@@ -0,0 +1,464 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use crate::classify::Category;
+use crate::db::Backend;
+use crate::labels::{self, Affected};
+use crate::{approved, lookup, platform};
+
+#[derive(Debug, Serialize)]
+pub struct ModMatch {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub name: String,
+    pub category: Option<String>,
+    pub ratings: Option<Ratings>,
+    #[serde(flatten)]
+    pub affected: Affected,
+}
+
+/// Rating summary of a mod as shown on mod.io.
+#[derive(Debug, Serialize)]
+pub struct Ratings {
+    pub positive: i64,
+    pub negative: i64,
+    /// e.g. "Very Positive"
+    pub display: String,
+}
+
+impl Ratings {
+    /// `None` for mods not synced since ratings were first indexed.
+    fn from_row(
+        positive: Option<i64>,
+        negative: Option<i64>,
+        display: Option<String>,
+    ) -> Option<Self> {
+        Some(Ratings {
+            positive: positive?,
+            negative: negative?,
+            display: display?,
+        })
+    }
+}
+
+impl std::fmt::Display for Ratings {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} +{}/-{}", self.display, self.positive, self.negative)
+    }
+}
+
+/// Filters applied to the mods any query returns.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ModFilter {
+    /// Leave out mods flagged with any mature content. Mods not synced since maturity was
+    /// recorded count as not mature
+    #[clap(long, conflicts_with = "only_mature")]
+    pub hide_mature: bool,
+    /// Only mods flagged with mature content
+    #[clap(long)]
+    pub only_mature: bool,
+    /// Only mods whose current modfile mod.io approved for this platform, e.g. windows or ps5.
+    /// Lists every such mod when given alone
+    #[clap(long, value_parser, group = "selection")]
+    pub platform: Option<String>,
+}
+
+impl ModFilter {
+    /// Keep the items whose mod passes the filter.
+    pub async fn retain<T>(
+        &self,
+        pool: &AnyPool,
+        items: &mut Vec<T>,
+        id_mod: impl Fn(&T) -> i64,
+    ) -> Result<()> {
+        if let Some(platform) = &self.platform {
+            platform::retain(pool, platform, items, &id_mod).await?;
+        }
+        if !self.hide_mature && !self.only_mature {
+            return Ok(());
+        }
+        let mature: Vec<i64> =
+            sqlx::query_scalar("SELECT id_mod FROM mod WHERE maturity_option > 0")
+                .fetch_all(pool)
+                .await?;
+        let mature = mature.into_iter().collect::<std::collections::HashSet<_>>();
+        items.retain(|item| mature.contains(&id_mod(item)) == self.only_mature);
+        Ok(())
+    }
+}
+
+type ModMatchRow = (
+    i64,
+    String,
+    String,
+    Option<String>,
+    Option<i64>,
+    Option<i64>,
+    Option<String>,
+);
+
+fn mod_match(
+    (id_mod, name_id, name, category, positive, negative, display): ModMatchRow,
+) -> ModMatch {
+    ModMatch {
+        id_mod,
+        name_id,
+        name,
+        category,
+        ratings: Ratings::from_row(positive, negative, display),
+        affected: Affected::default(),
+    }
+}
+
+/// Fill in what each mod in `mods` affects.
+async fn with_affected(pool: &AnyPool, mut mods: Vec<ModMatch>) -> Result<Vec<ModMatch>> {
+    let mut affected = labels::affected_by_mod(pool).await?;
+    for m in &mut mods {
+        if let Some(a) = affected.remove(&m.id_mod) {
+            m.affected = a;
+        }
+    }
+    Ok(mods)
+}
+
+pub fn print_mods(mods: &[ModMatch]) {
+    for m in mods {
+        print!(
+            "{} {} {} [{}]",
+            m.id_mod,
+            m.name_id,
+            m.name,
+            m.category.as_deref().unwrap_or("unclassified")
+        );
+        if let Some(ratings) = &m.ratings {
+            print!(" ({ratings})");
+        }
+        let places = m.affected.places();
+        if !places.is_empty() {
+            print!(" affects {}", places.join(", "));
+        }
+        println!();
+    }
+}
+
+/// Mods derived to be of `category`, see [`crate::classify`].
+pub async fn mods_by_category(pool: &AnyPool, category: Category) -> Result<Vec<ModMatch>> {
+    let rows: Vec<ModMatchRow> = sqlx::query_as(
+        "SELECT id_mod, name_id, name, category, ratings_positive, ratings_negative, ratings_display
+         FROM mod WHERE category = $1 ORDER BY id_mod",
+    )
+    .bind(category.as_str())
+    .fetch_all(pool)
+    .await?;
+    with_affected(pool, rows.into_iter().map(mod_match).collect()).await
+}
+
+/// Indexed mods in the approved list `name`, see [`crate::approved`].
+pub async fn mods_in_list(pool: &AnyPool, name: &str) -> Result<Vec<ModMatch>> {
+    let id_list = approved::resolve(pool, name).await?;
+    let rows: Vec<ModMatchRow> = sqlx::query_as(
+        "SELECT mod.id_mod, name_id, name, category, ratings_positive, ratings_negative, ratings_display
+         FROM mod JOIN approved_list_mod ON approved_list_mod.id_mod = mod.id_mod
+         WHERE id_approved_list = $1 ORDER BY mod.id_mod",
+    )
+    .bind(id_list)
+    .fetch_all(pool)
+    .await?;
+    with_affected(pool, rows.into_iter().map(mod_match).collect()).await
+}
+
+/// Every indexed mod, to be narrowed down by a [`ModFilter`].
+pub async fn all_mods(pool: &AnyPool) -> Result<Vec<ModMatch>> {
+    let rows: Vec<ModMatchRow> = sqlx::query_as(
+        "SELECT id_mod, name_id, name, category, ratings_positive, ratings_negative, ratings_display
+         FROM mod ORDER BY id_mod",
+    )
+    .fetch_all(pool)
+    .await?;
+    with_affected(pool, rows.into_iter().map(mod_match).collect()).await
+}
+
+/// Mods whose name or name_id resembles `name`, closest first, see [`lookup::fuzzy_matches`].
+pub async fn mods_named(pool: &AnyPool, name: &str) -> Result<Vec<ModMatch>> {
+    let candidates = lookup::fuzzy_matches(pool, name).await?;
+    let rows: Vec<ModMatchRow> = sqlx::query_as(
+        "SELECT id_mod, name_id, name, category, ratings_positive, ratings_negative, ratings_display
+         FROM mod",
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut rows = rows
+        .into_iter()
+        .map(|row| (row.0, row))
+        .collect::<std::collections::HashMap<_, _>>();
+    let mods = candidates
+        .iter()
+        .filter_map(|c| rows.remove(&c.id_mod))
+        .map(mod_match)
+        .collect();
+    with_affected(pool, mods).await
+}
+
+/// A localized string found in a mod.
+#[derive(Debug, Serialize)]
+pub struct StringMatch {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub path: String,
+    pub namespace: String,
+    pub key: String,
+    pub text: String,
+}
+
+pub fn print_strings(strings: &[StringMatch]) {
+    for s in strings {
+        println!(
+            "{} {} {} {}/{}: {}",
+            s.id_mod, s.name_id, s.path, s.namespace, s.key, s.text
+        );
+    }
+}
+
+/// `LIKE` pattern matching values containing `text`, for lowercased values and `ESCAPE '\'`.
+fn contains_pattern(text: &str) -> String {
+    format!(
+        "%{}%",
+        text.to_lowercase()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    )
+}
+
+/// Condition on pack_file selecting entries whose path contains `text` ignoring case, and the
+/// value to bind to `$1` for it. Goes through the trigram index of pack_file paths, which on
+/// SQLite only finds substrings of at least three characters.
+fn path_containing(backend: Backend, text: &str) -> (&'static str, String) {
+    if backend == Backend::Sqlite && text.chars().count() >= 3 {
+        (
+            "pack_file.rowid IN (SELECT rowid FROM pack_file_path WHERE pack_file_path MATCH $1)",
+            format!("\"{}\"", text.replace('"', "\"\"")),
+        )
+    } else {
+        (
+            "LOWER(pack_file.path) LIKE $1 ESCAPE '\\'",
+            contains_pattern(text),
+        )
+    }
+}
+
+/// Strings in the current modfiles of mods that contain `text`, ignoring case.
+pub async fn strings_containing(pool: &AnyPool, text: &str) -> Result<Vec<StringMatch>> {
+    let pattern = contains_pattern(text);
+    let rows: Vec<(i64, String, String, String, String, String)> = sqlx::query_as(
+        "SELECT mod.id_mod, mod.name_id, path, namespace, key, text
+         FROM pack_file_string JOIN mod ON mod.id_modfile = pack_file_string.id_modfile
+         WHERE LOWER(text) LIKE $1 ESCAPE '\\'
+         ORDER BY mod.id_mod, path, namespace, key",
+    )
+    .bind(pattern)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id_mod, name_id, path, namespace, key, text)| StringMatch {
+                id_mod,
+                name_id,
+                path,
+                namespace,
+                key,
+                text,
+            },
+        )
+        .collect())
+}
+
+/// Mods whose current modfile contains `path`. The extension may be left off.
+pub async fn mods_with_asset(
+    pool: &AnyPool,
+    backend: Backend,
+    path: &str,
+) -> Result<Vec<ModMatch>> {
+    // the path index narrows the entries down before comparing them exactly
+    let (containing, search) = path_containing(backend, path);
+    let rows: Vec<ModMatchRow> = sqlx::query_as(&format!(
+        "SELECT DISTINCT mod.id_mod, name_id, mod.name, category,
+                ratings_positive, ratings_negative, ratings_display
+         FROM mod JOIN pack_file ON pack_file.id_modfile = mod.id_modfile
+         WHERE {containing}
+           AND (pack_file.path = $2 OR pack_file.path_no_extension = $2 || '.')
+         ORDER BY mod.id_mod"
+    ))
+    .bind(search)
+    .bind(path)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(mod_match).collect())
+}
+
+/// Mods whose current modfile has entries whose path contains `text`, ignoring case.
+pub async fn mods_with_path(pool: &AnyPool, backend: Backend, text: &str) -> Result<Vec<ModMatch>> {
+    let (containing, search) = path_containing(backend, text);
+    let rows: Vec<ModMatchRow> = sqlx::query_as(&format!(
+        "SELECT DISTINCT mod.id_mod, name_id, mod.name, category,
+                ratings_positive, ratings_negative, ratings_display
+         FROM mod JOIN pack_file ON pack_file.id_modfile = mod.id_modfile
+         WHERE {containing}
+         ORDER BY mod.id_mod"
+    ))
+    .bind(search)
+    .fetch_all(pool)
+    .await?;
+    with_affected(pool, rows.into_iter().map(mod_match).collect()).await
+}
+
+/// A mod with entries of some extension.
+#[derive(Debug, Serialize)]
+pub struct ExtensionMatch {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub name: String,
+    /// Number of entries with the extension in the current modfile
+    pub entries: i64,
+}
+
+pub fn print_extension_matches(mods: &[ExtensionMatch]) {
+    for m in mods {
+        println!(
+            "{} {} {} ({} entries)",
+            m.id_mod, m.name_id, m.name, m.entries
+        );
+    }
+}
+
+/// Mods whose current modfile contains entries with `extension`, ignoring case and a leading dot,
+/// most entries first.
+pub async fn mods_with_extension(pool: &AnyPool, extension: &str) -> Result<Vec<ExtensionMatch>> {
+    let extension = extension.trim_start_matches('.').to_lowercase();
+    let rows: Vec<(i64, String, String, i64)> = sqlx::query_as(
+        "SELECT mod.id_mod, name_id, mod.name, COUNT(*) AS entries
+         FROM mod JOIN pack_file ON pack_file.id_modfile = mod.id_modfile
+         WHERE LOWER(pack_file.extension) = $1
+         GROUP BY mod.id_mod, name_id, mod.name
+         ORDER BY entries DESC, mod.id_mod",
+    )
+    .bind(extension)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id_mod, name_id, name, entries)| ExtensionMatch {
+            id_mod,
+            name_id,
+            name,
+            entries,
+        })
+        .collect())
+}
+
+/// One line overview of a mod, displayed tab separated so it splits cleanly with `cut` or `read`.
+#[derive(Debug, Serialize)]
+pub struct ModSummary {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub name: String,
+    pub id_modfile: Option<i64>,
+    pub version: Option<String>,
+    pub category: Option<String>,
+    pub pack_files: i64,
+    /// Pack files replacing assets of the base game, `None` until a game version is indexed
+    pub vanilla_overrides: Option<i64>,
+    pub ratings: Option<Ratings>,
+    /// Creatures, biomes and mission types the current modfile touches
+    #[serde(flatten)]
+    pub affected: Affected,
+}
+
+impl std::fmt::Display for ModSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let list = |verb, names: &[String]| {
+            if names.is_empty() {
+                "-".to_string()
+            } else {
+                format!("{verb} {}", names.join(", "))
+            }
+        };
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.id_mod,
+            self.name_id,
+            self.version.as_deref().unwrap_or("-"),
+            self.category.as_deref().unwrap_or("-"),
+            self.pack_files,
+            self.name,
+            self.ratings
+                .as_ref()
+                .map(|r| r.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            list("modifies", &self.affected.enemies),
+            list("affects", &self.affected.places()),
+            self.vanilla_overrides
+                .map(|n| format!("overrides {n} base game assets"))
+                .unwrap_or_else(|| "-".to_string())
+        )
+    }
+}
+
+pub async fn mod_summary(pool: &AnyPool, id_mod: i64) -> Result<ModSummary> {
+    let row: ModSummaryRow = sqlx::query_as(
+        "SELECT name_id, name, mod.id_modfile, version, category,
+                (SELECT COUNT(*) FROM pack_file WHERE pack_file.id_modfile = mod.id_modfile),
+                (SELECT CAST(SUM(vanilla_override) AS BIGINT) FROM pack_file
+                 WHERE pack_file.id_modfile = mod.id_modfile),
+                ratings_positive, ratings_negative, ratings_display
+             FROM mod LEFT JOIN modfile ON modfile.id_modfile = mod.id_modfile
+             WHERE mod.id_mod = $1",
+    )
+    .bind(id_mod)
+    .fetch_one(pool)
+    .await?;
+    let (
+        name_id,
+        name,
+        id_modfile,
+        version,
+        category,
+        pack_files,
+        vanilla_overrides,
+        positive,
+        negative,
+        display,
+    ) = row;
+    let affected = match id_modfile {
+        Some(id_modfile) => labels::affected(pool, id_modfile).await?,
+        None => Affected::default(),
+    };
+    Ok(ModSummary {
+        id_mod,
+        name_id,
+        name,
+        id_modfile,
+        version,
+        category,
+        pack_files,
+        vanilla_overrides,
+        ratings: Ratings::from_row(positive, negative, display),
+        affected,
+    })
+}
+
+type ModSummaryRow = (
+    String,
+    String,
+    Option<i64>,
+    Option<String>,
+    Option<String>,
+    i64,
+    Option<i64>,
+    Option<i64>,
+    Option<i64>,
+    Option<String>,
+);
@@ -0,0 +1,174 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::warn;
+
+use std::collections::HashMap;
+
+use crate::{api, local};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DriftKind {
+    /// Listed upstream but not indexed
+    Missing,
+    /// Indexed but no longer listed upstream
+    Deleted,
+    /// The current modfile upstream is not the indexed one
+    Modfile,
+    /// The current modfile kept its id but was re-uploaded with a different hash
+    Hash,
+    /// Name, name_id, summary or description differ
+    Metadata,
+}
+
+impl DriftKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            DriftKind::Missing => "missing",
+            DriftKind::Deleted => "deleted",
+            DriftKind::Modfile => "modfile",
+            DriftKind::Hash => "hash",
+            DriftKind::Metadata => "metadata",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Drift {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub kind: DriftKind,
+    pub indexed: Option<String>,
+    pub upstream: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReconcileReport {
+    pub upstream: u64,
+    pub indexed: u64,
+    pub drift: Vec<Drift>,
+}
+
+impl std::fmt::Display for ReconcileReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for d in &self.drift {
+            write!(f, "{} mod {} {}", d.kind.as_str(), d.id_mod, d.name_id)?;
+            if d.indexed.is_some() || d.upstream.is_some() {
+                write!(f, ": indexed {:?} upstream {:?}", d.indexed, d.upstream)?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "{} mods upstream, {} indexed, {} drifted",
+            self.upstream,
+            self.indexed,
+            self.drift.len()
+        )
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IndexedMod {
+    id_mod: i64,
+    name: String,
+    name_id: String,
+    summary: String,
+    description: Option<String>,
+    id_modfile: Option<i64>,
+    hash_md5: Option<String>,
+}
+
+/// Compare a full listing of the catalog with the index without changing or downloading
+/// anything. Meant to run nightly next to frequent syncs to catch what they missed: mods that
+/// were deleted or never indexed, modfiles replaced or re-uploaded in place and stale metadata.
+/// A sync picks up missing mods, replaced modfiles and metadata changes, re-uploads need
+/// `audit-upstream` and deleted mods are kept in the index.
+pub async fn reconcile(pool: &AnyPool) -> Result<ReconcileReport> {
+    let modio = api::client()?;
+    let mods = api::mod_list(&modio).await?;
+
+    let indexed: Vec<IndexedMod> = sqlx::query_as(
+        "SELECT mod.id_mod, name, name_id, summary, description, mod.id_modfile, hash_md5
+         FROM mod LEFT JOIN modfile ON modfile.id_modfile = mod.id_modfile",
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut indexed = indexed
+        .into_iter()
+        .map(|row| (row.id_mod, row))
+        .collect::<HashMap<_, _>>();
+    // local archives are not in the catalog
+    indexed.remove(&local::LOCAL_MOD);
+
+    let mut report = ReconcileReport {
+        upstream: mods.len() as u64,
+        indexed: indexed.len() as u64,
+        drift: vec![],
+    };
+    let mut drift = |id_mod, name_id: &str, kind, indexed, upstream| {
+        warn!(
+            id_mod,
+            name_id,
+            kind = DriftKind::as_str(kind),
+            "Index drifted"
+        );
+        report.drift.push(Drift {
+            id_mod,
+            name_id: name_id.to_string(),
+            kind,
+            indexed,
+            upstream,
+        });
+    };
+
+    for m in &mods {
+        let id_mod = i64::from(m.id);
+        let Some(row) = indexed.remove(&id_mod) else {
+            drift(id_mod, &m.name_id, DriftKind::Missing, None, None);
+            continue;
+        };
+        if row.name != m.name
+            || row.name_id != m.name_id
+            || row.summary != m.summary
+            || row.description != m.description
+        {
+            drift(
+                id_mod,
+                &m.name_id,
+                DriftKind::Metadata,
+                Some(row.name),
+                Some(m.name.clone()),
+            );
+        }
+        let upstream_modfile = m.modfile.as_ref().map(|f| i64::from(f.id));
+        if upstream_modfile != row.id_modfile {
+            drift(
+                id_mod,
+                &m.name_id,
+                DriftKind::Modfile,
+                row.id_modfile.map(|id| id.to_string()),
+                upstream_modfile.map(|id| id.to_string()),
+            );
+        } else if let Some(file) = &m.modfile {
+            if row.hash_md5.as_deref() != Some(file.filehash.md5.as_str()) {
+                drift(
+                    id_mod,
+                    &m.name_id,
+                    DriftKind::Hash,
+                    row.hash_md5,
+                    Some(file.filehash.md5.clone()),
+                );
+            }
+        }
+    }
+
+    let mut deleted = indexed.into_values().collect::<Vec<_>>();
+    deleted.sort_by_key(|row| row.id_mod);
+    for row in deleted {
+        drift(row.id_mod, &row.name_id, DriftKind::Deleted, None, None);
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,194 @@
+use anyhow::{bail, Context, Result};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use tracing::info;
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::pak_source::Window;
+use crate::PakEntry;
+
+/// Bytes fetched per range request. The zip central directory and a pak index usually fit in one
+/// or two blocks.
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// A file served over HTTP read with range requests, fetching blocks as they are read. Has to be
+/// used from a blocking thread as reads wait on the runtime.
+struct RangeReader {
+    client: reqwest::Client,
+    url: reqwest::Url,
+    runtime: tokio::runtime::Handle,
+    size: u64,
+    pos: u64,
+    /// Fetched ranges by start offset
+    blocks: BTreeMap<u64, Vec<u8>>,
+    fetched: u64,
+}
+
+impl RangeReader {
+    fn open(url: reqwest::Url, runtime: tokio::runtime::Handle) -> Result<Self> {
+        let mut reader = Self {
+            client: reqwest::Client::new(),
+            url,
+            runtime,
+            size: 0,
+            pos: 0,
+            blocks: BTreeMap::new(),
+            fetched: 0,
+        };
+        // the zip central directory is at the end, and the response reports the total size
+        let (start, size, data) = reader.fetch(&format!("bytes=-{BLOCK_SIZE}"))?;
+        reader.size = size;
+        reader.fetched += data.len() as u64;
+        reader.blocks.insert(start, data);
+        Ok(reader)
+    }
+
+    /// Request `range`, returning the start offset of the response, the size of the whole file
+    /// and the data.
+    fn fetch(&self, range: &str) -> Result<(u64, u64, Vec<u8>)> {
+        self.runtime.block_on(async {
+            let res = self
+                .client
+                .get(self.url.clone())
+                .header(RANGE, range)
+                .send()
+                .await?
+                .error_for_status()?;
+            if res.status() != StatusCode::PARTIAL_CONTENT {
+                bail!("server does not support range requests");
+            }
+            let content_range = res
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|v| v.to_str().ok())
+                .context("response has no Content-Range")?
+                .to_string();
+            let (start, size) = parse_content_range(&content_range)
+                .with_context(|| format!("invalid Content-Range {content_range:?}"))?;
+            Ok((start, size, res.bytes().await?.to_vec()))
+        })
+    }
+
+    /// The fetched block containing `pos`, fetching it if needed.
+    fn block(&mut self, pos: u64) -> Result<(u64, &[u8])> {
+        let cached = self
+            .blocks
+            .range(..=pos)
+            .next_back()
+            .is_some_and(|(start, data)| pos < start + data.len() as u64);
+        if !cached {
+            let end = (pos + BLOCK_SIZE).min(self.size) - 1;
+            let (start, _, data) = self.fetch(&format!("bytes={pos}-{end}"))?;
+            if start != pos || data.is_empty() {
+                bail!("server returned a different range than requested");
+            }
+            self.fetched += data.len() as u64;
+            self.blocks.insert(start, data);
+        }
+        let (start, data) = self.blocks.range(..=pos).next_back().unwrap();
+        Ok((*start, data))
+    }
+}
+
+/// Start offset and total size from a `bytes start-end/size` Content-Range.
+fn parse_content_range(value: &str) -> Option<(u64, u64)> {
+    let (range, size) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.parse().ok()?, size.parse().ok()?))
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let pos = self.pos;
+        let (start, data) = self.block(pos).map_err(std::io::Error::other)?;
+        let data = &data[(pos - start) as usize..];
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for RangeReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.pos = seek(self.pos, self.size, pos)?;
+        Ok(self.pos)
+    }
+}
+
+fn seek(current: u64, len: u64, pos: SeekFrom) -> std::io::Result<u64> {
+    let new = match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::End(offset) => len.checked_add_signed(offset),
+        SeekFrom::Current(offset) => current.checked_add_signed(offset),
+    };
+    new.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "seek to a negative position",
+        )
+    })
+}
+
+/// List the entries of the pak in the archive of `file` without downloading it, by reading only
+/// the zip central directory and the pak index from the CDN with range requests. Entries have no
+/// hash, asset class or strings as their data is never read. Fails if the pak is compressed inside
+/// the zip, which can only be read from the start, so the caller has to download it instead.
+pub async fn list_modfile(file: &modio::files::File) -> Result<crate::PakListing> {
+    let url = file.download.binary_url.clone();
+    let runtime = tokio::runtime::Handle::current();
+    let id_modfile = file.id;
+    tokio::task::spawn_blocking(move || {
+        let reader = RangeReader::open(url, runtime)?;
+        let size = reader.size;
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let mut pak = None;
+        for i in 0..archive.len() {
+            let entry = archive.by_index_raw(i)?;
+            if entry.is_file() && entry.name().to_lowercase().ends_with(".pak") {
+                if entry.compression() != zip::CompressionMethod::Stored {
+                    bail!("pak is compressed in the archive");
+                }
+                pak = Some((entry.data_start(), entry.compressed_size()));
+                break;
+            }
+        }
+        let (start, len) = pak.context("archive contains no pak")?;
+
+        let mut window = Window::new(archive.into_inner(), start, len)?;
+        let pak = repak::PakReader::new_any(&mut window, None)
+            .map_err(|e| crate::PakError::ErrorReadingPak { e })?;
+        let mount_point = pak.mount_point().to_string();
+        let entries = pak
+            .files()
+            .map(|record| {
+                Ok(PakEntry {
+                    path: crate::asset_path(&mount_point, &record)?,
+                    hash: None,
+                    size: None,
+                    asset_class: None,
+                    strings: vec![],
+                    audio: vec![],
+                    references: vec![],
+                    rows: Default::default(),
+                })
+            })
+            .collect::<Result<Vec<_>, crate::PakError>>()?;
+        info!(
+            id_modfile,
+            fetched = window.get_ref().fetched,
+            size,
+            "Listed pak remotely"
+        );
+        Ok(crate::PakListing {
+            mount_point,
+            entries,
+        })
+    })
+    .await?
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use async_graphql::http::GraphiQLSource;
+use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Deserialize;
+use sqlx::AnyPool;
+use tracing::{error, info};
+
+use std::net::SocketAddr;
+
+use crate::graphql::{self, IndexSchema};
+
+/// Modfiles in `/feed.xml` unless `?limit=` is given.
+const FEED_LIMIT: i64 = 50;
+
+/// Serve the index over HTTP until SIGINT or SIGTERM: GraphQL queries are posted to `/graphql`,
+/// which opens GraphiQL in a browser, the Atom feed of new modfiles is at `/feed.xml` and
+/// Prometheus scrapes `/metrics`.
+pub async fn serve(pool: AnyPool, address: SocketAddr) -> Result<()> {
+    let app = Router::new()
+        .route("/graphql", get(graphiql).post(execute))
+        .with_state(graphql::schema(pool.clone()))
+        .merge(
+            Router::new()
+                .route("/feed.xml", get(feed))
+                .with_state(pool.clone()),
+        )
+        .merge(metrics_routes(pool));
+
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    info!(address = %listener.local_addr()?, "Serving the index");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            if let Err(e) = crate::daemon::wait_for_signal().await {
+                error!("Failed to listen for shutdown signals: {e:#}");
+                std::future::pending::<()>().await;
+            }
+        })
+        .await?;
+    info!("Server stopped");
+    Ok(())
+}
+
+/// Serve only `/metrics` in the background, for the daemon. Binds before returning so a busy
+/// address fails the command instead of a background task.
+pub async fn spawn_metrics(pool: AnyPool, address: SocketAddr) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    info!(address = %listener.local_addr()?, "Serving metrics");
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, metrics_routes(pool)).await {
+            error!("Metrics server failed: {e:#}");
+        }
+    });
+    Ok(())
+}
+
+fn metrics_routes(pool: AnyPool) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(pool)
+}
+
+async fn metrics(State(pool): State<AnyPool>) -> Response {
+    match crate::metrics::render(&pool).await {
+        Ok(body) => ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response(),
+        Err(e) => {
+            error!("Failed to render metrics: {e:#}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FeedParams {
+    limit: Option<i64>,
+}
+
+async fn feed(State(pool): State<AnyPool>, Query(params): Query<FeedParams>) -> Response {
+    let limit = params.limit.unwrap_or(FEED_LIMIT);
+    match crate::feed::atom_feed(&pool, limit).await {
+        Ok(body) => ([(header::CONTENT_TYPE, "application/atom+xml")], body).into_response(),
+        Err(e) => {
+            error!("Failed to render the feed: {e:#}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+async fn execute(
+    State(schema): State<IndexSchema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use crate::api::{self, QuotaSample};
+use crate::store::{self, StoreStats};
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub mods: i64,
+    pub modfiles: i64,
+    pub pack_files: i64,
+    pub store: StoreStats,
+    /// Quota reported by the last mod.io response, `None` before the first sync
+    pub quota: Option<QuotaSample>,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "mods: {}", self.mods)?;
+        writeln!(f, "modfiles: {}", self.modfiles)?;
+        writeln!(f, "pack files: {}", self.pack_files)?;
+        writeln!(f, "store: {}", self.store)?;
+        match &self.quota {
+            Some(quota) => {
+                write!(
+                    f,
+                    "api quota: {}/{} remaining as of {}",
+                    quota.remaining, quota.rate_limit, quota.date_sampled
+                )?;
+                if let Some(retry_after) = quota.retry_after {
+                    write!(f, ", retry after {retry_after}s")?;
+                }
+                Ok(())
+            }
+            None => write!(f, "api quota: unknown"),
+        }
+    }
+}
+
+pub async fn stats(pool: &AnyPool) -> Result<Stats> {
+    let (mods, modfiles, pack_files): (i64, i64, i64) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM mod), (SELECT COUNT(*) FROM modfile),
+                (SELECT COUNT(*) FROM pack_file)",
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(Stats {
+        mods,
+        modfiles,
+        pack_files,
+        store: store::stats(pool).await?,
+        quota: api::latest_quota(pool).await?,
+    })
+}
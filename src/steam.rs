@@ -0,0 +1,277 @@
+use anyhow::{bail, Result};
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Steam app id of Deep Rock Galactic.
+const DRG_APP_ID: u32 = 548430;
+
+/// Directory of the game below a library's `steamapps/common`.
+const DRG_DIR_NAME: &str = "Deep Rock Galactic";
+
+/// Where Steam keeps its own library on this platform.
+fn steam_roots() -> Vec<PathBuf> {
+    let mut roots = vec![];
+    if cfg!(windows) {
+        for var in ["ProgramFiles(x86)", "ProgramFiles"] {
+            if let Ok(dir) = env::var(var) {
+                roots.push(Path::new(&dir).join("Steam"));
+            }
+        }
+        roots.push(PathBuf::from(r"C:\Program Files (x86)\Steam"));
+    } else if let Some(home) = dirs::home_dir() {
+        roots.push(home.join(".steam/steam"));
+        roots.push(home.join(".local/share/Steam"));
+        roots.push(home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"));
+    }
+    roots
+}
+
+/// A value of a VDF (KeyValues) file, the format of Steam's config files.
+enum Vdf {
+    String(String),
+    Object(Vec<(String, Vdf)>),
+}
+
+impl Vdf {
+    fn get(&self, key: &str) -> Option<&Vdf> {
+        match self {
+            Vdf::Object(entries) => entries
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            Vdf::String(_) => None,
+        }
+    }
+}
+
+/// Parse the entries of a VDF object up to its closing brace, or to the end of the file at the top
+/// level. Comments and malformed trailing input are skipped.
+fn parse_vdf(chars: &mut std::iter::Peekable<std::str::Chars>) -> Vdf {
+    fn string(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+        let mut s = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => s.extend(chars.next()),
+                c => s.push(c),
+            }
+        }
+        s
+    }
+
+    let mut entries = vec![];
+    let mut key = None;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let s = string(chars);
+                match key.take() {
+                    None => key = Some(s),
+                    Some(k) => entries.push((k, Vdf::String(s))),
+                }
+            }
+            '{' => {
+                let value = parse_vdf(chars);
+                if let Some(k) = key.take() {
+                    entries.push((k, value));
+                }
+            }
+            '}' => break,
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Vdf::Object(entries)
+}
+
+/// Library folders listed in `libraryfolders.vdf`, each with the app ids Steam installed in it.
+/// Older files list the paths directly under numbered keys, without apps.
+fn library_folders(contents: &str) -> Vec<(PathBuf, Vec<u32>)> {
+    let vdf = parse_vdf(&mut contents.chars().peekable());
+    let Some(Vdf::Object(folders)) = vdf.get("libraryfolders") else {
+        return vec![];
+    };
+    folders
+        .iter()
+        .filter(|(key, _)| key.parse::<u32>().is_ok())
+        .filter_map(|(_, folder)| match folder {
+            Vdf::String(path) => Some((PathBuf::from(path), vec![])),
+            Vdf::Object(_) => {
+                let Some(Vdf::String(path)) = folder.get("path") else {
+                    return None;
+                };
+                let apps = match folder.get("apps") {
+                    Some(Vdf::Object(apps)) => {
+                        apps.iter().filter_map(|(id, _)| id.parse().ok()).collect()
+                    }
+                    _ => vec![],
+                };
+                Some((PathBuf::from(path), apps))
+            }
+        })
+        .collect()
+}
+
+/// Whether `dir` looks like a DRG install, a directory containing `FSD`.
+pub fn is_game_dir(dir: &Path) -> bool {
+    dir.join("FSD").is_dir()
+}
+
+/// The DRG install directory: `DRG_GAME_DIR` if set, otherwise found through the Steam library
+/// folders, preferring the library Steam lists the game in.
+pub fn detect_game_dir() -> Result<PathBuf> {
+    if let Ok(dir) = env::var("DRG_GAME_DIR") {
+        if !dir.trim().is_empty() {
+            let dir = PathBuf::from(dir);
+            if !is_game_dir(&dir) {
+                bail!("DRG_GAME_DIR {} has no FSD directory", dir.display());
+            }
+            return Ok(dir);
+        }
+    }
+
+    let mut libraries = vec![];
+    for root in steam_roots() {
+        let vdf = root.join("steamapps/libraryfolders.vdf");
+        if let Ok(contents) = std::fs::read_to_string(&vdf) {
+            libraries.extend(library_folders(&contents));
+        }
+        libraries.push((root, vec![]));
+    }
+    // libraries listing the game first, then every library in case the list is stale
+    libraries.sort_by_key(|(_, apps)| !apps.contains(&DRG_APP_ID));
+    for (library, _) in libraries {
+        let dir = library.join("steamapps/common").join(DRG_DIR_NAME);
+        if is_game_dir(&dir) {
+            return Ok(dir);
+        }
+    }
+    bail!("could not find Deep Rock Galactic in any Steam library, pass --game-dir or set DRG_GAME_DIR")
+}
+
+/// `game_dir` if given, otherwise the detected install.
+pub fn game_dir(game_dir: Option<PathBuf>) -> Result<PathBuf> {
+    match game_dir {
+        Some(dir) => Ok(dir),
+        None => detect_game_dir(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Vdf {
+        parse_vdf(&mut s.chars().peekable())
+    }
+
+    fn string<'a>(vdf: &'a Vdf, key: &str) -> Option<&'a str> {
+        match vdf.get(key) {
+            Some(Vdf::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn library_folders_with_apps() {
+        let contents = r#"
+"libraryfolders"
+{
+	"0"
+	{
+		"path"		"/home/user/.local/share/Steam"
+		"label"		""
+		"apps"
+		{
+			"228980"		"123"
+		}
+	}
+	"1"
+	{
+		"path"		"/mnt/games/SteamLibrary"
+		"apps"
+		{
+			"548430"		"456"
+			"1966720"		"789"
+		}
+	}
+}
+"#;
+        assert_eq!(
+            library_folders(contents),
+            vec![
+                (PathBuf::from("/home/user/.local/share/Steam"), vec![228980]),
+                (
+                    PathBuf::from("/mnt/games/SteamLibrary"),
+                    vec![548430, 1966720]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn library_folders_old_format() {
+        let contents = r#"
+"LibraryFolders"
+{
+	"TimeNextStatsReport"		"1600000000"
+	"ContentStatsID"		"-1234"
+	"1"		"D:\\SteamLibrary"
+}
+"#;
+        assert_eq!(
+            library_folders(contents),
+            vec![(PathBuf::from(r"D:\SteamLibrary"), vec![])]
+        );
+    }
+
+    #[test]
+    fn escaped_quotes_and_backslashes() {
+        let vdf = parse(r#""name" "a \"quoted\" value" "path" "C:\\Program Files (x86)\\Steam""#);
+        assert_eq!(string(&vdf, "name"), Some(r#"a "quoted" value"#));
+        assert_eq!(string(&vdf, "path"), Some(r"C:\Program Files (x86)\Steam"));
+    }
+
+    #[test]
+    fn comments_and_case_insensitive_keys() {
+        let vdf = parse(
+            r#"
+// written by Steam
+"Root"
+{
+	// "commented" "out"
+	"Key"		"value"
+}
+"#,
+        );
+        let root = vdf.get("root").unwrap();
+        assert_eq!(string(root, "KEY"), Some("value"));
+        assert_eq!(string(root, "commented"), None);
+    }
+
+    #[test]
+    fn truncated_input() {
+        for contents in [
+            "",
+            "\"",
+            "\"libraryfolders\"",
+            "\"libraryfolders\" {",
+            "\"libraryfolders\" { \"0\" { \"path\" \\",
+            "}}}",
+        ] {
+            assert_eq!(library_folders(contents), vec![], "{contents:?}");
+        }
+        // what was read of a cut off value is kept
+        assert_eq!(
+            library_folders("\"libraryfolders\" { \"0\" { \"path\" \"/mnt"),
+            vec![(PathBuf::from("/mnt"), vec![])]
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::{info, warn};
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Directory holding the content addressed store of archives.
+pub const STORE_DIR: &str = "mods";
+
+/// Length of the hash prefix naming the directory an object is stored in, which keeps any one
+/// directory from growing to tens of thousands of entries.
+const PREFIX_LEN: usize = 2;
+
+/// Location of the object with hash `md5`, e.g. `mods/3f/3fa2….zip`. Archives are named by content
+/// hash so identical uploads share one object.
+pub fn object_path(md5: &str) -> PathBuf {
+    let prefix = md5.get(..PREFIX_LEN).unwrap_or(md5);
+    Path::new(STORE_DIR).join(prefix).join(format!("{md5}.zip"))
+}
+
+/// Hash of the object stored at `path`, if it is one.
+fn object_hash(path: &Path) -> Option<String> {
+    if path.extension().and_then(|e| e.to_str()) != Some("zip") {
+        return None;
+    }
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .map(str::to_string)
+}
+
+/// Every object in the store as `(hash, path)`, sorted by hash. Partial downloads are skipped.
+pub fn objects() -> Result<Vec<(String, PathBuf)>> {
+    let mut objects = vec![];
+    if !Path::new(STORE_DIR).exists() {
+        return Ok(objects);
+    }
+    for prefix in std::fs::read_dir(STORE_DIR)? {
+        let prefix = prefix?.path();
+        if !prefix.is_dir() {
+            continue;
+        }
+        for entry in std::fs::read_dir(&prefix)? {
+            let path = entry?.path();
+            if let Some(hash) = object_hash(&path) {
+                objects.push((hash, path));
+            }
+        }
+    }
+    objects.sort();
+    Ok(objects)
+}
+
+/// Move archives stored directly in [`STORE_DIR`] by earlier versions into their prefix
+/// directory. Returns the number of archives moved.
+pub fn migrate_layout() -> Result<u64> {
+    if !Path::new(STORE_DIR).exists() {
+        return Ok(0);
+    }
+    let mut moved = 0;
+    for entry in std::fs::read_dir(STORE_DIR)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let Some(hash) = object_hash(&path) else {
+            continue;
+        };
+        let target = object_path(&hash);
+        std::fs::create_dir_all(target.parent().unwrap())?;
+        std::fs::rename(&path, &target)?;
+        moved += 1;
+    }
+    if moved > 0 {
+        info!("Moved {moved} archives into the content addressed layout");
+    }
+    Ok(moved)
+}
+
+/// Size of the store and what sharing objects between modfiles saves.
+#[derive(Debug, Default, Serialize)]
+pub struct StoreStats {
+    pub objects: i64,
+    pub bytes: i64,
+    /// Modfiles referring to a stored object
+    pub refs: i64,
+    /// Bytes that storing every modfile separately would take on top of `bytes`
+    pub saved_bytes: i64,
+}
+
+impl std::fmt::Display for StoreStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} objects, {} bytes, {} references, {} bytes saved by deduplication",
+            self.objects, self.bytes, self.refs, self.saved_bytes
+        )
+    }
+}
+
+/// Record the objects in the store and count the modfiles referring to each. Objects referred to
+/// by no modfile are kept with 0 references for `verify --fix` to delete.
+pub async fn refresh(pool: &AnyPool) -> Result<StoreStats> {
+    let objects = objects()?;
+    let refs: Vec<(String, i64)> =
+        sqlx::query_as("SELECT hash_md5, COUNT(*) FROM modfile GROUP BY hash_md5")
+            .fetch_all(pool)
+            .await?;
+    let refs = refs.into_iter().collect::<HashMap<_, _>>();
+
+    let recorded: Vec<String> = sqlx::query_scalar("SELECT hash_md5 FROM store_object")
+        .fetch_all(pool)
+        .await?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut tx = pool.begin().await?;
+    let stored = objects.iter().map(|(hash, _)| hash).collect::<HashSet<_>>();
+    for hash_md5 in recorded.iter().filter(|hash| !stored.contains(hash)) {
+        sqlx::query("DELETE FROM store_object WHERE hash_md5 = $1")
+            .bind(hash_md5)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for (hash_md5, path) in &objects {
+        let size = std::fs::metadata(path)?.len();
+        sqlx::query(
+            "INSERT INTO store_object(hash_md5, size, refs, date_stored) VALUES ($1, $2, $3, $4)
+             ON CONFLICT(hash_md5) DO UPDATE SET size = excluded.size, refs = excluded.refs",
+        )
+        .bind(hash_md5)
+        .bind(size as i64)
+        .bind(refs.get(hash_md5).copied().unwrap_or(0))
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    stats(pool).await
+}
+
+pub async fn stats(pool: &AnyPool) -> Result<StoreStats> {
+    let (objects, bytes, refs, saved_bytes) = sqlx::query_as(
+        "SELECT COUNT(*),
+                CAST(COALESCE(SUM(size), 0) AS BIGINT),
+                CAST(COALESCE(SUM(refs), 0) AS BIGINT),
+                CAST(COALESCE(SUM(CASE WHEN refs > 1 THEN (refs - 1) * size ELSE 0 END), 0) AS BIGINT)
+         FROM store_object",
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(StoreStats {
+        objects,
+        bytes,
+        refs,
+        saved_bytes,
+    })
+}
+
+/// Parse a size like `200GB`, `500M` or `1024`. Units are powers of 1024 and the `B` is optional.
+pub fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("invalid size {s:?}"))?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let shift = match unit.strip_suffix('B').unwrap_or(&unit) {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => bail!("invalid size unit {unit:?}, expected B, KB, MB, GB or TB"),
+    };
+    number
+        .checked_mul(1 << shift)
+        .with_context(|| format!("size {s:?} is too large"))
+}
+
+/// Storage budget for the store from `MAX_STORE_SIZE`, e.g. `200GB`. `None` if unlimited.
+pub fn max_size() -> Result<Option<u64>> {
+    match env::var("MAX_STORE_SIZE") {
+        Ok(size) if !size.trim().is_empty() => parse_size(&size)
+            .map(Some)
+            .context("invalid MAX_STORE_SIZE"),
+        _ => Ok(None),
+    }
+}
+
+/// What [`evict`] removed.
+#[derive(Debug, Default, Serialize)]
+pub struct Eviction {
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+/// Delete archives of superseded modfiles until the store fits in [`max_size`], starting with the
+/// ones whose newest modfile was uploaded longest ago. Archives of current modfiles and of modfiles
+/// pinned by a collection are never evicted. Pack files are kept so the index still covers the
+/// evicted modfiles, which are recorded as discarded so verify does not report them as missing.
+/// Expects the store to have been [`refresh`]ed.
+pub async fn evict(pool: &AnyPool) -> Result<Eviction> {
+    let mut eviction = Eviction::default();
+    let Some(max_size) = max_size()? else {
+        return Ok(eviction);
+    };
+    let size = stats(pool).await?.bytes as u64;
+    if size <= max_size {
+        return Ok(eviction);
+    }
+    if crate::archive::enabled() {
+        warn!(
+            size,
+            max_size, "Store is over MAX_STORE_SIZE but nothing is evicted in archive mode"
+        );
+        return Ok(eviction);
+    }
+
+    let candidates: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT store_object.hash_md5, size
+         FROM store_object JOIN modfile ON modfile.hash_md5 = store_object.hash_md5
+         WHERE store_object.hash_md5 NOT IN
+                (SELECT hash_md5 FROM mod JOIN modfile ON modfile.id_modfile = mod.id_modfile)
+           AND store_object.hash_md5 NOT IN
+                (SELECT hash_md5 FROM collection_mod
+                 JOIN collection ON collection.id_collection = collection_mod.id_collection
+                 JOIN modfile ON modfile.id_modfile = collection_mod.id_modfile
+                 WHERE collection.date_deleted IS NULL)
+         GROUP BY store_object.hash_md5, size
+         ORDER BY MAX(modfile.date_added), store_object.hash_md5",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut size = size;
+    for (hash_md5, object_size) in candidates {
+        if size <= max_size {
+            break;
+        }
+        match std::fs::remove_file(object_path(&hash_md5)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        let ids: Vec<i64> =
+            sqlx::query_scalar("SELECT id_modfile FROM modfile WHERE hash_md5 = $1")
+                .bind(&hash_md5)
+                .fetch_all(pool)
+                .await?;
+        for id_modfile in ids {
+            crate::download::mark_discarded(pool, id_modfile).await?;
+        }
+        sqlx::query("DELETE FROM store_object WHERE hash_md5 = $1")
+            .bind(&hash_md5)
+            .execute(pool)
+            .await?;
+        size = size.saturating_sub(object_size as u64);
+        eviction.objects += 1;
+        eviction.bytes += object_size as u64;
+    }
+    if eviction.objects > 0 {
+        info!(
+            objects = eviction.objects,
+            bytes = eviction.bytes,
+            "Evicted superseded archives"
+        );
+    }
+    if size > max_size {
+        warn!(
+            size,
+            max_size, "Store is over MAX_STORE_SIZE with only current and pinned archives left"
+        );
+    }
+    Ok(eviction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("512B").unwrap(), 512);
+        assert_eq!(parse_size("4K").unwrap(), 4 << 10);
+        assert_eq!(parse_size("4kb").unwrap(), 4 << 10);
+        assert_eq!(parse_size("20 MB").unwrap(), 20 << 20);
+        assert_eq!(parse_size(" 200GB ").unwrap(), 200 << 30);
+        assert_eq!(parse_size("3TB").unwrap(), 3 << 40);
+    }
+
+    #[test]
+    fn parse_size_rejects_invalid() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("GB").is_err());
+        assert!(parse_size("5PB").is_err());
+        assert!(parse_size("1.5GB").is_err());
+        assert!(parse_size("-1GB").is_err());
+    }
+
+    #[test]
+    fn parse_size_overflow() {
+        assert_eq!(parse_size(&u64::MAX.to_string()).unwrap(), u64::MAX);
+        assert_eq!(parse_size("16777215TB").unwrap(), 16777215 << 40);
+        assert!(parse_size("16777216TB").is_err());
+        assert!(parse_size(&format!("{}KB", u64::MAX)).is_err());
+        // does not fit in a u64 at all
+        assert!(parse_size("99999999999999999999").is_err());
+    }
+}
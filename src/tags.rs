@@ -0,0 +1,53 @@
+//! Tags mods carry on mod.io, recorded on every sync so they can be listed and filtered on without
+//! asking mod.io.
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+/// Replace the recorded tags of `m`.
+pub async fn record(conn: &mut sqlx::AnyConnection, m: &modio::mods::Mod) -> Result<()> {
+    let id_mod = i64::from(m.id);
+    sqlx::query("DELETE FROM mod_tag WHERE id_mod = $1")
+        .bind(id_mod)
+        .execute(&mut *conn)
+        .await?;
+    for tag in &m.tags {
+        sqlx::query("INSERT INTO mod_tag(id_mod, tag) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(id_mod)
+            .bind(&tag.name)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// A tag and how many indexed mods carry it.
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub mods: i64,
+}
+
+/// Every recorded tag, most used first.
+pub async fn counts(pool: &AnyPool) -> Result<Vec<TagCount>> {
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT tag, COUNT(*) FROM mod_tag GROUP BY tag ORDER BY COUNT(*) DESC, tag",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(tag, mods)| TagCount { tag, mods })
+        .collect())
+}
+
+/// Tags of a mod, sorted.
+pub async fn of_mod(pool: &AnyPool, id_mod: i64) -> Result<Vec<String>> {
+    Ok(
+        sqlx::query_scalar("SELECT tag FROM mod_tag WHERE id_mod = $1 ORDER BY tag")
+            .bind(id_mod)
+            .fetch_all(pool)
+            .await?,
+    )
+}
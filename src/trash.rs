@@ -0,0 +1,195 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::info;
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{daemon, store};
+
+/// Directory archives are moved to instead of being deleted.
+pub const TRASH_DIR: &str = "trash";
+
+/// How long trashed data is kept when `TRASH_RETENTION` is not set.
+const DEFAULT_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrashKind {
+    /// An archive moved out of the store
+    Archive,
+    /// A collection flagged as deleted
+    Collection,
+}
+
+impl TrashKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TrashKind::Archive => "archive",
+            TrashKind::Collection => "collection",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrashEntry {
+    pub id_trash: i64,
+    pub kind: String,
+    pub name: String,
+    pub reason: String,
+    pub date_trashed: String,
+}
+
+pub fn print_entries(entries: &[TrashEntry]) {
+    for e in entries {
+        println!(
+            "{} {} {} trashed {} ({})",
+            e.id_trash, e.kind, e.name, e.date_trashed, e.reason
+        );
+    }
+}
+
+/// Time trashed data is kept before it is purged, from `TRASH_RETENTION`, e.g. `7d`.
+pub fn retention() -> Result<Duration> {
+    match env::var("TRASH_RETENTION") {
+        Ok(retention) => daemon::parse_duration(&retention).context("invalid TRASH_RETENTION"),
+        Err(_) => Ok(DEFAULT_RETENTION),
+    }
+}
+
+fn archive_trash_path(md5: &str) -> PathBuf {
+    Path::new(TRASH_DIR).join(format!("{md5}.zip"))
+}
+
+/// Record `name` as trashed.
+pub async fn record(
+    conn: &mut sqlx::AnyConnection,
+    kind: TrashKind,
+    name: &str,
+    reason: &str,
+) -> Result<()> {
+    sqlx::query("INSERT INTO trash(kind, name, reason, date_trashed) VALUES ($1, $2, $3, $4)")
+        .bind(kind.as_str())
+        .bind(name)
+        .bind(reason)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Move the stored archive with hash `md5` to the trash instead of deleting it.
+pub async fn trash_archive(pool: &AnyPool, md5: &str, reason: &str) -> Result<()> {
+    std::fs::create_dir_all(TRASH_DIR)?;
+    std::fs::rename(store::object_path(md5), archive_trash_path(md5))?;
+    let mut tx = pool.begin().await?;
+    // an archive trashed before replaces the earlier copy
+    sqlx::query("DELETE FROM trash WHERE kind = $1 AND name = $2")
+        .bind(TrashKind::Archive.as_str())
+        .bind(md5)
+        .execute(&mut *tx)
+        .await?;
+    record(&mut tx, TrashKind::Archive, md5, reason).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn list(pool: &AnyPool) -> Result<Vec<TrashEntry>> {
+    let rows: Vec<(i64, String, String, String, String)> = sqlx::query_as(
+        "SELECT id_trash, kind, name, reason, date_trashed FROM trash ORDER BY id_trash",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id_trash, kind, name, reason, date_trashed)| TrashEntry {
+            id_trash,
+            kind,
+            name,
+            reason,
+            date_trashed,
+        })
+        .collect())
+}
+
+/// Put a trashed archive back into the store or undelete a trashed collection.
+pub async fn restore(pool: &AnyPool, id_trash: i64) -> Result<TrashEntry> {
+    let Some(entry) = list(pool)
+        .await?
+        .into_iter()
+        .find(|e| e.id_trash == id_trash)
+    else {
+        bail!("no trash entry {id_trash}, see `restore` without an id for the list");
+    };
+
+    let mut tx = pool.begin().await?;
+    if entry.kind == TrashKind::Archive.as_str() {
+        let target = store::object_path(&entry.name);
+        if target.exists() {
+            // downloaded again in the meantime
+            std::fs::remove_file(archive_trash_path(&entry.name))?;
+        } else {
+            std::fs::create_dir_all(target.parent().unwrap())?;
+            std::fs::rename(archive_trash_path(&entry.name), &target)?;
+        }
+    } else {
+        sqlx::query("UPDATE collection SET date_deleted = NULL WHERE name = $1")
+            .bind(&entry.name)
+            .execute(&mut *tx)
+            .await?;
+    }
+    sqlx::query("DELETE FROM trash WHERE id_trash = $1")
+        .bind(id_trash)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(entry)
+}
+
+/// Permanently remove trashed data older than the retention window. Returns the number of
+/// entries purged.
+pub async fn purge_expired(pool: &AnyPool) -> Result<u64> {
+    let cutoff = chrono::Utc::now() - chrono::Duration::from_std(retention()?)?;
+    let expired = list(pool)
+        .await?
+        .into_iter()
+        .filter(|e| {
+            chrono::DateTime::parse_from_rfc3339(&e.date_trashed)
+                .map(|date| date < cutoff)
+                .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+
+    for entry in &expired {
+        let mut tx = pool.begin().await?;
+        if entry.kind == TrashKind::Archive.as_str() {
+            match std::fs::remove_file(archive_trash_path(&entry.name)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        } else {
+            sqlx::query(
+                "DELETE FROM collection_mod WHERE id_collection IN
+                    (SELECT id_collection FROM collection
+                     WHERE name = $1 AND date_deleted IS NOT NULL)",
+            )
+            .bind(&entry.name)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("DELETE FROM collection WHERE name = $1 AND date_deleted IS NOT NULL")
+                .bind(&entry.name)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM trash WHERE id_trash = $1")
+            .bind(entry.id_trash)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+    }
+    if !expired.is_empty() {
+        info!("Purged {} expired trash entries", expired.len());
+    }
+    Ok(expired.len() as u64)
+}
@@ -0,0 +1,1220 @@
+//! Minimal reader for cooked UE4 packages (`.uasset` + `.uexp`): the package summary, name,
+//! import and export maps, and tagged property data. Unversioned property data is read with the
+//! schemas of the `USMAP` mappings when configured. Only what the indexer needs to summarize
+//! assets is implemented; anything else is skipped using the sizes recorded in the package.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use std::sync::Arc;
+
+use crate::usmap::{self, Mappings, PropertyType};
+
+const PACKAGE_TAG: u32 = 0x9E2A83C1;
+/// File version assumed for unversioned packages, which DRG's UE 4.27 cooks use.
+const VER_UE4_LATEST: i32 = 522;
+
+const VER_UE4_ARRAY_PROPERTY_INNER_TAGS: i32 = 282;
+const VER_UE4_LOAD_FOR_EDITOR_GAME: i32 = 365;
+const VER_UE4_STRUCT_GUID_IN_PROPERTY_TAG: i32 = 441;
+const VER_UE4_SERIALIZE_TEXT_IN_PACKAGES: i32 = 459;
+const VER_UE4_COOKED_ASSETS_IN_EDITOR_SUPPORT: i32 = 485;
+const VER_UE4_PROPERTY_TAG_SET_MAP_SUPPORT: i32 = 500;
+const VER_UE4_PROPERTY_GUID_IN_PROPERTY_TAG: i32 = 503;
+const VER_UE4_NAME_HASHES_SERIALIZED: i32 = 504;
+const VER_UE4_PRELOAD_DEPENDENCIES_IN_COOKED_EXPORTS: i32 = 507;
+const VER_UE4_TEMPLATE_INDEX_IN_COOKED_EXPORTS: i32 = 508;
+const VER_UE4_64BIT_EXPORTMAP_SERIALSIZES: i32 = 511;
+const VER_UE4_ADDED_PACKAGE_SUMMARY_LOCALIZATION_ID: i32 = 516;
+const VER_UE4_NON_OUTER_PACKAGE_IMPORT: i32 = 520;
+
+const PKG_UNVERSIONED_PROPERTIES: u32 = 0x2000;
+const PKG_FILTER_EDITOR_ONLY: u32 = 0x80000000;
+
+/// Deepest nesting of structs read, far beyond what the game's structs need. Every level recurses,
+/// so a malformed package nesting them endlessly would otherwise overflow the stack.
+const MAX_STRUCT_DEPTH: usize = 64;
+
+/// Little endian cursor over a byte slice.
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Structs currently being read, see [`MAX_STRUCT_DEPTH`]
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader {
+            data,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    pub fn seek(&mut self, pos: usize) -> Result<()> {
+        if pos > self.data.len() {
+            bail!("seek to {pos} past end of data ({} bytes)", self.data.len());
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .with_context(|| {
+                format!("unexpected end of data reading {len} bytes at {}", self.pos)
+            })?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+    pub fn i8(&mut self) -> Result<i8> {
+        Ok(i8::from_le_bytes(self.array()?))
+    }
+    pub fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+    pub fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_le_bytes(self.array()?))
+    }
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+    pub fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+    pub fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+    pub fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+    pub fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+    pub fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    pub fn guid(&mut self) -> Result<String> {
+        Ok(self.bytes(16)?.iter().map(|b| format!("{b:02x}")).collect())
+    }
+
+    /// An `FString`: positive lengths are Latin-1, negative lengths UTF-16, both including the
+    /// terminating NUL.
+    pub fn fstring(&mut self) -> Result<String> {
+        let len = self.i32()?;
+        if len == 0 {
+            return Ok(String::new());
+        }
+        if len > 0 {
+            let bytes = self.bytes(len as usize)?;
+            let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+            Ok(bytes.iter().map(|&b| b as char).collect())
+        } else {
+            let units = len.checked_neg().context("invalid string length")? as usize;
+            let bytes = self.bytes(units.checked_mul(2).context("string too long")?)?;
+            let mut chars = bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+            if chars.last() == Some(&0) {
+                chars.pop();
+            }
+            Ok(String::from_utf16_lossy(&chars))
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Import {
+    pub class_package: String,
+    pub class_name: String,
+    pub outer_index: i32,
+    pub object_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Export {
+    pub class_index: i32,
+    pub super_index: i32,
+    pub outer_index: i32,
+    pub object_name: String,
+    pub serial_size: i64,
+    pub serial_offset: i64,
+}
+
+/// A reference from a package to an object of another package, read from its import table.
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetRef {
+    /// Referenced package, e.g. `/Game/Character/BP_PlayerCharacter` or `/Script/FSD`
+    pub package: String,
+    /// Referenced object, e.g. `BP_PlayerCharacter_C`
+    pub object: String,
+    /// Class of the referenced object, e.g. `BlueprintGeneratedClass`
+    pub class: String,
+    /// Whether an export derives from the object, as a Blueprint does from its parent class
+    pub parent: bool,
+}
+
+/// A parsed package. `data` is the `.uasset` followed by the `.uexp`, which is the layout export
+/// offsets are relative to.
+pub struct Package {
+    pub names: Vec<String>,
+    pub imports: Vec<Import>,
+    pub exports: Vec<Export>,
+    pub file_version: i32,
+    pub package_flags: u32,
+    /// Schemas for unversioned properties, see [`usmap::configured`]
+    pub mappings: Option<Arc<Mappings>>,
+    data: Vec<u8>,
+}
+
+impl Package {
+    pub fn parse(uasset: &[u8], uexp: Option<&[u8]>) -> Result<Package> {
+        let mut data = uasset.to_vec();
+        if let Some(uexp) = uexp {
+            data.extend_from_slice(uexp);
+        }
+
+        let mut r = Reader::new(&data);
+        if r.u32()? != PACKAGE_TAG {
+            bail!("not a UE package: bad tag");
+        }
+        let legacy_version = r.i32()?;
+        if legacy_version <= -8 {
+            bail!("UE5 packages are not supported");
+        }
+        if legacy_version != -4 {
+            r.i32()?; // legacy UE3 version
+        }
+        let mut file_version = r.i32()?;
+        r.i32()?; // licensee version
+        if legacy_version <= -2 {
+            let count = r.i32()?;
+            for _ in 0..count {
+                match legacy_version {
+                    -2 => {
+                        r.bytes(8)?;
+                    }
+                    -5..=-3 => {
+                        r.bytes(20)?;
+                        r.fstring()?;
+                    }
+                    _ => {
+                        r.bytes(20)?;
+                    }
+                }
+            }
+        }
+        if file_version == 0 {
+            file_version = VER_UE4_LATEST;
+        }
+        r.i32()?; // total header size
+        r.fstring()?; // folder name
+        let package_flags = r.u32()?;
+        let name_count = r.i32()?;
+        let name_offset = r.i32()?;
+        if file_version >= VER_UE4_ADDED_PACKAGE_SUMMARY_LOCALIZATION_ID
+            && package_flags & PKG_FILTER_EDITOR_ONLY == 0
+        {
+            r.fstring()?; // localization id
+        }
+        if file_version >= VER_UE4_SERIALIZE_TEXT_IN_PACKAGES {
+            r.i32()?; // gatherable text data count
+            r.i32()?; // gatherable text data offset
+        }
+        let export_count = r.i32()?;
+        let export_offset = r.i32()?;
+        let import_count = r.i32()?;
+        let import_offset = r.i32()?;
+
+        let mut names = vec![];
+        r.seek(usize::try_from(name_offset)?)?;
+        for _ in 0..name_count {
+            names.push(r.fstring()?);
+            if file_version >= VER_UE4_NAME_HASHES_SERIALIZED {
+                r.u32()?;
+            }
+        }
+
+        let mut package = Package {
+            names,
+            imports: vec![],
+            exports: vec![],
+            file_version,
+            package_flags,
+            mappings: usmap::configured(),
+            data: vec![],
+        };
+
+        r.seek(usize::try_from(import_offset)?)?;
+        for _ in 0..import_count {
+            let class_package = package.fname(&mut r)?;
+            let class_name = package.fname(&mut r)?;
+            let outer_index = r.i32()?;
+            let object_name = package.fname(&mut r)?;
+            if file_version >= VER_UE4_NON_OUTER_PACKAGE_IMPORT
+                && package_flags & PKG_FILTER_EDITOR_ONLY == 0
+            {
+                package.fname(&mut r)?; // package name
+            }
+            package.imports.push(Import {
+                class_package,
+                class_name,
+                outer_index,
+                object_name,
+            });
+        }
+
+        r.seek(usize::try_from(export_offset)?)?;
+        for _ in 0..export_count {
+            let class_index = r.i32()?;
+            let super_index = r.i32()?;
+            if file_version >= VER_UE4_TEMPLATE_INDEX_IN_COOKED_EXPORTS {
+                r.i32()?; // template index
+            }
+            let outer_index = r.i32()?;
+            let object_name = package.fname(&mut r)?;
+            r.u32()?; // object flags
+            let (serial_size, serial_offset) =
+                if file_version >= VER_UE4_64BIT_EXPORTMAP_SERIALSIZES {
+                    (r.i64()?, r.i64()?)
+                } else {
+                    (r.i32()?.into(), r.i32()?.into())
+                };
+            r.bytes(12)?; // forced export, not for client, not for server
+            r.bytes(16)?; // package guid
+            r.u32()?; // package flags
+            if file_version >= VER_UE4_LOAD_FOR_EDITOR_GAME {
+                r.i32()?; // not always loaded for editor game
+            }
+            if file_version >= VER_UE4_COOKED_ASSETS_IN_EDITOR_SUPPORT {
+                r.i32()?; // is asset
+            }
+            if file_version >= VER_UE4_PRELOAD_DEPENDENCIES_IN_COOKED_EXPORTS {
+                r.bytes(20)?; // preload dependency offsets and counts
+            }
+            package.exports.push(Export {
+                class_index,
+                super_index,
+                outer_index,
+                object_name,
+                serial_size,
+                serial_offset,
+            });
+        }
+
+        package.data = data;
+        Ok(package)
+    }
+
+    /// Read an `FName` as its display string.
+    pub fn fname(&self, r: &mut Reader) -> Result<String> {
+        let index = r.i32()?;
+        let number = r.i32()?;
+        let name = usize::try_from(index)
+            .ok()
+            .and_then(|i| self.names.get(i))
+            .with_context(|| format!("name index {index} out of range"))?;
+        Ok(if number > 0 {
+            format!("{name}_{}", number - 1)
+        } else {
+            name.clone()
+        })
+    }
+
+    /// Name of the object a package index refers to: exports are positive, imports negative.
+    pub fn object_name(&self, index: i32) -> Option<&str> {
+        match index {
+            0 => None,
+            i if i > 0 => self
+                .exports
+                .get(i as usize - 1)
+                .map(|e| e.object_name.as_str()),
+            i => self
+                .imports
+                .get(i.unsigned_abs() as usize - 1)
+                .map(|i| i.object_name.as_str()),
+        }
+    }
+
+    /// Full path of an import, e.g. `/Script/Engine.DataTable` or `/Game/Foo/Bar.Bar`.
+    pub fn import_path(&self, index: i32) -> Option<String> {
+        self.import_path_below(index, 0)
+    }
+
+    /// Path of an import that is `depth` outers up from the one asked for. No import is nested
+    /// deeper than there are imports, so going further means the outers form a cycle.
+    fn import_path_below(&self, index: i32, depth: usize) -> Option<String> {
+        if depth > self.imports.len() {
+            return None;
+        }
+        let i = usize::try_from(index.checked_neg()?).ok()?.checked_sub(1)?;
+        let import = self.imports.get(i)?;
+        match import.outer_index {
+            0 => Some(import.object_name.clone()),
+            outer if outer < 0 => {
+                let outer_path = self.import_path_below(outer, depth + 1)?;
+                let separator = if outer_path.contains('.') { ':' } else { '.' };
+                Some(format!("{outer_path}{separator}{}", import.object_name))
+            }
+            _ => Some(import.object_name.clone()),
+        }
+    }
+
+    /// Objects of other packages the package references: the imports directly inside a package
+    /// import, which are the ones its exports point to.
+    pub fn references(&self) -> Vec<AssetRef> {
+        let parents = self
+            .exports
+            .iter()
+            .map(|e| e.super_index)
+            .filter(|i| *i < 0)
+            .collect::<Vec<_>>();
+        self.imports
+            .iter()
+            .enumerate()
+            .filter_map(|(i, import)| {
+                let outer = usize::try_from(import.outer_index.checked_neg()?).ok()?;
+                let outer = self.imports.get(outer.checked_sub(1)?)?;
+                if outer.outer_index != 0 || outer.class_name != "Package" {
+                    return None;
+                }
+                Some(AssetRef {
+                    package: outer.object_name.clone(),
+                    object: import.object_name.clone(),
+                    class: import.class_name.clone(),
+                    parent: parents.contains(&(-(i as i32) - 1)),
+                })
+            })
+            .collect()
+    }
+
+    /// Class name of an export, e.g. `DataTable` or `BlueprintGeneratedClass`.
+    pub fn export_class(&self, export: &Export) -> Option<&str> {
+        self.object_name(export.class_index)
+    }
+
+    /// Class of the export a package is named after, e.g. `Texture2D`, `SoundWave`,
+    /// `BlueprintGeneratedClass` or `DataTable`. Blueprints are named after their generated `_C`
+    /// class. Falls back to the first top level export.
+    pub fn primary_class(&self, asset_name: &str) -> Option<&str> {
+        let top_level = || self.exports.iter().filter(|e| e.outer_index == 0);
+        let generated_class = format!("{asset_name}_C");
+        top_level()
+            .find(|e| e.object_name == asset_name)
+            .or_else(|| top_level().find(|e| e.object_name == generated_class))
+            .or_else(|| top_level().next())
+            .and_then(|e| self.export_class(e))
+    }
+
+    /// Serialized data of an export.
+    pub fn export_data(&self, export: &Export) -> Result<&[u8]> {
+        let start = usize::try_from(export.serial_offset)?;
+        let end = start.checked_add(usize::try_from(export.serial_size)?);
+        end.and_then(|end| self.data.get(start..end))
+            .with_context(|| format!("export {} data out of range", export.object_name))
+    }
+
+    /// Whether property data is stored without tags and needs mappings to be read.
+    pub fn unversioned(&self) -> bool {
+        self.package_flags & PKG_UNVERSIONED_PROPERTIES != 0
+    }
+
+    /// Properties of an object or struct of class `struct_name`, which is only needed to look up
+    /// the schema of unversioned properties.
+    pub fn read_object(&self, r: &mut Reader, struct_name: &str) -> Result<Map<String, Value>> {
+        if self.unversioned() {
+            self.read_unversioned(r, struct_name)
+        } else {
+            self.read_properties(r)
+        }
+    }
+
+    /// Rows of a DataTable export as `{row name: {property: value}}`.
+    pub fn data_table_rows(&self, export: &Export) -> Result<Map<String, Value>> {
+        let data = self.export_data(export)?;
+        let mut r = Reader::new(data);
+        let class = self.export_class(export).unwrap_or("DataTable");
+        let properties = self.read_object(&mut r, class)?;
+        if r.i32()? != 0 {
+            r.bytes(16)?; // object guid
+        }
+        // unversioned rows are read with the schema of the table's row struct, an import like
+        // /Script/FSD.WeaponStatRow
+        let row_struct = match properties.get("RowStruct").and_then(Value::as_str) {
+            Some(path) => path.rsplit(['.', ':']).next().unwrap_or(path).to_string(),
+            None if self.unversioned() => bail!("DataTable has no RowStruct"),
+            None => String::new(),
+        };
+        let count = r.i32()?;
+        if count < 0 || count as usize > r.remaining() {
+            bail!("invalid DataTable row count {count}");
+        }
+        let mut rows = Map::new();
+        for _ in 0..count {
+            let name = self.fname(&mut r)?;
+            rows.insert(name, Value::Object(self.read_object(&mut r, &row_struct)?));
+        }
+        Ok(rows)
+    }
+
+    /// Rows of a CurveTable export as `{row name: {property: value}}`.
+    pub fn curve_table_rows(&self, export: &Export) -> Result<Map<String, Value>> {
+        let data = self.export_data(export)?;
+        let mut r = Reader::new(data);
+        self.read_object(&mut r, self.export_class(export).unwrap_or("CurveTable"))?;
+        if r.i32()? != 0 {
+            r.bytes(16)?; // object guid
+        }
+        let count = r.i32()?;
+        if count < 0 || count as usize > r.remaining() {
+            bail!("invalid CurveTable row count {count}");
+        }
+        // tagged rows describe themselves, unversioned ones need the curve struct of the mode
+        let row_struct = match r.u8()? {
+            1 => "SimpleCurve",
+            _ => "RichCurve",
+        };
+        let mut rows = Map::new();
+        for _ in 0..count {
+            let name = self.fname(&mut r)?;
+            rows.insert(name, Value::Object(self.read_object(&mut r, row_struct)?));
+        }
+        Ok(rows)
+    }
+
+    /// Namespace and `(key, text)` entries of a StringTable export.
+    pub fn string_table(&self, export: &Export) -> Result<(String, Vec<(String, String)>)> {
+        let data = self.export_data(export)?;
+        let mut r = Reader::new(data);
+        if !self.unversioned() {
+            self.read_properties(&mut r)?;
+        } else {
+            r.u16()?; // empty unversioned property header
+        }
+        if r.i32()? != 0 {
+            r.bytes(16)?; // object guid
+        }
+        let namespace = r.fstring()?;
+        let count = r.i32()?;
+        if count < 0 || count as usize > r.remaining() {
+            bail!("invalid StringTable entry count {count}");
+        }
+        let mut entries = vec![];
+        for _ in 0..count {
+            entries.push((r.fstring()?, r.fstring()?));
+        }
+        Ok((namespace, entries))
+    }
+
+    /// Rows of a table-like export: the rows of DataTables and CurveTables, or the properties of
+    /// curve assets such as `CurveFloat`. `None` for any other class.
+    pub fn table_rows(&self, export: &Export) -> Option<Result<Map<String, Value>>> {
+        match self.export_class(export)? {
+            "DataTable" => Some(self.data_table_rows(export)),
+            "CurveTable" => Some(self.curve_table_rows(export)),
+            class @ ("CurveFloat" | "CurveVector" | "CurveLinearColor") => Some(
+                self.export_data(export)
+                    .and_then(|data| self.read_object(&mut Reader::new(data), class)),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Read unversioned properties: a header of fragments telling which schema positions are
+    /// serialized and which of those hold zero, followed by the values without any tags.
+    fn read_unversioned(&self, r: &mut Reader, struct_name: &str) -> Result<Map<String, Value>> {
+        let mappings = self.mappings.as_deref().context(
+            "package uses unversioned properties, set USMAP to a .usmap mappings file to read them",
+        )?;
+        let schema = mappings.schema(struct_name)?;
+
+        // (positions to skip, values that follow, whether any of them are zero)
+        let mut fragments = vec![];
+        loop {
+            let packed = r.u16()?;
+            fragments.push((
+                usize::from(packed & 0x7f),
+                usize::from(packed >> 9),
+                packed & 0x80 != 0,
+            ));
+            if packed & 0x100 != 0 {
+                break;
+            }
+        }
+        let zero_bits = fragments
+            .iter()
+            .filter(|(_, _, zeroes)| *zeroes)
+            .map(|(_, values, _)| values)
+            .sum::<usize>();
+        let zero_mask = match zero_bits {
+            0 => vec![],
+            1..=8 => vec![u32::from(r.u8()?)],
+            9..=16 => vec![u32::from(r.u16()?)],
+            bits => (0..bits.div_ceil(32))
+                .map(|_| r.u32())
+                .collect::<Result<Vec<_>>>()?,
+        };
+        let is_zero = |bit: usize| {
+            zero_mask
+                .get(bit / 32)
+                .is_some_and(|w| (w >> (bit % 32)) & 1 != 0)
+        };
+
+        let mut properties = Map::new();
+        let mut index = 0;
+        let mut zero_bit = 0;
+        for (skip, values, zeroes) in fragments {
+            index += skip;
+            for _ in 0..values {
+                let zero = zeroes && is_zero(zero_bit);
+                if zeroes {
+                    zero_bit += 1;
+                }
+                let (name, ty) = schema
+                    .get(index)
+                    .cloned()
+                    .flatten()
+                    .with_context(|| format!("{struct_name} has no property at {index}"))?;
+                let value = if zero {
+                    zero_value(ty)
+                } else {
+                    self.read_mapped_value(r, mappings, ty)?
+                };
+                properties.insert(name, value);
+                index += 1;
+            }
+        }
+        Ok(properties)
+    }
+
+    /// Read a single unversioned value, whose type only the mappings know.
+    fn read_mapped_value(
+        &self,
+        r: &mut Reader,
+        mappings: &Mappings,
+        ty: &PropertyType,
+    ) -> Result<Value> {
+        Ok(match ty {
+            PropertyType::Struct(name) => self.read_struct(r, name)?,
+            PropertyType::Enum { inner, name } => {
+                let value = self.read_mapped_value(r, mappings, inner)?;
+                match value
+                    .as_u64()
+                    .and_then(|i| mappings.enums.get(name)?.get(i as usize))
+                {
+                    Some(entry) => json!(format!("{name}::{entry}")),
+                    None => value,
+                }
+            }
+            PropertyType::Array(inner) | PropertyType::Set(inner) => {
+                if matches!(ty, PropertyType::Set(_)) {
+                    r.i32()?; // elements to remove
+                }
+                let count = r.i32()?;
+                if count < 0 || count as usize > r.remaining() {
+                    bail!("invalid array length {count}");
+                }
+                let mut items = vec![];
+                for _ in 0..count {
+                    items.push(self.read_mapped_value(r, mappings, inner)?);
+                }
+                Value::Array(items)
+            }
+            PropertyType::Map(key, value) => {
+                let removed = r.i32()?;
+                for _ in 0..removed.max(0) {
+                    self.read_mapped_value(r, mappings, key)?;
+                }
+                let count = r.i32()?;
+                if count < 0 || count as usize > r.remaining() {
+                    bail!("invalid map length {count}");
+                }
+                let mut entries = vec![];
+                for _ in 0..count {
+                    entries.push(json!({
+                        "key": self.read_mapped_value(r, mappings, key)?,
+                        "value": self.read_mapped_value(r, mappings, value)?,
+                    }));
+                }
+                Value::Array(entries)
+            }
+            PropertyType::Other(ty) => {
+                let tag = PropertyTag {
+                    name: String::new(),
+                    ty: ty.to_string(),
+                    size: 0,
+                    array_index: 0,
+                    struct_name: None,
+                    bool_value: false,
+                    enum_name: None,
+                    inner_type: None,
+                };
+                self.read_value(r, ty, &tag, None)?
+            }
+        })
+    }
+
+    /// Read tagged properties up to the terminating `None`.
+    pub fn read_properties(&self, r: &mut Reader) -> Result<Map<String, Value>> {
+        let mut properties = Map::new();
+        loop {
+            let name = self.fname(r)?;
+            if name == "None" {
+                break;
+            }
+            let tag = self.read_tag(r, name)?;
+            let start = r.position();
+            let value = self
+                .read_value(r, &tag.ty, &tag, Some(tag.size))
+                .unwrap_or_else(|_| json!(format!("<unparsed {}>", tag.ty)));
+            // always resynchronize on the recorded size so one bad value can't derail the rest
+            r.seek(start + tag.size)?;
+            let key = if tag.array_index > 0 {
+                format!("{}[{}]", tag.name, tag.array_index)
+            } else {
+                tag.name
+            };
+            properties.insert(key, value);
+        }
+        Ok(properties)
+    }
+
+    fn read_tag(&self, r: &mut Reader, name: String) -> Result<PropertyTag> {
+        let ty = self.fname(r)?;
+        let size = usize::try_from(r.i32()?).context("negative property size")?;
+        let array_index = r.i32()?;
+        let mut tag = PropertyTag {
+            name,
+            ty,
+            size,
+            array_index,
+            struct_name: None,
+            bool_value: false,
+            enum_name: None,
+            inner_type: None,
+        };
+        match tag.ty.as_str() {
+            "StructProperty" => {
+                tag.struct_name = Some(self.fname(r)?);
+                if self.file_version >= VER_UE4_STRUCT_GUID_IN_PROPERTY_TAG {
+                    r.bytes(16)?;
+                }
+            }
+            "BoolProperty" => tag.bool_value = r.u8()? != 0,
+            "ByteProperty" | "EnumProperty" => tag.enum_name = Some(self.fname(r)?),
+            "ArrayProperty" if self.file_version >= VER_UE4_ARRAY_PROPERTY_INNER_TAGS => {
+                tag.inner_type = Some(self.fname(r)?)
+            }
+            "SetProperty" if self.file_version >= VER_UE4_PROPERTY_TAG_SET_MAP_SUPPORT => {
+                tag.inner_type = Some(self.fname(r)?)
+            }
+            "MapProperty" if self.file_version >= VER_UE4_PROPERTY_TAG_SET_MAP_SUPPORT => {
+                tag.inner_type = Some(self.fname(r)?);
+                self.fname(r)?; // value type
+            }
+            _ => {}
+        }
+        if self.file_version >= VER_UE4_PROPERTY_GUID_IN_PROPERTY_TAG && r.u8()? != 0 {
+            r.bytes(16)?;
+        }
+        Ok(tag)
+    }
+
+    fn object_ref(&self, index: i32) -> Value {
+        match index {
+            0 => Value::Null,
+            i if i < 0 => json!(self.import_path(i)),
+            i => json!(self.object_name(i)),
+        }
+    }
+
+    /// Read a single value of type `ty`. `size` is known for top-level properties and lets
+    /// unsupported types be skipped; inside arrays it is not.
+    fn read_value(
+        &self,
+        r: &mut Reader,
+        ty: &str,
+        tag: &PropertyTag,
+        size: Option<usize>,
+    ) -> Result<Value> {
+        Ok(match ty {
+            "BoolProperty" if size.is_some() => json!(tag.bool_value),
+            "BoolProperty" => json!(r.u8()? != 0),
+            "Int8Property" => json!(r.i8()?),
+            "Int16Property" => json!(r.i16()?),
+            "IntProperty" => json!(r.i32()?),
+            "Int64Property" => json!(r.i64()?),
+            "UInt16Property" => json!(r.u16()?),
+            "UInt32Property" => json!(r.u32()?),
+            "UInt64Property" => json!(r.u64()?),
+            "FloatProperty" => json!(r.f32()?),
+            "DoubleProperty" => json!(r.f64()?),
+            "StrProperty" => json!(r.fstring()?),
+            "NameProperty" => json!(self.fname(r)?),
+            "ObjectProperty" | "ClassProperty" | "WeakObjectProperty" | "InterfaceProperty" => {
+                self.object_ref(r.i32()?)
+            }
+            "SoftObjectProperty" | "SoftClassProperty" => {
+                let path = self.fname(r)?;
+                let sub_path = r.fstring()?;
+                if sub_path.is_empty() {
+                    json!(path)
+                } else {
+                    json!(format!("{path}:{sub_path}"))
+                }
+            }
+            // plain bytes, or enum values stored by name
+            "ByteProperty" => match &tag.enum_name {
+                Some(e) if e != "None" => json!(self.fname(r)?),
+                _ => json!(r.u8()?),
+            },
+            "EnumProperty" => json!(self.fname(r)?),
+            "TextProperty" => self.read_text(r)?,
+            "StructProperty" => {
+                let struct_name = tag.struct_name.as_deref().unwrap_or_default();
+                self.read_struct(r, struct_name)?
+            }
+            "ArrayProperty" | "SetProperty" => {
+                if ty == "SetProperty" {
+                    r.i32()?; // elements to remove
+                }
+                let count = r.i32()?;
+                if count < 0 || count as usize > r.remaining() {
+                    bail!("invalid array length {count}");
+                }
+                let inner = tag
+                    .inner_type
+                    .as_deref()
+                    .context("array without inner type")?;
+                let mut items = vec![];
+                if inner == "StructProperty" {
+                    let name = self.fname(r)?;
+                    let inner_tag = self.read_tag(r, name)?;
+                    let struct_name = inner_tag.struct_name.clone().unwrap_or_default();
+                    for _ in 0..count {
+                        items.push(self.read_struct(r, &struct_name)?);
+                    }
+                } else {
+                    let inner_tag = PropertyTag {
+                        inner_type: None,
+                        ..tag.clone()
+                    };
+                    for _ in 0..count {
+                        items.push(self.read_value(r, inner, &inner_tag, None)?);
+                    }
+                }
+                Value::Array(items)
+            }
+            _ => bail!("unsupported property type {ty}"),
+        })
+    }
+
+    fn read_struct(&self, r: &mut Reader, struct_name: &str) -> Result<Value> {
+        if r.depth >= MAX_STRUCT_DEPTH {
+            bail!("structs nested deeper than {MAX_STRUCT_DEPTH} levels");
+        }
+        r.depth += 1;
+        let value = self.read_struct_fields(r, struct_name);
+        r.depth -= 1;
+        value
+    }
+
+    fn read_struct_fields(&self, r: &mut Reader, struct_name: &str) -> Result<Value> {
+        Ok(match struct_name {
+            "Vector" | "Rotator" => json!([r.f32()?, r.f32()?, r.f32()?]),
+            "Vector2D" => json!([r.f32()?, r.f32()?]),
+            "Vector4" | "Quat" | "LinearColor" => json!([r.f32()?, r.f32()?, r.f32()?, r.f32()?]),
+            "IntPoint" => json!([r.i32()?, r.i32()?]),
+            "Color" => {
+                let [blue, green, red, alpha] = [r.u8()?, r.u8()?, r.u8()?, r.u8()?];
+                json!([red, green, blue, alpha])
+            }
+            "Guid" => json!(r.guid()?),
+            "RichCurveKey" => {
+                let [interp_mode, tangent_mode, tangent_weight_mode] = [r.u8()?, r.u8()?, r.u8()?];
+                json!({
+                    "InterpMode": interp_mode,
+                    "TangentMode": tangent_mode,
+                    "TangentWeightMode": tangent_weight_mode,
+                    "Time": r.f32()?,
+                    "Value": r.f32()?,
+                    "ArriveTangent": r.f32()?,
+                    "ArriveTangentWeight": r.f32()?,
+                    "LeaveTangent": r.f32()?,
+                    "LeaveTangentWeight": r.f32()?,
+                })
+            }
+            "SimpleCurveKey" => json!({ "Time": r.f32()?, "Value": r.f32()? }),
+            "DateTime" | "Timespan" => json!(r.i64()?),
+            "SoftObjectPath" | "SoftClassPath" => {
+                let path = self.fname(r)?;
+                let sub_path = r.fstring()?;
+                json!(if sub_path.is_empty() {
+                    path
+                } else {
+                    format!("{path}:{sub_path}")
+                })
+            }
+            "GameplayTagContainer" => {
+                let count = r.i32()?;
+                let mut tags = vec![];
+                for _ in 0..count.max(0) {
+                    tags.push(json!(self.fname(r)?));
+                }
+                Value::Array(tags)
+            }
+            _ => Value::Object(self.read_object(r, struct_name)?),
+        })
+    }
+
+    fn read_text(&self, r: &mut Reader) -> Result<Value> {
+        r.u32()?; // flags
+        match r.i8()? {
+            // no history: optionally a culture invariant string
+            -1 => {
+                if r.i32()? != 0 {
+                    Ok(json!(r.fstring()?))
+                } else {
+                    Ok(json!(""))
+                }
+            }
+            // base: namespace, key, source string
+            0 => {
+                let namespace = r.fstring()?;
+                let key = r.fstring()?;
+                let source = r.fstring()?;
+                Ok(json!({ "namespace": namespace, "key": key, "source": source }))
+            }
+            // string table reference
+            11 => {
+                let table = self.fname(r)?;
+                let key = r.fstring()?;
+                Ok(json!({ "table": table, "key": key }))
+            }
+            history => bail!("unsupported text history type {history}"),
+        }
+    }
+}
+
+/// Value of an unversioned property whose zero mask bit is set.
+fn zero_value(ty: &PropertyType) -> Value {
+    match ty {
+        PropertyType::Array(_) | PropertyType::Set(_) | PropertyType::Map(..) => json!([]),
+        PropertyType::Other("BoolProperty") => json!(false),
+        PropertyType::Other("StrProperty" | "TextProperty") => json!(""),
+        PropertyType::Other("NameProperty") => json!("None"),
+        PropertyType::Other("FloatProperty" | "DoubleProperty" | "ByteProperty") => json!(0),
+        PropertyType::Other(ty) if ty.contains("Int") => json!(0),
+        PropertyType::Enum { .. } => json!(0),
+        _ => Value::Null,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PropertyTag {
+    name: String,
+    ty: String,
+    size: usize,
+    array_index: i32,
+    struct_name: Option<String>,
+    bool_value: bool,
+    enum_name: Option<String>,
+    inner_type: Option<String>,
+}
+
+/// JSON summary of a package for inspection: its imports, exports and, for tables and curves,
+/// rows.
+pub fn summarize(package: &Package) -> Value {
+    let imports = (1..=package.imports.len() as i32)
+        .map(|i| json!(package.import_path(-i)))
+        .collect::<Vec<_>>();
+    let exports = package
+        .exports
+        .iter()
+        .map(|export| {
+            let class = package.export_class(export);
+            let mut summary = json!({
+                "name": export.object_name,
+                "class": class,
+                "size": export.serial_size,
+            });
+            if let Some(rows) = package.table_rows(export) {
+                summary["rows"] = match rows {
+                    Ok(rows) => Value::Object(rows),
+                    Err(e) => json!(format!("<unparsed: {e:#}>")),
+                };
+            }
+            summary
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "file_version": package.file_version,
+        "imports": imports,
+        "exports": exports,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT_UASSET: &[u8] = include_bytes!("../tests/fixtures/assets/DT_WeaponStats.uasset");
+    const DT_UEXP: &[u8] = include_bytes!("../tests/fixtures/assets/DT_WeaponStats.uexp");
+    const BP_UASSET: &[u8] = include_bytes!("../tests/fixtures/assets/BP_Sample.uasset");
+    const BP_UEXP: &[u8] = include_bytes!("../tests/fixtures/assets/BP_Sample.uexp");
+    const UNVERSIONED_UASSET: &[u8] =
+        include_bytes!("../tests/fixtures/assets/DT_Unversioned.uasset");
+    const UNVERSIONED_UEXP: &[u8] = include_bytes!("../tests/fixtures/assets/DT_Unversioned.uexp");
+    const USMAP: &[u8] = include_bytes!("../tests/fixtures/assets/Sample.usmap");
+
+    /// Small xorshift generator, so garbage inputs are the same on every run.
+    fn garbage(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn data_table() {
+        let package = Package::parse(DT_UASSET, Some(DT_UEXP)).unwrap();
+        assert_eq!(package.file_version, 522);
+        assert!(!package.unversioned());
+        assert_eq!(package.primary_class("DT_WeaponStats"), Some("DataTable"));
+        assert_eq!(
+            package.import_path(-4).unwrap(),
+            "/Script/FSD.WeaponStatRow"
+        );
+        assert_eq!(
+            package.import_path(-5).unwrap(),
+            "/Script/Engine.Default__DataTable"
+        );
+
+        let export = &package.exports[0];
+        let rows = package.table_rows(export).unwrap().unwrap();
+        assert_eq!(
+            Value::Object(rows),
+            json!({
+                "Fast": {
+                    "Damage": 12.5,
+                    "Magazine": 30,
+                    "DisplayName": {
+                        "namespace": "Weapons",
+                        "key": "Fast_Name",
+                        "source": "Fast Rifle",
+                    },
+                    "Tags": ["Hitscan", "Explosive"],
+                    "Mode": "EFireMode::Auto",
+                    "Spread": [0.5, 1.5],
+                },
+                "Slow": {
+                    "Damage": 40.0,
+                    "Magazine": 4,
+                    "Mode": "EFireMode::Single",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn blueprint_references() {
+        let package = Package::parse(BP_UASSET, Some(BP_UEXP)).unwrap();
+        assert_eq!(
+            package.primary_class("BP_Sample"),
+            Some("BlueprintGeneratedClass")
+        );
+        assert!(package.table_rows(&package.exports[0]).is_none());
+
+        let references = package.references();
+        assert_eq!(
+            references
+                .iter()
+                .map(|r| (
+                    r.package.as_str(),
+                    r.object.as_str(),
+                    r.class.as_str(),
+                    r.parent
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("/Script/Engine", "BlueprintGeneratedClass", "Class", false),
+                (
+                    "/Game/WeaponsNTools/WPN_Base",
+                    "WPN_Base_C",
+                    "BlueprintGeneratedClass",
+                    true,
+                ),
+                ("/Game/Audio/SC_Fire", "SC_Fire", "SoundCue", false),
+                (
+                    "/Game/WeaponsNTools/WPN_Base",
+                    "Default__WPN_Base_C",
+                    "WPN_Base_C",
+                    false,
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unversioned_data_table() {
+        let mut package = Package::parse(UNVERSIONED_UASSET, Some(UNVERSIONED_UEXP)).unwrap();
+        assert!(package.unversioned());
+        package.mappings = None;
+        let err = package
+            .table_rows(&package.exports[0])
+            .unwrap()
+            .unwrap_err();
+        assert!(err.to_string().contains("set USMAP"), "{err:#}");
+
+        package.mappings = Some(Arc::new(usmap::parse(USMAP).unwrap()));
+        let rows = package.table_rows(&package.exports[0]).unwrap().unwrap();
+        assert_eq!(
+            Value::Object(rows),
+            json!({
+                "Fast": {"Damage": 12.5, "Mode": "EFireMode::Auto", "Tags": ["Hitscan"]},
+                "Slow": {"Damage": 0, "Mode": "EFireMode::Single"},
+            })
+        );
+    }
+
+    #[test]
+    fn truncated_packages() {
+        for len in 0..DT_UASSET.len() {
+            assert!(Package::parse(&DT_UASSET[..len], None).is_err(), "{len}");
+        }
+        // the export data ends early, at every length
+        let mut package = Package::parse(DT_UASSET, Some(DT_UEXP)).unwrap();
+        for len in 0..package.exports[0].serial_size {
+            package.exports[0].serial_size = len;
+            assert!(
+                package.table_rows(&package.exports[0]).unwrap().is_err(),
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn garbage_packages() {
+        for seed in 1..200 {
+            let mut data = garbage(seed, 512);
+            assert!(Package::parse(&data, None).is_err());
+            // past the tag, so the counts and offsets are garbage instead
+            data[..4].copy_from_slice(&PACKAGE_TAG.to_le_bytes());
+            data[4..8].copy_from_slice(&(-7i32).to_le_bytes());
+            let _ = Package::parse(&data, None);
+        }
+
+        // garbage export data
+        let mut package = Package::parse(DT_UASSET, Some(DT_UEXP)).unwrap();
+        let export = &package.exports[0];
+        let (offset, size) = (export.serial_offset as usize, export.serial_size as usize);
+        for seed in 1..200 {
+            package.data[offset..offset + size].copy_from_slice(&garbage(seed, size));
+            assert!(package.table_rows(&package.exports[0]).unwrap().is_err());
+        }
+    }
+
+    #[test]
+    fn huge_counts_and_offsets() {
+        let mut package = Package::parse(DT_UASSET, Some(DT_UEXP)).unwrap();
+        package.exports[0].serial_offset = i64::MAX;
+        package.exports[0].serial_size = i64::MAX;
+        assert!(package.export_data(&package.exports[0]).is_err());
+        package.exports[0].serial_offset = -1;
+        assert!(package.export_data(&package.exports[0]).is_err());
+
+        // a row count in the billions is rejected before reading any row
+        let package = Package::parse(DT_UASSET, Some(DT_UEXP)).unwrap();
+        let mut data = package.export_data(&package.exports[0]).unwrap().to_vec();
+        let count = {
+            let mut r = Reader::new(&data);
+            package.read_properties(&mut r).unwrap();
+            r.i32().unwrap(); // object guid
+            r.position()
+        };
+        data[count..count + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+        let package = Package::parse(DT_UASSET, Some(&data)).unwrap();
+        let err = package
+            .table_rows(&package.exports[0])
+            .unwrap()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("invalid DataTable row count"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn import_cycles() {
+        let mut package = Package::parse(BP_UASSET, Some(BP_UEXP)).unwrap();
+        // the engine package inside the class it holds
+        package.imports[0].outer_index = -2;
+        assert_eq!(package.import_path(-2), None);
+        assert_eq!(package.import_path(0), None);
+        assert_eq!(package.import_path(1), None);
+        assert_eq!(package.import_path(i32::MIN), None);
+        assert_eq!(package.import_path(-100), None);
+    }
+
+    #[test]
+    fn deeply_nested_structs() {
+        let mut package = Package::parse(DT_UASSET, Some(DT_UEXP)).unwrap();
+        package.names.push("Inner".into());
+        let name = |n: &str| {
+            let i = package.names.iter().position(|x| x == n).unwrap() as i32;
+            [i.to_le_bytes(), 0i32.to_le_bytes()].concat()
+        };
+        // Inner { Inner: Inner { Inner: ... } } 2000 levels deep
+        let (inner, struct_property, none) = (name("Inner"), name("StructProperty"), name("None"));
+        let mut data = none.clone();
+        for _ in 0..2000 {
+            let mut property = [&inner[..], &struct_property].concat();
+            property.extend((data.len() as i32).to_le_bytes());
+            property.extend(0i32.to_le_bytes());
+            property.extend(&inner);
+            property.extend([0; 16]);
+            property.push(0);
+            property.extend(&data);
+            property.extend(&none);
+            data = property;
+        }
+        let properties = package.read_properties(&mut Reader::new(&data)).unwrap();
+        let mut value = &properties["Inner"];
+        let mut depth = 1;
+        while let Some(inner) = value.get("Inner") {
+            value = inner;
+            depth += 1;
+        }
+        assert_eq!(depth, MAX_STRUCT_DEPTH + 1);
+        assert_eq!(value, &json!("<unparsed StructProperty>"));
+    }
+}
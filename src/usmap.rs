@@ -0,0 +1,448 @@
+//! Reader for `.usmap` mappings, the property schemas dumped from a running game. Cooked packages
+//! with unversioned properties leave out every property name and type, so their data can only be
+//! read with the schema of the class or struct it belongs to.
+
+use anyhow::{bail, Context, Result};
+use tracing::warn;
+
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use crate::uasset::Reader;
+
+const MAGIC: u16 = 0x30C4;
+
+const VERSION_PACKAGE_VERSIONING: u8 = 1;
+const VERSION_LONG_FNAME: u8 = 2;
+const VERSION_LARGE_ENUMS: u8 = 3;
+
+const COMPRESSION_NONE: u8 = 0;
+
+/// Deepest nesting of container types read, e.g. an array of maps of sets. Types are read
+/// recursively, so a malformed file nesting them endlessly would otherwise overflow the stack.
+const MAX_TYPE_DEPTH: usize = 16;
+
+/// Type of a property as recorded in the mappings.
+#[derive(Debug, Clone)]
+pub enum PropertyType {
+    Struct(String),
+    Enum {
+        inner: Box<PropertyType>,
+        name: String,
+    },
+    Array(Box<PropertyType>),
+    Set(Box<PropertyType>),
+    Map(Box<PropertyType>, Box<PropertyType>),
+    /// Any other type by its property class name, e.g. `IntProperty` or `SoftObjectProperty`
+    Other(&'static str),
+}
+
+#[derive(Debug)]
+pub struct Property {
+    /// Position in the struct's schema, counting every element of fixed size arrays
+    pub index: u16,
+    pub array_size: u8,
+    pub name: String,
+    pub ty: PropertyType,
+}
+
+#[derive(Debug)]
+pub struct Struct {
+    pub super_struct: Option<String>,
+    /// Number of schema positions the struct itself adds to those of its super struct
+    pub property_count: u16,
+    pub properties: Vec<Property>,
+}
+
+#[derive(Debug, Default)]
+pub struct Mappings {
+    pub structs: HashMap<String, Struct>,
+    pub enums: HashMap<String, Vec<String>>,
+}
+
+impl Mappings {
+    /// Properties of a class or struct including those it inherits, by schema position. Elements
+    /// of fixed size arrays are named like tagged properties, `Name[1]`.
+    pub fn schema(&self, struct_name: &str) -> Result<Vec<Option<(String, &PropertyType)>>> {
+        // the struct and its super structs, the root last
+        let mut chain = vec![];
+        let mut name = struct_name;
+        loop {
+            if chain.len() == self.structs.len() {
+                bail!("super structs of {struct_name} form a cycle");
+            }
+            let s = self
+                .structs
+                .get(name)
+                .with_context(|| format!("no mappings for {name}"))?;
+            chain.push(s);
+            match &s.super_struct {
+                Some(super_struct) => name = super_struct,
+                None => break,
+            }
+        }
+
+        let mut schema = vec![];
+        for s in chain.into_iter().rev() {
+            let offset = schema.len();
+            schema.resize(offset + usize::from(s.property_count), None);
+            for property in &s.properties {
+                for i in 0..usize::from(property.array_size.max(1)) {
+                    let name = if i > 0 {
+                        format!("{}[{i}]", property.name)
+                    } else {
+                        property.name.clone()
+                    };
+                    if let Some(slot) = schema.get_mut(offset + usize::from(property.index) + i) {
+                        *slot = Some((name, &property.ty));
+                    }
+                }
+            }
+        }
+        Ok(schema)
+    }
+}
+
+/// Read a mappings file.
+pub fn parse(data: &[u8]) -> Result<Mappings> {
+    let mut r = Reader::new(data);
+    if r.u16()? != MAGIC {
+        bail!("not a usmap file: bad magic");
+    }
+    let version = r.u8()?;
+    if version > VERSION_LARGE_ENUMS {
+        bail!("unsupported usmap version {version}");
+    }
+    if version >= VERSION_PACKAGE_VERSIONING && r.i32()? != 0 {
+        r.i32()?; // UE4 file version
+        r.i32()?; // UE5 file version
+        let custom_versions = r.i32()?;
+        for _ in 0..custom_versions.max(0) {
+            r.bytes(20)?; // guid and version
+        }
+        r.i32()?; // net changelist
+    }
+    let compression = r.u8()?;
+    let compressed_size = r.u32()? as usize;
+    r.u32()?; // decompressed size
+    if compression != COMPRESSION_NONE {
+        bail!("compressed usmap files are not supported, dump the mappings without compression");
+    }
+    let payload = r.bytes(compressed_size)?;
+
+    let mut r = Reader::new(payload);
+    let name_count = r.u32()?;
+    let mut names = vec![];
+    for _ in 0..name_count {
+        let len = if version >= VERSION_LONG_FNAME {
+            usize::from(r.u16()?)
+        } else {
+            usize::from(r.u8()?)
+        };
+        names.push(String::from_utf8_lossy(r.bytes(len)?).into_owned());
+    }
+    let mut mappings = Mappings::default();
+    let enum_count = r.u32()?;
+    for _ in 0..enum_count {
+        let enum_name = name(&mut r, &names)?;
+        let entries = if version >= VERSION_LARGE_ENUMS {
+            usize::from(r.u16()?)
+        } else {
+            usize::from(r.u8()?)
+        };
+        let mut values = vec![];
+        for _ in 0..entries {
+            values.push(name(&mut r, &names)?);
+        }
+        mappings.enums.insert(enum_name, values);
+    }
+
+    let struct_count = r.u32()?;
+    for _ in 0..struct_count {
+        let struct_name = name(&mut r, &names)?;
+        let super_struct = match r.u32()? {
+            u32::MAX => None,
+            index => Some(name_at(&names, index)?),
+        };
+        let property_count = r.u16()?;
+        let serializable = r.u16()?;
+        let mut properties = vec![];
+        for _ in 0..serializable {
+            let index = r.u16()?;
+            let array_size = r.u8()?;
+            let property_name = name(&mut r, &names)?;
+            properties.push(Property {
+                index,
+                array_size,
+                name: property_name,
+                ty: read_type(&mut r, &names, 0)?,
+            });
+        }
+        mappings.structs.insert(
+            struct_name,
+            Struct {
+                super_struct,
+                property_count,
+                properties,
+            },
+        );
+    }
+    Ok(mappings)
+}
+
+fn name_at(names: &[String], index: u32) -> Result<String> {
+    names
+        .get(index as usize)
+        .cloned()
+        .with_context(|| format!("name index {index} out of range"))
+}
+
+fn name(r: &mut Reader, names: &[String]) -> Result<String> {
+    name_at(names, r.u32()?)
+}
+
+/// Read a property type, `depth` containers deep.
+fn read_type(r: &mut Reader, names: &[String], depth: usize) -> Result<PropertyType> {
+    if depth >= MAX_TYPE_DEPTH {
+        bail!("property types nested deeper than {MAX_TYPE_DEPTH} levels");
+    }
+    Ok(match r.u8()? {
+        0 => PropertyType::Other("ByteProperty"),
+        1 => PropertyType::Other("BoolProperty"),
+        2 => PropertyType::Other("IntProperty"),
+        3 => PropertyType::Other("FloatProperty"),
+        4 => PropertyType::Other("ObjectProperty"),
+        5 => PropertyType::Other("NameProperty"),
+        6 => PropertyType::Other("DelegateProperty"),
+        7 => PropertyType::Other("DoubleProperty"),
+        8 => PropertyType::Array(Box::new(read_type(r, names, depth + 1)?)),
+        9 => PropertyType::Struct(name(r, names)?),
+        10 => PropertyType::Other("StrProperty"),
+        11 => PropertyType::Other("TextProperty"),
+        12 => PropertyType::Other("InterfaceProperty"),
+        13 => PropertyType::Other("MulticastDelegateProperty"),
+        14 => PropertyType::Other("WeakObjectProperty"),
+        15 => PropertyType::Other("LazyObjectProperty"),
+        16 => PropertyType::Other("AssetObjectProperty"),
+        17 => PropertyType::Other("SoftObjectProperty"),
+        18 => PropertyType::Other("UInt64Property"),
+        19 => PropertyType::Other("UInt32Property"),
+        20 => PropertyType::Other("UInt16Property"),
+        21 => PropertyType::Other("Int64Property"),
+        22 => PropertyType::Other("Int16Property"),
+        23 => PropertyType::Other("Int8Property"),
+        24 => {
+            let key = read_type(r, names, depth + 1)?;
+            let value = read_type(r, names, depth + 1)?;
+            PropertyType::Map(Box::new(key), Box::new(value))
+        }
+        25 => PropertyType::Set(Box::new(read_type(r, names, depth + 1)?)),
+        26 => {
+            let inner = read_type(r, names, depth + 1)?;
+            PropertyType::Enum {
+                inner: Box::new(inner),
+                name: name(r, names)?,
+            }
+        }
+        27 => PropertyType::Other("FieldPathProperty"),
+        ty => bail!("unknown usmap property type {ty}"),
+    })
+}
+
+/// Mappings from the file at `path`.
+pub fn load(path: &Path) -> Result<Mappings> {
+    let data = std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    parse(&data).with_context(|| format!("failed to read mappings {}", path.display()))
+}
+
+/// The mappings configured with `USMAP`, `None` if unset.
+pub fn from_env() -> Result<Option<Mappings>> {
+    match env::var("USMAP") {
+        Ok(path) if !path.trim().is_empty() => Ok(Some(load(Path::new(path.trim()))?)),
+        _ => Ok(None),
+    }
+}
+
+/// The configured mappings, read once. Mappings that fail to load are left out rather than
+/// failing every package, `check-config` reports them.
+pub fn configured() -> Option<Arc<Mappings>> {
+    static MAPPINGS: OnceLock<Option<Arc<Mappings>>> = OnceLock::new();
+    MAPPINGS
+        .get_or_init(|| match from_env() {
+            Ok(mappings) => mappings.map(Arc::new),
+            Err(e) => {
+                warn!("{e:#}, reading packages without mappings");
+                None
+            }
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USMAP: &[u8] = include_bytes!("../tests/fixtures/assets/Sample.usmap");
+
+    fn names(schema: &[Option<(String, &PropertyType)>]) -> Vec<Option<String>> {
+        schema
+            .iter()
+            .map(|p| p.as_ref().map(|(name, _)| name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn sample() {
+        let mappings = parse(USMAP).unwrap();
+        assert_eq!(mappings.enums["EFireMode"], ["Single", "Burst", "Auto"]);
+        let schema = mappings.schema("WeaponStatRow").unwrap();
+        assert_eq!(
+            names(&schema),
+            [Some("Damage"), Some("Mode"), Some("Tags")].map(|n| n.map(String::from))
+        );
+        assert!(matches!(
+            schema[0],
+            Some((_, PropertyType::Other("FloatProperty")))
+        ));
+        assert!(matches!(
+            schema[1],
+            Some((_, PropertyType::Enum { name, .. })) if name == "EFireMode"
+        ));
+        assert!(matches!(
+            schema[2],
+            Some((_, PropertyType::Array(inner))) if matches!(**inner, PropertyType::Other("NameProperty"))
+        ));
+        assert!(mappings.schema("Missing").is_err());
+    }
+
+    #[test]
+    fn inherited_and_fixed_size_arrays() {
+        let mut mappings = Mappings::default();
+        let property = |index, array_size, name: &str| Property {
+            index,
+            array_size,
+            name: name.into(),
+            ty: PropertyType::Other("IntProperty"),
+        };
+        mappings.structs.insert(
+            "Base".into(),
+            Struct {
+                super_struct: None,
+                property_count: 2,
+                properties: vec![property(0, 1, "A"), property(1, 1, "B")],
+            },
+        );
+        mappings.structs.insert(
+            "Derived".into(),
+            Struct {
+                super_struct: Some("Base".into()),
+                property_count: 4,
+                properties: vec![property(0, 3, "C"), property(3, 1, "D")],
+            },
+        );
+        assert_eq!(
+            names(&mappings.schema("Derived").unwrap()),
+            ["A", "B", "C", "C[1]", "C[2]", "D"].map(|n| Some(n.to_string()))
+        );
+    }
+
+    #[test]
+    fn super_struct_cycle() {
+        let mut mappings = Mappings::default();
+        for (name, super_struct) in [("A", "B"), ("B", "A")] {
+            mappings.structs.insert(
+                name.into(),
+                Struct {
+                    super_struct: Some(super_struct.into()),
+                    property_count: 0,
+                    properties: vec![],
+                },
+            );
+        }
+        let err = mappings.schema("A").unwrap_err();
+        assert!(err.to_string().contains("cycle"), "{err:#}");
+    }
+
+    #[test]
+    fn truncated() {
+        for len in 0..USMAP.len() {
+            assert!(parse(&USMAP[..len]).is_err(), "{len}");
+        }
+    }
+
+    #[test]
+    fn garbage() {
+        let mut state = 0x2545f4914f6cdd1du64;
+        for _ in 0..200 {
+            let data = (0..256)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect::<Vec<_>>();
+            assert!(parse(&data).is_err());
+        }
+    }
+
+    /// A payload of `names` followed by `rest`, wrapped in an uncompressed version 3 file.
+    fn file(names: &[&str], rest: &[u8]) -> Vec<u8> {
+        let mut payload = (names.len() as u32).to_le_bytes().to_vec();
+        for name in names {
+            payload.extend((name.len() as u16).to_le_bytes());
+            payload.extend(name.as_bytes());
+        }
+        payload.extend(rest);
+        let mut data = MAGIC.to_le_bytes().to_vec();
+        data.push(VERSION_LARGE_ENUMS);
+        data.extend(0i32.to_le_bytes());
+        data.push(COMPRESSION_NONE);
+        data.extend((payload.len() as u32).to_le_bytes());
+        data.extend((payload.len() as u32).to_le_bytes());
+        data.extend(payload);
+        data
+    }
+
+    #[test]
+    fn huge_counts() {
+        // billions of names, enums and structs claimed by a few bytes
+        assert!(parse(&file(&[], &[])).is_err());
+        let mut data = file(&[], &[]);
+        let count = data.len() - 4;
+        data[count..].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse(&data).is_err());
+        assert!(parse(&file(&["A"], &u32::MAX.to_le_bytes())).is_err());
+        // a payload larger than the file
+        let mut data = file(&[], &[0; 12]);
+        data[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse(&data).is_err());
+    }
+
+    #[test]
+    fn deeply_nested_types() {
+        // struct S with a property P of array of array of ... 100000 deep
+        let mut rest = 0u32.to_le_bytes().to_vec(); // enums
+        rest.extend(1u32.to_le_bytes()); // structs
+        rest.extend(0u32.to_le_bytes()); // S
+        rest.extend(u32::MAX.to_le_bytes()); // no super struct
+        rest.extend(1u16.to_le_bytes());
+        rest.extend(1u16.to_le_bytes());
+        rest.extend(0u16.to_le_bytes());
+        rest.push(1);
+        rest.extend(1u32.to_le_bytes()); // P
+        rest.extend([8; 100_000]);
+        rest.push(2);
+        let err = parse(&file(&["S", "P"], &rest)).unwrap_err();
+        assert!(err.to_string().contains("nested"), "{err:#}");
+
+        // nested as deep as allowed
+        rest.truncate(rest.len() - 100_001);
+        rest.extend([8; MAX_TYPE_DEPTH - 1]);
+        rest.push(2);
+        let mappings = parse(&file(&["S", "P"], &rest)).unwrap();
+        assert_eq!(mappings.schema("S").unwrap().len(), 1);
+    }
+}
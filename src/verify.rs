@@ -0,0 +1,340 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::{info, warn};
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::{api, daemon, download, store, trash, SyncSummary};
+
+/// A modfile whose archive is not in the store.
+#[derive(Debug, Serialize)]
+pub struct MissingArchive {
+    pub id_mod: i64,
+    pub id_modfile: i64,
+    pub hash_md5: String,
+}
+
+/// What `--fix` did about the inconsistencies found.
+#[derive(Debug, Default, Serialize)]
+pub struct Fixes {
+    /// Extra archives moved to the trash
+    pub trashed: u64,
+    pub downloaded: u64,
+    pub analyzed: u64,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyReport {
+    pub modfiles: u64,
+    pub archives: u64,
+    /// Modfiles whose archive is missing from the store
+    pub missing: Vec<MissingArchive>,
+    /// Archives in the store that no modfile refers to
+    pub extra: Vec<PathBuf>,
+    /// Modfiles whose archive is stored but that have no pack files
+    pub unanalyzed: Vec<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fixes: Option<Fixes>,
+}
+
+impl std::fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for m in &self.missing {
+            writeln!(
+                f,
+                "missing: mod {} modfile {} {}",
+                m.id_mod,
+                m.id_modfile,
+                download::archive_path(&m.hash_md5).display()
+            )?;
+        }
+        for path in &self.extra {
+            writeln!(f, "extra: {}", path.display())?;
+        }
+        for id_modfile in &self.unanalyzed {
+            writeln!(f, "unanalyzed: modfile {id_modfile}")?;
+        }
+        write!(
+            f,
+            "{} modfiles and {} archives verified, {} missing, {} extra, {} unanalyzed",
+            self.modfiles,
+            self.archives,
+            self.missing.len(),
+            self.extra.len(),
+            self.unanalyzed.len()
+        )?;
+        if let Some(fixes) = &self.fixes {
+            for e in &fixes.errors {
+                write!(f, "\n{e}")?;
+            }
+            write!(
+                f,
+                "\nfixed: {} moved to trash, {} downloaded, {} analyzed, {} errors",
+                fixes.trashed,
+                fixes.downloaded,
+                fixes.analyzed,
+                fixes.errors.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that the archive store and the index agree: every modfile has its archive, every archive
+/// belongs to a modfile and every stored archive has been analyzed. Archives deleted by `--no-keep`
+/// are not expected to be stored.
+///
+/// With `fix`, extra archives are moved to the trash, missing archives are downloaded again and anything not
+/// yet analyzed is analyzed.
+pub async fn verify(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    fix: bool,
+) -> Result<VerifyReport> {
+    let modfiles: Vec<(i64, i64, String, i64)> = sqlx::query_as(
+        "SELECT id_mod, id_modfile, hash_md5,
+            CASE WHEN EXISTS (SELECT 1 FROM pack_file WHERE pack_file.id_modfile = modfile.id_modfile)
+                THEN 1 ELSE 0 END
+         FROM modfile
+         WHERE NOT EXISTS (SELECT 1 FROM download
+                           WHERE download.id_modfile = modfile.id_modfile AND download.state = $1)
+         ORDER BY id_modfile",
+    )
+    .bind(download::DownloadState::Discarded.as_str())
+    .fetch_all(pool)
+    .await?;
+
+    let mut report = VerifyReport {
+        modfiles: modfiles.len() as u64,
+        archives: 0,
+        missing: vec![],
+        extra: vec![],
+        unanalyzed: vec![],
+        fixes: None,
+    };
+
+    let hashes = modfiles
+        .iter()
+        .map(|(_, _, md5, _)| md5.as_str())
+        .collect::<HashSet<_>>();
+    for (md5, path) in store::objects()? {
+        report.archives += 1;
+        if !hashes.contains(md5.as_str()) {
+            report.extra.push(path);
+        }
+    }
+    report.extra.sort();
+
+    let mut unanalyzed = vec![];
+    for (id_mod, id_modfile, hash_md5, analyzed) in modfiles {
+        if !download::archive_path(&hash_md5).exists() {
+            report.missing.push(MissingArchive {
+                id_mod,
+                id_modfile,
+                hash_md5,
+            });
+        } else if analyzed == 0 {
+            report.unanalyzed.push(id_modfile);
+            unanalyzed.push((id_modfile, hash_md5));
+        }
+    }
+
+    if fix {
+        report.fixes =
+            Some(apply_fixes(multi_bar, pool, &report.missing, &report.extra, unanalyzed).await?);
+        store::refresh(pool).await?;
+    }
+
+    Ok(report)
+}
+
+async fn apply_fixes(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    missing: &[MissingArchive],
+    extra: &[PathBuf],
+    mut unanalyzed: Vec<(i64, String)>,
+) -> Result<Fixes> {
+    let mut fixes = Fixes::default();
+
+    for path in extra {
+        let Some(md5) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        info!(path = %path.display(), "Moving unreferenced archive to the trash");
+        trash::trash_archive(pool, md5, "verify --fix: not referenced by any modfile").await?;
+        fixes.trashed += 1;
+    }
+
+    redownload(multi_bar, pool, missing, &mut fixes, &mut unanalyzed).await?;
+
+    if !unanalyzed.is_empty() {
+        let mut summary = SyncSummary::default();
+        let bar = multi_bar.add(ProgressBar::new(unanalyzed.len().try_into().unwrap()));
+        crate::analyze_modfiles(multi_bar, pool, &bar, unanalyzed, &mut summary).await?;
+        bar.finish();
+        fixes.analyzed = summary.analyzed;
+        fixes.errors.extend(summary.analysis_errors);
+    }
+
+    Ok(fixes)
+}
+
+/// Download the archives of `missing` modfiles again and queue them in `unanalyzed`. Modfiles are
+/// fetched from mod.io first for a fresh download link and skipped if they were re-uploaded or
+/// deleted since.
+async fn redownload(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+    missing: &[MissingArchive],
+    fixes: &mut Fixes,
+    unanalyzed: &mut Vec<(i64, String)>,
+) -> Result<()> {
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let modio = api::client()?;
+    let bar = multi_bar.add(ProgressBar::new(missing.len().try_into().unwrap()));
+    for m in missing {
+        if daemon::shutdown_requested() {
+            info!("Stopping early, shutdown requested");
+            break;
+        }
+        let file = modio
+            .game(api::DRG)
+            .mod_(m.id_mod as u32)
+            .file(m.id_modfile as u32)
+            .get()
+            .await;
+        match file {
+            Ok(file) if file.filehash.md5 == m.hash_md5 => {
+                match download::download_modfile(multi_bar, pool, &modio, &file).await {
+                    Ok(download::Downloaded::Downloaded) => fixes.downloaded += 1,
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!(id_modfile = m.id_modfile, "{e:#}");
+                        fixes.errors.push(format!("{e:#}"));
+                    }
+                }
+                if download::archive_path(&m.hash_md5).exists() {
+                    unanalyzed.push((m.id_modfile, m.hash_md5.clone()));
+                }
+            }
+            Ok(file) => {
+                warn!(id_modfile = m.id_modfile, "Upstream hash differs");
+                fixes.errors.push(format!(
+                    "modfile {} was re-uploaded with hash {}, run audit-upstream",
+                    m.id_modfile, file.filehash.md5
+                ));
+            }
+            Err(e) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                warn!(
+                    id_modfile = m.id_modfile,
+                    "Modfile no longer exists upstream"
+                );
+                fixes.errors.push(format!(
+                    "modfile {} no longer exists upstream",
+                    m.id_modfile
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+
+    Ok(())
+}
+
+/// What `fetch-missing` did.
+#[derive(Debug, Serialize)]
+pub struct FetchMissingReport {
+    /// Modfiles whose archive was missing from the store
+    pub missing: Vec<MissingArchive>,
+    pub downloaded: u64,
+    pub analyzed: u64,
+    pub errors: Vec<String>,
+}
+
+impl std::fmt::Display for FetchMissingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for e in &self.errors {
+            writeln!(f, "{e}")?;
+        }
+        write!(
+            f,
+            "{} archives missing, {} downloaded, {} analyzed, {} errors",
+            self.missing.len(),
+            self.downloaded,
+            self.analyzed,
+            self.errors.len()
+        )
+    }
+}
+
+/// Download the archive of every indexed modfile that is missing from the store, e.g. to restore
+/// the mods directory from the database alone after losing it. Archives whose pack files are
+/// already indexed are not analyzed again and those deleted by `--no-keep` are left alone.
+pub async fn fetch_missing(
+    multi_bar: &indicatif::MultiProgress,
+    pool: &AnyPool,
+) -> Result<FetchMissingReport> {
+    let modfiles: Vec<(i64, i64, String, i64)> = sqlx::query_as(
+        "SELECT id_mod, id_modfile, hash_md5,
+            CASE WHEN EXISTS (SELECT 1 FROM pack_file WHERE pack_file.id_modfile = modfile.id_modfile)
+                THEN 1 ELSE 0 END
+         FROM modfile
+         WHERE NOT EXISTS (SELECT 1 FROM download
+                           WHERE download.id_modfile = modfile.id_modfile AND download.state = $1)
+         ORDER BY id_modfile",
+    )
+    .bind(download::DownloadState::Discarded.as_str())
+    .fetch_all(pool)
+    .await?;
+
+    let mut missing = vec![];
+    let mut analyzed = HashSet::new();
+    for (id_mod, id_modfile, hash_md5, has_pack_files) in modfiles {
+        if !download::archive_path(&hash_md5).exists() {
+            if has_pack_files != 0 {
+                analyzed.insert(id_modfile);
+            }
+            missing.push(MissingArchive {
+                id_mod,
+                id_modfile,
+                hash_md5,
+            });
+        }
+    }
+    info!("{} archives missing", missing.len());
+
+    let mut fixes = Fixes::default();
+    let mut downloaded = vec![];
+    redownload(multi_bar, pool, &missing, &mut fixes, &mut downloaded).await?;
+
+    let unanalyzed = downloaded
+        .into_iter()
+        .filter(|(id_modfile, _)| !analyzed.contains(id_modfile))
+        .collect::<Vec<_>>();
+    if !unanalyzed.is_empty() {
+        let mut summary = SyncSummary::default();
+        let bar = multi_bar.add(ProgressBar::new(unanalyzed.len().try_into().unwrap()));
+        crate::analyze_modfiles(multi_bar, pool, &bar, unanalyzed, &mut summary).await?;
+        bar.finish();
+        fixes.analyzed = summary.analyzed;
+        fixes.errors.extend(summary.analysis_errors);
+    }
+
+    store::refresh(pool).await?;
+
+    Ok(FetchMissingReport {
+        missing,
+        downloaded: fixes.downloaded,
+        analyzed: fixes.analyzed,
+        errors: fixes.errors,
+    })
+}
@@ -0,0 +1,116 @@
+//! mod.io's virus scan results. Modfiles the scan flagged are indexed but not downloaded unless
+//! `--allow-flagged` is given.
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::AnyPool;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ALLOW_FLAGGED: AtomicBool = AtomicBool::new(false);
+
+/// Download modfiles flagged by the virus scan as well.
+pub fn allow_flagged() {
+    ALLOW_FLAGGED.store(true, Ordering::Relaxed);
+}
+
+/// Whether the virus scan found something in `file`.
+pub fn is_flagged(file: &modio::files::File) -> bool {
+    i64::from(file.virus_positive) != 0
+}
+
+/// Whether `file` must not be downloaded.
+pub fn refused(file: &modio::files::File) -> bool {
+    is_flagged(file) && !ALLOW_FLAGGED.load(Ordering::Relaxed)
+}
+
+fn status_name(status: Option<i64>) -> &'static str {
+    match status {
+        Some(0) => "not scanned",
+        Some(1) => "scanned",
+        Some(2) => "scan in progress",
+        Some(3) => "too large to scan",
+        Some(4) => "file not found",
+        Some(5) => "scan failed",
+        _ => "unknown",
+    }
+}
+
+fn finding_name(positive: i64) -> &'static str {
+    match positive {
+        1 => "malicious",
+        2 => "potentially harmful files",
+        _ => "flagged",
+    }
+}
+
+/// A modfile the virus scan flagged.
+#[derive(Debug, Serialize)]
+pub struct FlaggedModfile {
+    pub id_mod: i64,
+    pub name_id: String,
+    pub id_modfile: i64,
+    pub filename: String,
+    pub virus_status: Option<i64>,
+    pub virus_positive: i64,
+    /// Whether it is the mod's current modfile
+    pub current: bool,
+    /// Whether the archive was downloaded anyway
+    pub stored: bool,
+}
+
+pub fn print_flagged(modfiles: &[FlaggedModfile]) {
+    for f in modfiles {
+        println!(
+            "{} {} modfile {} {}: {}, {}{}{}",
+            f.id_mod,
+            f.name_id,
+            f.id_modfile,
+            f.filename,
+            finding_name(f.virus_positive),
+            status_name(f.virus_status),
+            if f.current { ", current" } else { "" },
+            if f.stored { ", stored" } else { "" },
+        );
+    }
+}
+
+/// Indexed modfiles flagged by the virus scan, current modfiles first.
+pub async fn flagged(pool: &AnyPool) -> Result<Vec<FlaggedModfile>> {
+    let rows: Vec<(i64, String, i64, String, String, Option<i64>, i64, i64)> = sqlx::query_as(
+        "SELECT modfile.id_mod, mod.name_id, modfile.id_modfile, filename, hash_md5,
+                virus_status, virus_positive,
+                CASE WHEN mod.id_modfile = modfile.id_modfile THEN 1 ELSE 0 END AS current
+         FROM modfile JOIN mod ON mod.id_mod = modfile.id_mod
+         WHERE virus_positive != 0
+         ORDER BY current DESC, modfile.id_mod, modfile.id_modfile",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(
+                id_mod,
+                name_id,
+                id_modfile,
+                filename,
+                md5,
+                virus_status,
+                virus_positive,
+                current,
+            )| {
+                FlaggedModfile {
+                    id_mod,
+                    name_id,
+                    id_modfile,
+                    filename,
+                    virus_status,
+                    virus_positive,
+                    current: current != 0,
+                    stored: crate::download::archive_path(&md5).exists(),
+                }
+            },
+        )
+        .collect())
+}
@@ -0,0 +1,193 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use sqlx::AnyPool;
+use tracing::{error, info};
+
+use std::collections::{HashMap, HashSet};
+
+use crate::channel::Notification;
+use crate::{classify, lookup};
+
+/// A webhook receiving the notifications about one mod, every mod in a category or every mod with
+/// a mod.io tag, e.g. so a mod author hears about their own mods without following every channel.
+#[derive(Debug, Serialize)]
+pub struct Webhook {
+    pub id_webhook: i64,
+    pub url: String,
+    pub id_mod: Option<i64>,
+    pub name_id: Option<String>,
+    pub category: Option<String>,
+    pub tag: Option<String>,
+    pub date_created: String,
+}
+
+impl std::fmt::Display for Webhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} for ", self.id_webhook, self.url)?;
+        match (&self.name_id, &self.category, &self.tag) {
+            (Some(name_id), _, _) => write!(f, "mod {name_id}")?,
+            (_, Some(category), _) => write!(f, "category {category}")?,
+            (_, _, Some(tag)) => write!(f, "tag {tag}")?,
+            _ => write!(f, "nothing")?,
+        }
+        write!(f, " (created {})", self.date_created)
+    }
+}
+
+/// Where a webhook is registered for.
+pub enum Scope<'a> {
+    /// A mod id or name_id
+    Mod(&'a str),
+    Category(classify::Category),
+    Tag(&'a str),
+}
+
+/// Register `url` for the notifications about the mods in `scope`. Returns the id of the new
+/// webhook.
+pub async fn add(pool: &AnyPool, url: &str, scope: Scope<'_>) -> Result<i64> {
+    reqwest::Url::parse(url).with_context(|| format!("{url} is not a valid URL"))?;
+    let (mut id_mod, mut category, mut tag) = (None, None, None);
+    match scope {
+        Scope::Mod(reference) => id_mod = Some(lookup::resolve_mod(pool, reference).await?),
+        Scope::Category(c) => category = Some(c.as_str()),
+        Scope::Tag(t) => tag = Some(t),
+    }
+    let id_webhook = sqlx::query_scalar(
+        "INSERT INTO webhook(url, id_mod, category, tag, date_created)
+         VALUES ($1, $2, $3, $4, $5) RETURNING id_webhook",
+    )
+    .bind(url)
+    .bind(id_mod)
+    .bind(category)
+    .bind(tag)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .fetch_one(pool)
+    .await?;
+    Ok(id_webhook)
+}
+
+pub async fn remove(pool: &AnyPool, id_webhook: i64) -> Result<()> {
+    let removed = sqlx::query("DELETE FROM webhook WHERE id_webhook = $1")
+        .bind(id_webhook)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if removed == 0 {
+        bail!("no webhook {id_webhook}");
+    }
+    Ok(())
+}
+
+type WebhookRow = (
+    i64,
+    String,
+    Option<i64>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    String,
+);
+
+pub async fn list(pool: &AnyPool) -> Result<Vec<Webhook>> {
+    let rows: Vec<WebhookRow> = sqlx::query_as(
+        "SELECT id_webhook, url, webhook.id_mod, name_id, webhook.category, tag, date_created
+         FROM webhook LEFT JOIN mod ON mod.id_mod = webhook.id_mod
+         ORDER BY id_webhook",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id_webhook, url, id_mod, name_id, category, tag, date_created)| Webhook {
+                id_webhook,
+                url,
+                id_mod,
+                name_id,
+                category,
+                tag,
+                date_created,
+            },
+        )
+        .collect())
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    id_webhook: i64,
+    notifications: Vec<PayloadNotification<'a>>,
+}
+
+#[derive(Serialize)]
+struct PayloadNotification<'a> {
+    kind: &'static str,
+    title: &'a str,
+    url: Option<&'a str>,
+    description: &'a str,
+    mods: &'a [i64],
+}
+
+/// POST the notifications matching each webhook to it as JSON. A webhook that fails is logged and
+/// does not keep the others from being sent to.
+pub async fn dispatch(
+    pool: &AnyPool,
+    client: &reqwest::Client,
+    webhooks: &[Webhook],
+    notifications: &[Notification],
+) -> Result<()> {
+    let categories: Vec<(i64, Option<String>)> = sqlx::query_as("SELECT id_mod, category FROM mod")
+        .fetch_all(pool)
+        .await?;
+    let categories = categories.into_iter().collect::<HashMap<_, _>>();
+    let tags: Vec<(i64, String)> = sqlx::query_as("SELECT id_mod, tag FROM mod_tag")
+        .fetch_all(pool)
+        .await?;
+    let tags = tags.into_iter().collect::<HashSet<_>>();
+
+    for webhook in webhooks {
+        let matches = |id_mod: &i64| match (webhook.id_mod, &webhook.category, &webhook.tag) {
+            (Some(subscribed), _, _) => *id_mod == subscribed,
+            (_, Some(category), _) => {
+                categories.get(id_mod).and_then(Option::as_ref) == Some(category)
+            }
+            (_, _, Some(tag)) => tags.contains(&(*id_mod, tag.clone())),
+            _ => false,
+        };
+        let matching = notifications
+            .iter()
+            .filter(|n| n.mods.iter().any(matches))
+            .map(|n| PayloadNotification {
+                kind: n.kind.as_str(),
+                title: &n.title,
+                url: n.url.as_deref(),
+                description: &n.description,
+                mods: &n.mods,
+            })
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            continue;
+        }
+        info!(
+            id_webhook = webhook.id_webhook,
+            "Sending {} notifications",
+            matching.len()
+        );
+        let payload = Payload {
+            id_webhook: webhook.id_webhook,
+            notifications: matching,
+        };
+        let res = client
+            .post(&webhook.url)
+            .json(&payload)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        if let Err(e) = res {
+            error!(
+                id_webhook = webhook.id_webhook,
+                "Failed to send notifications: {e:#}"
+            );
+        }
+    }
+    Ok(())
+}
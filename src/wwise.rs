@@ -0,0 +1,295 @@
+//! Reader for Wwise soundbanks (`.bnk`) and encoded media (`.wem`), which DRG's audio is cooked
+//! into. Media is named by numeric id so the objects of a bank are what tell music, voice lines
+//! and sound effects apart.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::uasset::Reader;
+
+/// Oldest bank version whose sounds are read for their media, the layout of `AkBankSourceData`
+/// differs before it.
+const SOURCE_LAYOUT_VERSION: u32 = 113;
+
+const HIRC_SOUND: u8 = 2;
+
+/// An object of a soundbank, a media file embedded in one or a loose `.wem`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioObject {
+    /// `bank`, `media` or the type of a hierarchy object, e.g. `sound`, `event` or `music-track`
+    pub kind: &'static str,
+    pub id: u32,
+    /// Media played by a sound
+    pub source: Option<u32>,
+    /// Whether a sound's media is language specific
+    pub localized: bool,
+}
+
+impl AudioObject {
+    fn new(kind: &'static str, id: u32) -> AudioObject {
+        AudioObject {
+            kind,
+            id,
+            source: None,
+            localized: false,
+        }
+    }
+}
+
+/// What a bank or media file holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Content {
+    Music,
+    /// Dialogue and localized sounds
+    Voice,
+    /// Any other sound, e.g. weapons, creatures and UI
+    Sfx,
+}
+
+impl Content {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Content::Music => "music",
+            Content::Voice => "voice",
+            Content::Sfx => "sfx",
+        }
+    }
+}
+
+/// Content of a file from the kinds of its objects and whether they are localized: music if it
+/// has any music objects, voice if it has dialogue or localized sounds, otherwise sound effects if
+/// it has sounds. `None` for media alone, which only a bank playing it can tell.
+pub fn content<'a>(objects: impl IntoIterator<Item = (&'a str, bool)>) -> Option<Content> {
+    let mut content = None;
+    for (kind, localized) in objects {
+        if kind.starts_with("music-") {
+            return Some(Content::Music);
+        }
+        if kind == "dialogue-event" || (kind == "sound" && localized) {
+            content = Some(Content::Voice);
+        } else if kind == "sound" && content.is_none() {
+            content = Some(Content::Sfx);
+        }
+    }
+    content
+}
+
+/// Name of a hierarchy object type as used from bank version 113 on.
+fn hirc_kind(kind: u8) -> &'static str {
+    match kind {
+        1 => "state",
+        2 => "sound",
+        3 => "action",
+        4 => "event",
+        5 => "random-sequence",
+        6 => "switch",
+        7 => "actor-mixer",
+        8 => "bus",
+        9 => "layer",
+        10 => "music-segment",
+        11 => "music-track",
+        12 => "music-switch",
+        13 => "music-sequence",
+        14 => "attenuation",
+        15 => "dialogue-event",
+        16 => "fx-share-set",
+        17 => "fx-custom",
+        18 => "aux-bus",
+        19 => "lfo",
+        20 => "envelope",
+        21 => "audio-device",
+        22 => "time-modulator",
+        _ => "unknown",
+    }
+}
+
+/// Read the media a sound plays: `AkBankSourceData` starting with the plugin id and stream type,
+/// then `AkMediaInformation`.
+fn sound_source(r: &mut Reader) -> Result<(u32, bool)> {
+    r.u32()?; // plugin id
+    r.u8()?; // stream type
+    let source = r.u32()?;
+    r.u32()?; // in memory media size
+    let bits = r.u8()?;
+    Ok((source, bits & 1 != 0))
+}
+
+/// Objects of a soundbank: the bank itself, the media in its data index and every object of its
+/// hierarchy.
+pub fn parse_bank(data: &[u8]) -> Result<Vec<AudioObject>> {
+    if !data.starts_with(b"BKHD") {
+        bail!("not a Wwise soundbank");
+    }
+    let mut r = Reader::new(data);
+    let mut objects = vec![];
+    let mut version = 0;
+    while r.remaining() >= 8 {
+        let tag = r.bytes(4)?;
+        let size = r.u32()? as usize;
+        let mut chunk = Reader::new(r.bytes(size)?);
+        match tag {
+            b"BKHD" => {
+                version = chunk.u32()?;
+                objects.push(AudioObject::new("bank", chunk.u32()?));
+            }
+            b"DIDX" => {
+                while chunk.remaining() >= 12 {
+                    objects.push(AudioObject::new("media", chunk.u32()?));
+                    chunk.u32()?; // offset
+                    chunk.u32()?; // size
+                }
+            }
+            b"HIRC" => {
+                let count = chunk.u32()?;
+                for _ in 0..count {
+                    let kind = chunk.u8()?;
+                    let size = chunk.u32()? as usize;
+                    let mut body = Reader::new(chunk.bytes(size)?);
+                    let mut object = AudioObject::new(hirc_kind(kind), body.u32()?);
+                    if kind == HIRC_SOUND && version >= SOURCE_LAYOUT_VERSION {
+                        if let Ok((source, localized)) = sound_source(&mut body) {
+                            object.source = Some(source);
+                            object.localized = localized;
+                        }
+                    }
+                    objects.push(object);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(objects)
+}
+
+/// The media id of a loose `.wem`, taken from its name as the file itself does not hold it.
+pub fn parse_wem(path: &str, data: &[u8]) -> Result<Vec<AudioObject>> {
+    if !(data.starts_with(b"RIFF") || data.starts_with(b"RIFX")) {
+        bail!("not a Wwise media file");
+    }
+    let id = std::path::Path::new(path)
+        .file_stem()
+        .and_then(std::ffi::OsStr::to_str)
+        .and_then(|stem| stem.parse().ok());
+    Ok(id
+        .map(|id| vec![AudioObject::new("media", id)])
+        .unwrap_or_default())
+}
+
+/// Audio objects of the entry at `path`, none unless it is a `.bnk` or `.wem`.
+pub fn parse(path: &str, data: &[u8]) -> Result<Vec<AudioObject>> {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("bnk") => parse_bank(data),
+        Some("wem") => parse_wem(path, data),
+        _ => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BANK: &[u8] = include_bytes!("../tests/fixtures/assets/Sample.bnk");
+
+    fn summary(objects: &[AudioObject]) -> Vec<(&str, u32, Option<u32>, bool)> {
+        objects
+            .iter()
+            .map(|o| (o.kind, o.id, o.source, o.localized))
+            .collect()
+    }
+
+    #[test]
+    fn bank() {
+        let objects = parse("Content/Audio/WwiseAudio/Sample.BNK", BANK).unwrap();
+        assert_eq!(
+            summary(&objects),
+            [
+                ("bank", 3991942870, None, false),
+                ("media", 1001, None, false),
+                ("media", 1002, None, false),
+                ("sound", 200, Some(1001), false),
+                ("sound", 201, Some(1002), true),
+                ("event", 300, None, false),
+                ("action", 400, None, false),
+                ("music-track", 500, None, false),
+            ]
+        );
+        let kinds = |objects: &[AudioObject]| {
+            objects
+                .iter()
+                .map(|o| (o.kind, o.localized))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(content(kinds(&objects)), Some(Content::Music));
+        assert_eq!(content(kinds(&objects[..5])), Some(Content::Voice));
+        assert_eq!(content(kinds(&objects[..4])), Some(Content::Sfx));
+        assert_eq!(content(kinds(&objects[..3])), None);
+    }
+
+    #[test]
+    fn old_bank_sounds_have_no_source() {
+        let mut bank = BANK.to_vec();
+        bank[8..12].copy_from_slice(&88u32.to_le_bytes());
+        let objects = parse_bank(&bank).unwrap();
+        assert!(objects
+            .iter()
+            .filter(|o| o.kind == "sound")
+            .all(|o| o.source.is_none()));
+    }
+
+    #[test]
+    fn media() {
+        let wem = b"RIFF\x10\x00\x00\x00WAVEfmt ";
+        assert_eq!(
+            summary(&parse("Content/Audio/123456.wem", wem).unwrap()),
+            [("media", 123456, None, false)]
+        );
+        assert!(parse("Content/Audio/Music.wem", wem).unwrap().is_empty());
+        assert!(parse("Content/Audio/123456.wem", b"OggS").is_err());
+        assert!(parse("Content/Audio/123456.ogg", b"OggS")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let all = parse_bank(BANK).unwrap();
+        for len in 0..BANK.len() {
+            // cut between chunks the bank is read up to there, otherwise it fails
+            if let Ok(objects) = parse_bank(&BANK[..len]) {
+                assert!(summary(&all).starts_with(&summary(&objects)), "{len}");
+            }
+        }
+        assert!(parse_bank(&BANK[..BANK.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn garbage() {
+        let mut state = 0x9e3779b97f4a7c15u64;
+        for _ in 0..200 {
+            let mut data = b"BKHD".to_vec();
+            data.extend((0..256).map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            }));
+            let _ = parse_bank(&data);
+        }
+        assert!(parse_bank(b"garbage!").is_err());
+
+        // sizes and counts claiming far more than there is
+        let mut data = b"BKHD".to_vec();
+        data.extend(u32::MAX.to_le_bytes());
+        data.extend([0; 8]);
+        assert!(parse_bank(&data).is_err());
+        let mut data = BANK.to_vec();
+        let hirc = data.windows(4).position(|w| w == b"HIRC").unwrap();
+        data[hirc + 8..hirc + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_bank(&data).is_err());
+    }
+}
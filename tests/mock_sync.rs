@@ -0,0 +1,150 @@
+//! Runs `sync` end to end against the `mock-api` server and checks what ends up in the index.
+//! The fixtures in tests/fixtures/mock are one mod with one modfile, a zip holding a pak with the
+//! sample DataTable.
+
+#![cfg(feature = "mock-api")]
+
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+const BIN: &str = env!("CARGO_BIN_EXE_drg-modio-index");
+
+/// A directory of its own for the index, the store and the lock file, deleted when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("drg-modio-index-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The binary run in `dir` without any of the caller's configuration, so a stored token or a
+/// `.env` does not leak into the test.
+fn command(dir: &Path) -> Command {
+    let mut command = Command::new(BIN);
+    command
+        .current_dir(dir)
+        .env_clear()
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .env(
+            "DATABASE_URL",
+            format!("sqlite:{}", dir.join("index.db").display()),
+        );
+    command
+}
+
+/// A running `mock-api` server, stopped when dropped.
+struct MockApi {
+    child: Child,
+    address: SocketAddr,
+}
+
+impl MockApi {
+    fn start(dir: &Path) -> Self {
+        // a port that was free a moment ago, the server binds it itself
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mock");
+        let child = command(dir)
+            .arg("mock-api")
+            .arg(fixtures)
+            .arg("--listen")
+            .arg(address.to_string())
+            .spawn()
+            .unwrap();
+        let mock = MockApi { child, address };
+        let started = Instant::now();
+        while TcpStream::connect(address).is_err() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "mock-api did not start listening on {address}"
+            );
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        mock
+    }
+
+    fn api_base(&self) -> String {
+        format!("http://{}/v1", self.address)
+    }
+}
+
+impl Drop for MockApi {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[tokio::test]
+async fn sync_indexes_fixtures() {
+    let dir = TempDir::new("mock-sync");
+    let mock = MockApi::start(&dir.0);
+
+    let output = command(&dir.0)
+        .arg("--json")
+        .arg("--api-base")
+        .arg(mock.api_base())
+        .arg("sync")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "sync failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(summary["mods"], 1);
+    assert_eq!(summary["downloaded"], 1);
+    assert_eq!(summary["analysis_errors"], serde_json::json!([]));
+
+    let pool = sqlx::SqlitePool::connect(&format!("sqlite:{}", dir.0.join("index.db").display()))
+        .await
+        .unwrap();
+    let mods: Vec<(i64, String, String, Option<i64>)> =
+        sqlx::query_as("SELECT id_mod, name, name_id, id_modfile FROM mod")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        mods,
+        [(
+            1001,
+            "Weapon Stats Tweaks".to_string(),
+            "weapon-stats-tweaks".to_string(),
+            Some(2001)
+        )]
+    );
+    let pack_files: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id_modfile, path FROM pack_file ORDER BY path")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        pack_files,
+        [
+            (
+                2001,
+                "FSD/Content/GameElements/Weapons/DT_WeaponStats.uasset".to_string()
+            ),
+            (
+                2001,
+                "FSD/Content/GameElements/Weapons/DT_WeaponStats.uexp".to_string()
+            ),
+        ]
+    );
+    pool.close().await;
+}